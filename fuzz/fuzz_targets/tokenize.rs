@@ -0,0 +1,24 @@
+#![no_main]
+
+use ghetto_llvm::{
+    diagnostics::Diagnostics,
+    tokenizer::{Token, Tokenizer},
+};
+use libfuzzer_sys::fuzz_target;
+
+// Run with `cargo +nightly fuzz run tokenize` from the repository root
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+
+    if let Ok(tokens) = Tokenizer::new(&source, "fuzz".to_string()).tokenize() {
+        assert!(matches!(tokens.last(), Some(Token::Eof { .. })));
+    }
+
+    let mut diagnostics = Diagnostics::default();
+    let tokens = Tokenizer::new(&source, "fuzz".to_string()).tokenize_recovering(&mut diagnostics);
+    assert!(matches!(tokens.last(), Some(Token::Eof { .. })));
+    for token in &tokens {
+        let span = token.span();
+        assert!(span.start <= span.end && span.end <= source.chars().count());
+    }
+});
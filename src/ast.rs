@@ -1,303 +1,7178 @@
+use std::collections::HashMap;
+
 use error_stack::ResultExt;
 use thiserror::Error;
 
-use crate::tokenizer::{self, BinaryOp};
-pub struct AstParser {
-    tokens: Vec<tokenizer::Token>,
+use crate::{
+    const_eval,
+    json::Json,
+    symbol::Symbol,
+    token_cursor::TokenCursor,
+    tokenizer::{self, BinaryOp, TokenKind},
+};
+
+/// Which values the arms of a match handle
+mod coverage;
+/// Graphviz graphs of the AST
+pub mod dot;
+/// Turning the AST back into source code
+pub mod pretty_print;
+
+pub struct AstParser<'src> {
+    tokens: TokenCursor<'src>,
+    /// The variables declared so far, innermost scope last
+    scopes: Vec<HashMap<Symbol, Variable>>,
+    /// The constants declared so far, visible everywhere after their declaration
+    constants: HashMap<Symbol, Variable>,
+    /// The types and values of `constants`
+    values: HashMap<Symbol, (Symbol, const_eval::Value)>,
+    /// The function whose body is being parsed, functions can't be nested
+    function: Option<Signature>,
+    /// Every function defined so far
+    functions: HashMap<Symbol, Signature>,
+    /// The `#[deprecated]` functions with their messages
+    deprecated: HashMap<Symbol, Option<String>>,
+    /// Every struct defined so far, with its fields
+    structs: HashMap<Symbol, Vec<Field>>,
+    /// Every enum defined so far, with its variants
+    enums: HashMap<Symbol, Vec<Symbol>>,
+    /// How many loops the statement being parsed is nested in
+    loop_depth: usize,
+    /// Set while parsing a call statement, whose function doesn't have to return a value
+    statement_call: bool,
+    /// The statements that failed to parse so far, in source order
+    errors: Vec<error_stack::Report<AstParseError>>,
+    /// Everything the parser accepted but warns about, in source order
+    warnings: Vec<Warning>,
+}
+
+/// What the parser knows about a declared variable
+#[derive(Debug, Clone, Copy)]
+struct Variable {
+    t: Symbol,
+    /// Declared with `let mut`, only those can be assigned to
+    mutable: bool,
+    /// Where the name is declared, for errors about using it
+    span: tokenizer::Span,
+}
+
+/// What calls and `return`s need to know about a function
+#[derive(Debug, Clone)]
+struct Signature {
+    params: usize,
+    return_type: Option<Symbol>,
+}
+
+/// Where in the source code a node was parsed from. Nodes are equal no matter where they come
+/// from, so comparing trees and finding repeated expressions only looks at what they contain
+#[derive(Debug, Clone, Copy)]
+pub struct NodeSpan(pub tokenizer::Span);
+
+impl PartialEq for NodeSpan {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
 }
 
 #[derive(PartialEq, Debug)]
 pub enum AstStatement {
+    /// `let name: t = value;`, or `let mut name: t = value;` if it can be assigned to later.
+    /// Without `: t` in the source, `t` is the type inferred from `value`
     Let {
         value: AstExpression,
-        name: String,
-        t: String,
+        name: Symbol,
+        t: Symbol,
+        mutable: bool,
+        span: NodeSpan,
     },
     Exit {
         value: AstExpression,
+        span: NodeSpan,
+    },
+    /// `print expr;` and `println expr;`
+    Print {
+        value: AstExpression,
+        newline: bool,
+        span: NodeSpan,
+    },
+    /// `const NAME: t = value;` at the top level, with the value computed at compile time
+    Const {
+        name: Symbol,
+        t: Symbol,
+        value: const_eval::Value,
+        span: NodeSpan,
+    },
+    /// `fn name(a: T, b: U) -> R { ... }`, without `-> R` the function returns nothing and with
+    /// `-> !` it never returns
+    Function {
+        /// The `///` comments in front of it, one line each
+        doc: Option<String>,
+        attrs: Vec<Attribute>,
+        name: Symbol,
+        params: Vec<Parameter>,
+        return_type: Option<Symbol>,
+        body: Vec<AstStatement>,
+        span: NodeSpan,
+    },
+    /// `struct Name { a: T, b: U }` at the top level, a type whose values hold a value of every
+    /// field
+    Struct {
+        /// The `///` comments in front of it, one line each
+        doc: Option<String>,
+        name: Symbol,
+        fields: Vec<Field>,
+        span: NodeSpan,
+    },
+    /// `enum Name { A, B }` at the top level, a type whose values are one of the variants
+    Enum {
+        name: Symbol,
+        variants: Vec<Symbol>,
+        span: NodeSpan,
+    },
+    /// `impl Name { fn method(self, a: T) -> R { ... } ... }` at the top level, the methods of
+    /// the struct or enum `Name`. Each is a [`AstStatement::Function`] whose first parameter is
+    /// the `self: Name` it is called on, named [`method_name`] among the other functions
+    Impl {
+        name: Symbol,
+        methods: Vec<AstStatement>,
+        span: NodeSpan,
+    },
+    /// A call whose result isn't used, `name(args);`
+    Call {
+        name: Symbol,
+        args: Vec<AstExpression>,
+        span: NodeSpan,
+    },
+    /// A method call whose result isn't used, `value.method(args);` with `value` a `t`
+    MethodCall {
+        value: AstExpression,
+        t: Symbol,
+        method: Symbol,
+        args: Vec<AstExpression>,
+        span: NodeSpan,
+    },
+    /// `assert_eq!(left, right);`, fails the program or the test running it if the values of
+    /// the type `t` differ
+    AssertEq {
+        left: AstExpression,
+        right: AstExpression,
+        t: Symbol,
+        span: NodeSpan,
+    },
+    /// `if condition { ... } else { ... }`, an `else if` is an `If` alone in `otherwise`
+    If {
+        condition: AstExpression,
+        then: Vec<AstStatement>,
+        otherwise: Vec<AstStatement>,
+        span: NodeSpan,
+    },
+    /// `match value { pattern => { ... } ... }`, runs the body of the first arm whose pattern is
+    /// `value`. `t` is the type `value` is compared as
+    Match {
+        value: AstExpression,
+        t: Symbol,
+        arms: Vec<MatchArm>,
+        span: NodeSpan,
+    },
+    /// `while condition { ... }`
+    While {
+        condition: AstExpression,
+        body: Vec<AstStatement>,
+        span: NodeSpan,
+    },
+    /// `for name: t in range { ... }`, counting up from the start of the range to just before
+    /// its end, or up to the end itself if the range is `inclusive`
+    For {
+        name: Symbol,
+        t: Symbol,
+        range: AstExpression,
+        inclusive: bool,
+        body: Vec<AstStatement>,
+        span: NodeSpan,
+    },
+    /// `name = value;` to a variable declared earlier, `t` is the type it was declared with
+    Assign {
+        name: Symbol,
+        t: Symbol,
+        value: AstExpression,
+        span: NodeSpan,
+    },
+    /// `name += value;` and the like, `operator` is applied to the old value and `value`
+    CompoundAssign {
+        name: Symbol,
+        t: Symbol,
+        operator: BinaryOp,
+        value: AstExpression,
+        span: NodeSpan,
+    },
+    /// `return value;`, or `return;` in a function that returns nothing
+    Return {
+        value: Option<AstExpression>,
+        span: NodeSpan,
     },
+    /// `{ ... }`, whose variables are only visible inside of it
+    Block {
+        body: Vec<AstStatement>,
+        span: NodeSpan,
+    },
+    /// `break;`, leaves the innermost loop
+    Break { span: NodeSpan },
+    /// `continue;`, starts the next iteration of the innermost loop
+    Continue { span: NodeSpan },
+}
+
+impl AstStatement {
+    /// From the first token of the statement to its last
+    pub fn span(&self) -> tokenizer::Span {
+        match self {
+            Self::Let { span, .. }
+            | Self::Exit { span, .. }
+            | Self::Print { span, .. }
+            | Self::Const { span, .. }
+            | Self::Function { span, .. }
+            | Self::Struct { span, .. }
+            | Self::Enum { span, .. }
+            | Self::Impl { span, .. }
+            | Self::Call { span, .. }
+            | Self::MethodCall { span, .. }
+            | Self::AssertEq { span, .. }
+            | Self::If { span, .. }
+            | Self::Match { span, .. }
+            | Self::While { span, .. }
+            | Self::For { span, .. }
+            | Self::Assign { span, .. }
+            | Self::CompoundAssign { span, .. }
+            | Self::Return { span, .. }
+            | Self::Block { span, .. }
+            | Self::Break { span }
+            | Self::Continue { span } => span.0,
+        }
+    }
+}
+
+/// A function parameter `name: t`
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Parameter {
+    pub name: Symbol,
+    pub t: Symbol,
+}
+
+/// An attribute `#[name]` in front of a function definition
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Attribute {
+    /// `#[inline]`, calls to the function are always inlined
+    Inline,
+    /// `#[no_mangle]`, the function keeps its name in the generated code, so C can call it
+    NoMangle,
+    /// `#[deprecated]` or `#[deprecated = "message"]`, calls to the function are warned about
+    Deprecated { message: Option<String> },
+    /// `#[test]`, the function is run by the test runner built with `--test`
+    Test,
+}
+
+impl std::fmt::Display for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Attribute::Inline => f.write_str("#[inline]"),
+            Attribute::NoMangle => f.write_str("#[no_mangle]"),
+            Attribute::Deprecated { message: None } => f.write_str("#[deprecated]"),
+            Attribute::Deprecated {
+                message: Some(message),
+            } => write!(f, "#[deprecated = {message:?}]"),
+            Attribute::Test => f.write_str("#[test]"),
+        }
+    }
 }
 
+/// A field `name: t` of a struct
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Field {
+    pub name: Symbol,
+    pub t: Symbol,
+}
+
+/// `pattern => { ... }` in a match. The pattern is a number literal or an enum variant, `None`
+/// is the `_` that matches any value
 #[derive(PartialEq, Debug)]
+pub struct MatchArm {
+    pub pattern: Option<AstExpression>,
+    pub body: Vec<AstStatement>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub enum AstExpression {
     Number {
         raw: String,
         flags: Vec<tokenizer::NumberTypeFlag>,
+        suffix: Option<String>,
+        span: NodeSpan,
     },
     BinaryOperation {
         left: Box<AstExpression>,
         operator: tokenizer::BinaryOp,
         right: Box<AstExpression>,
+        span: NodeSpan,
+    },
+    UnaryOperation {
+        operator: UnaryOp,
+        operand: Box<AstExpression>,
+        span: NodeSpan,
     },
     Identifier {
-        name: String,
+        name: Symbol,
+        span: NodeSpan,
+    },
+    Bool {
+        value: bool,
+        span: NodeSpan,
+    },
+    Call {
+        name: Symbol,
+        args: Vec<AstExpression>,
+        span: NodeSpan,
+    },
+    /// `value as t`
+    Cast {
+        value: Box<AstExpression>,
+        t: Symbol,
+        span: NodeSpan,
     },
+    /// `Name { a: value, ... }`, with a value for every field in the order of the definition
+    StructLiteral {
+        name: Symbol,
+        fields: Vec<(Symbol, AstExpression)>,
+        span: NodeSpan,
+    },
+    /// `value.field` of a struct value
+    FieldAccess {
+        value: Box<AstExpression>,
+        field: Symbol,
+        span: NodeSpan,
+    },
+    /// `value.method(args)`, a call to the method of `t`, the type of `value`
+    MethodCall {
+        value: Box<AstExpression>,
+        t: Symbol,
+        method: Symbol,
+        args: Vec<AstExpression>,
+        span: NodeSpan,
+    },
+    /// `Name::variant` of the enum `Name`
+    Variant {
+        name: Symbol,
+        variant: Symbol,
+        span: NodeSpan,
+    },
+    /// `[a, b, ...]`, the value of an array
+    ArrayLiteral {
+        values: Vec<AstExpression>,
+        span: NodeSpan,
+    },
+    /// `value[index]` of an array value
+    Index {
+        value: Box<AstExpression>,
+        index: Box<AstExpression>,
+        span: NodeSpan,
+    },
+    /// `start..end` or `start..=end`, a [`RANGE`] or [`RANGE_INCLUSIVE`]
+    Range {
+        start: Box<AstExpression>,
+        end: Box<AstExpression>,
+        inclusive: bool,
+        span: NodeSpan,
+    },
+}
+
+/// The type of comparisons, logical operators and `true` and `false`
+pub const BOOL: &str = "bool";
+
+/// The return type `!` of functions that never return
+pub const NEVER: &str = "!";
+
+/// The type of `start..end`, which every program has. It is a struct of the `u64` fields
+/// `start` and `end`, counting from `start` up to but not including `end`
+pub const RANGE: &str = "Range";
+
+/// The type of `start..=end`, the struct [`RANGE`] is, but including `end`
+pub const RANGE_INCLUSIVE: &str = "RangeInclusive";
+
+/// The name of the method `method` of `t` among the functions, which no function in the source
+/// code can have
+pub fn method_name(t: Symbol, method: Symbol) -> Symbol {
+    Symbol::intern(&format!("{t}::{method}"))
+}
+
+/// The type of a range, [`RANGE_INCLUSIVE`] if it includes its end and [`RANGE`] otherwise
+pub fn range_type(inclusive: bool) -> Symbol {
+    Symbol::intern(if inclusive { RANGE_INCLUSIVE } else { RANGE })
+}
+
+/// The fields of [`RANGE`] and [`RANGE_INCLUSIVE`]
+pub fn range_fields() -> Vec<Field> {
+    ["start", "end"]
+        .into_iter()
+        .map(|name| Field {
+            name: Symbol::intern(name),
+            t: Symbol::intern("u64"),
+        })
+        .collect()
+}
+
+impl AstExpression {
+    /// Where the expression is in the source code
+    pub fn span(&self) -> tokenizer::Span {
+        match self {
+            Self::Number { span, .. }
+            | Self::BinaryOperation { span, .. }
+            | Self::UnaryOperation { span, .. }
+            | Self::Identifier { span, .. }
+            | Self::Bool { span, .. }
+            | Self::Call { span, .. }
+            | Self::Cast { span, .. }
+            | Self::StructLiteral { span, .. }
+            | Self::FieldAccess { span, .. }
+            | Self::MethodCall { span, .. }
+            | Self::Variant { span, .. }
+            | Self::ArrayLiteral { span, .. }
+            | Self::Index { span, .. }
+            | Self::Range { span, .. } => span.0,
+        }
+    }
+
+    /// Whether the expression is a comparison, a logical operator or a boolean literal. These
+    /// are `bool`s, which only mix with numbers through a cast
+    pub fn is_boolean(&self) -> bool {
+        match self {
+            AstExpression::Bool { value: _, span: _ }
+            | AstExpression::UnaryOperation {
+                operator: UnaryOp::Not,
+                operand: _,
+                span: _,
+            } => true,
+            AstExpression::BinaryOperation {
+                left: _,
+                operator,
+                right: _,
+                span: _,
+            } => operator.is_comparison() || matches!(operator, BinaryOp::And | BinaryOp::Or),
+            _ => false,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum UnaryOp {
+    /// `-x`
+    Negate,
+    /// `!x`, whether the `bool` `x` is false
+    Not,
+    /// `~x`
+    BitNot,
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UnaryOp::Negate => "-",
+            UnaryOp::Not => "!",
+            UnaryOp::BitNot => "~",
+        })
+    }
 }
 
 impl std::fmt::Display for AstExpression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Number { raw, flags } => {
-                f.write_str(&raw)?;
+            Self::Number {
+                raw,
+                flags: _,
+                suffix,
+                span: _,
+            } => {
+                f.write_str(raw)?;
+                f.write_str(suffix.as_deref().unwrap_or_default())?;
+            }
+            Self::Identifier { name, span: _ } => {
+                f.write_str(name.as_str())?;
+            }
+            Self::Bool { value, span: _ } => write!(f, "{value}")?,
+            Self::Call {
+                name,
+                args,
+                span: _,
+            } => {
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")?;
             }
-            Self::Identifier { name } => {
-                f.write_str(&name)?;
+            Self::Cast { value, t, span: _ } => {
+                write_operand(f, value)?;
+                write!(f, " as {t}")?;
+            }
+            Self::StructLiteral {
+                name,
+                fields,
+                span: _,
+            } => {
+                write!(f, "{name} {{")?;
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, " {field}: {value}")?;
+                }
+                f.write_str(" }")?;
+            }
+            Self::FieldAccess {
+                value,
+                field,
+                span: _,
+            } => {
+                write_operand(f, value)?;
+                write!(f, ".{field}")?;
+            }
+            Self::MethodCall {
+                value,
+                t: _,
+                method,
+                args,
+                span: _,
+            } => {
+                write_operand(f, value)?;
+                let args = args.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, ".{method}({})", args.join(", "))?;
+            }
+            Self::Variant {
+                name,
+                variant,
+                span: _,
+            } => write!(f, "{name}::{variant}")?,
+            Self::ArrayLiteral { values, span: _ } => {
+                let values = values.iter().map(ToString::to_string).collect::<Vec<_>>();
+                write!(f, "[{}]", values.join(", "))?;
+            }
+            Self::Index {
+                value,
+                index,
+                span: _,
+            } => {
+                write_operand(f, value)?;
+                write!(f, "[{index}]")?;
             }
             Self::BinaryOperation {
                 left,
                 operator,
                 right,
+                span: _,
             } => {
-                f.write_str(format!("{}", left).as_str())?;
-                f.write_str(
-                    format!(
-                        "{}",
-                        match operator {
-                            BinaryOp::Plus => '+',
-                            BinaryOp::Minus => '-',
-                            BinaryOp::Star => '*',
-                            BinaryOp::SingleEqual => '=',
-                        }
-                    )
-                    .as_str(),
-                )?;
-                f.write_str(format!("{}", right).as_str())?;
+                write_operand(f, left)?;
+                write!(f, "{operator}")?;
+                write_operand(f, right)?;
+            }
+            Self::UnaryOperation {
+                operator,
+                operand,
+                span: _,
+            } => {
+                write!(f, "{operator}")?;
+                write_operand(f, operand)?;
+            }
+            Self::Range {
+                start,
+                end,
+                inclusive,
+                span: _,
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                write!(f, "{start}{operator}{end}")?;
             }
         }
         Ok(())
     }
 }
 
+/// The tree already encodes precedence, so nested operations are parenthesized to keep it when
+/// the expression is printed. This also keeps `a - -b` from turning into `a--b`
+fn write_operand(f: &mut std::fmt::Formatter<'_>, e: &AstExpression) -> std::fmt::Result {
+    match e {
+        AstExpression::BinaryOperation { .. }
+        | AstExpression::UnaryOperation { .. }
+        | AstExpression::Cast { .. }
+        | AstExpression::Range { .. } => write!(f, "({e})"),
+        _ => write!(f, "{e}"),
+    }
+}
+
 pub type AstProgram = Vec<AstStatement>;
 
-#[derive(Debug, Error)]
-pub enum AstParseError {
-    #[error("invalid expression found during ast parsing")]
-    InvalidExpression,
-    #[error("found an expression at the top level")]
-    ExpressionAtToplevel,
-    #[error("invalid let statement")]
-    InvalidLetStatement,
-}
+/// Read-only traversal over the AST.
+///
+/// Every method defaults to walking the node's children through [`DefaultWalker`], so an
+/// implementor only overrides the nodes it cares about (and calls back into the walker if it
+/// still wants the children visited).
+pub trait AstVisitor {
+    fn visit_program(&mut self, program: &AstProgram) {
+        DefaultWalker::walk_program(self, program);
+    }
 
-pub type AstParseResult = error_stack::Result<AstProgram, AstParseError>;
+    fn visit_statement(&mut self, stmt: &AstStatement) {
+        DefaultWalker::walk_statement(self, stmt);
+    }
 
-#[derive(Debug, Error)]
-pub enum ExpressionParseError {
-    #[error("unexpected token found when parsing `factor`, got: {found:?}")]
-    InvalidFactorToken { found: Option<tokenizer::Token> },
+    fn visit_expression(&mut self, expr: &AstExpression) {
+        DefaultWalker::walk_expression(self, expr);
+    }
 }
-pub type ExpressionParseResult = error_stack::Result<AstExpression, ExpressionParseError>;
 
-impl AstParser {
-    pub fn new(mut tokens: Vec<tokenizer::Token>) -> Self {
-        tokens.reverse();
-        Self { tokens }
-    }
+/// Calls the `visit_*` methods of a visitor on the children of a node, in tree order
+pub struct DefaultWalker;
 
-    pub fn parse(&mut self) -> AstParseResult {
-        let mut nodes = vec![];
+impl DefaultWalker {
+    pub fn walk_program<V: AstVisitor + ?Sized>(visitor: &mut V, program: &AstProgram) {
+        for stmt in program {
+            visitor.visit_statement(stmt);
+        }
+    }
 
-        while !self.finished() {
-            match self.peek().unwrap() {
-                tokenizer::Token::Let => {
-                    self.eat(); // Let
-                    let name = self.eat();
-                    self.eat(); // Colon
-                    let t = self.eat();
-                    self.eat(); // `=`
-                    let value = self
-                        .expression()
-                        .change_context(AstParseError::InvalidExpression)
-                        .attach_printable("found an invalid expression")?;
-                    self.eat(); // `;`
-
-                    nodes.push(match (name, t, &value) {
-                        (
-                            Some(tokenizer::Token::Identifier(name)),
-                            Some(tokenizer::Token::Identifier(t)),
-                            AstExpression::BinaryOperation {
-                                left: _,
-                                operator: _,
-                                right: _,
-                            },
-                        ) => AstStatement::Let { value, name, t },
-                        (
-                            Some(tokenizer::Token::Identifier(name)),
-                            Some(tokenizer::Token::Identifier(t)),
-                            AstExpression::Number { raw: _, flags: _ },
-                        ) => AstStatement::Let { value, name, t },
-
-                        _ => {
-                            return Err(AstParseError::InvalidLetStatement)
-                                .attach_printable("found an invalid let statement")
-                        }
-                    });
-                }
-                tokenizer::Token::Exit => {
-                    self.eat();
-                    nodes.push(AstStatement::Exit {
-                        value: self
-                            .expression()
-                            .change_context(AstParseError::InvalidExpression)?,
-                    });
-                }
-                tokenizer::Token::Semicolon => {
-                    while self
-                        .peek()
-                        .is_some_and(|t| matches!(t, tokenizer::Token::Semicolon))
-                    {
-                        self.eat();
+    pub fn walk_statement<V: AstVisitor + ?Sized>(visitor: &mut V, stmt: &AstStatement) {
+        match stmt {
+            AstStatement::Let {
+                value,
+                name: _,
+                t: _,
+                mutable: _,
+                span: _,
+            } => visitor.visit_expression(value),
+            AstStatement::Exit { value, span: _ } => visitor.visit_expression(value),
+            AstStatement::Print {
+                value,
+                newline: _,
+                span: _,
+            } => visitor.visit_expression(value),
+            AstStatement::AssertEq {
+                left,
+                right,
+                t: _,
+                span: _,
+            } => {
+                visitor.visit_expression(left);
+                visitor.visit_expression(right);
+            }
+            AstStatement::Function { body, .. } => {
+                for stmt in body {
+                    visitor.visit_statement(stmt);
+                }
+            }
+            AstStatement::Impl {
+                name: _,
+                methods,
+                span: _,
+            } => {
+                for stmt in methods {
+                    visitor.visit_statement(stmt);
+                }
+            }
+            AstStatement::Call {
+                name: _,
+                args,
+                span: _,
+            } => {
+                for arg in args {
+                    visitor.visit_expression(arg);
+                }
+            }
+            AstStatement::MethodCall {
+                value,
+                t: _,
+                method: _,
+                args,
+                span: _,
+            } => {
+                visitor.visit_expression(value);
+                for arg in args {
+                    visitor.visit_expression(arg);
+                }
+            }
+            AstStatement::If {
+                condition,
+                then,
+                otherwise,
+                span: _,
+            } => {
+                visitor.visit_expression(condition);
+                for stmt in then.iter().chain(otherwise) {
+                    visitor.visit_statement(stmt);
+                }
+            }
+            AstStatement::Match {
+                value,
+                t: _,
+                arms,
+                span: _,
+            } => {
+                visitor.visit_expression(value);
+                for arm in arms {
+                    if let Some(pattern) = &arm.pattern {
+                        visitor.visit_expression(pattern);
+                    }
+                    for stmt in &arm.body {
+                        visitor.visit_statement(stmt);
                     }
                 }
-                _ => {
-                    return Err(AstParseError::ExpressionAtToplevel)
-                        .attach_printable("failed to parse program")
+            }
+            AstStatement::While {
+                condition,
+                body,
+                span: _,
+            } => {
+                visitor.visit_expression(condition);
+                for stmt in body {
+                    visitor.visit_statement(stmt);
                 }
             }
-        }
-
-        Ok(nodes)
-    }
-
-    fn expression(&mut self) -> ExpressionParseResult {
-        let mut node = self.term()?;
-        let term_operator = |token: &tokenizer::Token| {
-            matches!(
-                token,
-                tokenizer::Token::BinaryOperator {
-                    op: BinaryOp::Plus,
-                    offset: _
-                } | tokenizer::Token::BinaryOperator {
-                    op: BinaryOp::Minus,
-                    offset: _
+            AstStatement::For {
+                name: _,
+                t: _,
+                range,
+                inclusive: _,
+                body,
+                span: _,
+            } => {
+                visitor.visit_expression(range);
+                for stmt in body {
+                    visitor.visit_statement(stmt);
                 }
-            )
-        };
-        while self.peek().is_some_and(term_operator) {
-            if let Some(tokenizer::Token::BinaryOperator { op, offset: _ }) = self.eat() {
-                node = AstExpression::BinaryOperation {
-                    left: Box::new(node),
-                    operator: op,
-                    right: Box::new(self.term()?),
+            }
+            AstStatement::Assign {
+                name: _,
+                t: _,
+                value,
+                span: _,
+            }
+            | AstStatement::CompoundAssign {
+                name: _,
+                t: _,
+                operator: _,
+                value,
+                span: _,
+            } => visitor.visit_expression(value),
+            AstStatement::Return { value, span: _ } => {
+                if let Some(value) = value {
+                    visitor.visit_expression(value);
+                }
+            }
+            AstStatement::Block { body, span: _ } => {
+                for stmt in body {
+                    visitor.visit_statement(stmt);
                 }
             }
+            AstStatement::Const { .. }
+            | AstStatement::Struct { .. }
+            | AstStatement::Enum { .. }
+            | AstStatement::Break { span: _ }
+            | AstStatement::Continue { span: _ } => {}
         }
-        Ok(node)
     }
 
-    fn term(&mut self) -> ExpressionParseResult {
-        let mut node = self.factor()?;
-
-        let factor_operator = |token: &tokenizer::Token| {
-            matches!(
-                token,
-                tokenizer::Token::BinaryOperator {
-                    op: BinaryOp::Star,
-                    offset: _
+    pub fn walk_expression<V: AstVisitor + ?Sized>(visitor: &mut V, expr: &AstExpression) {
+        match expr {
+            AstExpression::Number { .. } => {}
+            AstExpression::Identifier { name: _, span: _ } => {}
+            AstExpression::Bool { value: _, span: _ } => {}
+            AstExpression::Variant { .. } => {}
+            AstExpression::Call {
+                name: _,
+                args,
+                span: _,
+            } => {
+                for arg in args {
+                    visitor.visit_expression(arg);
                 }
-            )
-        };
-
-        while self.peek().is_some_and(factor_operator) {
-            if let Some(tokenizer::Token::BinaryOperator { op, offset: _ }) = self.eat() {
-                node = AstExpression::BinaryOperation {
-                    left: Box::new(node),
-                    operator: op,
-                    right: Box::new(self.factor()?),
+            }
+            AstExpression::MethodCall {
+                value,
+                t: _,
+                method: _,
+                args,
+                span: _,
+            } => {
+                visitor.visit_expression(value);
+                for arg in args {
+                    visitor.visit_expression(arg);
                 }
             }
-        }
-        Ok(node)
-    }
-
-    fn factor(&mut self) -> ExpressionParseResult {
-        match self.peek().cloned() {
-            Some(tokenizer::Token::Number {
-                raw,
-                flags,
-                offset: _,
-            }) => {
-                self.eat();
-                Ok(AstExpression::Number { raw, flags })
+            AstExpression::ArrayLiteral { values, span: _ } => {
+                for value in values {
+                    visitor.visit_expression(value);
+                }
+            }
+            AstExpression::Index {
+                value,
+                index,
+                span: _,
+            } => {
+                visitor.visit_expression(value);
+                visitor.visit_expression(index);
             }
-            Some(tokenizer::Token::Identifier(name)) => {
-                self.eat();
-                Ok(AstExpression::Identifier { name })
+            AstExpression::Range {
+                start,
+                end,
+                inclusive: _,
+                span: _,
+            } => {
+                visitor.visit_expression(start);
+                visitor.visit_expression(end);
             }
-            Some(tokenizer::Token::OpenParen) => {
-                self.eat();
-                let node = self.expression()?;
-
-                if let Some(tokenizer::Token::CloseParen) = self.peek() {
-                    self.eat();
-                    Ok(node)
-                } else {
-                    Err(ExpressionParseError::InvalidFactorToken {
-                        found: self.peek().cloned(),
-                    })
-                    .attach_printable("unclosed parenthesis found")
+            AstExpression::Cast {
+                value,
+                t: _,
+                span: _,
+            } => visitor.visit_expression(value),
+            AstExpression::BinaryOperation {
+                left,
+                operator: _,
+                right,
+                span: _,
+            } => {
+                visitor.visit_expression(left);
+                visitor.visit_expression(right);
+            }
+            AstExpression::UnaryOperation {
+                operator: _,
+                operand,
+                span: _,
+            } => visitor.visit_expression(operand),
+            AstExpression::StructLiteral {
+                name: _,
+                fields,
+                span: _,
+            } => {
+                for (_, value) in fields {
+                    visitor.visit_expression(value);
                 }
             }
-            _ => Err(ExpressionParseError::InvalidFactorToken {
-                found: self.peek().cloned(),
-            })
-            .attach_printable("failed to parse factor"),
+            AstExpression::FieldAccess {
+                value,
+                field: _,
+                span: _,
+            } => visitor.visit_expression(value),
         }
     }
+}
 
-    fn finished(&self) -> bool {
-        self.tokens.is_empty()
+/// Traversal over the AST that can change the nodes it visits in place, otherwise the same as
+/// [`AstVisitor`]. Its methods have their own names, so one type can implement both
+pub trait AstVisitorMut {
+    fn visit_program_mut(&mut self, program: &mut AstProgram) {
+        DefaultWalkerMut::walk_program(self, program);
     }
 
-    fn peek(&self) -> Option<&tokenizer::Token> {
-        self.tokens.last()
+    fn visit_statement_mut(&mut self, stmt: &mut AstStatement) {
+        DefaultWalkerMut::walk_statement(self, stmt);
     }
-    fn eat(&mut self) -> Option<tokenizer::Token> {
-        self.tokens.pop()
+
+    fn visit_expression_mut(&mut self, expr: &mut AstExpression) {
+        DefaultWalkerMut::walk_expression(self, expr);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        ast::{self, AstExpression, AstStatement},
-        tokenizer,
-    };
-
-    #[test]
-    fn let_statement() {
-        {
-            let src = "let a: u64 = (123 + 69) * 2;".to_string();
-            let tokens = tokenizer::Tokenizer::new(src, "tests::let_statement".to_string())
-                .tokenize()
-                .unwrap();
+/// Calls the `visit_*_mut` methods of a visitor on the children of a node, in tree order
+pub struct DefaultWalkerMut;
 
-            assert_eq!(
-                ast::AstParser::new(tokens).parse().unwrap(),
-                vec![AstStatement::Let {
-                    value: AstExpression::BinaryOperation {
-                        left: Box::new(AstExpression::BinaryOperation {
-                            left: Box::new(AstExpression::Number {
-                                raw: "123".to_string(),
-                                flags: vec![]
-                            }),
-                            operator: tokenizer::BinaryOp::Plus,
-                            right: Box::new(AstExpression::Number {
-                                raw: "69".to_string(),
-                                flags: vec![]
-                            }),
-                        }),
-                        operator: tokenizer::BinaryOp::Star,
-                        right: Box::new(AstExpression::Number {
-                            raw: "2".to_string(),
-                            flags: vec![]
-                        })
-                    },
-                    name: String::from("a"),
-                    t: String::from("u64"),
-                },]
-            )
+impl DefaultWalkerMut {
+    pub fn walk_program<V: AstVisitorMut + ?Sized>(visitor: &mut V, program: &mut AstProgram) {
+        for stmt in program {
+            visitor.visit_statement_mut(stmt);
         }
     }
+
+    pub fn walk_statement<V: AstVisitorMut + ?Sized>(visitor: &mut V, stmt: &mut AstStatement) {
+        match stmt {
+            AstStatement::Let {
+                value,
+                name: _,
+                t: _,
+                mutable: _,
+                span: _,
+            } => visitor.visit_expression_mut(value),
+            AstStatement::Exit { value, span: _ } => visitor.visit_expression_mut(value),
+            AstStatement::Print {
+                value,
+                newline: _,
+                span: _,
+            } => visitor.visit_expression_mut(value),
+            AstStatement::AssertEq {
+                left,
+                right,
+                t: _,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(left);
+                visitor.visit_expression_mut(right);
+            }
+            AstStatement::Function { body, .. } => {
+                for stmt in body {
+                    visitor.visit_statement_mut(stmt);
+                }
+            }
+            AstStatement::Impl {
+                name: _,
+                methods,
+                span: _,
+            } => {
+                for stmt in methods {
+                    visitor.visit_statement_mut(stmt);
+                }
+            }
+            AstStatement::Call {
+                name: _,
+                args,
+                span: _,
+            } => {
+                for arg in args {
+                    visitor.visit_expression_mut(arg);
+                }
+            }
+            AstStatement::MethodCall {
+                value,
+                t: _,
+                method: _,
+                args,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(value);
+                for arg in args {
+                    visitor.visit_expression_mut(arg);
+                }
+            }
+            AstStatement::If {
+                condition,
+                then,
+                otherwise,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(condition);
+                for stmt in then.iter_mut().chain(otherwise) {
+                    visitor.visit_statement_mut(stmt);
+                }
+            }
+            AstStatement::Match {
+                value,
+                t: _,
+                arms,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(value);
+                for arm in arms {
+                    if let Some(pattern) = &mut arm.pattern {
+                        visitor.visit_expression_mut(pattern);
+                    }
+                    for stmt in &mut arm.body {
+                        visitor.visit_statement_mut(stmt);
+                    }
+                }
+            }
+            AstStatement::While {
+                condition,
+                body,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(condition);
+                for stmt in body {
+                    visitor.visit_statement_mut(stmt);
+                }
+            }
+            AstStatement::For {
+                name: _,
+                t: _,
+                range,
+                inclusive: _,
+                body,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(range);
+                for stmt in body {
+                    visitor.visit_statement_mut(stmt);
+                }
+            }
+            AstStatement::Assign {
+                name: _,
+                t: _,
+                value,
+                span: _,
+            }
+            | AstStatement::CompoundAssign {
+                name: _,
+                t: _,
+                operator: _,
+                value,
+                span: _,
+            } => visitor.visit_expression_mut(value),
+            AstStatement::Return { value, span: _ } => {
+                if let Some(value) = value {
+                    visitor.visit_expression_mut(value);
+                }
+            }
+            AstStatement::Block { body, span: _ } => {
+                for stmt in body {
+                    visitor.visit_statement_mut(stmt);
+                }
+            }
+            AstStatement::Const { .. }
+            | AstStatement::Struct { .. }
+            | AstStatement::Enum { .. }
+            | AstStatement::Break { span: _ }
+            | AstStatement::Continue { span: _ } => {}
+        }
+    }
+
+    pub fn walk_expression<V: AstVisitorMut + ?Sized>(visitor: &mut V, expr: &mut AstExpression) {
+        match expr {
+            AstExpression::Number { .. } => {}
+            AstExpression::Identifier { name: _, span: _ } => {}
+            AstExpression::Bool { value: _, span: _ } => {}
+            AstExpression::Variant { .. } => {}
+            AstExpression::Call {
+                name: _,
+                args,
+                span: _,
+            } => {
+                for arg in args {
+                    visitor.visit_expression_mut(arg);
+                }
+            }
+            AstExpression::MethodCall {
+                value,
+                t: _,
+                method: _,
+                args,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(value);
+                for arg in args {
+                    visitor.visit_expression_mut(arg);
+                }
+            }
+            AstExpression::ArrayLiteral { values, span: _ } => {
+                for value in values {
+                    visitor.visit_expression_mut(value);
+                }
+            }
+            AstExpression::Index {
+                value,
+                index,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(value);
+                visitor.visit_expression_mut(index);
+            }
+            AstExpression::Range {
+                start,
+                end,
+                inclusive: _,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(start);
+                visitor.visit_expression_mut(end);
+            }
+            AstExpression::Cast {
+                value,
+                t: _,
+                span: _,
+            } => visitor.visit_expression_mut(value),
+            AstExpression::BinaryOperation {
+                left,
+                operator: _,
+                right,
+                span: _,
+            } => {
+                visitor.visit_expression_mut(left);
+                visitor.visit_expression_mut(right);
+            }
+            AstExpression::UnaryOperation {
+                operator: _,
+                operand,
+                span: _,
+            } => visitor.visit_expression_mut(operand),
+            AstExpression::StructLiteral {
+                name: _,
+                fields,
+                span: _,
+            } => {
+                for (_, value) in fields {
+                    visitor.visit_expression_mut(value);
+                }
+            }
+            AstExpression::FieldAccess {
+                value,
+                field: _,
+                span: _,
+            } => visitor.visit_expression_mut(value),
+        }
+    }
+}
+
+/// Collects the names of all declared variables, in declaration order
+#[derive(Debug, Default)]
+pub struct VariableCollector {
+    pub names: Vec<Symbol>,
+}
+
+impl AstVisitor for VariableCollector {
+    fn visit_statement(&mut self, stmt: &AstStatement) {
+        if let AstStatement::Let { name, .. } = stmt {
+            self.names.push(*name);
+        }
+        DefaultWalker::walk_statement(self, stmt);
+    }
+}
+
+/// Collects the names of all called functions, in call order
+#[derive(Debug, Default)]
+pub struct CallCollector {
+    pub names: Vec<Symbol>,
+}
+
+impl AstVisitor for CallCollector {
+    fn visit_statement(&mut self, stmt: &AstStatement) {
+        match stmt {
+            AstStatement::Call { name, .. } => self.names.push(*name),
+            AstStatement::MethodCall { t, method, .. } => self.names.push(method_name(*t, *method)),
+            _ => {}
+        }
+        DefaultWalker::walk_statement(self, stmt);
+    }
+
+    fn visit_expression(&mut self, expr: &AstExpression) {
+        match expr {
+            AstExpression::Call { name, .. } => self.names.push(*name),
+            AstExpression::MethodCall { t, method, .. } => {
+                self.names.push(method_name(*t, *method))
+            }
+            _ => {}
+        }
+        DefaultWalker::walk_expression(self, expr);
+    }
+}
+
+/// Serializes `program` as an array of statement nodes. Every node is an object with its
+/// `"kind"`, the fields of the node with their children nested as nodes, and its `"span"`
+pub fn program_to_json(program: &AstProgram) -> Json {
+    Json::Array(program.iter().map(statement_to_json).collect())
+}
+
+fn statement_to_json(stmt: &AstStatement) -> Json {
+    let body = |stmts: &[AstStatement]| Json::Array(stmts.iter().map(statement_to_json).collect());
+    let (kind, fields) = match stmt {
+        AstStatement::Let {
+            value,
+            name,
+            t,
+            mutable,
+            span: _,
+        } => (
+            "let",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("type", Json::string(t.as_str())),
+                ("mutable", Json::from(*mutable)),
+                ("value", expression_to_json(value)),
+            ],
+        ),
+        AstStatement::Exit { value, span: _ } => {
+            ("exit", vec![("value", expression_to_json(value))])
+        }
+        AstStatement::Print {
+            value,
+            newline,
+            span: _,
+        } => (
+            "print",
+            vec![
+                ("value", expression_to_json(value)),
+                ("newline", Json::from(*newline)),
+            ],
+        ),
+        AstStatement::AssertEq {
+            left,
+            right,
+            t,
+            span: _,
+        } => (
+            "assert_eq",
+            vec![
+                ("left", expression_to_json(left)),
+                ("right", expression_to_json(right)),
+                ("type", Json::string(t.as_str())),
+            ],
+        ),
+        AstStatement::Const {
+            name,
+            t,
+            value: const_eval::Value::Integer(value),
+            span: _,
+        } => (
+            "const",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("type", Json::string(t.as_str())),
+                // As a string, JSON numbers can't hold every `u64`
+                ("value", Json::string(value.to_string())),
+            ],
+        ),
+        AstStatement::Function {
+            doc,
+            attrs,
+            name,
+            params,
+            return_type,
+            body: stmts,
+            span: _,
+        } => (
+            "function",
+            vec![
+                ("doc", doc.as_deref().map_or(Json::Null, Json::string)),
+                (
+                    "attrs",
+                    Json::Array(
+                        attrs
+                            .iter()
+                            .map(|attr| match attr {
+                                Attribute::Inline => Json::string("inline"),
+                                Attribute::NoMangle => Json::string("no_mangle"),
+                                Attribute::Test => Json::string("test"),
+                                Attribute::Deprecated { message } => Json::object([
+                                    ("name", Json::string("deprecated")),
+                                    (
+                                        "message",
+                                        message.as_deref().map_or(Json::Null, Json::string),
+                                    ),
+                                ]),
+                            })
+                            .collect(),
+                    ),
+                ),
+                ("name", Json::string(name.as_str())),
+                (
+                    "params",
+                    Json::Array(
+                        params
+                            .iter()
+                            .map(|param| {
+                                Json::object([
+                                    ("name", Json::string(param.name.as_str())),
+                                    ("type", Json::string(param.t.as_str())),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "return_type",
+                    return_type.map_or(Json::Null, |t| Json::string(t.as_str())),
+                ),
+                ("body", body(stmts)),
+            ],
+        ),
+        AstStatement::Struct {
+            doc,
+            name,
+            fields,
+            span: _,
+        } => (
+            "struct",
+            vec![
+                ("doc", doc.as_deref().map_or(Json::Null, Json::string)),
+                ("name", Json::string(name.as_str())),
+                (
+                    "fields",
+                    Json::Array(
+                        fields
+                            .iter()
+                            .map(|field| {
+                                Json::object([
+                                    ("name", Json::string(field.name.as_str())),
+                                    ("type", Json::string(field.t.as_str())),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ],
+        ),
+        AstStatement::Enum {
+            name,
+            variants,
+            span: _,
+        } => (
+            "enum",
+            vec![
+                ("name", Json::string(name.as_str())),
+                (
+                    "variants",
+                    Json::Array(
+                        variants
+                            .iter()
+                            .map(|variant| Json::string(variant.as_str()))
+                            .collect(),
+                    ),
+                ),
+            ],
+        ),
+        AstStatement::Impl {
+            name,
+            methods,
+            span: _,
+        } => (
+            "impl",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("methods", body(methods)),
+            ],
+        ),
+        AstStatement::Call {
+            name,
+            args,
+            span: _,
+        } => (
+            "call",
+            vec![
+                ("name", Json::string(name.as_str())),
+                (
+                    "args",
+                    Json::Array(args.iter().map(expression_to_json).collect()),
+                ),
+            ],
+        ),
+        AstStatement::MethodCall {
+            value,
+            t,
+            method,
+            args,
+            span: _,
+        } => (
+            "method_call",
+            vec![
+                ("value", expression_to_json(value)),
+                ("type", Json::string(t.as_str())),
+                ("method", Json::string(method.as_str())),
+                (
+                    "args",
+                    Json::Array(args.iter().map(expression_to_json).collect()),
+                ),
+            ],
+        ),
+        AstStatement::If {
+            condition,
+            then,
+            otherwise,
+            span: _,
+        } => (
+            "if",
+            vec![
+                ("condition", expression_to_json(condition)),
+                ("then", body(then)),
+                ("otherwise", body(otherwise)),
+            ],
+        ),
+        AstStatement::Match {
+            value,
+            t,
+            arms,
+            span: _,
+        } => (
+            "match",
+            vec![
+                ("value", expression_to_json(value)),
+                ("type", Json::string(t.as_str())),
+                (
+                    "arms",
+                    Json::Array(
+                        arms.iter()
+                            .map(|arm| {
+                                Json::object([
+                                    (
+                                        "pattern",
+                                        arm.pattern.as_ref().map_or(Json::Null, expression_to_json),
+                                    ),
+                                    ("body", body(&arm.body)),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ],
+        ),
+        AstStatement::While {
+            condition,
+            body: stmts,
+            span: _,
+        } => (
+            "while",
+            vec![
+                ("condition", expression_to_json(condition)),
+                ("body", body(stmts)),
+            ],
+        ),
+        AstStatement::For {
+            name,
+            t,
+            range,
+            inclusive,
+            body: stmts,
+            span: _,
+        } => (
+            "for",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("type", Json::string(t.as_str())),
+                ("range", expression_to_json(range)),
+                ("inclusive", Json::Bool(*inclusive)),
+                ("body", body(stmts)),
+            ],
+        ),
+        AstStatement::Assign {
+            name,
+            t,
+            value,
+            span: _,
+        } => (
+            "assign",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("type", Json::string(t.as_str())),
+                ("value", expression_to_json(value)),
+            ],
+        ),
+        AstStatement::CompoundAssign {
+            name,
+            t,
+            operator,
+            value,
+            span: _,
+        } => (
+            "compound_assign",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("type", Json::string(t.as_str())),
+                ("operator", Json::string(operator.to_string())),
+                ("value", expression_to_json(value)),
+            ],
+        ),
+        AstStatement::Return { value, span: _ } => (
+            "return",
+            vec![(
+                "value",
+                value.as_ref().map_or(Json::Null, expression_to_json),
+            )],
+        ),
+        AstStatement::Block {
+            body: stmts,
+            span: _,
+        } => ("block", vec![("body", body(stmts))]),
+        AstStatement::Break { span: _ } => ("break", vec![]),
+        AstStatement::Continue { span: _ } => ("continue", vec![]),
+    };
+    node(kind, fields, stmt.span())
+}
+
+fn expression_to_json(expr: &AstExpression) -> Json {
+    let (kind, fields) = match expr {
+        AstExpression::Number {
+            raw,
+            flags: _,
+            suffix,
+            span: _,
+        } => (
+            "number",
+            vec![
+                ("raw", Json::string(raw)),
+                ("suffix", suffix.as_deref().map_or(Json::Null, Json::string)),
+            ],
+        ),
+        AstExpression::BinaryOperation {
+            left,
+            operator,
+            right,
+            span: _,
+        } => (
+            "binary",
+            vec![
+                ("operator", Json::string(operator.to_string())),
+                ("left", expression_to_json(left)),
+                ("right", expression_to_json(right)),
+            ],
+        ),
+        AstExpression::UnaryOperation {
+            operator,
+            operand,
+            span: _,
+        } => (
+            "unary",
+            vec![
+                ("operator", Json::string(operator.to_string())),
+                ("operand", expression_to_json(operand)),
+            ],
+        ),
+        AstExpression::Identifier { name, span: _ } => {
+            ("identifier", vec![("name", Json::string(name.as_str()))])
+        }
+        AstExpression::Bool { value, span: _ } => ("bool", vec![("value", Json::from(*value))]),
+        AstExpression::Call {
+            name,
+            args,
+            span: _,
+        } => (
+            "call",
+            vec![
+                ("name", Json::string(name.as_str())),
+                (
+                    "args",
+                    Json::Array(args.iter().map(expression_to_json).collect()),
+                ),
+            ],
+        ),
+        AstExpression::Cast { value, t, span: _ } => (
+            "cast",
+            vec![
+                ("value", expression_to_json(value)),
+                ("type", Json::string(t.as_str())),
+            ],
+        ),
+        AstExpression::StructLiteral {
+            name,
+            fields,
+            span: _,
+        } => (
+            "struct_literal",
+            vec![
+                ("name", Json::string(name.as_str())),
+                (
+                    "fields",
+                    Json::Array(
+                        fields
+                            .iter()
+                            .map(|(field, value)| {
+                                Json::object([
+                                    ("name", Json::string(field.as_str())),
+                                    ("value", expression_to_json(value)),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+            ],
+        ),
+        AstExpression::FieldAccess {
+            value,
+            field,
+            span: _,
+        } => (
+            "field_access",
+            vec![
+                ("value", expression_to_json(value)),
+                ("field", Json::string(field.as_str())),
+            ],
+        ),
+        AstExpression::MethodCall {
+            value,
+            t,
+            method,
+            args,
+            span: _,
+        } => (
+            "method_call",
+            vec![
+                ("value", expression_to_json(value)),
+                ("type", Json::string(t.as_str())),
+                ("method", Json::string(method.as_str())),
+                (
+                    "args",
+                    Json::Array(args.iter().map(expression_to_json).collect()),
+                ),
+            ],
+        ),
+        AstExpression::Variant {
+            name,
+            variant,
+            span: _,
+        } => (
+            "variant",
+            vec![
+                ("name", Json::string(name.as_str())),
+                ("variant", Json::string(variant.as_str())),
+            ],
+        ),
+        AstExpression::ArrayLiteral { values, span: _ } => (
+            "array",
+            vec![(
+                "values",
+                Json::Array(values.iter().map(expression_to_json).collect()),
+            )],
+        ),
+        AstExpression::Index {
+            value,
+            index,
+            span: _,
+        } => (
+            "index",
+            vec![
+                ("value", expression_to_json(value)),
+                ("index", expression_to_json(index)),
+            ],
+        ),
+        AstExpression::Range {
+            start,
+            end,
+            inclusive,
+            span: _,
+        } => (
+            "range",
+            vec![
+                ("start", expression_to_json(start)),
+                ("end", expression_to_json(end)),
+                ("inclusive", Json::Bool(*inclusive)),
+            ],
+        ),
+    };
+    node(kind, fields, expr.span())
+}
+
+fn node(kind: &str, fields: Vec<(&str, Json)>, span: tokenizer::Span) -> Json {
+    let span = Json::object([
+        ("start", Json::from(span.start)),
+        ("end", Json::from(span.end)),
+    ]);
+    Json::object(
+        std::iter::once(("kind", Json::string(kind)))
+            .chain(fields)
+            .chain([("span", span)]),
+    )
+}
+
+#[derive(Debug, Error)]
+pub enum AstParseError {
+    #[error("invalid expression found during ast parsing")]
+    InvalidExpression { span: tokenizer::Span },
+    #[error("found an expression at the top level")]
+    ExpressionAtToplevel { span: tokenizer::Span },
+    #[error("invalid let statement")]
+    InvalidLetStatement { span: tokenizer::Span },
+    #[error("invalid function definition")]
+    InvalidFunction { span: tokenizer::Span },
+    #[error("functions can only be defined at the top level")]
+    NestedFunction { span: tokenizer::Span },
+    #[error("invalid constant declaration")]
+    InvalidConst { span: tokenizer::Span },
+    #[error("constants can only be declared at the top level")]
+    NestedConst { span: tokenizer::Span },
+    #[error("invalid struct definition")]
+    InvalidStruct { span: tokenizer::Span },
+    #[error("structs can only be defined at the top level")]
+    NestedStruct { span: tokenizer::Span },
+    #[error("invalid impl block")]
+    InvalidImpl { span: tokenizer::Span },
+    #[error("invalid assert_eq!")]
+    InvalidAssert { span: tokenizer::Span },
+    #[error("impl blocks can only be at the top level")]
+    NestedImpl { span: tokenizer::Span },
+    #[error("invalid `{name}` literal")]
+    InvalidStructLiteral { name: Symbol, span: tokenizer::Span },
+    #[error("`{t}` has no field `{field}`")]
+    UnknownField {
+        t: Symbol,
+        field: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("`{t}` has no method `{method}`")]
+    UnknownMethod {
+        t: Symbol,
+        method: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("a `{t}` can't be used as a number")]
+    NotANumber { t: Symbol, span: tokenizer::Span },
+    #[error("a `{t}` can't be used as an integer")]
+    NotAnInteger { t: Symbol, span: tokenizer::Span },
+    #[error("invalid enum definition")]
+    InvalidEnum { span: tokenizer::Span },
+    #[error("enums can only be defined at the top level")]
+    NestedEnum { span: tokenizer::Span },
+    #[error("`{t}` has no variant `{variant}`")]
+    UnknownVariant {
+        t: Symbol,
+        variant: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("invalid array")]
+    InvalidArray { span: tokenizer::Span },
+    #[error("expected an array of {expected} values, found {found}")]
+    ArrayLength {
+        expected: usize,
+        found: usize,
+        span: tokenizer::Span,
+    },
+    #[error("a `{t}` can't be indexed")]
+    NotAnArray { t: Symbol, span: tokenizer::Span },
+    #[error("a `{t}` can't be used as an index")]
+    InvalidIndex { t: Symbol, span: tokenizer::Span },
+    #[error("index {index} is out of bounds for an array of length {length}")]
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+        span: tokenizer::Span,
+    },
+    #[error("the value of a constant has to be known at compile time")]
+    NotConstant { span: tokenizer::Span },
+    #[error("the value doesn't fit into `{t}`")]
+    Overflow { t: Symbol, span: tokenizer::Span },
+    #[error("invalid if statement")]
+    InvalidIf { span: tokenizer::Span },
+    #[error("invalid match statement")]
+    InvalidMatch { span: tokenizer::Span },
+    #[error("the match doesn't handle every `{t}`")]
+    NonExhaustiveMatch {
+        t: Symbol,
+        missing: Vec<String>,
+        span: tokenizer::Span,
+    },
+    #[error("invalid while loop")]
+    InvalidWhile { span: tokenizer::Span },
+    #[error("invalid for loop")]
+    InvalidFor { span: tokenizer::Span },
+    #[error("`return` outside of a function")]
+    ReturnOutsideFunction { span: tokenizer::Span },
+    #[error("`{keyword}` outside of a loop")]
+    OutsideLoop {
+        keyword: &'static str,
+        span: tokenizer::Span,
+    },
+    #[error("invalid return statement")]
+    InvalidReturn { span: tokenizer::Span },
+    #[error("`{name}` doesn't return a `{t}` on every path")]
+    MissingReturn {
+        name: Symbol,
+        t: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("`{name}` returns `!`, but the end of it can be reached")]
+    MissingDivergence { name: Symbol, span: tokenizer::Span },
+    #[error("invalid assignment")]
+    InvalidAssignment { span: tokenizer::Span },
+    #[error("can't assign to `{name}`, it isn't declared with `let mut`")]
+    ImmutableAssignment {
+        name: Symbol,
+        span: tokenizer::Span,
+        declaration: tokenizer::Span,
+    },
+    #[error("expected a value of type `{expected}`, found one of type `{found}`")]
+    TypeMismatch {
+        expected: Symbol,
+        found: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("use of undeclared variable `{name}`")]
+    UndeclaredIdentifier { name: Symbol, span: tokenizer::Span },
+    #[error("call to undeclared function `{name}`")]
+    UndeclaredFunction { name: Symbol, span: tokenizer::Span },
+    #[error("`{name}` returns no value")]
+    NoValue { name: Symbol, span: tokenizer::Span },
+    #[error("unknown type `{t}`")]
+    UnknownType { t: Symbol, span: tokenizer::Span },
+    #[error("`{name}` takes {expected} arguments but {found} were given")]
+    ArityMismatch {
+        name: Symbol,
+        expected: usize,
+        found: usize,
+        span: tokenizer::Span,
+    },
+    #[error("division by zero")]
+    DivisionByZero { span: tokenizer::Span },
+    #[error("unexpected end of file")]
+    UnexpectedEof { span: tokenizer::Span },
+    #[error("missing end of file token")]
+    MissingEof,
+}
+
+impl AstParseError {
+    /// Where in the source code parsing failed, if known
+    pub fn span(&self) -> Option<tokenizer::Span> {
+        match self {
+            Self::InvalidExpression { span }
+            | Self::ExpressionAtToplevel { span }
+            | Self::InvalidLetStatement { span }
+            | Self::InvalidFunction { span }
+            | Self::NestedFunction { span }
+            | Self::InvalidConst { span }
+            | Self::NestedConst { span }
+            | Self::InvalidStruct { span }
+            | Self::NestedStruct { span }
+            | Self::InvalidImpl { span }
+            | Self::InvalidAssert { span }
+            | Self::NestedImpl { span }
+            | Self::InvalidStructLiteral { name: _, span }
+            | Self::UnknownField { span, .. }
+            | Self::UnknownMethod { span, .. }
+            | Self::NotANumber { t: _, span }
+            | Self::NotAnInteger { t: _, span }
+            | Self::InvalidEnum { span }
+            | Self::NestedEnum { span }
+            | Self::UnknownVariant { span, .. }
+            | Self::InvalidArray { span }
+            | Self::ArrayLength { span, .. }
+            | Self::NotAnArray { t: _, span }
+            | Self::InvalidIndex { t: _, span }
+            | Self::IndexOutOfBounds { span, .. }
+            | Self::NotConstant { span }
+            | Self::Overflow { t: _, span }
+            | Self::InvalidIf { span }
+            | Self::InvalidMatch { span }
+            | Self::NonExhaustiveMatch { span, .. }
+            | Self::InvalidWhile { span }
+            | Self::InvalidFor { span }
+            | Self::ReturnOutsideFunction { span }
+            | Self::OutsideLoop { keyword: _, span }
+            | Self::InvalidReturn { span }
+            | Self::MissingReturn { span, .. }
+            | Self::MissingDivergence { name: _, span }
+            | Self::InvalidAssignment { span }
+            | Self::ImmutableAssignment { span, .. }
+            | Self::TypeMismatch { span, .. }
+            | Self::UndeclaredIdentifier { name: _, span }
+            | Self::UndeclaredFunction { name: _, span }
+            | Self::NoValue { name: _, span }
+            | Self::UnknownType { t: _, span }
+            | Self::ArityMismatch { span, .. }
+            | Self::DivisionByZero { span }
+            | Self::UnexpectedEof { span } => Some(*span),
+            Self::MissingEof => None,
+        }
+    }
+}
+
+pub type AstParseResult = error_stack::Result<AstProgram, AstParseError>;
+
+/// Something that parses fine but is likely a mistake
+#[derive(Debug, Error, PartialEq, Eq, Clone)]
+pub enum Warning {
+    #[error("`{name}` is deprecated{}", message.as_ref().map(|message| format!(": {message}")).unwrap_or_default())]
+    UsedDeprecated {
+        name: Symbol,
+        message: Option<String>,
+        span: tokenizer::Span,
+    },
+    #[error("some values of the pattern are matched by an arm before it")]
+    OverlappingPattern { span: tokenizer::Span },
+}
+
+impl Warning {
+    /// Where in the source code the warning is about
+    pub fn span(&self) -> tokenizer::Span {
+        match self {
+            Self::UsedDeprecated { span, .. } | Self::OverlappingPattern { span } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExpressionParseError {
+    #[error("unexpected {found} found when parsing `factor`")]
+    InvalidFactorToken {
+        found: TokenKind,
+        span: tokenizer::Span,
+    },
+    #[error("unexpected end of file")]
+    UnexpectedEof { span: tokenizer::Span },
+    #[error("use of undeclared variable `{name}`")]
+    UndeclaredIdentifier { name: Symbol, span: tokenizer::Span },
+    #[error("call to undeclared function `{name}`")]
+    UndeclaredFunction { name: Symbol, span: tokenizer::Span },
+    #[error("`{name}` returns no value")]
+    NoValue { name: Symbol, span: tokenizer::Span },
+    #[error("unknown type `{t}`")]
+    UnknownType { t: Symbol, span: tokenizer::Span },
+    #[error("`{name}` takes {expected} arguments but {found} were given")]
+    ArityMismatch {
+        name: Symbol,
+        expected: usize,
+        found: usize,
+        span: tokenizer::Span,
+    },
+    #[error("division by zero")]
+    DivisionByZero { span: tokenizer::Span },
+    #[error("invalid `{name}` literal")]
+    InvalidStructLiteral { name: Symbol, span: tokenizer::Span },
+    #[error("`{t}` has no field `{field}`")]
+    UnknownField {
+        t: Symbol,
+        field: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("`{t}` has no method `{method}`")]
+    UnknownMethod {
+        t: Symbol,
+        method: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("a `{t}` can't be used as a number")]
+    NotANumber { t: Symbol, span: tokenizer::Span },
+    #[error("a `{t}` can't be used as an integer")]
+    NotAnInteger { t: Symbol, span: tokenizer::Span },
+    #[error("expected a value of type `{expected}`, found one of type `{found}`")]
+    TypeMismatch {
+        expected: Symbol,
+        found: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("`{t}` has no variant `{variant}`")]
+    UnknownVariant {
+        t: Symbol,
+        variant: Symbol,
+        span: tokenizer::Span,
+    },
+    #[error("invalid array")]
+    InvalidArray { span: tokenizer::Span },
+    #[error("a `{t}` can't be indexed")]
+    NotAnArray { t: Symbol, span: tokenizer::Span },
+    #[error("a `{t}` can't be used as an index")]
+    InvalidIndex { t: Symbol, span: tokenizer::Span },
+    #[error("index {index} is out of bounds for an array of length {length}")]
+    IndexOutOfBounds {
+        index: i64,
+        length: usize,
+        span: tokenizer::Span,
+    },
+}
+
+/// The error of a statement whose expression starting at `value_span` failed to parse.
+/// Undeclared names, type errors and a premature end of file are reported as such, anything
+/// else as an invalid expression
+fn statement_error(
+    report: error_stack::Report<ExpressionParseError>,
+    value_span: tokenizer::Span,
+) -> error_stack::Report<AstParseError> {
+    let context = match *report.current_context() {
+        ExpressionParseError::UndeclaredIdentifier { name, span } => {
+            AstParseError::UndeclaredIdentifier { name, span }
+        }
+        ExpressionParseError::UndeclaredFunction { name, span } => {
+            AstParseError::UndeclaredFunction { name, span }
+        }
+        ExpressionParseError::NoValue { name, span } => AstParseError::NoValue { name, span },
+        ExpressionParseError::UnknownType { t, span } => AstParseError::UnknownType { t, span },
+        ExpressionParseError::ArityMismatch {
+            name,
+            expected,
+            found,
+            span,
+        } => AstParseError::ArityMismatch {
+            name,
+            expected,
+            found,
+            span,
+        },
+        ExpressionParseError::DivisionByZero { span } => AstParseError::DivisionByZero { span },
+        ExpressionParseError::UnexpectedEof { span } => AstParseError::UnexpectedEof { span },
+        ExpressionParseError::InvalidStructLiteral { name, span } => {
+            AstParseError::InvalidStructLiteral { name, span }
+        }
+        ExpressionParseError::UnknownField { t, field, span } => {
+            AstParseError::UnknownField { t, field, span }
+        }
+        ExpressionParseError::UnknownMethod { t, method, span } => {
+            AstParseError::UnknownMethod { t, method, span }
+        }
+        ExpressionParseError::NotANumber { t, span } => AstParseError::NotANumber { t, span },
+        ExpressionParseError::NotAnInteger { t, span } => AstParseError::NotAnInteger { t, span },
+        ExpressionParseError::UnknownVariant { t, variant, span } => {
+            AstParseError::UnknownVariant { t, variant, span }
+        }
+        ExpressionParseError::InvalidArray { span } => AstParseError::InvalidArray { span },
+        ExpressionParseError::NotAnArray { t, span } => AstParseError::NotAnArray { t, span },
+        ExpressionParseError::InvalidIndex { t, span } => AstParseError::InvalidIndex { t, span },
+        ExpressionParseError::IndexOutOfBounds {
+            index,
+            length,
+            span,
+        } => AstParseError::IndexOutOfBounds {
+            index,
+            length,
+            span,
+        },
+        ExpressionParseError::TypeMismatch {
+            expected,
+            found,
+            span,
+        } => AstParseError::TypeMismatch {
+            expected,
+            found,
+            span,
+        },
+        ExpressionParseError::InvalidFactorToken { found: _, span: _ } => {
+            return report
+                .change_context(AstParseError::InvalidExpression { span: value_span })
+                .attach_printable(format!("found an invalid expression at {value_span}"));
+        }
+    };
+    report.change_context(context)
+}
+
+/// The error of a value starting at `span` that couldn't be computed as a `t` at compile time
+fn const_error(
+    error: const_eval::ConstEvalError,
+    t: Symbol,
+    span: tokenizer::Span,
+) -> error_stack::Report<AstParseError> {
+    let context = match error {
+        const_eval::ConstEvalError::Overflow { .. }
+        | const_eval::ConstEvalError::ShiftOverflow { .. } => AstParseError::Overflow { t, span },
+        _ => AstParseError::NotConstant { span },
+    };
+    error_stack::Report::new(error).change_context(context)
+}
+
+fn is_integer_zero(expr: &AstExpression) -> bool {
+    matches!(
+        expr,
+        AstExpression::Number { raw, flags, suffix: _, span: _ } if const_eval::integer_literal(raw, flags) == Some(0)
+    )
+}
+
+/// The binary operators from the loosest to the tightest binding, the ones in a row bind equally
+/// tight. Unary operators and `as` bind tighter than all of them
+const PRECEDENCE: &[&[BinaryOp]] = &[
+    &[BinaryOp::Or],
+    &[BinaryOp::And],
+    &[
+        BinaryOp::DoubleEqual,
+        BinaryOp::NotEqual,
+        BinaryOp::Less,
+        BinaryOp::Greater,
+        BinaryOp::LessEqual,
+        BinaryOp::GreaterEqual,
+    ],
+    &[BinaryOp::BitOr],
+    &[BinaryOp::BitXor],
+    &[BinaryOp::BitAnd],
+    &[BinaryOp::ShiftLeft, BinaryOp::ShiftRight],
+    &[BinaryOp::Plus, BinaryOp::Minus],
+    &[BinaryOp::Star, BinaryOp::Slash, BinaryOp::Percent],
+];
+
+/// How tightly `op` binds, starting at 1 for the loosest. `None` for `=`, which isn't an
+/// operator inside of expressions
+/// The element type and length of an array type like `[u64; 4]`, `None` for any other type
+pub fn array_type(t: Symbol) -> Option<(Symbol, usize)> {
+    let (element, length) = t
+        .as_str()
+        .strip_prefix('[')?
+        .strip_suffix(']')?
+        .split_once("; ")?;
+    Some((Symbol::intern(element), length.parse().ok()?))
+}
+
+/// The type of an array of `length` values of type `element`, types are compared by name
+fn array_of(element: Symbol, length: usize) -> Symbol {
+    Symbol::intern(&format!("[{element}; {length}]"))
+}
+
+fn binding_power(op: &BinaryOp) -> Option<usize> {
+    PRECEDENCE
+        .iter()
+        .position(|operators| operators.contains(op))
+        .map(|position| position + 1)
+}
+
+/// Whether `token` can only start a statement, which is where parsing picks up again after an error
+fn starts_statement(token: &tokenizer::Token) -> bool {
+    matches!(
+        token,
+        tokenizer::Token::Let { span: _ }
+            | tokenizer::Token::Const { span: _ }
+            | tokenizer::Token::Exit { span: _ }
+            | tokenizer::Token::Print { span: _ }
+            | tokenizer::Token::Println { span: _ }
+            | tokenizer::Token::Fn { span: _ }
+            | tokenizer::Token::Hash { span: _ }
+            | tokenizer::Token::Struct { span: _ }
+            | tokenizer::Token::Enum { span: _ }
+            | tokenizer::Token::If { span: _ }
+            | tokenizer::Token::Match { span: _ }
+            | tokenizer::Token::Impl { span: _ }
+            | tokenizer::Token::While { span: _ }
+            | tokenizer::Token::For { span: _ }
+            | tokenizer::Token::Return { span: _ }
+            | tokenizer::Token::Break { span: _ }
+            | tokenizer::Token::Continue { span: _ }
+    )
+}
+
+/// Whether `body` can leave the loop it is the body of
+fn breaks(body: &[AstStatement]) -> bool {
+    body.iter().any(|stmt| match stmt {
+        AstStatement::Break { span: _ } => true,
+        AstStatement::If {
+            condition: _,
+            then,
+            otherwise,
+            span: _,
+        } => breaks(then) || breaks(otherwise),
+        AstStatement::Block { body, span: _ } => breaks(body),
+        AstStatement::Match {
+            value: _,
+            t: _,
+            arms,
+            span: _,
+        } => arms.iter().any(|arm| breaks(&arm.body)),
+        // A `break` in there leaves the inner loop
+        _ => false,
+    })
+}
+
+/// Whether `op` shifts its left side by its right side
+pub(crate) fn is_shift(op: &BinaryOp) -> bool {
+    matches!(op, BinaryOp::ShiftLeft | BinaryOp::ShiftRight)
+}
+
+/// Whether `op` only computes with integers
+fn integer_only(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Percent
+            | BinaryOp::BitAnd
+            | BinaryOp::BitOr
+            | BinaryOp::BitXor
+            | BinaryOp::ShiftLeft
+            | BinaryOp::ShiftRight
+    )
+}
+
+/// Whether arithmetic of no particular type has a float literal in it, making it an `f64`
+pub(crate) fn has_float_literal(expr: &AstExpression) -> bool {
+    match expr {
+        AstExpression::Number { flags, .. } => flags.contains(&tokenizer::NumberTypeFlag::Floating),
+        AstExpression::BinaryOperation {
+            left,
+            operator: _,
+            right,
+            span: _,
+        } => has_float_literal(left) || has_float_literal(right),
+        AstExpression::UnaryOperation {
+            operator: _,
+            operand,
+            span: _,
+        } => has_float_literal(operand),
+        _ => false,
+    }
+}
+pub type ExpressionParseResult = error_stack::Result<AstExpression, ExpressionParseError>;
+
+impl<'src> AstParser<'src> {
+    pub fn new(tokens: Vec<tokenizer::Token<'src>>) -> Self {
+        Self {
+            tokens: TokenCursor::new(tokens),
+            scopes: vec![HashMap::new()],
+            constants: HashMap::new(),
+            values: HashMap::new(),
+            function: None,
+            loop_depth: 0,
+            statement_call: false,
+            functions: HashMap::new(),
+            deprecated: HashMap::new(),
+            structs: HashMap::from([
+                (Symbol::intern(RANGE), range_fields()),
+                (Symbol::intern(RANGE_INCLUSIVE), range_fields()),
+            ]),
+            enums: HashMap::new(),
+            errors: vec![],
+            warnings: vec![],
+        }
+    }
+
+    /// What the parser warned about so far, in source order
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Parses the whole program. A statement that fails to parse doesn't stop the parser, so
+    /// the error holds every failed statement, grouped into one report in source order
+    pub fn parse(&mut self) -> AstParseResult {
+        let mut nodes = vec![];
+
+        while !self.finished() {
+            if self.tokens.peek().is_none() {
+                self.errors.push(
+                    error_stack::Report::new(AstParseError::MissingEof)
+                        .attach_printable("the token stream ended without an end of file token"),
+                );
+                break;
+            }
+            if let Some(statement) = self.recovering_statement() {
+                nodes.push(statement);
+            }
+        }
+
+        let mut errors = std::mem::take(&mut self.errors).into_iter();
+        match errors.next() {
+            Some(mut first) => {
+                for error in errors {
+                    first.extend_one(error);
+                }
+                Err(first)
+            }
+            None => Ok(nodes),
+        }
+    }
+
+    /// Like [`AstParser::statement`], but a statement that fails to parse is recorded and
+    /// skipped, so the one after it is parsed next
+    fn recovering_statement(&mut self) -> Option<AstStatement> {
+        let remaining = self.tokens.remaining();
+        match self.statement() {
+            Ok(statement) => statement,
+            Err(report) => {
+                self.errors.push(report);
+                // Otherwise the same token fails again
+                if self.tokens.remaining() == remaining && !self.finished() {
+                    self.tokens.eat();
+                }
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    /// Skips the rest of a statement that failed to parse, up to and including the next `;`,
+    /// or up to the next keyword starting a statement. Blocks are skipped as a whole, and the
+    /// `}` closing the block around the statement is left for it
+    fn synchronize(&mut self) {
+        // Some errors are only found once the whole statement is read
+        let at_else = matches!(self.tokens.peek(), Some(tokenizer::Token::Else { span: _ }));
+        if matches!(
+            self.tokens.previous(),
+            Some(
+                tokenizer::Token::Semicolon { span: _ } | tokenizer::Token::CloseBrace { span: _ }
+            )
+        ) && !at_else
+        {
+            return;
+        }
+        let mut depth = 0usize;
+        while let Some(token) = self.tokens.peek() {
+            match token {
+                tokenizer::Token::Eof { span: _ } => return,
+                tokenizer::Token::OpenBrace { span: _ } => depth += 1,
+                tokenizer::Token::CloseBrace { span: _ } if depth == 0 => return,
+                tokenizer::Token::CloseBrace { span: _ } => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.tokens.eat();
+                        // The block might have been the first one of an `if`
+                        if !matches!(self.tokens.peek(), Some(tokenizer::Token::Else { span: _ })) {
+                            return;
+                        }
+                        continue;
+                    }
+                }
+                tokenizer::Token::Semicolon { span: _ } if depth == 0 => {
+                    self.tokens.eat();
+                    return;
+                }
+                token if depth == 0 && starts_statement(token) => return,
+                _ => {}
+            }
+            self.tokens.eat();
+        }
+    }
+
+    /// Parses the next statement. Semicolons and doc comments that aren't in front of a function
+    /// or struct aren't statements and give `None`
+    fn statement(&mut self) -> error_stack::Result<Option<AstStatement>, AstParseError> {
+        let span = self.tokens.peek_span();
+        match self.tokens.peek() {
+            None | Some(tokenizer::Token::Eof { span: _ }) => {
+                Err(AstParseError::UnexpectedEof { span })
+                    .attach_printable(format!("expected a statement at {span}"))
+            }
+            Some(tokenizer::Token::Let { span: _ }) => {
+                let invalid = || AstParseError::InvalidLetStatement { span };
+                self.tokens.eat(); // Let
+                let mutable = self.tokens.expect(TokenKind::Keyword("mut")).is_ok();
+                let name_span = self.tokens.peek_span();
+                let name = self
+                    .tokens
+                    .expect_identifier()
+                    .change_context_lazy(invalid)?;
+                // Without `: T` the type is inferred from the value
+                let annotation = match self.tokens.peek() {
+                    Some(tokenizer::Token::BinaryOperator {
+                        op: BinaryOp::SingleEqual,
+                        span: _,
+                    }) => None,
+                    _ => {
+                        self.tokens
+                            .expect(TokenKind::Colon)
+                            .change_context_lazy(invalid)?;
+                        Some(self.let_type(invalid)?)
+                    }
+                };
+                self.tokens
+                    .expect(TokenKind::BinaryOperator(BinaryOp::SingleEqual))
+                    .change_context_lazy(invalid)?;
+                let value_span = self.tokens.peek_span();
+                // Declared even if the value is wrong, so the uses after it aren't errors too
+                let fallback = Variable {
+                    t: annotation.unwrap_or(Symbol::intern("u64")),
+                    mutable,
+                    span: name_span,
+                };
+                let value = self
+                    .statement_expression()
+                    .inspect_err(|_| self.declare(name, fallback))?;
+                self.tokens
+                    .expect(TokenKind::Semicolon)
+                    .change_context_lazy(invalid)
+                    .inspect_err(|_| self.declare(name, fallback))?;
+                let t = match annotation {
+                    Some(t) => {
+                        self.check_value(name, t, &value, value_span)
+                            .inspect_err(|_| self.declare(name, fallback))?;
+                        t
+                    }
+                    None => {
+                        let t = self.inferred_type(&value);
+                        // The values of an array literal are only checked against each other here
+                        if array_type(t).is_some() {
+                            self.check_value(name, t, &value, value_span)
+                                .inspect_err(|_| self.declare(name, fallback))?;
+                        }
+                        t
+                    }
+                };
+
+                self.declare(
+                    name,
+                    Variable {
+                        t,
+                        mutable,
+                        span: name_span,
+                    },
+                );
+                Ok(Some(AstStatement::Let {
+                    value,
+                    name,
+                    t,
+                    mutable,
+                    span: self.span_from(span),
+                }))
+            }
+            Some(tokenizer::Token::Exit { span: _ }) => {
+                self.tokens.eat();
+                let value = self.number_expression()?;
+                Ok(Some(AstStatement::Exit {
+                    value,
+                    span: self.span_from(span),
+                }))
+            }
+            Some(tokenizer::Token::Print { span: _ } | tokenizer::Token::Println { span: _ }) => {
+                let newline = matches!(
+                    self.tokens.eat(),
+                    Some(tokenizer::Token::Println { span: _ })
+                );
+                let value = self.number_expression()?;
+                Ok(Some(AstStatement::Print {
+                    value,
+                    newline,
+                    span: self.span_from(span),
+                }))
+            }
+            Some(tokenizer::Token::Fn { span: _ } | tokenizer::Token::Hash { span: _ }) => {
+                self.function(None, None).map(Some)
+            }
+            Some(tokenizer::Token::Impl { span: _ }) => self.impl_block().map(Some),
+            Some(tokenizer::Token::Const { span: _ }) => self.constant().map(Some),
+            Some(tokenizer::Token::Struct { span: _ }) => self.struct_definition(None).map(Some),
+            Some(tokenizer::Token::Enum { span: _ }) => self.enum_definition().map(Some),
+            Some(tokenizer::Token::If { span: _ }) => self.if_statement().map(Some),
+            Some(tokenizer::Token::Match { span: _ }) => self.match_statement().map(Some),
+            Some(tokenizer::Token::While { span: _ }) => {
+                let invalid = || AstParseError::InvalidWhile { span };
+                self.tokens.eat(); // While
+                let condition = self.condition()?;
+                let body = self.loop_body(invalid)?;
+                Ok(Some(AstStatement::While {
+                    condition,
+                    body,
+                    span: self.span_from(span),
+                }))
+            }
+            Some(tokenizer::Token::For { span: _ }) => self.for_loop().map(Some),
+            Some(tokenizer::Token::Return { span: _ }) => self.return_statement().map(Some),
+            Some(
+                token @ (tokenizer::Token::Break { span: _ }
+                | tokenizer::Token::Continue { span: _ }),
+            ) => {
+                let keyword = token.keyword().expect("both are keywords");
+                let statement = match token {
+                    tokenizer::Token::Break { span: _ } => AstStatement::Break {
+                        span: NodeSpan(span),
+                    },
+                    _ => AstStatement::Continue {
+                        span: NodeSpan(span),
+                    },
+                };
+                self.tokens.eat();
+                if self.loop_depth == 0 {
+                    return Err(AstParseError::OutsideLoop { keyword, span }).attach_printable(
+                        format!("`{keyword}` at {span} is not inside of a loop"),
+                    );
+                }
+                Ok(Some(statement))
+            }
+            Some(tokenizer::Token::OpenBrace { span: _ }) => {
+                let body = self.block(|| unreachable!("just peeked the `{{`"))?;
+                Ok(Some(AstStatement::Block {
+                    body,
+                    span: self.span_from(span),
+                }))
+            }
+            Some(tokenizer::Token::Identifier { .. })
+                if matches!(
+                    self.tokens.peek_n(1),
+                    Some(
+                        tokenizer::Token::BinaryOperator {
+                            op: BinaryOp::SingleEqual,
+                            span: _
+                        } | tokenizer::Token::CompoundAssign { .. }
+                    )
+                ) =>
+            {
+                self.assignment().map(Some)
+            }
+            Some(tokenizer::Token::Identifier { .. })
+                if matches!(
+                    self.tokens.peek_n(1),
+                    Some(tokenizer::Token::OpenParen { span: _ })
+                ) =>
+            {
+                self.statement_call = true;
+                match self.statement_expression()? {
+                    AstExpression::Call { name, args, span } => {
+                        Ok(Some(AstStatement::Call { name, args, span }))
+                    }
+                    _ => Err(AstParseError::ExpressionAtToplevel { span }).attach_printable(
+                        format!("only a function call can be used as a statement at {span}"),
+                    ),
+                }
+            }
+            Some(tokenizer::Token::Identifier { name, span: _ })
+                if name.as_str() == "assert_eq"
+                    && matches!(
+                        self.tokens.peek_n(1),
+                        Some(tokenizer::Token::Bang { span: _ })
+                    ) =>
+            {
+                self.assert_eq().map(Some)
+            }
+            Some(tokenizer::Token::Identifier { .. })
+                if matches!(
+                    self.tokens.peek_n(1),
+                    Some(tokenizer::Token::Dot { span: _ })
+                ) =>
+            {
+                self.statement_call = true;
+                match self.statement_expression()? {
+                    AstExpression::MethodCall {
+                        value,
+                        t,
+                        method,
+                        args,
+                        span,
+                    } => Ok(Some(AstStatement::MethodCall {
+                        value: *value,
+                        t,
+                        method,
+                        args,
+                        span,
+                    })),
+                    _ => Err(AstParseError::ExpressionAtToplevel { span }).attach_printable(
+                        format!("only a method call can be used as a statement at {span}"),
+                    ),
+                }
+            }
+            Some(tokenizer::Token::DocComment { .. }) => {
+                let doc = self.doc_comment();
+                match self.tokens.peek() {
+                    Some(tokenizer::Token::Fn { span: _ } | tokenizer::Token::Hash { span: _ }) => {
+                        self.function(Some(doc), None).map(Some)
+                    }
+                    Some(tokenizer::Token::Struct { span: _ }) => {
+                        self.struct_definition(Some(doc)).map(Some)
+                    }
+                    // Nothing else keeps its documentation
+                    _ => Ok(None),
+                }
+            }
+            Some(tokenizer::Token::Semicolon { span: _ }) => {
+                while self.tokens.expect(TokenKind::Semicolon).is_ok() {}
+                Ok(None)
+            }
+            // The tokenizer already reported what it couldn't read, like an unclosed `/*`
+            Some(tokenizer::Token::Invalid { span: _ }) => {
+                self.tokens.eat();
+                Ok(None)
+            }
+            Some(_) => Err(AstParseError::ExpressionAtToplevel { span })
+                .attach_printable(format!("failed to parse program at {span}")),
+        }
+    }
+
+    /// The lines of the `///` comments in a row, without the space after the slashes
+    fn doc_comment(&mut self) -> String {
+        let mut lines = vec![];
+        while let Some(tokenizer::Token::DocComment { content, span: _ }) = self.tokens.peek() {
+            lines.push(content.strip_prefix(' ').unwrap_or(content).to_string());
+            self.tokens.eat();
+        }
+        lines.join("\n")
+    }
+
+    /// The type after the `:` of a `let`, a name or `[T; N]` for an array of `N` values of type
+    /// `T`. Arrays can only be stored in variables, so no other type can be one
+    fn let_type(
+        &mut self,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Symbol, AstParseError> {
+        if self.tokens.expect(TokenKind::OpenBracket).is_err() {
+            return self.type_name(invalid);
+        }
+        let element = self.type_name(invalid)?;
+        self.tokens
+            .expect(TokenKind::Semicolon)
+            .change_context_lazy(invalid)?;
+        let length_span = self.tokens.peek_span();
+        let length = match self.tokens.eat() {
+            Some(tokenizer::Token::Number {
+                raw,
+                flags,
+                suffix: None,
+                span: _,
+            }) => const_eval::integer_literal(&raw, &flags)
+                .and_then(|length| usize::try_from(length).ok()),
+            _ => None,
+        };
+        // C has no empty arrays
+        let Some(length) = length.filter(|length| *length > 0) else {
+            return Err(invalid()).attach_printable(format!(
+                "the length of an array at {length_span} has to be a number literal above 0"
+            ));
+        };
+        self.tokens
+            .expect(TokenKind::CloseBracket)
+            .change_context_lazy(invalid)?;
+        Ok(array_of(element, length))
+    }
+
+    /// Parses `name = value;` or `name += value;` and the like, the variable has to be declared
+    /// in a scope around it
+    fn assignment(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        let invalid = || AstParseError::InvalidAssignment { span };
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        let Some(variable) = self.variable(name) else {
+            return Err(AstParseError::UndeclaredIdentifier { name, span }).attach_printable(
+                format!("`{name}` is assigned to at {span} before any `let`"),
+            );
+        };
+        if !variable.mutable {
+            let declaration = variable.span;
+            return Err(AstParseError::ImmutableAssignment {
+                name,
+                span,
+                declaration,
+            })
+            .attach_printable(format!("`{name}` is assigned to at {span}"))
+            .attach_printable(format!(
+                "`{name}` is declared at {declaration}, `let mut {name}` would allow assigning to it"
+            ));
+        }
+        let t = variable.t;
+        if array_type(t).is_some() {
+            return Err(invalid()).attach_printable(format!(
+                "`{name}` is a `{t}`, arrays can't be assigned to as a whole"
+            ));
+        }
+        let operator = match self.tokens.eat() {
+            Some(tokenizer::Token::CompoundAssign { op, span: _ }) => Some(op),
+            _ => None,
+        };
+        let value_span = self.tokens.peek_span();
+        let value = self.statement_expression()?;
+        self.tokens
+            .expect(TokenKind::Semicolon)
+            .change_context_lazy(invalid)?;
+        let Some(operator) = operator else {
+            self.check_value(name, t, &value, value_span)?;
+            return Ok(AstStatement::Assign {
+                name,
+                t,
+                value,
+                span: self.span_from(span),
+            });
+        };
+
+        if operator == BinaryOp::Slash && is_integer_zero(&value) {
+            return Err(AstParseError::DivisionByZero { span: value_span })
+                .attach_printable(format!("`{name}` is divided by zero at {value_span}"));
+        }
+        // The new value is what the operator computes from the old one
+        let new_value = AstExpression::BinaryOperation {
+            left: Box::new(AstExpression::Identifier {
+                name,
+                span: NodeSpan(span),
+            }),
+            operator: operator.clone(),
+            right: Box::new(value.clone()),
+            span: self.span_from(span),
+        };
+        self.check_number(&new_value)
+            .map_err(|report| statement_error(report, value_span))?;
+        self.check_value(name, t, &new_value, value_span)?;
+        Ok(AstStatement::CompoundAssign {
+            name,
+            t,
+            operator,
+            value,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Checks that `value` can be stored in the variable `name` of type `t`
+    fn check_value(
+        &self,
+        name: Symbol,
+        t: Symbol,
+        value: &AstExpression,
+        span: tokenizer::Span,
+    ) -> error_stack::Result<(), AstParseError> {
+        if let Some((element, length)) = array_type(t) {
+            // C can't copy arrays, so they are only ever initialized by a literal
+            let AstExpression::ArrayLiteral { values, span: _ } = value else {
+                return Err(AstParseError::InvalidArray { span }).attach_printable(format!(
+                    "`{name}` is a `{t}`, which can only be initialized with an array literal"
+                ));
+            };
+            if values.len() != length {
+                return Err(AstParseError::ArrayLength {
+                    expected: length,
+                    found: values.len(),
+                    span,
+                })
+                .attach_printable(format!("`{name}` is declared as a `{t}`"));
+            }
+            for value in values {
+                self.check_value(name, element, value, value.span())?;
+            }
+            return Ok(());
+        }
+        let Some(found) = self.mismatch(t, value) else {
+            return self.check_range(t, value, span);
+        };
+        let conversion = if self.fits_only_itself(t) || self.fits_only_itself(found) {
+            String::new()
+        } else {
+            format!(", convert the value with `as {t}`")
+        };
+        Err(AstParseError::TypeMismatch {
+            expected: t,
+            found,
+            span,
+        })
+        .attach_printable(format!("`{name}` is declared as a `{t}`{conversion}"))
+    }
+
+    /// Checks that `value` fits into a `t` if it is known at compile time, like a literal
+    fn check_range(
+        &self,
+        t: Symbol,
+        value: &AstExpression,
+        span: tokenizer::Span,
+    ) -> error_stack::Result<(), AstParseError> {
+        // The bounds of a range are stored as `u64`s
+        if let AstExpression::Range {
+            start,
+            end,
+            inclusive: _,
+            span: _,
+        } = value
+        {
+            for bound in [start, end] {
+                self.check_range(Symbol::intern("u64"), bound, bound.span())?;
+            }
+            return Ok(());
+        }
+        match const_eval::const_eval(value, t, &self.values)
+            .and_then(|const_eval::Value::Integer(computed)| const_eval::convert(computed, t))
+        {
+            Err(
+                error @ (const_eval::ConstEvalError::Overflow { .. }
+                | const_eval::ConstEvalError::ShiftOverflow { .. }),
+            ) => Err(const_error(error, t, span)),
+            // Anything else is computed when the program runs
+            _ => Ok(()),
+        }
+    }
+
+    /// The type of `value` if it can't be stored as a `t`. Numbers of different types convert
+    /// into each other, `bool`s need a cast and structs, enums and arrays only fit their own
+    /// type. A number of no particular type is still a number, so it is none of those
+    fn mismatch(&self, t: Symbol, value: &AstExpression) -> Option<Symbol> {
+        let found = self.expression_type(value);
+        let integer = || Symbol::intern("{integer}");
+        if self.fits_only_itself(t) || found.is_some_and(|t| self.fits_only_itself(t)) {
+            return (found != Some(t)).then(|| found.unwrap_or_else(integer));
+        }
+        let found = found.unwrap_or_else(integer);
+        ((found.as_str() == BOOL) != (t.as_str() == BOOL)).then_some(found)
+    }
+
+    /// Checks that `expr` is no struct, enum or array, which can't be computed with
+    fn check_number(&self, expr: &AstExpression) -> error_stack::Result<(), ExpressionParseError> {
+        let Some(t) = self.expression_type(expr) else {
+            return Ok(());
+        };
+        let note = if t.as_str() == BOOL {
+            "`bool`s can only be compared with `==` and `!=`, combined with `&&`, `||` and `!` and cast to numbers"
+        } else if array_type(t).is_some() {
+            "arrays can only be stored in variables and have their values read by indexing"
+        } else if self.structs.contains_key(&t) {
+            "structs can only be stored, passed, returned and have their fields read"
+        } else if self.enums.contains_key(&t) {
+            "enums can only be stored, passed, returned, compared with `==` and `!=` and cast to numbers"
+        } else {
+            return Ok(());
+        };
+        Err(ExpressionParseError::NotANumber {
+            t,
+            span: expr.span(),
+        })
+        .attach_printable(note)
+    }
+
+    /// Reports `expr` if it isn't a `bool`, which conditions and logical operators need
+    fn check_bool(&self, expr: &AstExpression) -> error_stack::Result<(), ExpressionParseError> {
+        let found = self.inferred_type(expr);
+        if found.as_str() == BOOL {
+            return Ok(());
+        }
+        Err(ExpressionParseError::TypeMismatch {
+            expected: Symbol::intern(BOOL),
+            found,
+            span: expr.span(),
+        })
+        .attach_printable("numbers aren't `bool`s, compare them with `!= 0` instead")
+    }
+
+    /// Reports `expr` if it's a float, which `operator` can't compute with
+    fn check_integer(
+        &self,
+        operator: &str,
+        expr: &AstExpression,
+    ) -> error_stack::Result<(), ExpressionParseError> {
+        let t = match self.expression_type(expr) {
+            Some(t) => t,
+            None if has_float_literal(expr) => Symbol::intern("f64"),
+            None => return Ok(()),
+        };
+        if !matches!(t.as_str(), "f32" | "f64") {
+            return Ok(());
+        }
+        Err(ExpressionParseError::NotAnInteger {
+            t,
+            span: expr.span(),
+        })
+        .attach_printable(format!("`{operator}` only works on integers"))
+    }
+
+    /// Whether `t` is a struct or an enum defined in the program
+    fn is_named_type(&self, t: Symbol) -> bool {
+        self.structs.contains_key(&t) || self.enums.contains_key(&t)
+    }
+
+    /// Whether `t` is a number type, `bool` or a struct or enum defined so far
+    fn is_type(&self, t: Symbol) -> bool {
+        tokenizer::NUMBER_SUFFIXES.contains(&t.as_str())
+            || t.as_str() == BOOL
+            || self.is_named_type(t)
+    }
+
+    /// Parses the name of a type, which has to be known already
+    fn type_name(
+        &mut self,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Symbol, AstParseError> {
+        let span = self.tokens.peek_span();
+        let t = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        if !self.is_type(t) {
+            return Err(AstParseError::UnknownType { t, span }).attach_printable(
+                "types are numbers, `bool` and the structs and enums defined before",
+            );
+        }
+        Ok(t)
+    }
+
+    /// Whether a value of type `t` can't be converted from or to any other type
+    fn fits_only_itself(&self, t: Symbol) -> bool {
+        self.is_named_type(t) || array_type(t).is_some()
+    }
+
+    /// Whether `expr` is a variant of some enum
+    fn is_variant(&self, expr: &AstExpression) -> bool {
+        self.expression_type(expr)
+            .is_some_and(|t| self.enums.contains_key(&t))
+    }
+
+    /// Parses `const NAME: T = value;`. The value is computed right away, so it can only use
+    /// literals and the constants declared before it
+    fn constant(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        if self.function.is_some() || self.scopes.len() > 1 {
+            return Err(AstParseError::NestedConst { span })
+                .attach_printable(format!("nested constant declaration at {span}"));
+        }
+        let invalid = || AstParseError::InvalidConst { span };
+        self.tokens.eat(); // Const
+        let name_span = self.tokens.peek_span();
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        self.tokens
+            .expect(TokenKind::Colon)
+            .change_context_lazy(invalid)?;
+        let type_span = self.tokens.peek_span();
+        let t = self.type_name(invalid)?;
+        self.tokens
+            .expect(TokenKind::BinaryOperator(BinaryOp::SingleEqual))
+            .change_context_lazy(invalid)?;
+        let value_span = self.tokens.peek_span();
+        let value = self.statement_expression()?;
+        self.tokens
+            .expect(TokenKind::Semicolon)
+            .change_context_lazy(invalid)?;
+        if self.constants.contains_key(&name) {
+            return Err(invalid()).attach_printable(format!(
+                "constant `{name}` at {name_span} is already declared"
+            ));
+        }
+        // Declared even if the value is wrong, so the uses after it aren't errors too
+        let constant = Variable {
+            t,
+            mutable: false,
+            span: name_span,
+        };
+        self.check_value(name, t, &value, value_span)
+            .inspect_err(|_| _ = self.constants.insert(name, constant))?;
+        // Only integers are computed at compile time
+        if matches!(t.as_str(), "f32" | "f64") || self.fits_only_itself(t) {
+            self.constants.insert(name, constant);
+            return Err(AstParseError::NotAnInteger { t, span: type_span }).attach_printable(
+                format!("constants must be integers or `bool`s, `{name}` is a `{t}`"),
+            );
+        }
+
+        let value = const_eval::const_eval(&value, t, &self.values)
+            .and_then(|const_eval::Value::Integer(computed)| const_eval::convert(computed, t))
+            .map_err(|error| const_error(error, t, value_span))
+            .inspect_err(|_| _ = self.constants.insert(name, constant))?;
+
+        self.constants.insert(name, constant);
+        self.values.insert(name, (t, value));
+        Ok(AstStatement::Const {
+            name,
+            t,
+            value,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses `struct Name { a: T, ... }`. Like functions, structs are only defined at the top
+    /// level and have to be defined before they are used
+    fn struct_definition(
+        &mut self,
+        doc: Option<String>,
+    ) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        if self.function.is_some() || self.scopes.len() > 1 {
+            return Err(AstParseError::NestedStruct { span })
+                .attach_printable(format!("nested struct definition at {span}"));
+        }
+        let invalid = || AstParseError::InvalidStruct { span };
+        self.tokens.eat(); // Struct
+        let name_span = self.tokens.peek_span();
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        self.tokens
+            .expect(TokenKind::OpenBrace)
+            .change_context_lazy(invalid)?;
+        // The rest of a broken definition is skipped, its `}` would look like the end of a block
+        let fields = self.struct_fields(name, invalid).inspect_err(|_| {
+            if !matches!(
+                self.tokens.previous(),
+                Some(tokenizer::Token::CloseBrace { span: _ })
+            ) {
+                self.skip_braces();
+            }
+        })?;
+        // C has no empty structs
+        if fields.is_empty() {
+            return Err(invalid()).attach_printable(format!(
+                "struct `{name}` at {name_span} needs at least one field"
+            ));
+        }
+        if self.is_named_type(name) {
+            return Err(invalid())
+                .attach_printable(format!("type `{name}` at {name_span} is already defined"));
+        }
+        // A struct with itself in it would be infinitely large
+        if let Some(field) = fields
+            .iter()
+            .find(|field| self.contains_type(field.t, name))
+        {
+            return Err(invalid()).attach_printable(format!(
+                "field `{}` of struct `{name}` at {name_span} has a `{name}` in it",
+                field.name
+            ));
+        }
+        self.structs.insert(name, fields.clone());
+        Ok(AstStatement::Struct {
+            doc,
+            name,
+            fields,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Whether a value of type `t` has a `target` in it, itself or in its fields
+    fn contains_type(&self, t: Symbol, target: Symbol) -> bool {
+        t == target
+            || self.structs.get(&t).is_some_and(|fields| {
+                fields
+                    .iter()
+                    .any(|field| self.contains_type(field.t, target))
+            })
+    }
+
+    /// The `a: T, ...}` of the struct `name`. With a field of an unknown type the struct is
+    /// still defined with its other fields, so the uses after it aren't errors too
+    fn struct_fields(
+        &mut self,
+        name: Symbol,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Vec<Field>, AstParseError> {
+        let mut fields: Vec<Field> = vec![];
+        let mut unknown = None;
+        // The last field can have a `,` after it too
+        while self.tokens.expect(TokenKind::CloseBrace).is_err() {
+            let field_span = self.tokens.peek_span();
+            let field = self
+                .tokens
+                .expect_identifier()
+                .change_context_lazy(invalid)?;
+            self.tokens
+                .expect(TokenKind::Colon)
+                .change_context_lazy(invalid)?;
+            let type_span = self.tokens.peek_span();
+            let t = self
+                .tokens
+                .expect_identifier()
+                .change_context_lazy(invalid)?;
+            // A field of the struct itself is reported once the fields are known
+            if fields.iter().any(|other| other.name == field) {
+                return Err(invalid()).attach_printable(format!(
+                    "field `{field}` at {field_span} is declared twice"
+                ));
+            }
+            if t != name && !self.is_type(t) {
+                unknown.get_or_insert(
+                    error_stack::Report::new(AstParseError::UnknownType { t, span: type_span })
+                        .attach_printable(format!("field `{field}` has an unknown type"))
+                        .change_context(invalid()),
+                );
+            } else {
+                fields.push(Field { name: field, t });
+            }
+            if self.tokens.expect(TokenKind::Comma).is_err() {
+                self.tokens
+                    .expect(TokenKind::CloseBrace)
+                    .change_context_lazy(invalid)?;
+                break;
+            }
+        }
+        match unknown {
+            Some(report) => {
+                if !self.is_named_type(name) {
+                    self.structs.insert(name, fields);
+                }
+                Err(report)
+            }
+            None => Ok(fields),
+        }
+    }
+
+    /// Parses `enum Name { A, B, ... }`. Like structs, enums are only defined at the top level
+    /// and have to be defined before they are used
+    fn enum_definition(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        if self.function.is_some() || self.scopes.len() > 1 {
+            return Err(AstParseError::NestedEnum { span })
+                .attach_printable(format!("nested enum definition at {span}"));
+        }
+        let invalid = || AstParseError::InvalidEnum { span };
+        self.tokens.eat(); // Enum
+        let name_span = self.tokens.peek_span();
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        self.tokens
+            .expect(TokenKind::OpenBrace)
+            .change_context_lazy(invalid)?;
+        // The rest of a broken definition is skipped, its `}` would look like the end of a block
+        let variants = self
+            .enum_variants(invalid)
+            .inspect_err(|_| self.skip_braces())?;
+        // C has no empty enums
+        if variants.is_empty() {
+            return Err(invalid()).attach_printable(format!(
+                "enum `{name}` at {name_span} needs at least one variant"
+            ));
+        }
+        if self.is_named_type(name) {
+            return Err(invalid())
+                .attach_printable(format!("type `{name}` at {name_span} is already defined"));
+        }
+        self.enums.insert(name, variants.clone());
+        Ok(AstStatement::Enum {
+            name,
+            variants,
+            span: self.span_from(span),
+        })
+    }
+
+    /// The `A, B, ...}` of an enum definition
+    fn enum_variants(
+        &mut self,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Vec<Symbol>, AstParseError> {
+        let mut variants: Vec<Symbol> = vec![];
+        // The last variant can have a `,` after it too
+        while self.tokens.expect(TokenKind::CloseBrace).is_err() {
+            let variant_span = self.tokens.peek_span();
+            let variant = self
+                .tokens
+                .expect_identifier()
+                .change_context_lazy(invalid)?;
+            if variants.contains(&variant) {
+                return Err(invalid()).attach_printable(format!(
+                    "variant `{variant}` at {variant_span} is declared twice"
+                ));
+            }
+            variants.push(variant);
+            if self.tokens.expect(TokenKind::Comma).is_err() {
+                self.tokens
+                    .expect(TokenKind::CloseBrace)
+                    .change_context_lazy(invalid)?;
+                break;
+            }
+        }
+        Ok(variants)
+    }
+
+    /// Parses `impl Name { fn ... }`, the methods of a struct or enum defined before
+    fn impl_block(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        if self.function.is_some() || self.scopes.len() > 1 {
+            return Err(AstParseError::NestedImpl { span })
+                .attach_printable(format!("nested impl block at {span}"));
+        }
+        let invalid = || AstParseError::InvalidImpl { span };
+        self.tokens.eat(); // Impl
+        let name_span = self.tokens.peek_span();
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        if !self.is_named_type(name) {
+            return Err(AstParseError::UnknownType {
+                t: name,
+                span: name_span,
+            })
+            .attach_printable("only the structs and enums defined before have methods");
+        }
+        self.tokens
+            .expect(TokenKind::OpenBrace)
+            .change_context_lazy(invalid)?;
+        let mut methods = vec![];
+        while self.tokens.expect(TokenKind::CloseBrace).is_err() {
+            let doc = match self.tokens.peek() {
+                Some(tokenizer::Token::DocComment { .. }) => Some(self.doc_comment()),
+                _ => None,
+            };
+            // The rest of the block is skipped, its `}` would look like the end of a block
+            let method = self
+                .function(doc, Some(name))
+                .inspect_err(|_| self.skip_braces())?;
+            methods.push(method);
+        }
+        Ok(AstStatement::Impl {
+            name,
+            methods,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses `fn name(a: T, ...) -> R { ... }` with its attributes. The body only sees the
+    /// parameters, not the variables around the definition. The function is declared before its
+    /// body, so it can call itself. A method of the type `owner` starts its parameters with
+    /// `self`, which is an `owner`, and is declared as [`method_name`]
+    fn function(
+        &mut self,
+        doc: Option<String>,
+        owner: Option<Symbol>,
+    ) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        // Outside of functions only the blocks of statements like `if` add scopes
+        if self.function.is_some() || self.scopes.len() > 1 {
+            return Err(AstParseError::NestedFunction { span })
+                .attach_printable(format!("nested function definition at {span}"));
+        }
+        let invalid = || AstParseError::InvalidFunction { span };
+        let attrs = self.attributes(invalid)?;
+        self.tokens
+            .expect(TokenKind::Keyword("fn"))
+            .change_context_lazy(invalid)
+            .attach_printable("attributes only go in front of functions")?;
+        let name_span = self.tokens.peek_span();
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        let key = owner.map_or(name, |t| method_name(t, name));
+        self.tokens
+            .expect(TokenKind::OpenParen)
+            .change_context_lazy(invalid)?;
+        let mut params: Vec<Parameter> = vec![];
+        let mut parameters = vec![];
+        while self.tokens.expect(TokenKind::CloseParen).is_err() {
+            if !params.is_empty() {
+                self.tokens
+                    .expect(TokenKind::Comma)
+                    .change_context_lazy(invalid)?;
+            }
+            let param_span = self.tokens.peek_span();
+            let param_name = self
+                .tokens
+                .expect_identifier()
+                .change_context_lazy(invalid)?;
+            let t = if param_name.as_str() == "self" {
+                let Some(t) = owner.filter(|_| params.is_empty()) else {
+                    return Err(invalid()).attach_printable(format!(
+                        "`self` at {param_span} can only be the first parameter of a method"
+                    ));
+                };
+                // `self: Name` can be written out too
+                if self.tokens.expect(TokenKind::Colon).is_ok() {
+                    let found = self.type_name(invalid)?;
+                    if found != t {
+                        return Err(AstParseError::TypeMismatch {
+                            expected: t,
+                            found,
+                            span: param_span,
+                        })
+                        .attach_printable(format!("the methods of `{t}` are called on a `{t}`"));
+                    }
+                }
+                t
+            } else {
+                self.tokens
+                    .expect(TokenKind::Colon)
+                    .change_context_lazy(invalid)?;
+                self.type_name(invalid)?
+            };
+            if params.iter().any(|param| param.name == param_name) {
+                return Err(invalid()).attach_printable(format!(
+                    "parameter `{param_name}` at {param_span} is declared twice"
+                ));
+            }
+            parameters.push((
+                param_name,
+                Variable {
+                    t,
+                    mutable: false,
+                    span: param_span,
+                },
+            ));
+            params.push(Parameter {
+                name: param_name,
+                t,
+            });
+        }
+        if let Some(t) = owner {
+            if params
+                .first()
+                .is_none_or(|param| param.name.as_str() != "self")
+            {
+                return Err(invalid()).attach_printable(format!(
+                    "the method `{name}` at {name_span} has no `self`, methods take the `{t}` \
+                     they are called on as their first parameter"
+                ));
+            }
+        }
+        let return_type = match self.tokens.expect(TokenKind::Arrow) {
+            Ok(_) if self.tokens.expect(TokenKind::Bang).is_ok() => Some(Symbol::intern(NEVER)),
+            Ok(_) => Some(self.type_name(invalid)?),
+            Err(_) => None,
+        };
+        // The value a method is called on isn't one of the arguments
+        let signature = Signature {
+            params: params.len() - usize::from(owner.is_some()),
+            return_type,
+        };
+        if self.functions.insert(key, signature.clone()).is_some() {
+            return Err(invalid()).attach_printable(format!(
+                "function `{key}` at {name_span} is already defined"
+            ));
+        }
+
+        let outer = std::mem::replace(&mut self.scopes, vec![parameters.into_iter().collect()]);
+        self.function = Some(signature);
+        let errors = self.errors.len();
+        let body = self.block(invalid);
+        self.function = None;
+        self.scopes = outer;
+        // After the body, so a deprecated function can still call itself without warnings
+        if let Some(Attribute::Deprecated { message }) = attrs
+            .iter()
+            .find(|attr| matches!(attr, Attribute::Deprecated { .. }))
+        {
+            self.deprecated.insert(key, message.clone());
+        }
+        let body = body?;
+        // A statement that failed to parse might have been the `return`
+        let recovered = self.errors.len() > errors;
+        // Functions can only call the ones defined before them, so only calling itself recurses
+        if attrs.contains(&Attribute::Inline) {
+            let mut calls = CallCollector::default();
+            calls.visit_program(&body);
+            if calls.names.contains(&key) {
+                return Err(invalid()).attach_printable(format!(
+                    "`{key}` at {name_span} calls itself, so it can't be `#[inline]`"
+                ));
+            }
+        }
+        // The test runner calls them without arguments and has nothing to do with a result
+        if attrs.contains(&Attribute::Test)
+            && (owner.is_some() || !params.is_empty() || return_type.is_some())
+        {
+            return Err(invalid()).attach_printable(format!(
+                "`{key}` at {name_span} is a `#[test]`, which takes no parameters and returns \
+                 nothing"
+            ));
+        }
+        if owner.is_some() && attrs.contains(&Attribute::NoMangle) {
+            return Err(invalid()).attach_printable(format!(
+                "`{key}` at {name_span} is a method, which the generated C code names after its \
+                 type, so it can't be `#[no_mangle]`"
+            ));
+        }
+        // The generated code has a `main` of its own, names starting with `__` and the types
+        if attrs.contains(&Attribute::NoMangle)
+            && (name.as_str() == "main"
+                || name.as_str().starts_with("__")
+                || tokenizer::NUMBER_SUFFIXES.contains(&name.as_str()))
+        {
+            return Err(invalid()).attach_printable(format!(
+                "`{name}` at {name_span} is a name the generated C code uses, so it can't be \
+                 `#[no_mangle]`"
+            ));
+        }
+        let never = return_type.is_some_and(|t| t.as_str() == NEVER);
+        if never && !recovered && !self.diverges(&body, true) {
+            return Err(AstParseError::MissingDivergence {
+                name: key,
+                span: name_span,
+            })
+            .attach_printable(format!(
+                "the end of `{key}` can be reached, end it with `exit`, a call to a `!` function \
+                 or a `while true` loop without `break`"
+            ));
+        }
+        if let Some(t) =
+            return_type.filter(|_| !never && !recovered && !self.diverges(&body, false))
+        {
+            return Err(AstParseError::MissingReturn {
+                name: key,
+                t,
+                span: name_span,
+            })
+            .attach_printable(format!(
+                "the end of `{key}` can be reached without a `return`, add one there"
+            ));
+        }
+
+        Ok(AstStatement::Function {
+            doc,
+            attrs,
+            name,
+            params,
+            return_type,
+            body,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Whether running `body` never gets past its end, because every path through it returns,
+    /// exits or calls a `!` function. With `loops`, a `while true` without a `break` counts too
+    fn diverges(&self, body: &[AstStatement], loops: bool) -> bool {
+        body.iter().any(|stmt| match stmt {
+            AstStatement::Return { .. } | AstStatement::Exit { .. } => true,
+            AstStatement::Call { name, .. } => self.never_returns(*name),
+            AstStatement::MethodCall { t, method, .. } => {
+                self.never_returns(method_name(*t, *method))
+            }
+            AstStatement::While {
+                condition: AstExpression::Bool { value: true, .. },
+                body,
+                span: _,
+            } => loops && !breaks(body),
+            AstStatement::If {
+                condition: _,
+                then,
+                otherwise,
+                span: _,
+            } => self.diverges(then, loops) && self.diverges(otherwise, loops),
+            AstStatement::Block { body, span: _ } => self.diverges(body, loops),
+            // The parser only allows matches that handle every value
+            AstStatement::Match {
+                value: _,
+                t: _,
+                arms,
+                span: _,
+            } => arms.iter().all(|arm| self.diverges(&arm.body, loops)),
+            _ => false,
+        })
+    }
+
+    /// The `#[name]` attributes in front of a function definition
+    fn attributes(
+        &mut self,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Vec<Attribute>, AstParseError> {
+        let mut attrs: Vec<Attribute> = vec![];
+        while self.tokens.expect(TokenKind::Hash).is_ok() {
+            self.tokens
+                .expect(TokenKind::OpenBracket)
+                .change_context_lazy(invalid)?;
+            let name_span = self.tokens.peek_span();
+            let name = self
+                .tokens
+                .expect_identifier()
+                .change_context_lazy(invalid)?;
+            let attr = match name.as_str() {
+                "inline" => Attribute::Inline,
+                "no_mangle" => Attribute::NoMangle,
+                "test" => Attribute::Test,
+                "deprecated" => Attribute::Deprecated {
+                    message: self.attribute_message(invalid)?,
+                },
+                _ => {
+                    return Err(invalid())
+                        .attach_printable(format!("unknown attribute `{name}` at {name_span}"))
+                }
+            };
+            self.tokens
+                .expect(TokenKind::CloseBracket)
+                .change_context_lazy(invalid)?;
+            if attrs
+                .iter()
+                .any(|other| std::mem::discriminant(other) == std::mem::discriminant(&attr))
+            {
+                return Err(invalid())
+                    .attach_printable(format!("`{attr}` at {name_span} is given twice"));
+            }
+            attrs.push(attr);
+        }
+        Ok(attrs)
+    }
+
+    /// The optional `= "message"` of an attribute like `#[deprecated]`
+    fn attribute_message(
+        &mut self,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Option<String>, AstParseError> {
+        if self
+            .tokens
+            .expect(TokenKind::BinaryOperator(BinaryOp::SingleEqual))
+            .is_err()
+        {
+            return Ok(None);
+        }
+        match self
+            .tokens
+            .expect(TokenKind::String)
+            .change_context_lazy(invalid)?
+        {
+            tokenizer::Token::String { value, span: _ } => Ok(Some(value.into_owned())),
+            tokenizer::Token::RawString { value, .. } => Ok(Some(value.to_string())),
+            _ => unreachable!("expect only returns strings"),
+        }
+    }
+
+    /// Parses `assert_eq!(left, right)`, whose values are compared like with `==`
+    fn assert_eq(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        let invalid = || AstParseError::InvalidAssert { span };
+        self.tokens.eat(); // assert_eq
+        self.tokens.eat(); // Bang
+        self.tokens
+            .expect(TokenKind::OpenParen)
+            .change_context_lazy(invalid)?;
+        let left_span = self.tokens.peek_span();
+        let left = self.statement_expression()?;
+        self.tokens
+            .expect(TokenKind::Comma)
+            .change_context_lazy(invalid)?;
+        let right_span = self.tokens.peek_span();
+        let right = self.statement_expression()?;
+        self.tokens
+            .expect(TokenKind::CloseParen)
+            .change_context_lazy(invalid)?;
+        // A number of no particular type takes the type of the other value
+        let t = self
+            .expression_type(&left)
+            .or_else(|| self.expression_type(&right))
+            .unwrap_or_else(|| self.inferred_type(&left));
+        if !(tokenizer::NUMBER_SUFFIXES.contains(&t.as_str())
+            || t.as_str() == BOOL
+            || self.enums.contains_key(&t))
+        {
+            return Err(invalid()).attach_printable(format!(
+                "`assert_eq!` at {span} compares with `==`, which `{t}` values can't be"
+            ));
+        }
+        for (value, value_span) in [(&left, left_span), (&right, right_span)] {
+            let found = self
+                .expression_type(value)
+                .filter(|found| *found != t)
+                .or_else(|| self.mismatch(t, value));
+            if let Some(found) = found {
+                return Err(AstParseError::TypeMismatch {
+                    expected: t,
+                    found,
+                    span: value_span,
+                })
+                .attach_printable("both values of `assert_eq!` have to be of the same type");
+            }
+            self.check_range(t, value, value_span)?;
+        }
+        Ok(AstStatement::AssertEq {
+            left,
+            right,
+            t,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses `if condition { ... }` with an optional `else { ... }` or `else if ...`
+    fn if_statement(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        let invalid = || AstParseError::InvalidIf { span };
+        self.tokens.eat(); // If
+        let condition = self.condition()?;
+        let then = self.block(invalid)?;
+        let otherwise = match self.tokens.expect(TokenKind::Keyword("else")) {
+            Ok(_) if matches!(self.tokens.peek(), Some(tokenizer::Token::If { span: _ })) => {
+                vec![self.if_statement()?]
+            }
+            Ok(_) => self.block(invalid)?,
+            Err(_) => vec![],
+        };
+        Ok(AstStatement::If {
+            condition,
+            then,
+            otherwise,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses `match value { pattern => { ... } ... }`. Integers, `bool`s and enums can be
+    /// matched, and the arms have to handle every value, either with a `_` or by having a
+    /// pattern for each
+    fn match_statement(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        let invalid = || AstParseError::InvalidMatch { span };
+        self.tokens.eat(); // Match
+        let value_span = self.tokens.peek_span();
+        let value = self.statement_expression()?;
+        let t = self.inferred_type(&value);
+        if !self.enums.contains_key(&t) && t.as_str() != BOOL {
+            if matches!(t.as_str(), "f32" | "f64") {
+                return Err(invalid()).attach_printable(format!(
+                    "the value at {value_span} is a `{t}`, only integers, `bool`s and enums can \
+                     be matched"
+                ));
+            }
+            self.check_number(&value)
+                .map_err(|report| statement_error(report, value_span))?;
+        }
+        self.tokens
+            .expect(TokenKind::OpenBrace)
+            .change_context_lazy(invalid)?;
+        // The rest of a broken match is skipped, its `}` would look like the end of a block
+        let mut coverage = coverage::Coverage::default();
+        let arms = self
+            .match_arms(t, &mut coverage, invalid)
+            .inspect_err(|_| self.skip_braces())?;
+
+        if arms.iter().all(|arm| arm.pattern.is_some()) {
+            let (min, max) = self.pattern_bounds(t);
+            let missing = coverage
+                .missing(min, max)
+                .into_iter()
+                .flat_map(|interval| self.describe_values(t, interval))
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                let mut shown = missing
+                    .iter()
+                    .take(3)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                if missing.len() > 3 {
+                    shown.push_str(&format!(" and {} more", missing.len() - 3));
+                }
+                return Err(AstParseError::NonExhaustiveMatch { t, missing, span })
+                    .attach_printable(format!(
+                        "{shown} isn't handled, add arms for it or a `_` arm"
+                    ));
+            }
+        }
+        Ok(AstStatement::Match {
+            value,
+            t,
+            arms,
+            span: self.span_from(span),
+        })
+    }
+
+    /// The `pattern => { ... } ... }` of a match on a `t`, adding the values each pattern
+    /// matches to `coverage`. An arm can have a `,` after it. An arm that can never be used
+    /// is an error, one whose values some arm before it matches partly is a warning
+    fn match_arms(
+        &mut self,
+        t: Symbol,
+        coverage: &mut coverage::Coverage,
+        invalid: impl Fn() -> AstParseError + Copy,
+    ) -> error_stack::Result<Vec<MatchArm>, AstParseError> {
+        let mut arms: Vec<MatchArm> = vec![];
+        let mut wildcard = None;
+        while self.tokens.expect(TokenKind::CloseBrace).is_err() {
+            let pattern_span = self.tokens.peek_span();
+            if let Some(wildcard) = wildcard {
+                return Err(invalid()).attach_printable(format!(
+                    "the arm at {pattern_span} is never used, the `_` at {wildcard} matches \
+                     everything before it"
+                ));
+            }
+            let pattern = match self.tokens.peek() {
+                Some(tokenizer::Token::Identifier { name, span: _ }) if name.as_str() == "_" => {
+                    self.tokens.eat();
+                    let (min, max) = self.pattern_bounds(t);
+                    if coverage.missing(min, max).is_empty() {
+                        return Err(invalid()).attach_printable(format!(
+                            "the `_` at {pattern_span} is never used, the arms before it \
+                             handle every `{t}`"
+                        ));
+                    }
+                    wildcard = Some(pattern_span);
+                    None
+                }
+                _ => Some(self.pattern(t, invalid)?),
+            };
+            if let Some(pattern) = &pattern {
+                // Compared by value, so `0x1` is the same pattern as `1`
+                let (start, end) = self.pattern_values(t, pattern, pattern_span, invalid)?;
+                match coverage.add(start, end) {
+                    coverage::Covered::Everything => {
+                        return Err(invalid()).attach_printable(format!(
+                            "the pattern at {pattern_span} is matched by arms before it"
+                        ));
+                    }
+                    coverage::Covered::Partly => {
+                        self.warnings.push(Warning::OverlappingPattern {
+                            span: pattern.span(),
+                        });
+                    }
+                    coverage::Covered::Nothing => {}
+                }
+            }
+            self.tokens
+                .expect(TokenKind::FatArrow)
+                .change_context_lazy(invalid)?;
+            let body = self.block(invalid)?;
+            arms.push(MatchArm { pattern, body });
+            let _ = self.tokens.expect(TokenKind::Comma);
+        }
+        Ok(arms)
+    }
+
+    /// The smallest and largest value of a `t` as the match patterns on it are counted, a
+    /// `bool` is `0` or `1` and a variant its position in the enum
+    fn pattern_bounds(&self, t: Symbol) -> (i128, i128) {
+        match self.enums.get(&t) {
+            Some(variants) => (0, variants.len() as i128 - 1),
+            None if t.as_str() == BOOL => (0, 1),
+            None => coverage::bounds(t.as_str()),
+        }
+    }
+
+    /// How the values `start..=end` of a `t` are written in a pattern, one by one for `bool`s
+    /// and variants
+    fn describe_values(&self, t: Symbol, (start, end): (i128, i128)) -> Vec<String> {
+        match self.enums.get(&t) {
+            Some(variants) => (start..=end)
+                .map(|position| format!("`{t}::{}`", variants[position as usize]))
+                .collect(),
+            None if t.as_str() == BOOL => (start..=end)
+                .map(|value| format!("`{}`", value == 1))
+                .collect(),
+            None if start == end => vec![format!("`{start}`")],
+            None => vec![format!("`{start}..={end}`")],
+        }
+    }
+
+    /// The first and last value of a `t` that `pattern` matches
+    fn pattern_values(
+        &self,
+        t: Symbol,
+        pattern: &AstExpression,
+        span: tokenizer::Span,
+        invalid: impl Fn() -> AstParseError,
+    ) -> error_stack::Result<(i128, i128), AstParseError> {
+        let value = |expr: &AstExpression| match expr {
+            AstExpression::Variant {
+                name,
+                variant,
+                span: _,
+            } => Ok(self.enums[name]
+                .iter()
+                .position(|other| other == variant)
+                .expect("the parser only allows declared variants") as i128),
+            expr => match const_eval::const_eval(expr, t, &self.values) {
+                Ok(const_eval::Value::Integer(value)) if const_eval::is_signed(t) => {
+                    Ok(i128::from(value as i64))
+                }
+                Ok(const_eval::Value::Integer(value)) => Ok(i128::from(value)),
+                Err(_) => Err(invalid())
+                    .attach_printable(format!("the pattern at {span} isn't a valid `{t}`")),
+            },
+        };
+        let AstExpression::Range {
+            start,
+            end,
+            inclusive,
+            span: _,
+        } = pattern
+        else {
+            let value = value(pattern)?;
+            return Ok((value, value));
+        };
+        let start = value(start)?;
+        let end = value(end)? - i128::from(!inclusive);
+        if start > end {
+            return Err(invalid())
+                .attach_printable(format!("the range pattern at {span} matches nothing"));
+        }
+        Ok((start, end))
+    }
+
+    /// A number literal, which can be negative, a range of them, `true`, `false` or a variant
+    /// that fits a `t`
+    fn pattern(
+        &mut self,
+        t: Symbol,
+        invalid: impl Fn() -> AstParseError,
+    ) -> error_stack::Result<AstExpression, AstParseError> {
+        let span = self.tokens.peek_span();
+        let pattern = self.statement_expression()?;
+        let literal = |expr: &AstExpression| match expr {
+            AstExpression::UnaryOperation {
+                operator: UnaryOp::Negate,
+                operand,
+                span: _,
+            } => matches!(**operand, AstExpression::Number { .. }),
+            AstExpression::Number { .. } => true,
+            _ => false,
+        };
+        let values = match &pattern {
+            AstExpression::Range {
+                start,
+                end,
+                inclusive: _,
+                span: _,
+            } if literal(start) && literal(end) => {
+                if self.fits_only_itself(t) || t.as_str() == BOOL {
+                    return Err(invalid()).attach_printable(format!(
+                        "the range pattern at {span} can't match a `{t}`, only integers"
+                    ));
+                }
+                vec![&**start, &**end]
+            }
+            AstExpression::Variant { .. } | AstExpression::Bool { .. } => vec![&pattern],
+            pattern if literal(pattern) => vec![pattern],
+            _ => {
+                return Err(invalid()).attach_printable(format!(
+                    "the pattern at {span} is neither a number literal, a range of them, a \
+                     `bool`, an enum variant nor `_`"
+                ));
+            }
+        };
+        for value in values {
+            if let Some(found) = self.mismatch(t, value) {
+                return Err(AstParseError::TypeMismatch {
+                    expected: t,
+                    found,
+                    span,
+                })
+                .attach_printable(format!("the pattern at {span} can't be a `{t}`"));
+            }
+            self.check_range(t, value, value.span())?;
+        }
+        Ok(pattern)
+    }
+
+    /// Parses `for name: T in range { ... }`. Like the values of `exit` and `print`, the counter
+    /// is a `u64` without `: T`. It is only visible inside the body. The bounds of a range
+    /// written out in the loop are `T`s, any other range is a [`RANGE`] or [`RANGE_INCLUSIVE`],
+    /// whose `u64` bounds only fit a `u64` counter
+    fn for_loop(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        let invalid = || AstParseError::InvalidFor { span };
+        self.tokens.eat(); // For
+        let name_span = self.tokens.peek_span();
+        let name = self
+            .tokens
+            .expect_identifier()
+            .change_context_lazy(invalid)?;
+        let t = match self.tokens.expect(TokenKind::Colon) {
+            Ok(_) => self.type_name(invalid)?,
+            Err(_) => Symbol::intern("u64"),
+        };
+        self.tokens
+            .expect(TokenKind::Keyword("in"))
+            .change_context_lazy(invalid)?;
+        let range_span = self.tokens.peek_span();
+        let range = self.statement_expression()?;
+        let inclusive = match &range {
+            AstExpression::Range {
+                start,
+                end,
+                inclusive,
+                span: _,
+            } => {
+                for bound in [start, end] {
+                    self.check_value(name, t, bound, bound.span())?;
+                }
+                *inclusive
+            }
+            _ => {
+                let found = self.inferred_type(&range);
+                let inclusive = found.as_str() == RANGE_INCLUSIVE;
+                if !inclusive && found.as_str() != RANGE {
+                    return Err(invalid()).attach_printable(format!(
+                        "`for` loops count over a range like `0..n`, the value at {range_span} \
+                         is a `{found}`"
+                    ));
+                }
+                if t.as_str() != "u64" {
+                    return Err(AstParseError::TypeMismatch {
+                        expected: t,
+                        found: Symbol::intern("u64"),
+                        span: range_span,
+                    })
+                    .attach_printable(format!("the bounds of a `{found}` are `u64`s"));
+                }
+                inclusive
+            }
+        };
+
+        let counter = Variable {
+            t,
+            mutable: false,
+            span: name_span,
+        };
+        self.scopes.push(HashMap::from([(name, counter)]));
+        let body = self.loop_body(invalid);
+        self.scopes.pop();
+
+        Ok(AstStatement::For {
+            name,
+            t,
+            range,
+            inclusive,
+            body: body?,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses `return value;`, whose value has to fit the return type of the function around it
+    fn return_statement(&mut self) -> error_stack::Result<AstStatement, AstParseError> {
+        let span = self.tokens.peek_span();
+        self.tokens.eat(); // Return
+        let Some(function) = &self.function else {
+            return Err(AstParseError::ReturnOutsideFunction { span })
+                .attach_printable(format!("`return` at {span} is not inside of a function"));
+        };
+        let return_type = function.return_type;
+        let value_span = self.tokens.peek_span();
+        let value = match self.tokens.peek() {
+            Some(tokenizer::Token::Semicolon { span: _ }) => None,
+            _ => Some(self.statement_expression()?),
+        };
+        self.tokens
+            .expect(TokenKind::Semicolon)
+            .change_context(AstParseError::InvalidReturn { span })?;
+
+        match (return_type, &value) {
+            (Some(t), _) if t.as_str() == NEVER => {
+                return Err(AstParseError::InvalidReturn { span })
+                    .attach_printable("the function returns `!`, so it can't `return`")
+            }
+            (None, Some(_)) => {
+                return Err(AstParseError::InvalidReturn { span })
+                    .attach_printable("the function returns nothing, but `return` has a value")
+            }
+            (Some(t), None) => {
+                return Err(AstParseError::InvalidReturn { span }).attach_printable(format!(
+                    "the function returns a `{t}`, but `return` has no value"
+                ))
+            }
+            (Some(expected), Some(value)) => {
+                // A number of no particular type fits any number type, but no struct or enum
+                let found = match self.expression_type(value) {
+                    None if self.is_named_type(expected) => Some(Symbol::intern("{integer}")),
+                    found => found,
+                };
+                if let Some(found) = found.filter(|found| *found != expected) {
+                    return Err(AstParseError::TypeMismatch {
+                        expected,
+                        found,
+                        span: value_span,
+                    })
+                    .attach_printable(format!("`return` at {span} doesn't fit the return type"));
+                }
+            }
+            (None, None) => {}
+        }
+        Ok(AstStatement::Return {
+            value,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses the block of a loop, in which `break` and `continue` can be used
+    fn loop_body(
+        &mut self,
+        invalid: impl FnOnce() -> AstParseError,
+    ) -> error_stack::Result<Vec<AstStatement>, AstParseError> {
+        self.loop_depth += 1;
+        let body = self.block(invalid);
+        self.loop_depth -= 1;
+        body
+    }
+
+    /// Parses `{ ... }`, whose variables are only visible inside of it. A missing `{` is reported
+    /// as `invalid`
+    fn block(
+        &mut self,
+        invalid: impl FnOnce() -> AstParseError,
+    ) -> error_stack::Result<Vec<AstStatement>, AstParseError> {
+        let open = self.tokens.peek_span();
+        self.tokens
+            .expect(TokenKind::OpenBrace)
+            .change_context_lazy(invalid)?;
+        self.scopes.push(HashMap::new());
+        let mut body = vec![];
+        loop {
+            match self.tokens.peek() {
+                Some(tokenizer::Token::CloseBrace { span: _ }) => {
+                    self.tokens.eat();
+                    self.scopes.pop();
+                    return Ok(body);
+                }
+                None | Some(tokenizer::Token::Eof { span: _ }) => {
+                    let span = self.tokens.peek_span();
+                    self.scopes.pop();
+                    return Err(AstParseError::UnexpectedEof { span })
+                        .attach_printable(format!("the `{{` at {open} is never closed"));
+                }
+                Some(_) => {
+                    if let Some(statement) = self.recovering_statement() {
+                        body.push(statement);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parses the expression of a statement. Undeclared variables and a premature end of file
+    /// are reported as such, anything else as an invalid expression
+    fn statement_expression(&mut self) -> error_stack::Result<AstExpression, AstParseError> {
+        let value_span = self.tokens.peek_span();
+        self.expression()
+            .map_err(|report| statement_error(report, value_span))
+    }
+
+    /// Like [`AstParser::statement_expression`] for the values that are computed with, like the
+    /// value of `exit`, which can't be structs or `bool`s
+    fn number_expression(&mut self) -> error_stack::Result<AstExpression, AstParseError> {
+        let value_span = self.tokens.peek_span();
+        let value = self.statement_expression()?;
+        self.check_number(&value)
+            .map_err(|report| statement_error(report, value_span))?;
+        Ok(value)
+    }
+
+    /// Like [`AstParser::statement_expression`] for the condition of an `if` or a `while`
+    fn condition(&mut self) -> error_stack::Result<AstExpression, AstParseError> {
+        let value_span = self.tokens.peek_span();
+        let value = self.statement_expression()?;
+        self.check_bool(&value)
+            .map_err(|report| statement_error(report, value_span))?;
+        Ok(value)
+    }
+
+    fn declare(&mut self, name: Symbol, variable: Variable) {
+        self.scopes
+            .last_mut()
+            .expect("the global scope is never popped")
+            .insert(name, variable);
+    }
+
+    /// The innermost variable called `name`, or the constant if no variable is. `None` if there
+    /// is neither
+    fn variable(&self, name: Symbol) -> Option<Variable> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).copied())
+            .or_else(|| self.constants.get(&name).copied())
+    }
+
+    /// The type of the innermost variable called `name`, `None` if there is none
+    fn variable_type(&self, name: Symbol) -> Option<Symbol> {
+        self.variable(name).map(|variable| variable.t)
+    }
+
+    /// The type of an expression as far as it is known without inference. Suffixed literals,
+    /// variables, calls, casts and booleans have one, arithmetic has the one its operands agree
+    /// on
+    fn expression_type(&self, expr: &AstExpression) -> Option<Symbol> {
+        if expr.is_boolean() {
+            return Some(Symbol::intern(BOOL));
+        }
+        match expr {
+            AstExpression::Number { suffix, .. } => suffix.as_deref().map(Symbol::intern),
+            AstExpression::Identifier { name, span: _ } => self.variable_type(*name),
+            AstExpression::Call {
+                name,
+                args: _,
+                span: _,
+            } => self
+                .functions
+                .get(name)
+                .and_then(|function| function.return_type),
+            AstExpression::MethodCall {
+                value: _,
+                t,
+                method,
+                args: _,
+                span: _,
+            } => self
+                .functions
+                .get(&method_name(*t, *method))
+                .and_then(|function| function.return_type),
+            AstExpression::Cast {
+                value: _,
+                t,
+                span: _,
+            } => Some(*t),
+            AstExpression::UnaryOperation {
+                operator: _,
+                operand,
+                span: _,
+            } => self.expression_type(operand),
+            // Both sides have the same type if they both have one
+            AstExpression::BinaryOperation {
+                left,
+                operator,
+                right: _,
+                span: _,
+            } if is_shift(operator) => self.expression_type(left),
+            AstExpression::BinaryOperation {
+                left,
+                operator: _,
+                right,
+                span: _,
+            } => self
+                .expression_type(left)
+                .or_else(|| self.expression_type(right)),
+            AstExpression::StructLiteral {
+                name,
+                fields: _,
+                span: _,
+            } => Some(*name),
+            AstExpression::FieldAccess {
+                value,
+                field,
+                span: _,
+            } => self.field_type(self.expression_type(value)?, *field),
+            AstExpression::Variant {
+                name,
+                variant: _,
+                span: _,
+            } => Some(*name),
+            // The values are checked to fit the first one with a type once the literal is stored
+            AstExpression::ArrayLiteral { values, span: _ } => {
+                let element = values
+                    .iter()
+                    .find_map(|value| self.expression_type(value))
+                    .unwrap_or_else(|| self.inferred_type(&values[0]));
+                Some(array_of(element, values.len()))
+            }
+            AstExpression::Index {
+                value,
+                index: _,
+                span: _,
+            } => array_type(self.expression_type(value)?).map(|(element, _)| element),
+            AstExpression::Range {
+                start: _,
+                end: _,
+                inclusive,
+                span: _,
+            } => Some(range_type(*inclusive)),
+            AstExpression::Bool { value: _, span: _ } => {
+                unreachable!("booleans were handled above")
+            }
+        }
+    }
+
+    /// The type of `field` of the struct `t`, `None` if `t` isn't a struct with that field
+    fn field_type(&self, t: Symbol, field: Symbol) -> Option<Symbol> {
+        self.structs
+            .get(&t)?
+            .iter()
+            .find(|other| other.name == field)
+            .map(|field| field.t)
+    }
+
+    /// The type of a `let` without one. Numbers of no particular type are `u64`s, or `f64`s if
+    /// any literal in them is a float
+    fn inferred_type(&self, value: &AstExpression) -> Symbol {
+        self.expression_type(value).unwrap_or_else(|| {
+            if has_float_literal(value) {
+                Symbol::intern("f64")
+            } else {
+                Symbol::intern("u64")
+            }
+        })
+    }
+
+    /// Parses an expression, which binds looser than every operator in it. A range binds the
+    /// loosest, so `0..n + 1` ends at `n + 1`
+    fn expression(&mut self) -> ExpressionParseResult {
+        let span = self.tokens.peek_span();
+        let start = self.binary(0)?;
+        let inclusive = match self.tokens.peek() {
+            Some(tokenizer::Token::DotDot { span: _ }) => false,
+            Some(tokenizer::Token::DotDotEqual { span: _ }) => true,
+            _ => return Ok(start),
+        };
+        self.tokens.eat();
+        let end = self.binary(0)?;
+        for bound in [&start, &end] {
+            self.check_number(bound)?;
+            self.check_integer("..", bound)?;
+        }
+        Ok(AstExpression::Range {
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses operands joined by the binary operators binding tighter than `min_power`. An
+    /// operator's right side only takes operators binding tighter than it, which makes every
+    /// operator left associative
+    fn binary(&mut self, min_power: usize) -> ExpressionParseResult {
+        let start = self.tokens.peek_span();
+        let mut node = self.cast()?;
+        while let Some(tokenizer::Token::BinaryOperator { op, span: _ }) = self.tokens.peek() {
+            let Some(power) = binding_power(op).filter(|power| *power > min_power) else {
+                break;
+            };
+            let operator = op.clone();
+            self.tokens.eat();
+            let right_span = self.tokens.peek_span();
+            let right = self.binary(power)?;
+            let left_type = self.expression_type(&node);
+            let right_type = self.expression_type(&right);
+            // Variants of the same enum and `bool`s can be compared, but not computed with
+            let compares_same = matches!(operator, BinaryOp::DoubleEqual | BinaryOp::NotEqual)
+                && left_type == right_type
+                && (self.is_variant(&node) || left_type.is_some_and(|t| t.as_str() == BOOL));
+            if matches!(operator, BinaryOp::And | BinaryOp::Or) {
+                self.check_bool(&node)?;
+                self.check_bool(&right)?;
+            } else if !compares_same {
+                self.check_number(&node)?;
+                self.check_number(&right)?;
+            }
+            // Numbers of no particular type take the type of the other side, and the amount of a
+            // shift can be any integer
+            if let (Some(left), Some(found)) = (left_type, right_type) {
+                if left != found && !is_shift(&operator) {
+                    return Err(ExpressionParseError::TypeMismatch {
+                        expected: left,
+                        found,
+                        span: right.span(),
+                    })
+                    .attach_printable(format!(
+                        "both sides of `{operator}` have to be of the same type, cast one with `as`"
+                    ));
+                }
+            }
+            if integer_only(&operator) {
+                self.check_integer(&operator.to_string(), &node)?;
+                self.check_integer(&operator.to_string(), &right)?;
+            }
+            if matches!(operator, BinaryOp::Slash | BinaryOp::Percent) && is_integer_zero(&right) {
+                return Err(ExpressionParseError::DivisionByZero { span: right_span })
+                    .attach_printable(format!("division by a literal zero at {right_span}"));
+            }
+            node = AstExpression::BinaryOperation {
+                left: Box::new(node),
+                operator,
+                right: Box::new(right),
+                span: self.span_from(start),
+            }
+        }
+        Ok(node)
+    }
+
+    /// Parses `factor as T as U ...`, which binds tighter than any binary operator
+    fn cast(&mut self) -> ExpressionParseResult {
+        let start = self.tokens.peek_span();
+        let mut node = self.factor()?;
+        while self.tokens.expect(TokenKind::Keyword("as")).is_ok() {
+            let type_span = self.tokens.peek_span();
+            let Ok(t) = self.tokens.expect_identifier() else {
+                return Err(self.unexpected_token()).attach_printable("expected a type after `as`");
+            };
+            if !self.is_type(t) {
+                return Err(ExpressionParseError::UnknownType { t, span: type_span })
+                    .attach_printable(
+                        "types are numbers, `bool` and the structs and enums defined before",
+                    );
+            }
+            // A variant is cast to its position in the enum, a `bool` to `0` or `1`
+            if !self.is_variant(&node)
+                && self
+                    .expression_type(&node)
+                    .is_none_or(|t| t.as_str() != BOOL)
+            {
+                self.check_number(&node)?;
+            }
+            if self.is_named_type(t) {
+                return Err(ExpressionParseError::NotANumber { t, span: type_span })
+                    .attach_printable("`as` only converts to numbers and `bool`s");
+            }
+            node = AstExpression::Cast {
+                value: Box::new(node),
+                t,
+                span: self.span_from(start),
+            };
+        }
+        Ok(node)
+    }
+
+    fn factor(&mut self) -> ExpressionParseResult {
+        let start = self.tokens.peek_span();
+        match self.tokens.peek().cloned() {
+            Some(tokenizer::Token::BinaryOperator {
+                op: BinaryOp::Minus,
+                span: _,
+            }) => {
+                self.tokens.eat();
+                let operand = Box::new(self.factor()?);
+                self.check_number(&operand)?;
+                Ok(AstExpression::UnaryOperation {
+                    operator: UnaryOp::Negate,
+                    operand,
+                    span: self.span_from(start),
+                })
+            }
+            Some(tokenizer::Token::Bang { span: _ }) => {
+                self.tokens.eat();
+                let operand = Box::new(self.factor()?);
+                self.check_bool(&operand)?;
+                Ok(AstExpression::UnaryOperation {
+                    operator: UnaryOp::Not,
+                    operand,
+                    span: self.span_from(start),
+                })
+            }
+            Some(tokenizer::Token::Tilde { span: _ }) => {
+                self.tokens.eat();
+                let operand = Box::new(self.factor()?);
+                self.check_number(&operand)?;
+                self.check_integer("~", &operand)?;
+                Ok(AstExpression::UnaryOperation {
+                    operator: UnaryOp::BitNot,
+                    operand,
+                    span: self.span_from(start),
+                })
+            }
+            _ => self.field_access(),
+        }
+    }
+
+    /// Parses `primary.a[i].b(...) ...`, which binds tighter than the unary operators. Only the
+    /// last method call of a call statement can give no value
+    fn field_access(&mut self) -> ExpressionParseResult {
+        let start = self.tokens.peek_span();
+        let mut node = self.primary()?;
+        let statement = std::mem::take(&mut self.statement_call);
+        loop {
+            let next = matches!(
+                self.tokens.peek(),
+                Some(tokenizer::Token::OpenBracket { span: _ } | tokenizer::Token::Dot { span: _ })
+            );
+            if let AstExpression::MethodCall { t, method, .. } = &node {
+                if next || !statement {
+                    self.check_returns(method_name(*t, *method), node.span())?;
+                }
+            }
+            if self.tokens.expect(TokenKind::OpenBracket).is_ok() {
+                node = self.index(node, start)?;
+                continue;
+            }
+            if self.tokens.expect(TokenKind::Dot).is_err() {
+                break;
+            }
+            let field_span = self.tokens.peek_span();
+            let Ok(field) = self.tokens.expect_identifier() else {
+                return Err(self.unexpected_token())
+                    .attach_printable("expected a field name after `.`");
+            };
+            if let Some(tokenizer::Token::OpenParen { span: _ }) = self.tokens.peek() {
+                node = self.method_call(node, field, field_span, start)?;
+                continue;
+            }
+            let t = self
+                .expression_type(&node)
+                .unwrap_or_else(|| Symbol::intern("{integer}"));
+            if self.field_type(t, field).is_none() {
+                return Err(ExpressionParseError::UnknownField {
+                    t,
+                    field,
+                    span: field_span,
+                })
+                .attach_printable(format!(
+                    "`.{field}` at {field_span} reads a field of a `{t}`"
+                ));
+            }
+            node = AstExpression::FieldAccess {
+                value: Box::new(node),
+                field,
+                span: self.span_from(start),
+            };
+        }
+        Ok(node)
+    }
+
+    /// Parses the `index]` after the `[` of `value[`, a constant index has to be in the bounds
+    /// of the array
+    fn index(&mut self, value: AstExpression, start: tokenizer::Span) -> ExpressionParseResult {
+        let index = self.expression()?;
+        let index_span = index.span();
+        if self.tokens.expect(TokenKind::CloseBracket).is_err() {
+            return Err(self.unexpected_token()).attach_printable("unclosed `[` found");
+        }
+        let t = self
+            .expression_type(&value)
+            .unwrap_or_else(|| Symbol::intern("{integer}"));
+        let Some((_, length)) = array_type(t) else {
+            return Err(ExpressionParseError::NotAnArray {
+                t,
+                span: value.span(),
+            })
+            .attach_printable("only arrays can be indexed");
+        };
+        // C only allows indexing a literal written as a compound literal
+        if let AstExpression::ArrayLiteral { .. } = value {
+            return Err(ExpressionParseError::InvalidArray { span: value.span() })
+                .attach_printable("store the array in a variable to index it");
+        }
+        if let Some(t) = self
+            .expression_type(&index)
+            .filter(|t| matches!(t.as_str(), BOOL | "f32" | "f64"))
+        {
+            return Err(ExpressionParseError::InvalidIndex {
+                t,
+                span: index_span,
+            })
+            .attach_printable("indices are integers");
+        }
+        self.check_number(&index)?;
+        let index_type = self
+            .expression_type(&index)
+            .unwrap_or_else(|| Symbol::intern("i64"));
+        if let Ok(const_eval::Value::Integer(constant)) =
+            const_eval::const_eval(&index, index_type, &self.values)
+        {
+            // Stored sign extended, so a negative index is reported as such
+            if usize::try_from(constant).map_or(true, |constant| constant >= length) {
+                return Err(ExpressionParseError::IndexOutOfBounds {
+                    index: constant as i64,
+                    length,
+                    span: index_span,
+                })
+                .attach_printable(format!(
+                    "the indices of a `{t}` go from 0 to {}",
+                    length - 1
+                ));
+            }
+        }
+        Ok(AstExpression::Index {
+            value: Box::new(value),
+            index: Box::new(index),
+            span: self.span_from(start),
+        })
+    }
+
+    fn primary(&mut self) -> ExpressionParseResult {
+        match self.tokens.peek().cloned() {
+            Some(tokenizer::Token::Number {
+                raw,
+                flags,
+                suffix,
+                span,
+            }) => {
+                self.tokens.eat();
+                Ok(AstExpression::Number {
+                    raw: raw.into_owned(),
+                    flags,
+                    suffix: suffix.map(String::from),
+                    span: NodeSpan(span),
+                })
+            }
+            Some(tokenizer::Token::Bool { value, span }) => {
+                self.tokens.eat();
+                Ok(AstExpression::Bool {
+                    value,
+                    span: NodeSpan(span),
+                })
+            }
+            Some(tokenizer::Token::Identifier { name, span }) => {
+                self.tokens.eat();
+                if let Some(tokenizer::Token::OpenParen { span: _ }) = self.tokens.peek() {
+                    return self.call(name, span);
+                }
+                if let Some(tokenizer::Token::DoubleColon { span: _ }) = self.tokens.peek() {
+                    return self.variant(name, span);
+                }
+                if self.structs.contains_key(&name)
+                    && matches!(
+                        self.tokens.peek(),
+                        Some(tokenizer::Token::OpenBrace { span: _ })
+                    )
+                {
+                    return self.struct_literal(name, span);
+                }
+                if self.variable_type(name).is_none() {
+                    return Err(ExpressionParseError::UndeclaredIdentifier { name, span })
+                        .attach_printable(
+                            "variables have to be declared with `let` before they are used",
+                        );
+                }
+                Ok(AstExpression::Identifier {
+                    name,
+                    span: NodeSpan(span),
+                })
+            }
+            Some(tokenizer::Token::OpenParen { span: _ }) => {
+                self.tokens.eat();
+                let node = self.expression()?;
+
+                if let Some(tokenizer::Token::CloseParen { span: _ }) = self.tokens.peek() {
+                    self.tokens.eat();
+                    Ok(node)
+                } else {
+                    Err(self.unexpected_token()).attach_printable("unclosed parenthesis found")
+                }
+            }
+            Some(tokenizer::Token::OpenBracket { span }) => {
+                self.tokens.eat();
+                let mut values = vec![];
+                // The last value can have a `,` after it too
+                while self.tokens.expect(TokenKind::CloseBracket).is_err() {
+                    values.push(self.expression()?);
+                    if self.tokens.expect(TokenKind::Comma).is_err() {
+                        if self.tokens.expect(TokenKind::CloseBracket).is_err() {
+                            return Err(self.unexpected_token())
+                                .attach_printable("expected `,` or `]` after an array value");
+                        }
+                        break;
+                    }
+                }
+                if values.is_empty() {
+                    return Err(ExpressionParseError::InvalidArray { span })
+                        .attach_printable("an array needs at least one value");
+                }
+                Ok(AstExpression::ArrayLiteral {
+                    values,
+                    span: self.span_from(span),
+                })
+            }
+            _ => Err(self.unexpected_token()).attach_printable("failed to parse factor"),
+        }
+    }
+
+    /// Parses the `::variant` after `name`, which has to be an enum with that variant
+    fn variant(&mut self, name: Symbol, span: tokenizer::Span) -> ExpressionParseResult {
+        self.tokens.eat(); // DoubleColon
+        let variant_span = self.tokens.peek_span();
+        let Ok(variant) = self.tokens.expect_identifier() else {
+            return Err(self.unexpected_token()).attach_printable("expected a variant after `::`");
+        };
+        let unknown = ExpressionParseError::UnknownVariant {
+            t: name,
+            variant,
+            span: variant_span,
+        };
+        let Some(variants) = self.enums.get(&name) else {
+            return Err(unknown).attach_printable(format!("`{name}` at {span} is not an enum"));
+        };
+        if !variants.contains(&variant) {
+            let variants = variants
+                .iter()
+                .map(|variant| format!("`{variant}`"))
+                .collect::<Vec<_>>();
+            return Err(unknown).attach_printable(format!(
+                "the variants of `{name}` are {}",
+                variants.join(", ")
+            ));
+        }
+        Ok(AstExpression::Variant {
+            name,
+            variant,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Parses the `{ a: value, ... }` after the name of the struct `name`, which needs a value
+    /// for every field
+    fn struct_literal(&mut self, name: Symbol, span: tokenizer::Span) -> ExpressionParseResult {
+        let definition = self.structs[&name].clone();
+        // The rest of a broken literal is skipped, its `}` would look like the end of a block
+        self.tokens.eat(); // OpenBrace
+        let mut values = self
+            .struct_literal_fields(name, span)
+            .inspect_err(|_| self.skip_braces())?;
+
+        let missing = definition
+            .iter()
+            .filter(|field| values.iter().all(|(other, _)| *other != field.name))
+            .map(|field| format!("`{}`", field.name))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(ExpressionParseError::InvalidStructLiteral { name, span })
+                .attach_printable(format!(
+                    "the `{name}` literal at {span} has no value for {}",
+                    missing.join(", ")
+                ));
+        }
+        let fields = definition
+            .iter()
+            .map(|field| {
+                let position = values
+                    .iter()
+                    .position(|(other, _)| *other == field.name)
+                    .expect("checked that no field is missing");
+                values.swap_remove(position)
+            })
+            .collect();
+        Ok(AstExpression::StructLiteral {
+            name,
+            fields,
+            span: self.span_from(span),
+        })
+    }
+
+    /// The `a: value, ...}` of a literal of the struct `name`, in source order
+    fn struct_literal_fields(
+        &mut self,
+        name: Symbol,
+        span: tokenizer::Span,
+    ) -> error_stack::Result<Vec<(Symbol, AstExpression)>, ExpressionParseError> {
+        let mut values: Vec<(Symbol, AstExpression)> = vec![];
+        // The last field can have a `,` after it too
+        while self.tokens.expect(TokenKind::CloseBrace).is_err() {
+            let field_span = self.tokens.peek_span();
+            let Ok(field) = self.tokens.expect_identifier() else {
+                return Err(self.unexpected_token()).attach_printable("expected a field name");
+            };
+            if self.tokens.expect(TokenKind::Colon).is_err() {
+                return Err(self.unexpected_token())
+                    .attach_printable(format!("expected `:` after the field `{field}`"));
+            }
+            let value_span = self.tokens.peek_span();
+            let value = self.expression()?;
+            let Some(t) = self.field_type(name, field) else {
+                return Err(ExpressionParseError::UnknownField {
+                    t: name,
+                    field,
+                    span: field_span,
+                })
+                .attach_printable(format!("the `{name}` literal at {span} has no such field"));
+            };
+            if values.iter().any(|(other, _)| *other == field) {
+                return Err(ExpressionParseError::InvalidStructLiteral { name, span })
+                    .attach_printable(format!("field `{field}` at {field_span} is given twice"));
+            }
+            if let Some(found) = self.mismatch(t, &value) {
+                return Err(ExpressionParseError::TypeMismatch {
+                    expected: t,
+                    found,
+                    span: value_span,
+                })
+                .attach_printable(format!("field `{field}` of `{name}` is a `{t}`"));
+            }
+            values.push((field, value));
+            if self.tokens.expect(TokenKind::Comma).is_err() {
+                if self.tokens.expect(TokenKind::CloseBrace).is_err() {
+                    return Err(self.unexpected_token())
+                        .attach_printable("expected `,` or `}` after a field");
+                }
+                break;
+            }
+        }
+        Ok(values)
+    }
+
+    /// Skips up to and including the `}` closing the `{` eaten last, or up to the end of file
+    fn skip_braces(&mut self) {
+        let mut depth = 1usize;
+        while let Some(token) = self.tokens.peek() {
+            match token {
+                tokenizer::Token::Eof { span: _ } => return,
+                tokenizer::Token::OpenBrace { span: _ } => depth += 1,
+                tokenizer::Token::CloseBrace { span: _ } => depth -= 1,
+                _ => {}
+            }
+            self.tokens.eat();
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Parses the `(a, b, ...)` after `name`, which has to be a function taking that many
+    /// arguments. Only the call of a call statement can be to a function returning nothing or
+    /// never returning
+    fn call(&mut self, name: Symbol, span: tokenizer::Span) -> ExpressionParseResult {
+        // The arguments are values again
+        let statement = std::mem::take(&mut self.statement_call);
+        let Some(function) = self.functions.get(&name) else {
+            return Err(ExpressionParseError::UndeclaredFunction { name, span })
+                .attach_printable("functions have to be defined before they are called");
+        };
+        let expected = function.params;
+        if let Some(message) = self.deprecated.get(&name) {
+            self.warnings.push(Warning::UsedDeprecated {
+                name,
+                message: message.clone(),
+                span,
+            });
+        }
+        if !statement {
+            self.check_returns(name, span)?;
+        }
+        self.tokens.eat(); // OpenParen
+        let mut args = vec![];
+        while self.tokens.expect(TokenKind::CloseParen).is_err() {
+            if !args.is_empty() && self.tokens.expect(TokenKind::Comma).is_err() {
+                return Err(self.unexpected_token())
+                    .attach_printable("expected `,` or `)` after a call argument");
+            }
+            let arg = self.expression()?;
+            // C would pass a pointer to the array instead
+            if self.expression_type(&arg).and_then(array_type).is_some() {
+                return Err(ExpressionParseError::InvalidArray { span: arg.span() })
+                    .attach_printable("arrays can't be passed to functions");
+            }
+            args.push(arg);
+        }
+        if args.len() != expected {
+            return Err(ExpressionParseError::ArityMismatch {
+                name,
+                expected,
+                found: args.len(),
+                span,
+            })
+            .attach_printable(format!("wrong number of arguments in the call at {span}"));
+        }
+        Ok(AstExpression::Call {
+            name,
+            args,
+            span: self.span_from(span),
+        })
+    }
+
+    /// Whether calling the function `name` at `span` gives a value
+    fn check_returns(
+        &self,
+        name: Symbol,
+        span: tokenizer::Span,
+    ) -> error_stack::Result<(), ExpressionParseError> {
+        match self
+            .functions
+            .get(&name)
+            .and_then(|function| function.return_type)
+        {
+            None => Err(ExpressionParseError::NoValue { name, span }).attach_printable(format!(
+                "the call at {span} is used as a value, but `{name}` has no return type"
+            )),
+            Some(t) if t.as_str() == NEVER => Err(ExpressionParseError::NoValue { name, span })
+                .attach_printable(format!(
+                    "the call at {span} is used as a value, but `{name}` never returns"
+                )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Whether the function `name` never returns
+    fn never_returns(&self, name: Symbol) -> bool {
+        self.functions
+            .get(&name)
+            .and_then(|function| function.return_type)
+            .is_some_and(|t| t.as_str() == NEVER)
+    }
+
+    /// Parses the `(a, b, ...)` after `value.method`, a method of the type of `value`
+    fn method_call(
+        &mut self,
+        value: AstExpression,
+        method: Symbol,
+        span: tokenizer::Span,
+        start: tokenizer::Span,
+    ) -> ExpressionParseResult {
+        let t = self
+            .expression_type(&value)
+            .unwrap_or_else(|| Symbol::intern("{integer}"));
+        let name = method_name(t, method);
+        if !self.functions.contains_key(&name) {
+            return Err(ExpressionParseError::UnknownMethod { t, method, span }).attach_printable(
+                format!("methods are defined in an `impl {t}` before the call"),
+            );
+        }
+        // Whether the call needs a value is up to the caller
+        self.statement_call = true;
+        let AstExpression::Call { args, .. } = self.call(name, span)? else {
+            unreachable!("`call` only parses calls")
+        };
+        Ok(AstExpression::MethodCall {
+            value: Box::new(value),
+            t,
+            method,
+            args,
+            span: self.span_from(start),
+        })
+    }
+
+    /// The error for the next token not fitting into an expression
+    fn unexpected_token(&self) -> ExpressionParseError {
+        let span = self.tokens.peek_span();
+        match self
+            .tokens
+            .peek()
+            .map_or(TokenKind::Eof, tokenizer::Token::kind)
+        {
+            TokenKind::Eof => ExpressionParseError::UnexpectedEof { span },
+            found => ExpressionParseError::InvalidFactorToken { found, span },
+        }
+    }
+
+    /// From the start of `start` to the end of the token eaten last
+    fn span_from(&self, start: tokenizer::Span) -> NodeSpan {
+        let end = self
+            .tokens
+            .previous()
+            .map_or(start.end, |token| token.span().end);
+        NodeSpan(tokenizer::Span {
+            start: start.start,
+            end,
+        })
+    }
+
+    fn finished(&self) -> bool {
+        matches!(self.tokens.peek(), Some(tokenizer::Token::Eof { span: _ }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{self, AstExpression, AstStatement, AstVisitor, AstVisitorMut},
+        const_eval,
+        json::Json,
+        symbol::Symbol,
+        tokenizer,
+    };
+
+    /// Spans don't take part in comparing nodes, so expected trees can put them anywhere
+    const SPAN: ast::NodeSpan = ast::NodeSpan(tokenizer::Span { start: 0, end: 0 });
+
+    #[test]
+    fn let_statement() {
+        {
+            let src = "let a: u64 = (123 + 69) * 2;".to_string();
+            let tokens = tokenizer::Tokenizer::new(&src, "tests::let_statement".to_string())
+                .tokenize()
+                .unwrap();
+
+            assert_eq!(
+                ast::AstParser::new(tokens).parse().unwrap(),
+                vec![AstStatement::Let {
+                    value: AstExpression::BinaryOperation {
+                        left: Box::new(AstExpression::BinaryOperation {
+                            left: Box::new(AstExpression::Number {
+                                raw: "123".to_string(),
+                                flags: vec![],
+                                suffix: None,
+                                span: SPAN,
+                            }),
+                            operator: tokenizer::BinaryOp::Plus,
+                            right: Box::new(AstExpression::Number {
+                                raw: "69".to_string(),
+                                flags: vec![],
+                                suffix: None,
+                                span: SPAN,
+                            }),
+                            span: SPAN,
+                        }),
+                        operator: tokenizer::BinaryOp::Star,
+                        right: Box::new(AstExpression::Number {
+                            raw: "2".to_string(),
+                            flags: vec![],
+                            suffix: None,
+                            span: SPAN,
+                        }),
+                        span: SPAN,
+                    },
+                    name: Symbol::intern("a"),
+                    t: Symbol::intern("u64"),
+                    mutable: false,
+                    span: SPAN,
+                },]
+            )
+        }
+    }
+
+    #[test]
+    fn variable_collector() {
+        let src = "let a: u64 = 1; let b: u64 = a + 2; exit(b);".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::variable_collector".to_string())
+            .tokenize()
+            .unwrap();
+        let program = ast::AstParser::new(tokens).parse().unwrap();
+
+        let mut collector = ast::VariableCollector::default();
+        collector.visit_program(&program);
+        assert_eq!(
+            collector.names,
+            vec![Symbol::intern("a"), Symbol::intern("b")]
+        );
+    }
+
+    #[test]
+    fn visitor_walks_in_tree_order() {
+        struct Identifiers(Vec<&'static str>);
+        impl AstVisitor for Identifiers {
+            fn visit_expression(&mut self, expr: &AstExpression) {
+                if let AstExpression::Identifier { name, span: _ } = expr {
+                    self.0.push(name.as_str());
+                }
+                ast::DefaultWalker::walk_expression(self, expr);
+            }
+        }
+
+        let src = "let a: u64 = 1; let b: u64 = 2; let c: u64 = 3; exit((a + b) * c);".to_string();
+        let tokens =
+            tokenizer::Tokenizer::new(&src, "tests::visitor_walks_in_tree_order".to_string())
+                .tokenize()
+                .unwrap();
+        let program = ast::AstParser::new(tokens).parse().unwrap();
+
+        let mut identifiers = Identifiers(vec![]);
+        identifiers.visit_program(&program);
+        assert_eq!(identifiers.0, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn mutable_visitor() {
+        struct Double;
+        impl AstVisitorMut for Double {
+            fn visit_expression_mut(&mut self, expr: &mut AstExpression) {
+                if let AstExpression::Number { raw, .. } = expr {
+                    *raw = (raw.parse::<u64>().unwrap() * 2).to_string();
+                }
+                ast::DefaultWalkerMut::walk_expression(self, expr);
+            }
+        }
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::mutable_visitor".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse().unwrap()
+        };
+
+        let mut program = parse(
+            "fn f(x: u64) -> u64 { if x > 1 { let a: u64 = -x + f(3); exit(a); } return x; }",
+        );
+        Double.visit_program_mut(&mut program);
+        assert_eq!(
+            program,
+            parse(
+                "fn f(x: u64) -> u64 { if x > 2 { let a: u64 = -x + f(6); exit(a); } return x; }"
+            )
+        );
+    }
+
+    #[test]
+    fn missing_eof() {
+        let err = ast::AstParser::new(vec![tokenizer::Token::Semicolon {
+            span: tokenizer::Span { start: 0, end: 1 },
+        }])
+        .parse()
+        .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::MissingEof
+        ));
+    }
+
+    #[test]
+    fn invalid_let_statement_span() {
+        let src = "exit(1);\nlet 5: u64 = 1;".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::invalid_let_span".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::InvalidLetStatement {
+                span: tokenizer::Span { start: 9, end: 12 }
+            }
+        ));
+        assert!(
+            format!("{err:?}").contains("expected an identifier, found a number literal at 13..14")
+        );
+    }
+
+    #[test]
+    fn unexpected_end_of_file() {
+        let src = "exit((1 +".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::eof".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::UnexpectedEof {
+                span: tokenizer::Span { start: 9, end: 9 }
+            }
+        ));
+        assert_eq!(err.current_context().to_string(), "unexpected end of file");
+    }
+
+    #[test]
+    fn let_statement_expectations() {
+        for (src, message) in [
+            ("let a u64 = 1;", "expected `:`, found an identifier"),
+            ("let a: = 1;", "expected an identifier, found `=`"),
+            ("let a: u64 1;", "expected `=`, found a number literal"),
+            ("let a: u64 = 1 exit(a);", "expected `;`, found `exit`"),
+            ("let a: u64 = 1", "expected `;`, found the end of the file"),
+        ] {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::let".to_string())
+                .tokenize()
+                .unwrap();
+            let err = ast::AstParser::new(tokens).parse().unwrap_err();
+            assert!(format!("{err:?}").contains(message), "{src}: {err:?}");
+        }
+    }
+
+    #[test]
+    fn display_keeps_precedence() {
+        let src =
+            "let a: u64 = 1; let b: u64 = 2; let c: u64 = 3; exit((a + 1) * 2 - b * (c - 3));"
+                .to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::display".to_string())
+            .tokenize()
+            .unwrap();
+        let Some(AstStatement::Exit { value, span: _ }) =
+            ast::AstParser::new(tokens).parse().unwrap().pop()
+        else {
+            panic!("expected exit");
+        };
+        assert_eq!(value.to_string(), "((a+1)*2)-(b*(c-3))");
+    }
+
+    #[test]
+    fn use_before_declare() {
+        let src = "exit(a);\nlet a: u64 = 1;".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::use_before_declare".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::UndeclaredIdentifier {
+                name,
+                span: tokenizer::Span { start: 5, end: 6 }
+            } if name.as_str() == "a"
+        ));
+    }
+
+    #[test]
+    fn declare_then_use() {
+        let src = "let a: u64 = 1; let b: u64 = a; exit(a * b);".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::declare_then_use".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        assert_eq!(
+            ast[1],
+            AstStatement::Let {
+                value: AstExpression::Identifier {
+                    name: Symbol::intern("a"),
+                    span: SPAN,
+                },
+                name: Symbol::intern("b"),
+                t: Symbol::intern("u64"),
+                mutable: false,
+                span: SPAN,
+            }
+        );
+    }
+
+    #[test]
+    fn print_statements() {
+        let src = "print 1; println 2;".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::print".to_string())
+            .tokenize()
+            .unwrap();
+        let number = |raw: &str| AstExpression::Number {
+            raw: raw.to_string(),
+            flags: vec![],
+            suffix: None,
+            span: SPAN,
+        };
+        assert_eq!(
+            ast::AstParser::new(tokens).parse().unwrap(),
+            vec![
+                AstStatement::Print {
+                    value: number("1"),
+                    newline: false,
+                    span: SPAN,
+                },
+                AstStatement::Print {
+                    value: number("2"),
+                    newline: true,
+                    span: SPAN,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unary_minus() {
+        let src = "let a: i64 = -5; exit(a - -(a * 2));".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::unary_minus".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        assert_eq!(
+            ast[0],
+            AstStatement::Let {
+                value: AstExpression::UnaryOperation {
+                    operator: ast::UnaryOp::Negate,
+                    operand: Box::new(AstExpression::Number {
+                        raw: "5".to_string(),
+                        flags: vec![],
+                        suffix: None,
+                        span: SPAN,
+                    }),
+                    span: SPAN,
+                },
+                name: Symbol::intern("a"),
+                t: Symbol::intern("i64"),
+                mutable: false,
+                span: SPAN,
+            }
+        );
+        let AstStatement::Exit { value, span: _ } = &ast[1] else {
+            panic!("expected exit");
+        };
+        assert_eq!(value.to_string(), "a-(-(a*2))");
+    }
+
+    #[test]
+    fn unary_precedence() {
+        let src = "let a: i64 = 2; exit(-a * ~a / ~~a + --a % 3);".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::unary_precedence".to_string())
+            .tokenize()
+            .unwrap();
+        let Some(AstStatement::Exit { value, span: _ }) =
+            ast::AstParser::new(tokens).parse().unwrap().pop()
+        else {
+            panic!("expected exit");
+        };
+        assert_eq!(value.to_string(), "(((-a)*(~a))/(~(~a)))+((-(-a))%3)");
+    }
+
+    #[test]
+    fn division() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::division".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let Some(AstStatement::Exit { value, span: _ }) =
+            parse("exit(1 + 6 / 2 * 3);").unwrap().pop()
+        else {
+            panic!("expected exit");
+        };
+        assert_eq!(value.to_string(), "1+((6/2)*3)");
+        let Some(AstStatement::Exit { value, span: _ }) =
+            parse("exit(7 - 5 % 3 * 2);").unwrap().pop()
+        else {
+            panic!("expected exit");
+        };
+        assert_eq!(value.to_string(), "7-((5%3)*2)");
+        assert!(parse("exit(1 % 0);").is_err());
+
+        let err = parse("exit(1 / 0x0);").unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::DivisionByZero {
+                span: tokenizer::Span { start: 9, end: 12 }
+            }
+        ));
+        assert!(parse("exit(1.0 / 0.0);").is_ok());
+        for src in ["exit(5.5 % 2);", "let f: f32 = 5.0; exit(7 % f);"] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::NotAnInteger { .. }
+                ),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        let src = "let a: u64 = 1; let b = a + 1 <= 2 * a;".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::comparisons".to_string())
+            .tokenize()
+            .unwrap();
+        let Some(AstStatement::Let { value, .. }) =
+            ast::AstParser::new(tokens).parse().unwrap().pop()
+        else {
+            panic!("expected let");
+        };
+        assert_eq!(value.to_string(), "(a+1)<=(2*a)");
+    }
+
+    #[test]
+    fn logical_operators() {
+        let src = "let a: u64 = 1; let b = true; let c = !b || a < 2 && a != 0 || b;".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::logical".to_string())
+            .tokenize()
+            .unwrap();
+        let Some(AstStatement::Let { value, .. }) =
+            ast::AstParser::new(tokens).parse().unwrap().pop()
+        else {
+            panic!("expected let");
+        };
+        assert_eq!(value.to_string(), "((!b)||((a<2)&&(a!=0)))||b");
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let src = "let a: u64 = 1; let b = a | ~a & 3 ^ a << 1 + 1 == 0;".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::bitwise".to_string())
+            .tokenize()
+            .unwrap();
+        let Some(AstStatement::Let { value, .. }) =
+            ast::AstParser::new(tokens).parse().unwrap().pop()
+        else {
+            panic!("expected let");
+        };
+        assert_eq!(value.to_string(), "(a|(((~a)&3)^(a<<(1+1))))==0");
+
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::bitwise".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        for src in [
+            "exit(~1.5);",
+            "let f: f64 = 1.0; exit(f << 1 & 3);",
+            "let f: f32 = 1.0; exit(1 | f);",
+            "exit(1 ^ 2.0);",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::NotAnInteger { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(parse("let f: f64 = 1.0; exit((f as u64) << 1);").is_ok());
+    }
+
+    #[test]
+    fn function_definition() {
+        let src = "fn add(a: u64, b: u64) -> u64 { let c: u64 = a + b; return c; }\nfn quit() { exit(0); }"
+            .to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::function".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        assert_eq!(
+            ast[0],
+            AstStatement::Function {
+                doc: None,
+                attrs: vec![],
+                name: Symbol::intern("add"),
+                params: vec![
+                    ast::Parameter {
+                        name: Symbol::intern("a"),
+                        t: Symbol::intern("u64"),
+                    },
+                    ast::Parameter {
+                        name: Symbol::intern("b"),
+                        t: Symbol::intern("u64"),
+                    },
+                ],
+                return_type: Some(Symbol::intern("u64")),
+                body: vec![
+                    AstStatement::Let {
+                        name: Symbol::intern("c"),
+                        t: Symbol::intern("u64"),
+                        mutable: false,
+                        value: AstExpression::BinaryOperation {
+                            left: Box::new(AstExpression::Identifier {
+                                name: Symbol::intern("a"),
+                                span: SPAN,
+                            }),
+                            operator: tokenizer::BinaryOp::Plus,
+                            right: Box::new(AstExpression::Identifier {
+                                name: Symbol::intern("b"),
+                                span: SPAN,
+                            }),
+                            span: SPAN,
+                        },
+                        span: SPAN,
+                    },
+                    AstStatement::Return {
+                        value: Some(AstExpression::Identifier {
+                            name: Symbol::intern("c"),
+                            span: SPAN,
+                        }),
+                        span: SPAN,
+                    }
+                ],
+                span: SPAN,
+            }
+        );
+        assert!(matches!(
+            &ast[1],
+            AstStatement::Function { params, return_type: None, body, .. }
+                if params.is_empty() && body.len() == 1
+        ));
+    }
+
+    #[test]
+    fn function_scope() {
+        let src = "let a: u64 = 1; fn f(b: u64) { exit(a + b); }".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::function_scope".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::UndeclaredIdentifier { name, .. } if name.as_str() == "a"
+        ));
+
+        let src = "fn f(b: u64) { let c: u64 = b; } exit(c);".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::function_scope".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::UndeclaredIdentifier { name, .. } if name.as_str() == "c"
+        ));
+    }
+
+    #[test]
+    fn invalid_functions() {
+        let src = "fn f() { fn g() { } }".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::nested_function".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::NestedFunction {
+                span: tokenizer::Span { start: 9, end: 11 }
+            }
+        ));
+
+        let src = "fn f() { exit(0);".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::unclosed_function".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(format!("{err:?}").contains("is never closed"));
+
+        let src = "fn f(a: u64, a: u64) { }".to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::duplicate_parameter".to_string())
+            .tokenize()
+            .unwrap();
+        let err = ast::AstParser::new(tokens).parse().unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::InvalidFunction { .. }
+        ));
+        assert!(format!("{err:?}").contains("parameter `a`"));
+    }
+
+    #[test]
+    fn attributes() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::attributes".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse("#[inline] fn add(a: u64, b: u64) -> u64 { return a + b; }").unwrap();
+        assert!(matches!(
+            &ast[0],
+            AstStatement::Function { attrs, .. } if attrs == &[ast::Attribute::Inline]
+        ));
+        let ast = parse("#[no_mangle] #[inline] fn f() { }").unwrap();
+        assert!(matches!(
+            &ast[0],
+            AstStatement::Function { attrs, .. }
+                if attrs == &[ast::Attribute::NoMangle, ast::Attribute::Inline]
+        ));
+
+        // Calls of deprecated functions are warned about, except in the function itself
+        let src = "#[deprecated = \"use g\"] fn f(a: u64) -> u64 { if a > 0 { return f(a - 1); } return a; } \
+                   #[deprecated] fn h() { } let x: u64 = f(1); h();";
+        let tokens = tokenizer::Tokenizer::new(src, "tests::attributes".to_string())
+            .tokenize()
+            .unwrap();
+        let mut parser = ast::AstParser::new(tokens);
+        let ast = parser.parse().unwrap();
+        assert!(matches!(
+            &ast[0],
+            AstStatement::Function { attrs, .. } if attrs == &[ast::Attribute::Deprecated {
+                message: Some("use g".to_string())
+            }]
+        ));
+        let warnings: Vec<_> = parser.warnings().iter().map(ToString::to_string).collect();
+        assert_eq!(warnings, ["`f` is deprecated: use g", "`h` is deprecated"]);
+        assert_eq!(parser.warnings()[0].span().start, src.find("f(1)").unwrap());
+
+        for (src, message) in [
+            ("#[cold] fn f() { }", "unknown attribute `cold`"),
+            ("#[inline] let a: u64 = 1;", "in front of functions"),
+            ("#[inline] #[inline] fn f() { }", "given twice"),
+            (
+                "#[inline] fn f(a: u64) -> u64 { if a > 0 { return f(a - 1); } return 0; }",
+                "calls itself",
+            ),
+            ("#[inline] fn f() { if true { f(); } }", "calls itself"),
+            ("#[no_mangle] fn main() { }", "can't be `#[no_mangle]`"),
+            (
+                "fn a() { } #[no_mangle] fn __fn_a() { }",
+                "can't be `#[no_mangle]`",
+            ),
+            ("#[no_mangle] #[no_mangle] fn f() { }", "given twice"),
+            (
+                "#[deprecated] #[deprecated = \"a\"] fn f() { }",
+                "given twice",
+            ),
+            ("#[deprecated = 1] fn f() { }", "expected"),
+            ("#[test] fn f(a: u64) { }", "takes no parameters"),
+            ("#[test] fn f() -> u8 { return 1; }", "returns nothing"),
+            (
+                "struct P { x: u8 } impl P { #[test] fn f(self) { } }",
+                "takes no parameters",
+            ),
+        ] {
+            let err = parse(src).unwrap_err();
+            assert!(
+                matches!(
+                    err.current_context(),
+                    ast::AstParseError::InvalidFunction { .. }
+                ),
+                "{src}"
+            );
+            assert!(format!("{err:?}").contains(message), "{src}");
+        }
+    }
+
+    #[test]
+    fn assertions() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::assertions".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "enum E { A, B } #[test] fn t() { let a: u8 = 1; assert_eq!(a, 2); assert_eq!(3, a + 1); \
+             assert_eq!(E::A, E::B); assert_eq!(1.5, 2); assert_eq!(a < 2, true); }",
+        )
+        .unwrap();
+        let AstStatement::Function { attrs, body, .. } = &ast[1] else {
+            panic!("expected function");
+        };
+        assert_eq!(attrs, &[ast::Attribute::Test]);
+        let types: Vec<_> = body[1..]
+            .iter()
+            .map(|stmt| match stmt {
+                AstStatement::AssertEq { left, right, t, .. } => {
+                    format!("{left} == {right}: {t}")
+                }
+                _ => panic!("expected assert_eq"),
+            })
+            .collect();
+        assert_eq!(
+            types,
+            [
+                "a == 2: u8",
+                "3 == a+1: u8",
+                "E::A == E::B: E",
+                "1.5 == 2: f64",
+                "a<2 == true: bool"
+            ]
+        );
+        // A function of that name is still called like any other
+        assert!(parse("fn assert_eq(a: u8) { } assert_eq(1);").is_ok());
+
+        for src in [
+            "let a: u8 = 1; assert_eq!(a, 1u16);",
+            "assert_eq!(true, 1);",
+            "assert_eq!(1, true);",
+            "enum E { A } enum F { A } assert_eq!(E::A, F::A);",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::TypeMismatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "struct P { x: u8 } let p = P { x: 1 }; assert_eq!(p, p);",
+            "assert_eq!(1);",
+            "assert_eq!(1, 2",
+            "assert_eq!(1, 2, 3);",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::InvalidAssert { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            parse("let a: u8 = 1; assert_eq!(a, 256);")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::Overflow { .. }
+        ));
+    }
+
+    #[test]
+    fn doc_comments() {
+        let src = "/// Adds\n/// two\nfn f() { }\n/// A point\nstruct P { x: u8 }\n/// Nothing\nlet a = 1;";
+        let tokens = tokenizer::Tokenizer::new(src, "tests::doc_comments".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        assert_eq!(ast.len(), 3);
+        assert!(matches!(
+            &ast[0],
+            AstStatement::Function { doc: Some(doc), .. } if doc == "Adds\ntwo"
+        ));
+        assert!(matches!(
+            &ast[1],
+            AstStatement::Struct { doc: Some(doc), .. } if doc == "A point"
+        ));
+        assert!(matches!(&ast[2], AstStatement::Let { .. }));
+    }
+
+    #[test]
+    fn calls() {
+        let src = "fn f(a: u64, b: u64) -> u64 { exit(f(a, b - 1)); }\nlet x: u64 = f(1, 2) * 3; f(x, f(0, 0));"
+            .to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::calls".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let AstStatement::Let { value, .. } = &ast[1] else {
+            panic!("expected let");
+        };
+        assert_eq!(value.to_string(), "f(1, 2)*3");
+        assert_eq!(
+            ast[2],
+            AstStatement::Call {
+                name: Symbol::intern("f"),
+                args: vec![
+                    AstExpression::Identifier {
+                        name: Symbol::intern("x"),
+                        span: SPAN,
+                    },
+                    AstExpression::Call {
+                        name: Symbol::intern("f"),
+                        args: vec![
+                            AstExpression::Number {
+                                raw: "0".to_string(),
+                                flags: vec![],
+                                suffix: None,
+                                span: SPAN,
+                            };
+                            2
+                        ],
+                        span: SPAN,
+                    },
+                ],
+                span: SPAN,
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_calls() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::invalid_calls".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse().unwrap_err()
+        };
+        assert!(matches!(
+            parse("exit(g());\nfn g() -> u64 { }").current_context(),
+            ast::AstParseError::UndeclaredFunction {
+                name,
+                span: tokenizer::Span { start: 5, end: 6 }
+            } if name.as_str() == "g"
+        ));
+        assert!(matches!(
+            parse("fn g(a: u64) { } g(1, 2);").current_context(),
+            ast::AstParseError::ArityMismatch {
+                expected: 1,
+                found: 2,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse("fn g() { } fn g() { }").current_context(),
+            ast::AstParseError::InvalidFunction { .. }
+        ));
+        assert!(matches!(
+            parse("fn g() -> u64 { return 1; } g() + 1;").current_context(),
+            ast::AstParseError::ExpressionAtToplevel { .. }
+        ));
+
+        // Calls to functions that return nothing are only statements
+        for src in [
+            "let y = g();",
+            "let x: u64 = g();",
+            "exit(g());",
+            "println g();",
+            "h(g());",
+            "let x: u64 = 1 + g();",
+        ] {
+            let src = format!("fn g() {{ }} fn h(a: u64) {{ }} {src}");
+            assert!(
+                matches!(
+                    parse(&src).current_context(),
+                    ast::AstParseError::NoValue { name, .. } if name.as_str() == "g"
+                ),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn if_else() {
+        let src = "let a: u64 = 1;\nif a == 1 { exit(1); } else if a < 1 { let b: u64 = a; exit(b); } else { exit(3); }"
+            .to_string();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::if_else".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let AstStatement::If {
+            condition,
+            then,
+            otherwise,
+            span: _,
+        } = &ast[1]
+        else {
+            panic!("expected if");
+        };
+        assert_eq!(condition.to_string(), "a==1");
+        assert_eq!(then.len(), 1);
+        assert!(matches!(
+            otherwise.as_slice(),
+            [AstStatement::If { then, otherwise, .. }] if then.len() == 2 && otherwise.len() == 1
+        ));
+    }
+
+    #[test]
+    fn if_scopes() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::if_scopes".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        assert!(matches!(
+            parse("if true { let a: u64 = 1; } exit(a);").unwrap_err().current_context(),
+            ast::AstParseError::UndeclaredIdentifier { name, .. } if name.as_str() == "a"
+        ));
+        assert!(matches!(
+            parse("if true { fn f() { } }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::NestedFunction { .. }
+        ));
+        assert!(matches!(
+            parse("if true exit(1);").unwrap_err().current_context(),
+            ast::AstParseError::InvalidIf { .. }
+        ));
+        assert!(parse("fn f(a: u64) { if a > 0 { exit(a); } }").is_ok());
+    }
+
+    #[test]
+    fn while_loop() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::while_loop".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse("let a: u64 = 1; while a < 10 { let b: u64 = a * 2; println b; }").unwrap();
+        let AstStatement::While {
+            condition,
+            body,
+            span: _,
+        } = &ast[1]
+        else {
+            panic!("expected while");
+        };
+        assert_eq!(condition.to_string(), "a<10");
+        assert_eq!(body.len(), 2);
+
+        assert!(matches!(
+            parse("while true { let b: u64 = 1; } exit(b);")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::UndeclaredIdentifier { .. }
+        ));
+        assert!(matches!(
+            parse("while true exit(0);").unwrap_err().current_context(),
+            ast::AstParseError::InvalidWhile {
+                span: tokenizer::Span { start: 0, end: 5 }
+            }
+        ));
+    }
+
+    #[test]
+    fn for_loop() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::for_loop".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast =
+            parse("let n: u8 = 3; for i: u8 in 0..n * 2 { println i; } for j in 1..2 { }").unwrap();
+        let AstStatement::For {
+            name,
+            t,
+            range,
+            inclusive: false,
+            body,
+            span: _,
+        } = &ast[1]
+        else {
+            panic!("expected for");
+        };
+        assert_eq!((name.as_str(), t.as_str()), ("i", "u8"));
+        assert_eq!(range.to_string(), "0..n*2");
+        assert_eq!(body.len(), 1);
+        assert!(matches!(&ast[2], AstStatement::For { t, .. } if t.as_str() == "u64"));
+
+        assert!(matches!(
+            parse("for i in 0..10 { } exit(i);")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::UndeclaredIdentifier { .. }
+        ));
+        assert!(matches!(
+            parse("for i in 0..i { }").unwrap_err().current_context(),
+            ast::AstParseError::UndeclaredIdentifier { .. }
+        ));
+        assert!(matches!(
+            parse("for i in 10 { }").unwrap_err().current_context(),
+            ast::AstParseError::InvalidFor { .. }
+        ));
+
+        // Ranges are values of their own, which loops count over too
+        let ast = parse(
+            "let r: RangeInclusive = 1..=10; for i in r { } for j: u8 in 0..=255 { } \
+             let last: u64 = r.end;",
+        )
+        .unwrap();
+        assert!(matches!(
+            &ast[1],
+            AstStatement::For {
+                inclusive: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &ast[2],
+            AstStatement::For {
+                inclusive: true,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse("let r = 0..3; let s: RangeInclusive = r;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::TypeMismatch { .. }
+        ));
+        assert!(matches!(
+            parse("let r = 0..3; for i: u8 in r { }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::TypeMismatch { .. }
+        ));
+        assert!(matches!(
+            parse("for i: u8 in 0..256 { }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::Overflow { .. }
+        ));
+        assert!(matches!(
+            parse("let r: Range = 0..-1;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::Overflow { .. }
+        ));
+        assert!(matches!(
+            parse("let r = 0..1.5;").unwrap_err().current_context(),
+            ast::AstParseError::NotAnInteger { .. }
+        ));
+        assert!(matches!(
+            parse("let r = 0..true;").unwrap_err().current_context(),
+            ast::AstParseError::NotANumber { .. }
+        ));
+    }
+
+    #[test]
+    fn return_statements() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::return".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "fn f(a: u8) -> u8 { if a > 0 { return a + 1u8; } return 0; }\nfn g() { return; }",
+        )
+        .unwrap();
+        let AstStatement::Function { body, .. } = &ast[0] else {
+            panic!("expected a function");
+        };
+        assert!(matches!(
+            &body[1],
+            AstStatement::Return {
+                value: Some(AstExpression::Number { .. }),
+                span: _
+            }
+        ));
+
+        assert!(matches!(
+            parse("return 1;").unwrap_err().current_context(),
+            ast::AstParseError::ReturnOutsideFunction {
+                span: tokenizer::Span { start: 0, end: 6 }
+            }
+        ));
+        assert!(matches!(
+            parse("fn f() { return 1; }").unwrap_err().current_context(),
+            ast::AstParseError::InvalidReturn { .. }
+        ));
+        assert!(matches!(
+            parse("fn f() -> u8 { return; }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::InvalidReturn { .. }
+        ));
+
+        // `exit` leaves the function too, a loop might not run at all
+        parse(
+            "fn f(a: u8) -> u8 { if a == 1 { return 1; } else if a == 2 { exit(2); } else { { return 3; } } } \
+             enum E { A, B } fn g(e: E) -> u8 { match e { E::A => { return 1; } _ => { exit(0); } } }",
+        )
+        .unwrap();
+        for src in [
+            "fn f() -> u8 { }",
+            "fn f() -> u8 { if true { return 1; } }",
+            "fn f(a: u8) -> u8 { if a == 1 { return 1; } else if a == 2 { return 2; } }",
+            "fn f() -> u8 { while true { return 1; } }",
+            "fn f() -> u8 { match 1 { 1 => { return 1; } _ => { } } }",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::MissingReturn { name, .. } if name.as_str() == "f"
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "fn f(a: u64) -> u8 { return a * 2; }",
+            "fn f() -> u8 { return 1u16; }",
+            "fn h() -> u64 { return 1; } fn f() -> u8 { return h(); }",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::TypeMismatch { expected, .. } if expected.as_str() == "u8"
+                ),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn never_returning_functions() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::never".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "fn f() -> ! { exit(1); } fn g() -> ! { while true { while true { break; } } } \
+             fn h(a: u8) -> ! { if a > 0 { f(); } else { g(); } } fn k() -> u8 { h(1); }",
+        )
+        .unwrap();
+        assert!(matches!(
+            &ast[0],
+            AstStatement::Function { return_type: Some(t), .. } if t.as_str() == ast::NEVER
+        ));
+
+        for src in [
+            "fn f() -> ! { }",
+            "fn f() -> ! { while true { break; } }",
+            "fn f() -> ! { while true { if true { break; } } }",
+            "fn f(a: u8) -> ! { while a > 0 { } }",
+            "fn g() { } fn f() -> ! { g(); }",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::MissingDivergence { name, .. } if name.as_str() == "f"
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            parse("fn f() -> ! { return; }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::InvalidReturn { .. }
+        ));
+        for src in ["let x: u64 = g();", "let x = g() + 1;", "exit(g());"] {
+            let src = format!("fn g() -> ! {{ exit(1); }} {src}");
+            assert!(
+                matches!(
+                    parse(&src).unwrap_err().current_context(),
+                    ast::AstParseError::NoValue { name, .. } if name.as_str() == "g"
+                ),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_types() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::unknown_types".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        assert!(matches!(
+            parse("let x: foo = 1;").unwrap_err().current_context(),
+            ast::AstParseError::UnknownType {
+                t,
+                span: tokenizer::Span { start: 7, end: 10 }
+            } if t.as_str() == "foo"
+        ));
+        for src in [
+            "let x: u6 = 1;",
+            "let x: boolS = 1;",
+            "let x: [foo; 2] = [1, 2];",
+            "const X: foo = 1;",
+            "fn f(a: foo) { }",
+            "fn f() -> foo { exit(1); }",
+            "for i: foo in 0..2 { }",
+            "exit(1 as foo);",
+            "let p: P = 1; struct P { x: u8 }",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::UnknownType { .. }
+                ),
+                "{src}"
+            );
+        }
+
+        // A struct with a broken field is still defined
+        let err = parse("struct P { x: foo, y: u8 } let p = P { y: 1 };").unwrap_err();
+        assert_eq!(err.current_frames().len(), 1, "{err:?}");
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::InvalidStruct { .. }
+        ));
+        assert!(format!("{err:?}").contains("unknown type `foo`"));
+
+        assert!(parse(
+            "struct P { x: u8 } enum E { A } fn f(p: P, e: E, b: bool) -> f32 { return 1.0; } \
+             let a: [E; 1] = [E::A]; exit(0);"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn block_scopes() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::block_scopes".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse("let a: u64 = 1; { let b: u64 = a; { exit(a + b); } } { }").unwrap();
+        assert!(matches!(
+            &ast[1],
+            AstStatement::Block { body, span: _ } if matches!(body[1], AstStatement::Block { .. })
+        ));
+        assert_eq!(
+            ast[2],
+            AstStatement::Block {
+                body: vec![],
+                span: SPAN,
+            }
+        );
+
+        assert!(matches!(
+            parse("{ let b: u64 = 1; } exit(b);").unwrap_err().current_context(),
+            ast::AstParseError::UndeclaredIdentifier { name, .. } if name.as_str() == "b"
+        ));
+        assert!(matches!(
+            parse("{ fn f() { } }").unwrap_err().current_context(),
+            ast::AstParseError::NestedFunction { .. }
+        ));
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::break".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse("while true { if true { break; } continue; } for i in 0..2 { { break; } }")
+            .unwrap();
+        let AstStatement::While { body, .. } = &ast[0] else {
+            panic!("expected while");
+        };
+        assert_eq!(body[1], AstStatement::Continue { span: SPAN });
+
+        assert!(matches!(
+            parse("while true { } break;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::OutsideLoop {
+                keyword: "break",
+                span: tokenizer::Span { start: 15, end: 20 }
+            }
+        ));
+        assert!(matches!(
+            parse("fn f() { if true { continue; } }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::OutsideLoop {
+                keyword: "continue",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn bool_expressions() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::bool".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        for src in [
+            "let a: u64 = 1 < 2;",
+            "let b: bool = 1;",
+            "let c: u8 = true;",
+            "let d: u8 = 1; let e: u8 = !d;",
+            "if 5 { }",
+            "let x: u64 = 1; while x { }",
+            "let x: u64 = 1; let y = x && true;",
+            "let x: u8 = 1; let y: i64 = 2; let z = x + y;",
+            "let x: u8 = 1; let y: u16 = 2; let z = x < y;",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::TypeMismatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "let h: u64 = 1 + (1 < 2);",
+            "let a: bool = true; let h: u64 = a + a;",
+            "let a: bool = true; let h = -a;",
+            "let a: bool = true; let h = a < true;",
+            "let a: bool = true; let h = a == 1;",
+            "exit(1 < 2);",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::NotANumber { .. }
+                ),
+                "{src}"
+            );
+        }
+
+        let ast = parse(
+            "let a: u64 = (1 < 2) as u64; let b: bool = a == 1 || true; let c: u8 = -a as u8 * 2;",
+        )
+        .unwrap();
+        let AstStatement::Let { value, .. } = &ast[2] else {
+            panic!("expected let");
+        };
+        assert_eq!(value.to_string(), "((-a) as u8)*2");
+        assert!(parse(
+            "let x: u8 = 1; let y: i64 = 2; let b = true; let z = (x as i64 + y) << x; \
+             if b == (z > 1) { exit(1 + (b as u8)); }"
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn assignments() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::assign".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast =
+            parse("let mut a: u8 = 1; { a = a + 1; } let mut b: bool = true; b = a > 2;").unwrap();
+        let AstStatement::Block { body, span: _ } = &ast[1] else {
+            panic!("expected block");
+        };
+        let AstStatement::Assign {
+            name,
+            t,
+            value,
+            span: _,
+        } = &body[0]
+        else {
+            panic!("expected assignment");
+        };
+        assert_eq!((name.as_str(), t.as_str()), ("a", "u8"));
+        assert_eq!(value.to_string(), "a+1");
+
+        assert!(matches!(
+            parse("{ let x: u64 = 1; } x = 2;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::UndeclaredIdentifier {
+                span: tokenizer::Span { start: 20, end: 21 },
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse("let mut x: u64 = 1; x = x == 1;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::TypeMismatch { .. }
+        ));
+        assert!(matches!(
+            parse("let mut x: u64 = 1; x = 2")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::InvalidAssignment { .. }
+        ));
+
+        assert!(matches!(
+            &parse("let mut x: u64 = 1; x *= 3;").unwrap()[1],
+            AstStatement::CompoundAssign {
+                operator: tokenizer::BinaryOp::Star,
+                ..
+            }
+        ));
+        assert!(matches!(
+            parse("let mut x: u64 = 1; x /= 0;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::DivisionByZero { .. }
+        ));
+        assert!(matches!(
+            parse("let mut b: bool = true; b += 1;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::NotANumber { .. }
+        ));
+        assert!(matches!(
+            parse("y -= 1;").unwrap_err().current_context(),
+            ast::AstParseError::UndeclaredIdentifier { .. }
+        ));
+    }
+
+    #[test]
+    fn mutability() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::mutability".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse("let mut a: u64 = 1; { let mut a: u64 = 2; a = 3; } a = 4;").unwrap();
+        assert!(matches!(ast[0], AstStatement::Let { mutable: true, .. }));
+
+        assert!(matches!(
+            parse("let a: u64 = 1; a = 2;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::ImmutableAssignment {
+                span: tokenizer::Span { start: 16, end: 17 },
+                declaration: tokenizer::Span { start: 4, end: 5 },
+                ..
+            }
+        ));
+        for src in [
+            "let mut a: u64 = 1; { let a: u64 = 2; a += 3; }",
+            "fn f(a: u64) { a = 1; }",
+            "for i in 0..3 { i = 1; }",
+        ] {
+            assert!(
+                matches!(
+                    parse(src).unwrap_err().current_context(),
+                    ast::AstParseError::ImmutableAssignment { .. }
+                ),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn constants() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::constants".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "const N: u8 = 2 * 100; const M: u64 = N + 1; const NEG: i8 = -1; fn f() -> u64 { return M; }",
+        )
+        .unwrap();
+        assert_eq!(
+            ast[..3],
+            [
+                AstStatement::Const {
+                    name: Symbol::intern("N"),
+                    t: Symbol::intern("u8"),
+                    value: const_eval::Value::Integer(200),
+                    span: SPAN,
+                },
+                AstStatement::Const {
+                    name: Symbol::intern("M"),
+                    t: Symbol::intern("u64"),
+                    value: const_eval::Value::Integer(201),
+                    span: SPAN,
+                },
+                AstStatement::Const {
+                    name: Symbol::intern("NEG"),
+                    t: Symbol::intern("i8"),
+                    value: const_eval::Value::Integer(u64::MAX),
+                    span: SPAN,
+                },
+            ]
+        );
+
+        // Signed constants divide, shift and compare like C does
+        let ast =
+            parse("const A: i64 = -7 / 2; const B: bool = -1 < 0; const C: i64 = A >> 1;").unwrap();
+        let values: Vec<_> = ast
+            .iter()
+            .map(|stmt| match stmt {
+                AstStatement::Const {
+                    value: const_eval::Value::Integer(value),
+                    ..
+                } => *value as i64,
+                other => panic!("expected a constant, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(values, [-3, 1, -2]);
+
+        // Values that don't fit into the type are errors instead of wrapping around
+        for src in [
+            "const A: u8 = 300;",
+            "const A: i8 = 255;",
+            "const A: u64 = 18446744073709551615 + 1;",
+            "const A: u64 = -1;",
+            "const A: u8 = 255; const B: u8 = A + 1;",
+        ] {
+            let err = parse(src).unwrap_err();
+            assert!(
+                matches!(
+                    err.current_context(),
+                    ast::AstParseError::Overflow { t, .. } if ["u8", "i8", "u64"].contains(&t.as_str())
+                ),
+                "{src}"
+            );
+            assert!(format!("{err:?}").contains("doesn't fit into"), "{src}");
+        }
+        let err = parse("const A: u8 = 255; const B: u8 = A + 1;").unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::Overflow { t, span: tokenizer::Span { start: 33, end: 34 } } if t.as_str() == "u8"
+        ));
+        // The failed constant is still declared
+        let err = parse("const A: u8 = 300; let b: u8 = A;").unwrap_err();
+        assert_eq!(err.current_frames().len(), 1, "{err:?}");
+
+        // Values of variables known at compile time get the same check
+        for (src, t) in [
+            ("let x: u8 = 300;", "u8"),
+            ("let x: u64 = 1 << 70;", "u64"),
+            ("let x: i8 = -129;", "i8"),
+            ("let x: [u8; 2] = [1, 256];", "u8"),
+            ("let mut x: u16 = 0; x = 65536;", "u16"),
+        ] {
+            let err = parse(src).unwrap_err();
+            assert!(
+                matches!(err.current_context(), ast::AstParseError::Overflow { t: found, .. } if found.as_str() == t),
+                "{src}: {err:?}"
+            );
+        }
+        assert!(parse("let x: u8 = 255; let y: i8 = -128; let z: u8 = x + 1;").is_ok());
+
+        let err = parse("let a: u64 = 1; const B: u64 = a * 2;").unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ast::AstParseError::NotConstant {
+                span: tokenizer::Span { start: 31, end: 32 }
+            }
+        ));
+        assert!(format!("{err:?}").contains("`a` is not a constant"));
+        assert!(matches!(
+            parse("fn f() { const C: u64 = 1; }")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::NestedConst { .. }
+        ));
+        for src in [
+            "const F: f32 = 1.5;",
+            "const F: f64 = 1;",
+            "struct P { x: u8 } const F: P = P { x: 1 };",
+        ] {
+            let err = parse(src).unwrap_err();
+            assert!(
+                matches!(
+                    err.current_context(),
+                    ast::AstParseError::NotAnInteger { .. }
+                ),
+                "{src}: {err:?}"
+            );
+            assert!(
+                format!("{err:?}").contains("constants must be integers"),
+                "{src}"
+            );
+        }
+        assert!(parse("const B: bool = 1 < 2; let x: bool = B;").is_ok());
+        assert!(matches!(
+            parse("const C: u64 = 1; const C: u64 = 2;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::InvalidConst { .. }
+        ));
+        assert!(matches!(
+            parse("const C: u64 = 1; C = 2;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::ImmutableAssignment { .. }
+        ));
+        assert!(matches!(
+            parse("const C: bool = 1;").unwrap_err().current_context(),
+            ast::AstParseError::TypeMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn inferred_types() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::inferred_types".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "let a = 1 + 2; let b = 3u8 * 2; let mut c = a < 2; let d = -1.5 * 2; let e = b; let f = 1 as i16; c = true;",
+        )
+        .unwrap();
+        let types: Vec<_> = ast
+            .iter()
+            .filter_map(|stmt| match stmt {
+                AstStatement::Let { t, .. } => Some(t.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(types, ["u64", "u8", "bool", "f64", "u8", "i16"]);
+
+        // The inferred type is checked like a written one
+        assert!(matches!(
+            parse("let a = 1 < 2; let b: u64 = a;")
+                .unwrap_err()
+                .current_context(),
+            ast::AstParseError::TypeMismatch { .. }
+        ));
+    }
+
+    #[test]
+    fn precedence_climbing() {
+        let src = "let a: u64 = 1; let b = true; let c = b || b && a == a | a ^ a & a << a + a * a; let d = a - a - a < a / a * a;";
+        let tokens = tokenizer::Tokenizer::new(src, "tests::precedence".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let values: Vec<_> = ast[2..]
+            .iter()
+            .map(|stmt| match stmt {
+                AstStatement::Let { value, .. } => value.to_string(),
+                _ => panic!("expected let"),
+            })
+            .collect();
+        assert_eq!(
+            values,
+            [
+                "b||(b&&(a==(a|(a^(a&(a<<(a+(a*a))))))))",
+                "((a-a)-a)<((a/a)*a)"
+            ]
+        );
+        assert_eq!(ast::binding_power(&tokenizer::BinaryOp::SingleEqual), None);
+    }
+
+    #[test]
+    fn error_recovery() {
+        let errors = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::error_recovery".to_string())
+                .tokenize()
+                .unwrap();
+            let report = ast::AstParser::new(tokens).parse().unwrap_err();
+            report
+                .frames()
+                .filter_map(|frame| frame.downcast_ref::<ast::AstParseError>())
+                .map(|error| format!("{error:?}"))
+                .collect::<Vec<_>>()
+        };
+        let src = "let a: u64 = ;\nexit(b);\nlet c: u64 = 1 exit(c);\n\
+                   fn f() { let d: bool = 1; d = 2; }\nexit(a + c);\n} exit(a);";
+        let found = errors(src);
+        let expected = [
+            "InvalidExpression",
+            "UndeclaredIdentifier",
+            "InvalidLetStatement",
+            "TypeMismatch",
+            "ImmutableAssignment",
+            "ExpressionAtToplevel",
+        ];
+        assert_eq!(found.len(), expected.len(), "{found:?}");
+        for (found, expected) in found.iter().zip(expected) {
+            assert!(found.starts_with(expected), "{found} is not {expected}");
+        }
+
+        // A statement failing after its `;` doesn't take the next one with it
+        assert_eq!(errors("let a: bool = 1; a = 2; exit(c);").len(), 3);
+        // Neither does an error in the first block of an `if`
+        assert_eq!(errors("if true { exit(b); } else { exit(c); }").len(), 2);
+        assert_eq!(errors("fn f() { exit(0);").len(), 1);
+    }
+
+    #[test]
+    fn spans() {
+        let src = "let a: u64 = (1 + 2) * 3;\nfn f(b: u64) -> u8 { return b as u8; }\nexit(-f(a));";
+        let tokens = tokenizer::Tokenizer::new(src, "tests::spans".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let text = |span: tokenizer::Span| &src[span.start..span.end];
+
+        assert_eq!(text(ast[0].span()), "let a: u64 = (1 + 2) * 3;");
+        let AstStatement::Let { value, .. } = &ast[0] else {
+            panic!("expected let");
+        };
+        assert_eq!(text(value.span()), "(1 + 2) * 3");
+        let AstExpression::BinaryOperation { left, right, .. } = value else {
+            panic!("expected a multiplication");
+        };
+        assert_eq!((text(left.span()), text(right.span())), ("1 + 2", "3"));
+
+        assert_eq!(
+            text(ast[1].span()),
+            "fn f(b: u64) -> u8 { return b as u8; }"
+        );
+        let AstStatement::Function { body, .. } = &ast[1] else {
+            panic!("expected function");
+        };
+        assert_eq!(text(body[0].span()), "return b as u8;");
+        let AstStatement::Return {
+            value: Some(value), ..
+        } = &body[0]
+        else {
+            panic!("expected return");
+        };
+        assert_eq!(text(value.span()), "b as u8");
+
+        assert_eq!(text(ast[2].span()), "exit(-f(a))");
+        let AstStatement::Exit { value, .. } = &ast[2] else {
+            panic!("expected exit");
+        };
+        let AstExpression::UnaryOperation { operand, .. } = value else {
+            panic!("expected negation");
+        };
+        assert_eq!(
+            (text(value.span()), text(operand.span())),
+            ("-f(a)", "f(a)")
+        );
+    }
+
+    #[test]
+    fn json() {
+        let src =
+            "fn f(b: u8) -> u8 { return b; } let a: u64 = f(1) + 2; if !(a == 0) { print a; }";
+        let tokens = tokenizer::Tokenizer::new(src, "tests::json".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let json = Json::parse(&ast::program_to_json(&ast).to_string()).unwrap();
+        let stmts = json.as_array().unwrap();
+        let kind = |node: &Json| node.get("kind").and_then(Json::as_str).unwrap().to_string();
+        let text = |node: &Json| {
+            let span = node.get("span").unwrap();
+            let offset = |key| match span.get(key) {
+                Some(Json::Number(n)) => *n as usize,
+                _ => panic!("expected a `{key}` offset"),
+            };
+            &src[offset("start")..offset("end")]
+        };
+
+        assert_eq!(
+            stmts.iter().map(kind).collect::<Vec<_>>(),
+            ["function", "let", "if"]
+        );
+        let function = &stmts[0];
+        assert_eq!(text(function), "fn f(b: u8) -> u8 { return b; }");
+        assert_eq!(function.get("return_type"), Some(&Json::string("u8")));
+        let param = &function.get("params").unwrap().as_array().unwrap()[0];
+        assert_eq!(param.get("type"), Some(&Json::string("u8")));
+        let ret = &function.get("body").unwrap().as_array().unwrap()[0];
+        assert_eq!((kind(ret), text(ret)), ("return".to_string(), "return b;"));
+
+        let value = stmts[1].get("value").unwrap();
+        assert_eq!(kind(value), "binary");
+        assert_eq!(value.get("operator"), Some(&Json::string("+")));
+        let call = value.get("left").unwrap();
+        assert_eq!((kind(call), text(call)), ("call".to_string(), "f(1)"));
+        assert_eq!(stmts[1].get("mutable"), Some(&Json::Bool(false)));
+
+        let condition = stmts[2].get("condition").unwrap();
+        assert_eq!(
+            (kind(condition), text(condition)),
+            ("unary".to_string(), "!(a == 0)")
+        );
+        assert_eq!(stmts[2].get("otherwise"), Some(&Json::Array(vec![])));
+    }
+
+    #[test]
+    fn structs() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::structs".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "struct Point { x: u64, y: u8, } fn f(p: Point) -> Point { return p; } \
+             let p = Point { y: 2, x: 1 + 1 }; let y = f(p).y;",
+        )
+        .unwrap();
+        assert_eq!(
+            ast[0],
+            AstStatement::Struct {
+                doc: None,
+                name: Symbol::intern("Point"),
+                fields: vec![
+                    ast::Field {
+                        name: Symbol::intern("x"),
+                        t: Symbol::intern("u64"),
+                    },
+                    ast::Field {
+                        name: Symbol::intern("y"),
+                        t: Symbol::intern("u8"),
+                    },
+                ],
+                span: SPAN,
+            }
+        );
+        // The values are in the order of the definition
+        let AstStatement::Let { value, t, .. } = &ast[2] else {
+            panic!("expected let");
+        };
+        assert_eq!(t.as_str(), "Point");
+        assert_eq!(value.to_string(), "Point { x: 1+1, y: 2 }");
+        let AstStatement::Let { value, t, .. } = &ast[3] else {
+            panic!("expected let");
+        };
+        assert_eq!((t.as_str(), value.to_string().as_str()), ("u8", "f(p).y"));
+    }
+
+    #[test]
+    fn struct_errors() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::struct_errors".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let error = |src: &str| {
+            let src = format!("struct P {{ x: u64 }} let p = P {{ x: 1 }}; {src}");
+            let err = parse(&src).unwrap_err();
+            // A broken literal is skipped as a whole, so nothing after it fails too
+            assert_eq!(err.current_frames().len(), 1, "{src}: {err:?}");
+            err
+        };
+        for src in [
+            "let q: u64 = p;",
+            "let q: P = 1;",
+            "let q = P { x: true };",
+            "fn f() -> P { return 1; }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::TypeMismatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "exit(p);",
+            "let q = p + p;",
+            "let q = -p;",
+            "let q = p as u8;",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::NotANumber { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("let q = p.y;").current_context(),
+            ast::AstParseError::UnknownField {
+                span: tokenizer::Span { start: 50, end: 51 },
+                ..
+            }
+        ));
+        assert!(matches!(
+            error("let q = P { x: 1, y: 2 };").current_context(),
+            ast::AstParseError::UnknownField { .. }
+        ));
+        for src in ["let q = P { };", "let q = P { x: 1, x: 2 };"] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::InvalidStructLiteral { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "struct P { y: u8 }",
+            "struct E {}",
+            "struct F { a: u8, a: u8 }",
+            "struct S { s: S }",
+            "struct A { b: B } struct B { a: A }",
+            "struct A { a: u8, b: B } struct B { p: P, a: A }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::InvalidStruct { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(parse("struct P { x: u64 } struct Q { a: P, b: P } exit(0);").is_ok());
+        assert!(matches!(
+            error("fn f() { struct Q { a: u8 } }").current_context(),
+            ast::AstParseError::NestedStruct { .. }
+        ));
+    }
+
+    #[test]
+    fn methods() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::methods".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "struct P { x: u64 } impl P { fn get(self) -> u64 { return self.x; } \
+             fn add(self: P, n: u64) -> P { return P { x: self.x + n }; } fn show(self) { println self.x; } } \
+             let p = P { x: 1 }; let y = p.add(2).get() * 2; p.add(y).show();",
+        )
+        .unwrap();
+        let AstStatement::Impl { name, methods, .. } = &ast[1] else {
+            panic!("expected impl");
+        };
+        assert_eq!(name.as_str(), "P");
+        let AstStatement::Function { name, params, .. } = &methods[1] else {
+            panic!("expected function");
+        };
+        assert_eq!(name.as_str(), "add");
+        assert_eq!(
+            params
+                .iter()
+                .map(|param| (param.name.as_str(), param.t.as_str()))
+                .collect::<Vec<_>>(),
+            [("self", "P"), ("n", "u64")]
+        );
+        let AstStatement::Let { value, t, .. } = &ast[3] else {
+            panic!("expected let");
+        };
+        assert_eq!(
+            (t.as_str(), value.to_string().as_str()),
+            ("u64", "p.add(2).get()*2")
+        );
+        let AstStatement::MethodCall {
+            value, t, method, ..
+        } = &ast[4]
+        else {
+            panic!("expected method call");
+        };
+        assert_eq!(
+            (value.to_string().as_str(), t.as_str(), method.as_str()),
+            ("p.add(y)", "P", "show")
+        );
+    }
+
+    #[test]
+    fn method_errors() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::method_errors".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let error = |src: &str| {
+            let src = format!(
+                "struct P {{ x: u64 }} impl P {{ fn get(self) -> u64 {{ return self.x; }} \
+                 fn show(self) {{ println self.x; }} }} let p = P {{ x: 1 }}; {src}"
+            );
+            let err = parse(&src).unwrap_err();
+            assert_eq!(err.current_frames().len(), 1, "{src}: {err:?}");
+            err
+        };
+        for src in [
+            "let y = p.set();",
+            "let y = 1; let z = y.get();",
+            "p.x.get();",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::UnknownMethod { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in ["let y = p.show();", "p.show().get();"] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::NoValue { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("let y = p.get(1);").current_context(),
+            ast::AstParseError::ArityMismatch {
+                expected: 0,
+                found: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            error("p.x;").current_context(),
+            ast::AstParseError::ExpressionAtToplevel { .. }
+        ));
+        for src in [
+            "impl P { fn f() {} }",
+            "impl P { fn f(a: u64, self) {} }",
+            "fn f(self) {}",
+            "impl P { fn get(self) -> u64 { return 1; } }",
+            "impl P { #[no_mangle] fn f(self) {} }",
+            "impl P { let a = 1; }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::InvalidFunction { .. }
+                        | ast::AstParseError::InvalidImpl { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("impl P { fn f(self: u64) {} }").current_context(),
+            ast::AstParseError::TypeMismatch { .. }
+        ));
+        assert!(matches!(
+            error("impl Q { fn f(self) {} }").current_context(),
+            ast::AstParseError::UnknownType { .. }
+        ));
+        assert!(matches!(
+            error("fn f() { impl P {} }").current_context(),
+            ast::AstParseError::NestedImpl { .. }
+        ));
+        // Each type has its own methods
+        assert!(parse("struct P { x: u64 } struct Q { x: u64 } impl P { fn f(self) {} } impl Q { fn f(self) {} }").is_ok());
+    }
+
+    #[test]
+    fn enums() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::enums".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "enum Color { Red, Green, } fn f(c: Color) -> Color { return c; } \
+             let c = f(Color::Green); let same = c != Color::Red; let n = c as u8;",
+        )
+        .unwrap();
+        assert_eq!(
+            ast[0],
+            AstStatement::Enum {
+                name: Symbol::intern("Color"),
+                variants: vec![Symbol::intern("Red"), Symbol::intern("Green")],
+                span: SPAN,
+            }
+        );
+        let types = ast[2..]
+            .iter()
+            .map(|stmt| match stmt {
+                AstStatement::Let { value, t, .. } => format!("{t} = {value}"),
+                _ => panic!("expected let"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                "Color = f(Color::Green)",
+                "bool = c!=Color::Red",
+                "u8 = c as u8"
+            ]
+        );
+    }
+
+    #[test]
+    fn enum_errors() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::enum_errors".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let error = |src: &str| {
+            let src = format!("enum C {{ R, G }} struct P {{ x: u64 }} let c = C::R; {src}");
+            let err = parse(&src).unwrap_err();
+            assert_eq!(err.current_frames().len(), 1, "{src}: {err:?}");
+            err
+        };
+        for src in [
+            "let d: u8 = c;",
+            "let d: C = 0;",
+            "let p = P { x: c };",
+            "fn f() -> C { return 1; }",
+            "let d = !c;",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::TypeMismatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "exit(c);",
+            "let d = c + c;",
+            "let d = c < C::G;",
+            "let d = c == 1;",
+            "enum D { A } let d = c == D::A;",
+            "let d = 1 as C;",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::NotANumber { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("let d = C::B;").current_context(),
+            ast::AstParseError::UnknownVariant {
+                span: tokenizer::Span { start: 61, end: 62 },
+                ..
+            }
+        ));
+        for src in ["let d = P::x;", "let d = D::R;"] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::UnknownVariant { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "enum C { A }",
+            "enum P { A }",
+            "struct C { a: u8 }",
+            "enum E {}",
+            "enum F { A, A }",
+            "enum G { A: u8 }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::InvalidEnum { .. }
+                        | ast::AstParseError::InvalidStruct { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("fn f() { enum D { A } }").current_context(),
+            ast::AstParseError::NestedEnum { .. }
+        ));
+    }
+
+    #[test]
+    fn match_statements() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::match_statements".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "enum C { R, G } fn f(c: C, n: i8) -> u8 { \
+             match c { C::R => { return 1; } _ => { match n { -1 => { return 2; }, 0x10 => {} } } } \
+             return 0; }",
+        );
+        assert!(matches!(
+            ast.unwrap_err().current_context(),
+            ast::AstParseError::NonExhaustiveMatch { .. }
+        ));
+
+        let ast = parse(
+            "enum C { R, G } fn f(c: C, n: i8) -> u8 { \
+             match c { C::R => { return 1; } _ => { match n { -1 => { return 2; }, 0x10 => {} _ => {} } } } \
+             return 0; } while true { match 1 { 1 => { break; } _ => { continue; } } }",
+        )
+        .unwrap();
+        let AstStatement::Function { body, .. } = &ast[1] else {
+            panic!("expected function");
+        };
+        let AstStatement::Match { value, t, arms, .. } = &body[0] else {
+            panic!("expected match");
+        };
+        assert_eq!((value.to_string(), t.as_str()), ("c".to_string(), "C"));
+        let patterns = arms
+            .iter()
+            .map(|arm| arm.pattern.as_ref().map(ToString::to_string))
+            .collect::<Vec<_>>();
+        assert_eq!(patterns, [Some("C::R".to_string()), None]);
+        let AstStatement::Match { t, arms, .. } = &arms[1].body[0] else {
+            panic!("expected match");
+        };
+        assert_eq!((t.as_str(), arms.len()), ("i8", 3));
+        // A number of no particular type is matched as a `u64`
+        let AstStatement::While { body, .. } = &ast[2] else {
+            panic!("expected while");
+        };
+        assert!(matches!(&body[0], AstStatement::Match { t, .. } if t.as_str() == "u64"));
+    }
+
+    #[test]
+    fn match_coverage() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::match_coverage".to_string())
+                .tokenize()
+                .unwrap();
+            let mut parser = ast::AstParser::new(tokens);
+            let result = parser.parse();
+            let warnings: Vec<_> = parser.warnings().iter().map(ToString::to_string).collect();
+            (result, warnings)
+        };
+        let missing = |src: &str| match parse(src).0.unwrap_err().current_context() {
+            ast::AstParseError::NonExhaustiveMatch { missing, .. } => missing.clone(),
+            error => panic!("{src}: {error:?}"),
+        };
+
+        // Covering every value is as good as a `_`
+        for src in [
+            "match true { true => {} false => {} }",
+            "let n: i8 = 1; match n { -128..0 => {} 0 => {} 1..=127 => {} }",
+            "enum C { R, G } match C::G { C::G => {} C::R => {} }",
+        ] {
+            let (result, warnings) = parse(src);
+            assert!(result.is_ok(), "{src}");
+            assert!(warnings.is_empty(), "{src}");
+        }
+        assert_eq!(missing("match false { true => {} }"), ["`false`"]);
+        assert_eq!(
+            missing("let n: u8 = 1; match n { 1 => {} 3..=9 => {} 200..255 => {} }"),
+            ["`0`", "`2`", "`10..=199`", "`255`"]
+        );
+        assert_eq!(
+            missing("match 1i16 { 0 => {} }"),
+            ["`-32768..=-1`", "`1..=32767`"]
+        );
+
+        let (result, warnings) =
+            parse("let n: u8 = 1; match n { 0..=10 => {} 5..20 => {} 20 => {} _ => {} }");
+        assert!(result.is_ok());
+        assert_eq!(
+            warnings,
+            ["some values of the pattern are matched by an arm before it"]
+        );
+    }
+
+    #[test]
+    fn match_errors() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::match_errors".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let error = |src: &str| {
+            let src = format!("enum C {{ R, G }} let c = C::R; let n: u8 = 1; {src}");
+            let err = parse(&src).unwrap_err();
+            // A broken match is skipped as a whole, so nothing after it fails too
+            assert_eq!(err.current_frames().len(), 1, "{src}: {err:?}");
+            err
+        };
+        for src in [
+            "match c { C::R => {} }",
+            "match c { }",
+            "match n { 1 => {} 2 => {} }",
+            "match true { true => {} }",
+            "match n { 0..128 => {} 129..=255 => {} }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::NonExhaustiveMatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "match n { _ => {} 1 => {} }",
+            "match n { 1 => {} 0x1 => {} _ => {} }",
+            "match c { C::R => {} C::R => {} _ => {} }",
+            "match n { 1 + 1 => {} _ => {} }",
+            "match n { n => {} _ => {} }",
+            "match n { 1.5 => {} _ => {} }",
+            "match n { 1 {} _ => {} }",
+            "match 1.5 { _ => {} }",
+            "match true { true => {} false => {} _ => {} }",
+            "match c { C::R => {} C::G => {} _ => {} }",
+            "match n { 0..=127 => {} 128..=255 => {} _ => {} }",
+            "match n { 0..10 => {} 5 => {} _ => {} }",
+            "match n { 2..2 => {} _ => {} }",
+            "match n { 0..n => {} _ => {} }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::InvalidMatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "match c { 1 => {} _ => {} }",
+            "match n { C::R => {} _ => {} }",
+            "match n { 1 => { let a: bool = 1; } _ => {} }",
+            "match true { 1 => {} _ => {} }",
+            "match n { true => {} _ => {} }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::TypeMismatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("match n { 0..=255 => {} 256 => {} _ => {} }").current_context(),
+            ast::AstParseError::Overflow { .. }
+        ));
+        assert!(matches!(
+            error("match c { C::R..C::G => {} _ => {} }").current_context(),
+            ast::AstParseError::NotANumber { .. }
+        ));
+        assert!(matches!(
+            error("struct P { x: u8 } let p = P { x: 1 }; match p { _ => {} }").current_context(),
+            ast::AstParseError::NotANumber { .. }
+        ));
+        assert!(matches!(
+            error("match n { 1 => { break; } _ => {} }").current_context(),
+            ast::AstParseError::OutsideLoop { .. }
+        ));
+    }
+
+    #[test]
+    fn arrays() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::arrays".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let ast = parse(
+            "const N: u64 = 3; let a: [u8; 4] = [1, 2, 3, 4,]; let b = [true, false]; \
+             let i: u64 = 9; let x = a[N] + a[i]; let y = b[0x1]; let z = [1.5][0] as u8;",
+        );
+        // Literals can't be indexed, C needs them in a variable
+        assert!(matches!(
+            ast.unwrap_err().current_context(),
+            ast::AstParseError::InvalidArray { .. }
+        ));
+
+        let ast = parse(
+            "const N: u64 = 3; let a: [u8; 4] = [1, 2, 3, 4,]; let b = [true, false]; \
+             let i: u64 = 9; let x = a[N] + a[i]; let y = b[0x1]; let c = [1, 2u16];",
+        )
+        .unwrap();
+        let types = ast[1..]
+            .iter()
+            .map(|stmt| match stmt {
+                AstStatement::Let { value, t, .. } => format!("{t} = {value}"),
+                _ => panic!("expected let"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            [
+                "[u8; 4] = [1, 2, 3, 4]",
+                "[bool; 2] = [true, false]",
+                "u64 = 9",
+                "u8 = a[N]+a[i]",
+                "bool = b[0x1]",
+                "[u16; 2] = [1, 2u16]"
+            ]
+        );
+        assert_eq!(
+            ast::array_type(Symbol::intern("[u8; 4]")),
+            Some((Symbol::intern("u8"), 4))
+        );
+        assert_eq!(ast::array_type(Symbol::intern("u8")), None);
+    }
+
+    #[test]
+    fn array_errors() {
+        let parse = |src: &str| {
+            let tokens = tokenizer::Tokenizer::new(src, "tests::array_errors".to_string())
+                .tokenize()
+                .unwrap();
+            ast::AstParser::new(tokens).parse()
+        };
+        let error = |src: &str| {
+            let src = format!("let mut a: [u64; 2] = [1, 2]; let n: u64 = 1; {src}");
+            let err = parse(&src).unwrap_err();
+            assert_eq!(err.current_frames().len(), 1, "{src}: {err:?}");
+            err
+        };
+        for src in [
+            "let b: [u64; 2] = a;",
+            "let b = a;",
+            "a = [3, 4];",
+            "a += 1;",
+            "let b = [];",
+            "let b: [u8; 0] = [1];",
+            "let b: [u8; n] = [1];",
+            "let b: [u8 4] = [1];",
+            "fn f(x: u64) { } f(a);",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::InvalidArray { .. }
+                        | ast::AstParseError::InvalidAssignment { .. }
+                        | ast::AstParseError::InvalidLetStatement { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("let b: [u8; 3] = [1, 2];").current_context(),
+            ast::AstParseError::ArrayLength {
+                expected: 3,
+                found: 2,
+                ..
+            }
+        ));
+        for src in [
+            "let b: u64 = a;",
+            "let b: [bool; 2] = [true, 1];",
+            "let b = [1, true];",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::TypeMismatch { .. }
+                ),
+                "{src}"
+            );
+        }
+        for src in [
+            "exit(a);",
+            "let b = a + 1;",
+            "let b = a as u8;",
+            "match a { _ => {} }",
+        ] {
+            assert!(
+                matches!(
+                    error(src).current_context(),
+                    ast::AstParseError::NotANumber { .. }
+                ),
+                "{src}"
+            );
+        }
+        assert!(matches!(
+            error("let b = n[0];").current_context(),
+            ast::AstParseError::NotAnArray { .. }
+        ));
+        assert!(matches!(
+            error("let b = a[true];").current_context(),
+            ast::AstParseError::InvalidIndex { .. }
+        ));
+        assert!(matches!(
+            error("let b = a[1 + 1];").current_context(),
+            ast::AstParseError::IndexOutOfBounds {
+                index: 2,
+                length: 2,
+                span: tokenizer::Span { start: 56, end: 61 },
+            }
+        ));
+        assert!(matches!(
+            error("const I: i8 = -1; let b = a[I];").current_context(),
+            ast::AstParseError::IndexOutOfBounds { index: -1, .. }
+        ));
+    }
 }
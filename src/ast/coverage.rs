@@ -0,0 +1,101 @@
+/// The values of an integer, `bool` or enum a match handles so far, as sorted inclusive
+/// intervals that don't touch each other. A `bool` is `0` or `1` and a variant is its position
+/// in the enum
+#[derive(Debug, Default)]
+pub struct Coverage {
+    intervals: Vec<(i128, i128)>,
+}
+
+/// How much of a pattern the arms before it already handle
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Covered {
+    Nothing,
+    Partly,
+    Everything,
+}
+
+impl Coverage {
+    /// Adds the values `start..=end`, telling how many of them were handled already
+    pub fn add(&mut self, start: i128, end: i128) -> Covered {
+        let overlapping: i128 = self
+            .intervals
+            .iter()
+            .map(|&(low, high)| (high.min(end) - low.max(start) + 1).max(0))
+            .sum();
+        let covered = if overlapping == 0 {
+            Covered::Nothing
+        } else if overlapping == end - start + 1 {
+            Covered::Everything
+        } else {
+            Covered::Partly
+        };
+        // Everything touching the new interval merges into it
+        let (mut start, mut end) = (start, end);
+        self.intervals.retain(|&(low, high)| {
+            let touches = low <= end.saturating_add(1) && high.saturating_add(1) >= start;
+            if touches {
+                start = start.min(low);
+                end = end.max(high);
+            }
+            !touches
+        });
+        let position = self.intervals.partition_point(|&(low, _)| low < start);
+        self.intervals.insert(position, (start, end));
+        covered
+    }
+
+    /// The intervals of `min..=max` that no pattern handles
+    pub fn missing(&self, min: i128, max: i128) -> Vec<(i128, i128)> {
+        let mut missing = vec![];
+        let mut next = min;
+        for &(low, high) in &self.intervals {
+            if low > next {
+                missing.push((next, low - 1));
+            }
+            next = next.max(high + 1);
+        }
+        if next <= max {
+            missing.push((next, max));
+        }
+        missing
+    }
+}
+
+/// The smallest and largest value of the integer type `t`
+pub fn bounds(t: &str) -> (i128, i128) {
+    match t {
+        "u8" => (0, u8::MAX.into()),
+        "u16" => (0, u16::MAX.into()),
+        "u32" => (0, u32::MAX.into()),
+        "i8" => (i8::MIN.into(), i8::MAX.into()),
+        "i16" => (i16::MIN.into(), i16::MAX.into()),
+        "i32" => (i32::MIN.into(), i32::MAX.into()),
+        "i64" => (i64::MIN.into(), i64::MAX.into()),
+        _ => (0, u64::MAX.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounds, Coverage, Covered};
+
+    #[test]
+    fn intervals() {
+        let mut coverage = Coverage::default();
+        assert_eq!(coverage.add(0, 9), Covered::Nothing);
+        assert_eq!(coverage.add(20, 29), Covered::Nothing);
+        assert_eq!(coverage.add(5, 5), Covered::Everything);
+        assert_eq!(coverage.add(8, 12), Covered::Partly);
+        assert_eq!(coverage.missing(0, 40), [(13, 19), (30, 40)]);
+        assert_eq!(coverage.add(13, 19), Covered::Nothing);
+        assert_eq!(coverage.add(0, 29), Covered::Everything);
+        assert_eq!(coverage.missing(0, 29), []);
+
+        let (min, max) = bounds("i8");
+        let mut coverage = Coverage::default();
+        coverage.add(min, -1);
+        coverage.add(1, max);
+        assert_eq!(coverage.missing(min, max), [(0, 0)]);
+        assert_eq!(bounds("u64"), (0, u64::MAX.into()));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::{
+    ast::{pretty_print, AstExpression, AstProgram, AstStatement},
+    symbol::Symbol,
+};
+
+/// A Graphviz graph of a whole program. Every node of the tree is a box labelled with what it
+/// is, operands are drawn left to right and the parts of a statement that aren't obvious from
+/// their order are named on their edge
+pub fn program(program: &AstProgram) -> String {
+    let mut graph = Graph {
+        out: String::from("digraph ast {\n    ordering=out;\n    node [shape=box];\n"),
+        nodes: 0,
+    };
+    let root = graph.node("program");
+    graph.statements(root, program, None);
+    graph.out.push_str("}\n");
+    graph.out
+}
+
+struct Graph {
+    out: String,
+    nodes: usize,
+}
+
+impl Graph {
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.nodes;
+        self.nodes += 1;
+        let label = label.replace('\\', "\\\\").replace('"', "\\\"");
+        self.out
+            .push_str(&format!("    n{id} [label=\"{label}\"];\n"));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<&str>) {
+        match label {
+            Some(label) => self
+                .out
+                .push_str(&format!("    n{from} -> n{to} [label=\"{label}\"];\n")),
+            None => self.out.push_str(&format!("    n{from} -> n{to};\n")),
+        }
+    }
+
+    fn statements(&mut self, parent: usize, stmts: &[AstStatement], label: Option<&str>) {
+        for stmt in stmts {
+            let child = self.statement(stmt);
+            self.edge(parent, child, label);
+        }
+    }
+
+    fn expression_child(&mut self, parent: usize, expr: &AstExpression, label: Option<&str>) {
+        let child = self.expression(expr);
+        self.edge(parent, child, label);
+    }
+
+    fn statement(&mut self, stmt: &AstStatement) -> usize {
+        match stmt {
+            AstStatement::Let {
+                value,
+                name,
+                t,
+                mutable,
+                span: _,
+            } => {
+                let mutable = if *mutable { "mut " } else { "" };
+                let id = self.node(&format!("let {mutable}{name}: {t}"));
+                self.expression_child(id, value, None);
+                id
+            }
+            AstStatement::Const {
+                name,
+                t,
+                value,
+                span: _,
+            } => self.node(&format!(
+                "const {name}: {t} = {}",
+                pretty_print::constant(*value, *t)
+            )),
+            AstStatement::Exit { value, span: _ } => {
+                let id = self.node("exit");
+                self.expression_child(id, value, None);
+                id
+            }
+            AstStatement::Print {
+                value,
+                newline,
+                span: _,
+            } => {
+                let id = self.node(if *newline { "println" } else { "print" });
+                self.expression_child(id, value, None);
+                id
+            }
+            AstStatement::AssertEq {
+                left,
+                right,
+                t,
+                span: _,
+            } => {
+                let id = self.node(&format!("assert_eq: {t}"));
+                self.expression_child(id, left, Some("left"));
+                self.expression_child(id, right, Some("right"));
+                id
+            }
+            AstStatement::Function {
+                doc: _,
+                attrs,
+                name,
+                params,
+                return_type,
+                body,
+                span: _,
+            } => {
+                let params = params
+                    .iter()
+                    .map(|param| format!("{}: {}", param.name, param.t))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let return_type = return_type.map(|t| format!(" -> {t}")).unwrap_or_default();
+                let attrs = attrs
+                    .iter()
+                    .map(|attr| format!("{attr} "))
+                    .collect::<String>();
+                let id = self.node(&format!("{attrs}fn {name}({params}){return_type}"));
+                self.statements(id, body, None);
+                id
+            }
+            AstStatement::Struct {
+                doc: _,
+                name,
+                fields,
+                span: _,
+            } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| format!("{}: {}", field.name, field.t))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.node(&format!("struct {name} {{ {fields} }}"))
+            }
+            AstStatement::Enum {
+                name,
+                variants,
+                span: _,
+            } => {
+                let variants = variants
+                    .iter()
+                    .map(|variant| variant.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.node(&format!("enum {name} {{ {variants} }}"))
+            }
+            AstStatement::Impl {
+                name,
+                methods,
+                span: _,
+            } => {
+                let id = self.node(&format!("impl {name}"));
+                self.statements(id, methods, None);
+                id
+            }
+            AstStatement::Call {
+                name,
+                args,
+                span: _,
+            } => self.call(name.as_str(), args),
+            AstStatement::MethodCall {
+                value,
+                t: _,
+                method,
+                args,
+                span: _,
+            } => self.method_call(value, *method, args),
+            AstStatement::If {
+                condition,
+                then,
+                otherwise,
+                span: _,
+            } => {
+                let id = self.node("if");
+                self.expression_child(id, condition, Some("condition"));
+                self.statements(id, then, Some("then"));
+                self.statements(id, otherwise, Some("else"));
+                id
+            }
+            AstStatement::Match {
+                value,
+                t,
+                arms,
+                span: _,
+            } => {
+                let id = self.node(&format!("match: {t}"));
+                self.expression_child(id, value, Some("value"));
+                for arm in arms {
+                    let pattern = arm
+                        .pattern
+                        .as_ref()
+                        .map_or_else(|| "_".to_string(), pretty_print::expression);
+                    let arm_id = self.node(&format!("{pattern} =>"));
+                    self.edge(id, arm_id, None);
+                    self.statements(arm_id, &arm.body, None);
+                }
+                id
+            }
+            AstStatement::While {
+                condition,
+                body,
+                span: _,
+            } => {
+                let id = self.node("while");
+                self.expression_child(id, condition, Some("condition"));
+                self.statements(id, body, None);
+                id
+            }
+            AstStatement::For {
+                name,
+                t,
+                range,
+                inclusive: _,
+                body,
+                span: _,
+            } => {
+                let id = self.node(&format!("for {name}: {t}"));
+                self.expression_child(id, range, Some("range"));
+                self.statements(id, body, None);
+                id
+            }
+            AstStatement::Assign {
+                name,
+                t: _,
+                value,
+                span: _,
+            } => {
+                let id = self.node(&format!("{name} ="));
+                self.expression_child(id, value, None);
+                id
+            }
+            AstStatement::CompoundAssign {
+                name,
+                t: _,
+                operator,
+                value,
+                span: _,
+            } => {
+                let id = self.node(&format!("{name} {operator}="));
+                self.expression_child(id, value, None);
+                id
+            }
+            AstStatement::Return { value, span: _ } => {
+                let id = self.node("return");
+                if let Some(value) = value {
+                    self.expression_child(id, value, None);
+                }
+                id
+            }
+            AstStatement::Block { body, span: _ } => {
+                let id = self.node("block");
+                self.statements(id, body, None);
+                id
+            }
+            AstStatement::Break { span: _ } => self.node("break"),
+            AstStatement::Continue { span: _ } => self.node("continue"),
+        }
+    }
+
+    fn expression(&mut self, expr: &AstExpression) -> usize {
+        match expr {
+            AstExpression::Number { .. }
+            | AstExpression::Identifier { .. }
+            | AstExpression::Bool { .. }
+            | AstExpression::Variant { .. } => self.node(&pretty_print::expression(expr)),
+            AstExpression::BinaryOperation {
+                left,
+                operator,
+                right,
+                span: _,
+            } => {
+                let id = self.node(&operator.to_string());
+                self.expression_child(id, left, None);
+                self.expression_child(id, right, None);
+                id
+            }
+            AstExpression::UnaryOperation {
+                operator,
+                operand,
+                span: _,
+            } => {
+                let id = self.node(&operator.to_string());
+                self.expression_child(id, operand, None);
+                id
+            }
+            AstExpression::Call {
+                name,
+                args,
+                span: _,
+            } => self.call(name.as_str(), args),
+            AstExpression::MethodCall {
+                value,
+                t: _,
+                method,
+                args,
+                span: _,
+            } => self.method_call(value, *method, args),
+            AstExpression::Cast { value, t, span: _ } => {
+                let id = self.node(&format!("as {t}"));
+                self.expression_child(id, value, None);
+                id
+            }
+            AstExpression::StructLiteral {
+                name,
+                fields,
+                span: _,
+            } => {
+                let id = self.node(&format!("{name} {{}}"));
+                for (field, value) in fields {
+                    self.expression_child(id, value, Some(field.as_str()));
+                }
+                id
+            }
+            AstExpression::FieldAccess {
+                value,
+                field,
+                span: _,
+            } => {
+                let id = self.node(&format!(".{field}"));
+                self.expression_child(id, value, None);
+                id
+            }
+            AstExpression::ArrayLiteral { values, span: _ } => {
+                let id = self.node("[]");
+                for value in values {
+                    self.expression_child(id, value, None);
+                }
+                id
+            }
+            AstExpression::Index {
+                value,
+                index,
+                span: _,
+            } => {
+                let id = self.node("index");
+                self.expression_child(id, value, None);
+                self.expression_child(id, index, Some("index"));
+                id
+            }
+            AstExpression::Range {
+                start,
+                end,
+                inclusive,
+                span: _,
+            } => {
+                let id = self.node(if *inclusive { "..=" } else { ".." });
+                self.expression_child(id, start, Some("start"));
+                self.expression_child(id, end, Some("end"));
+                id
+            }
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[AstExpression]) -> usize {
+        let id = self.node(&format!("call {name}"));
+        for arg in args {
+            self.expression_child(id, arg, None);
+        }
+        id
+    }
+
+    /// The value a method is called on comes first, like the `self` it is
+    fn method_call(
+        &mut self,
+        value: &AstExpression,
+        method: Symbol,
+        args: &[AstExpression],
+    ) -> usize {
+        let id = self.node(&format!("call .{method}"));
+        self.expression_child(id, value, Some("self"));
+        for arg in args {
+            self.expression_child(id, arg, None);
+        }
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::program;
+    use crate::{ast, tokenizer};
+
+    fn parse(src: &str) -> ast::AstProgram {
+        let tokens = tokenizer::Tokenizer::new(src, "tests::dot".to_string())
+            .tokenize()
+            .unwrap();
+        ast::AstParser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn expression_tree() {
+        assert_eq!(
+            program(&parse("exit(1 + 2 * -3);")),
+            "digraph ast {\n    ordering=out;\n    node [shape=box];\n    n0 [label=\"program\"];\n    \
+             n1 [label=\"exit\"];\n    n2 [label=\"+\"];\n    n3 [label=\"1\"];\n    n2 -> n3;\n    \
+             n4 [label=\"*\"];\n    n5 [label=\"2\"];\n    n4 -> n5;\n    n6 [label=\"-\"];\n    \
+             n7 [label=\"3\"];\n    n6 -> n7;\n    n4 -> n6;\n    n2 -> n4;\n    n1 -> n2;\n    \
+             n0 -> n1;\n}\n"
+        );
+    }
+
+    #[test]
+    fn statement_parts() {
+        let dot = program(&parse(
+            "fn f(a: u8) -> u8 { if a > 0 { return a; } else { return 0; } } for i in 0..f(1) { }",
+        ));
+        assert!(dot.contains("n1 [label=\"fn f(a: u8) -> u8\"];"), "{dot}");
+        assert!(dot.contains("n2 -> n3 [label=\"condition\"];"), "{dot}");
+        assert!(dot.contains("[label=\"then\"];"), "{dot}");
+        assert!(dot.contains("[label=\"else\"];"), "{dot}");
+        assert!(dot.contains("[label=\"for i: u64\"];"), "{dot}");
+        assert!(dot.contains("[label=\"call f\"];"), "{dot}");
+        assert!(dot.contains("[label=\"end\"];"), "{dot}");
+    }
+}
@@ -0,0 +1,541 @@
+use crate::{
+    ast::{binding_power, AstExpression, AstProgram, AstStatement, BOOL},
+    const_eval,
+    symbol::Symbol,
+};
+
+const INDENT: &str = "    ";
+
+/// The source code of a whole program, which parses into the same tree. It has one statement
+/// per line, blocks indented by four spaces and only the parentheses the precedence of the
+/// operators needs. Doc comments aren't part of the tree, so they are lost
+pub fn program(program: &AstProgram) -> String {
+    let mut out = String::new();
+    statements(&mut out, program, 0);
+    out
+}
+
+/// The source code of a single expression
+pub fn expression(expr: &AstExpression) -> String {
+    match expr {
+        AstExpression::Number {
+            raw,
+            flags: _,
+            suffix,
+            span: _,
+        } => format!("{raw}{}", suffix.as_deref().unwrap_or_default()),
+        AstExpression::Identifier { name, span: _ } => name.to_string(),
+        AstExpression::Bool { value, span: _ } => value.to_string(),
+        AstExpression::Call {
+            name,
+            args,
+            span: _,
+        } => format!("{name}({})", arguments(args)),
+        AstExpression::Cast { value, t, span: _ } => {
+            // `as` binds tighter than any binary operator
+            let value = match **value {
+                AstExpression::BinaryOperation { .. } => format!("({})", expression(value)),
+                _ => expression(value),
+            };
+            format!("{value} as {t}")
+        }
+        AstExpression::StructLiteral {
+            name,
+            fields,
+            span: _,
+        } => {
+            let fields = fields
+                .iter()
+                .map(|(field, value)| format!("{field}: {}", expression(value)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("{name} {{ {fields} }}")
+        }
+        AstExpression::FieldAccess {
+            value,
+            field,
+            span: _,
+        } => format!("{}.{field}", receiver(value)),
+        AstExpression::MethodCall {
+            value,
+            t: _,
+            method,
+            args,
+            span: _,
+        } => format!("{}.{method}({})", receiver(value), arguments(args)),
+        AstExpression::ArrayLiteral { values, span: _ } => format!("[{}]", arguments(values)),
+        // Only variables hold arrays, so the value never needs parentheses
+        AstExpression::Index {
+            value,
+            index,
+            span: _,
+        } => format!("{}[{}]", expression(value), expression(index)),
+        AstExpression::Variant {
+            name,
+            variant,
+            span: _,
+        } => format!("{name}::{variant}"),
+        AstExpression::UnaryOperation {
+            operator,
+            operand,
+            span: _,
+        } => match **operand {
+            AstExpression::BinaryOperation { .. } | AstExpression::Cast { .. } => {
+                format!("{operator}({})", expression(operand))
+            }
+            _ => format!("{operator}{}", expression(operand)),
+        },
+        AstExpression::BinaryOperation {
+            left,
+            operator,
+            right,
+            span: _,
+        } => {
+            let power = binding_power(operator);
+            // Operators are left associative, so a right side binding just as tight keeps its
+            // parentheses too
+            let left = operand(left, |inner| inner < power);
+            let right = operand(right, |inner| inner <= power);
+            format!("{left} {operator} {right}")
+        }
+        // The bounds are numbers, so they are never ranges themselves
+        AstExpression::Range {
+            start,
+            end,
+            inclusive,
+            span: _,
+        } => {
+            let operator = if *inclusive { "..=" } else { ".." };
+            format!("{}{operator}{}", expression(start), expression(end))
+        }
+    }
+}
+
+/// An operand of a binary operation, in parentheses if `parenthesize` says so for the binding
+/// power of its own operator
+fn operand(expr: &AstExpression, parenthesize: impl Fn(Option<usize>) -> bool) -> String {
+    match expr {
+        AstExpression::BinaryOperation { operator, .. }
+            if parenthesize(binding_power(operator)) =>
+        {
+            format!("({})", expression(expr))
+        }
+        _ => expression(expr),
+    }
+}
+
+/// The value before the `.` of a field access or a method call, which binds tighter than
+/// operators
+fn receiver(value: &AstExpression) -> String {
+    match value {
+        AstExpression::BinaryOperation { .. }
+        | AstExpression::UnaryOperation { .. }
+        | AstExpression::Cast { .. }
+        | AstExpression::Range { .. } => format!("({})", expression(value)),
+        _ => expression(value),
+    }
+}
+
+fn arguments(args: &[AstExpression]) -> String {
+    args.iter().map(expression).collect::<Vec<_>>().join(", ")
+}
+
+fn statements(out: &mut String, stmts: &[AstStatement], depth: usize) {
+    for stmt in stmts {
+        statement(out, stmt, depth);
+    }
+}
+
+fn statement(out: &mut String, stmt: &AstStatement, depth: usize) {
+    out.push_str(&INDENT.repeat(depth));
+    match stmt {
+        AstStatement::Let {
+            value,
+            name,
+            t,
+            mutable,
+            span: _,
+        } => {
+            let mutable = if *mutable { "mut " } else { "" };
+            out.push_str(&format!(
+                "let {mutable}{name}: {t} = {};\n",
+                expression(value)
+            ));
+        }
+        AstStatement::Const {
+            name,
+            t,
+            value,
+            span: _,
+        } => out.push_str(&format!("const {name}: {t} = {};\n", constant(*value, *t))),
+        AstStatement::Exit { value, span: _ } => {
+            out.push_str(&format!("exit({});\n", expression(value)));
+        }
+        AstStatement::Print {
+            value,
+            newline,
+            span: _,
+        } => {
+            let keyword = if *newline { "println" } else { "print" };
+            out.push_str(&format!("{keyword} {};\n", expression(value)));
+        }
+        AstStatement::AssertEq {
+            left,
+            right,
+            t: _,
+            span: _,
+        } => {
+            out.push_str(&format!(
+                "assert_eq!({}, {});\n",
+                expression(left),
+                expression(right)
+            ));
+        }
+        AstStatement::Function {
+            doc,
+            attrs,
+            name,
+            params,
+            return_type,
+            body,
+            span: _,
+        } => {
+            doc_comment(out, doc, depth);
+            for attr in attrs {
+                out.push_str(&format!("{attr}\n"));
+                out.push_str(&INDENT.repeat(depth));
+            }
+            // Only methods have a `self`, whose type is the one of the `impl`
+            let params = params
+                .iter()
+                .map(|param| match param.name.as_str() {
+                    "self" => "self".to_string(),
+                    name => format!("{name}: {}", param.t),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let return_type = return_type.map(|t| format!(" -> {t}")).unwrap_or_default();
+            out.push_str(&format!("fn {name}({params}){return_type} "));
+            block(out, body, depth);
+        }
+        AstStatement::Impl {
+            name,
+            methods,
+            span: _,
+        } => {
+            out.push_str(&format!("impl {name} {{\n"));
+            statements(out, methods, depth + 1);
+            out.push_str(&INDENT.repeat(depth));
+            out.push_str("}\n");
+        }
+        AstStatement::Struct {
+            doc,
+            name,
+            fields,
+            span: _,
+        } => {
+            doc_comment(out, doc, depth);
+            out.push_str(&format!("struct {name} {{\n"));
+            for field in fields {
+                out.push_str(&INDENT.repeat(depth + 1));
+                out.push_str(&format!("{}: {},\n", field.name, field.t));
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push_str("}\n");
+        }
+        AstStatement::Enum {
+            name,
+            variants,
+            span: _,
+        } => {
+            out.push_str(&format!("enum {name} {{\n"));
+            for variant in variants {
+                out.push_str(&INDENT.repeat(depth + 1));
+                out.push_str(&format!("{variant},\n"));
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push_str("}\n");
+        }
+        AstStatement::Call {
+            name,
+            args,
+            span: _,
+        } => out.push_str(&format!("{name}({});\n", arguments(args))),
+        AstStatement::MethodCall {
+            value,
+            t: _,
+            method,
+            args,
+            span: _,
+        } => out.push_str(&format!(
+            "{}.{method}({});\n",
+            receiver(value),
+            arguments(args)
+        )),
+        AstStatement::If {
+            condition,
+            then,
+            otherwise,
+            span: _,
+        } => {
+            out.push_str(&format!("if {} ", expression(condition)));
+            block(out, then, depth);
+            match otherwise.as_slice() {
+                [] => {}
+                // `else if`, which continues on the line of the `}`
+                [otherwise @ AstStatement::If { .. }] => {
+                    out.pop();
+                    out.push_str(" else ");
+                    let mut chained = String::new();
+                    statement(&mut chained, otherwise, depth);
+                    out.push_str(chained.trim_start());
+                }
+                _ => {
+                    out.pop();
+                    out.push_str(" else ");
+                    block(out, otherwise, depth);
+                }
+            }
+        }
+        AstStatement::Match {
+            value,
+            t: _,
+            arms,
+            span: _,
+        } => {
+            out.push_str(&format!("match {} {{\n", expression(value)));
+            for arm in arms {
+                let pattern = arm
+                    .pattern
+                    .as_ref()
+                    .map_or_else(|| "_".to_string(), expression);
+                out.push_str(&INDENT.repeat(depth + 1));
+                out.push_str(&format!("{pattern} => "));
+                block(out, &arm.body, depth + 1);
+            }
+            out.push_str(&INDENT.repeat(depth));
+            out.push_str("}\n");
+        }
+        AstStatement::While {
+            condition,
+            body,
+            span: _,
+        } => {
+            out.push_str(&format!("while {} ", expression(condition)));
+            block(out, body, depth);
+        }
+        AstStatement::For {
+            name,
+            t,
+            range,
+            inclusive: _,
+            body,
+            span: _,
+        } => {
+            out.push_str(&format!("for {name}: {t} in {} ", expression(range)));
+            block(out, body, depth);
+        }
+        AstStatement::Assign {
+            name,
+            t: _,
+            value,
+            span: _,
+        } => out.push_str(&format!("{name} = {};\n", expression(value))),
+        AstStatement::CompoundAssign {
+            name,
+            t: _,
+            operator,
+            value,
+            span: _,
+        } => out.push_str(&format!("{name} {operator}= {};\n", expression(value))),
+        AstStatement::Return { value, span: _ } => match value {
+            Some(value) => out.push_str(&format!("return {};\n", expression(value))),
+            None => out.push_str("return;\n"),
+        },
+        AstStatement::Block { body, span: _ } => block(out, body, depth),
+        AstStatement::Break { span: _ } => out.push_str("break;\n"),
+        AstStatement::Continue { span: _ } => out.push_str("continue;\n"),
+    }
+}
+
+/// The `///` lines of `doc`, the line of the statement after them is already indented
+fn doc_comment(out: &mut String, doc: &Option<String>, depth: usize) {
+    for line in doc.iter().flat_map(|doc| doc.split('\n')) {
+        match line {
+            "" => out.push_str("///\n"),
+            line => out.push_str(&format!("/// {line}\n")),
+        }
+        out.push_str(&INDENT.repeat(depth));
+    }
+}
+
+/// `{`, the statements one level deeper and `}` on a line of its own, or `{}` if there are none
+fn block(out: &mut String, body: &[AstStatement], depth: usize) {
+    if body.is_empty() {
+        out.push_str("{}\n");
+        return;
+    }
+    out.push_str("{\n");
+    statements(out, body, depth + 1);
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str("}\n");
+}
+
+/// The literal a constant was computed from, as far as it matters for `t`
+pub(super) fn constant(value: const_eval::Value, t: Symbol) -> String {
+    let const_eval::Value::Integer(value) = value;
+    match t.as_str() {
+        BOOL => (value != 0).to_string(),
+        // Stored sign extended, so negative values are printed as such
+        signed if signed.starts_with('i') && (value as i64) < 0 => (value as i64).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expression, program};
+    use crate::{
+        ast::{self, AstStatement},
+        tokenizer,
+    };
+
+    fn parse(src: &str) -> ast::AstProgram {
+        let tokens = tokenizer::Tokenizer::new(src, "tests::pretty_print".to_string())
+            .tokenize()
+            .unwrap();
+        ast::AstParser::new(tokens).parse().unwrap()
+    }
+
+    /// Printing gives `expected`, which parses into the tree it was printed from
+    fn round_trip(src: &str, expected: &str) {
+        let ast = parse(src);
+        let printed = program(&ast);
+        assert_eq!(printed, expected);
+        assert_eq!(parse(&printed), ast, "{printed}");
+    }
+
+    #[test]
+    fn parentheses() {
+        let src =
+            "let a: u64 = 1; let b: u8 = 1; exit((a + a) * (a - (a - a)) - a - (a << (b + b as u8)) as u64);";
+        let ast = parse(src);
+        let AstStatement::Exit { value, .. } = &ast[2] else {
+            panic!("expected exit");
+        };
+        assert_eq!(
+            expression(value),
+            "(a + a) * (a - (a - a)) - a - (a << b + b as u8) as u64"
+        );
+        round_trip(
+            "let a: i64 = 1; exit(-(a + 1) + -a as i64 + !(a < a || a == a && a != a) as i64);",
+            "let a: i64 = 1;\nexit(-(a + 1) + -a as i64 + !(a < a || a == a && a != a) as i64);\n",
+        );
+    }
+
+    #[test]
+    fn statements() {
+        round_trip(
+            "const N: i8 = -3; const B: bool = true; fn f(a: u64, b: u8) -> u64 { return a; } \
+             fn g() { return; } let mut x = f(1, 2); x += 1; x = x; print x; println 1.5;",
+            "const N: i8 = -3;\nconst B: bool = true;\nfn f(a: u64, b: u8) -> u64 {\n    return a;\n}\n\
+             fn g() {\n    return;\n}\nlet mut x: u64 = f(1, 2);\nx += 1;\nx = x;\nprint x;\nprintln 1.5;\n",
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        round_trip(
+            "let a: u64 = 1; if a > 0 { while a != 0 { break; } } else if a > 1 { for i in 0..a { continue; } } \
+             else { { } }",
+            "let a: u64 = 1;\nif a > 0 {\n    while a != 0 {\n        break;\n    }\n} else if a > 1 {\n    \
+             for i: u64 in 0..a {\n        continue;\n    }\n} else {\n    {}\n}\n",
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        round_trip(
+            "#[inline] fn f() { if true { return; } }",
+            "#[inline]\nfn f() {\n    if true {\n        return;\n    }\n}\n",
+        );
+    }
+
+    #[test]
+    fn doc_comments() {
+        round_trip(
+            "///Adds\n///\n///  two\n#[inline] fn f() { } /// P\nstruct P { x: u8 }",
+            "/// Adds\n///\n///  two\n#[inline]\nfn f() {}\n/// P\nstruct P {\n    x: u8,\n}\n",
+        );
+    }
+
+    #[test]
+    fn never_returning_functions() {
+        round_trip(
+            "fn f() -> ! { while true { } }",
+            "fn f() -> ! {\n    while true {}\n}\n",
+        );
+    }
+
+    #[test]
+    fn structs() {
+        round_trip(
+            "struct P { x: u64, y: u8 } fn f(p: P) -> P { return p; } let p = P { y: 1, x: 2 }; \
+             exit(f(P { x: p.x, y: f(p).y }).x);",
+            "struct P {\n    x: u64,\n    y: u8,\n}\nfn f(p: P) -> P {\n    return p;\n}\n\
+             let p: P = P { x: 2, y: 1 };\nexit(f(P { x: p.x, y: f(p).y }).x);\n",
+        );
+    }
+
+    #[test]
+    fn enums() {
+        round_trip(
+            "enum C { Red, Green, } let c = C::Green; exit((c == C::Red) as u8 + c as u8);",
+            "enum C {\n    Red,\n    Green,\n}\nlet c: C = C::Green;\nexit((c == C::Red) as u8 + c as u8);\n",
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        round_trip(
+            "let a: [u8; 3] = [1, 2, 3,]; let b = [a[0] + 1, a[a[1] as u64]]; exit(-(a[2] as i64));",
+            "let a: [u8; 3] = [1, 2, 3];\nlet b: [u8; 2] = [a[0] + 1, a[a[1] as u64]];\nexit(-(a[2] as i64));\n",
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        round_trip(
+            "let n: u8 = 2; let r = 1..=n + 1; for i: u8 in 0..n { } for j in r { } exit((0..n).end);",
+            "let n: u8 = 2;\nlet r: RangeInclusive = 1..=n + 1;\nfor i: u8 in 0..n {}\n\
+             for j: u64 in r {}\nexit((0..n).end);\n",
+        );
+    }
+
+    #[test]
+    fn match_statements() {
+        round_trip(
+            "enum C { R, G } let c = C::G; match c { C::R => { exit(1); }, _ => { match 2i8 { -1 => {} _ => { exit(2); } } } }",
+            "enum C {\n    R,\n    G,\n}\nlet c: C = C::G;\nmatch c {\n    C::R => {\n        exit(1);\n    }\n    \
+             _ => {\n        match 2i8 {\n            -1 => {}\n            _ => {\n                exit(2);\n            }\n        }\n    }\n}\n",
+        );
+    }
+
+    #[test]
+    fn assertions() {
+        round_trip(
+            "#[test] fn t() { let a = 1; assert_eq!(a + 1, 2); assert_eq!(a < 2, true); }",
+            "#[test]\nfn t() {\n    let a: u64 = 1;\n    assert_eq!(a + 1, 2);\n    assert_eq!(a < 2, true);\n}\n",
+        );
+    }
+
+    #[test]
+    fn methods() {
+        round_trip(
+            "struct P { x: u8 } impl P { /// Doubled\nfn twice(self: P) -> P { return P { x: self.x * 2 }; } fn show(self) { println self.x; } } \
+             let p = P { x: 1 }; p.twice().show(); exit(p.twice().x);",
+            "struct P {\n    x: u8,\n}\nimpl P {\n    /// Doubled\n    fn twice(self) -> P {\n        return P { x: self.x * 2 };\n    }\n    \
+             fn show(self) {\n        println self.x;\n    }\n}\nlet p: P = P { x: 1 };\np.twice().show();\nexit(p.twice().x);\n",
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::{path::PathBuf, sync::OnceLock};
+
+use crate::cbackend::CBackendOptions;
+
+/// On-disk cache of generated C code, keyed by a hash of the source it was generated from
+#[derive(Debug)]
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `$XDG_CACHE_HOME/ghetto-llvm`, falling back to `~/.cache/ghetto-llvm`
+    pub fn default_location() -> Option<Self> {
+        let base = std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+        Some(Self::new(base.join("ghetto-llvm")))
+    }
+
+    /// The cache key for a source file compiled with the given backend options. The compiler
+    /// version and build are part of the key so that a changed compiler never reuses stale output
+    pub fn key(source: &str, options: &CBackendOptions) -> String {
+        let mut input = format!(
+            "ghetto-llvm {} {}\n{options:?}\n",
+            env!("CARGO_PKG_VERSION"),
+            build_id()
+        )
+        .into_bytes();
+        input.extend_from_slice(source.as_bytes());
+        hex(&sha256(&input))
+    }
+
+    pub fn load(&self, key: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.dir.join(key)).ok()
+    }
+
+    /// Writes to a temporary file first and renames it into place, so a concurrent `load` never
+    /// sees a half-written entry
+    pub fn store(&self, key: &str, c_source: &[u8]) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let temporary = self.dir.join(format!(".{key}.{}.tmp", std::process::id()));
+        std::fs::write(&temporary, c_source)?;
+        std::fs::rename(&temporary, self.dir.join(key)).inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary);
+        })
+    }
+}
+
+/// A hash of the running compiler executable's path, size and modification time, which change
+/// with every rebuild of the compiler. Computed once per process, empty if the executable can't
+/// be found
+fn build_id() -> &'static str {
+    static BUILD_ID: OnceLock<String> = OnceLock::new();
+    BUILD_ID.get_or_init(|| {
+        let exe = match std::env::current_exe() {
+            Ok(exe) => exe,
+            Err(_) => return String::new(),
+        };
+        let metadata = match std::fs::metadata(&exe) {
+            Ok(metadata) => metadata,
+            Err(_) => return String::new(),
+        };
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        let id = format!(
+            "{}\n{}\n{}",
+            exe.display(),
+            metadata.len(),
+            modified.as_nanos()
+        );
+        hex(&sha256(id.as_bytes()))
+    })
+}
+
+fn hex(digest: &[u8; 32]) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut h: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut hh] = h;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = hh
+                .wrapping_add(s1)
+                .wrapping_add(ch)
+                .wrapping_add(K[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            hh = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e, f, g, hh]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_id, hex, sha256, Cache};
+    use crate::cbackend::CBackendOptions;
+
+    fn cache(test: &str) -> Cache {
+        let dir =
+            std::env::temp_dir().join(format!("ghetto-llvm-cache-{}-{test}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        Cache::new(dir)
+    }
+
+    #[test]
+    fn sha256_known_vector() {
+        assert_eq!(
+            hex(&sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn build_id_is_a_hash() {
+        assert_eq!(build_id().len(), 64);
+        assert_eq!(build_id(), build_id());
+    }
+
+    #[test]
+    fn miss_then_hit() {
+        let cache = cache("miss_then_hit");
+        let key = Cache::key("exit(0);", &CBackendOptions::default());
+        assert_eq!(cache.load(&key), None);
+
+        cache.store(&key, b"int main() {}").unwrap();
+        assert_eq!(cache.load(&key), Some(b"int main() {}".to_vec()));
+
+        cache.store(&key, b"int main() { return 1; }").unwrap();
+        assert_eq!(cache.load(&key), Some(b"int main() { return 1; }".to_vec()));
+        let entries: Vec<_> = std::fs::read_dir(&cache.dir).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn invalidated_on_content_change() {
+        let cache = cache("invalidated_on_content_change");
+        cache
+            .store(&Cache::key("exit(0);", &CBackendOptions::default()), b"old")
+            .unwrap();
+
+        let changed = Cache::key("exit(1);", &CBackendOptions::default());
+        assert_ne!(changed, Cache::key("exit(0);", &CBackendOptions::default()));
+        assert_eq!(cache.load(&changed), None);
+
+        let other_options = CBackendOptions {
+            overflow_check: true,
+            ..Default::default()
+        };
+        assert_eq!(cache.load(&Cache::key("exit(0);", &other_options)), None);
+    }
+}
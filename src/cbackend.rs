@@ -1,44 +1,1926 @@
-use crate::ir;
-use std::io::Write;
+use crate::{
+    ast,
+    const_eval::{self, integer_literal},
+    ir,
+    symbol::Symbol,
+    tokenizer::{BinaryOp, NumberTypeFlag},
+};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    io::Write,
+};
 
 pub struct CBackend {
-    program: Vec<ir::IR>,
+    program: ir::IrProgram,
+    options: CBackendOptions,
+    /// (type, operation) pairs that need an overflow checking helper
+    checked_operations: BTreeSet<(String, &'static str)>,
+    /// Whether the program prints anything and needs stdio
+    prints: bool,
+    /// Whether the program has an `assert_eq!` and needs `__panic`
+    panics: bool,
+    /// The parameter types of every function, each argument is computed as its parameter's type
+    parameter_types: HashMap<Symbol, Vec<Symbol>>,
+    /// The fields of every struct, each value of a literal is computed as its field's type
+    struct_fields: HashMap<Symbol, Vec<ast::Field>>,
+    /// The return type of every function
+    return_types: HashMap<Symbol, Option<Symbol>>,
+    /// The `#[no_mangle]` functions, whose C names are the ones from the source
+    unmangled: HashSet<Symbol>,
+    /// The return type of the function being generated
+    return_type: Option<Symbol>,
+    /// The types of the constants and of the variables of the function being generated. The IR
+    /// gives every variable of a function its own name
+    variable_types: HashMap<Symbol, Symbol>,
+}
+
+/// Everything besides the program itself that changes the generated C code
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CBackendOptions {
+    /// Headers included on top of the ones every program needs
+    pub extra_headers: Vec<String>,
+    /// Abort at runtime when integer arithmetic overflows its type
+    pub overflow_check: bool,
+    /// Time every call and print the calls and time of each function to stderr at exit
+    pub profile: bool,
+    /// Run the `#[test]` functions instead of the top level statements
+    pub test: bool,
+    /// Build a shared library of position-independent code, which exports the functions
+    pub pic: bool,
+}
+
+/// The headers and linker flags for a library passed with `--link-library`. Libraries that
+/// aren't known are linked as `-l<name>` without including anything
+pub fn link_library(name: &str) -> (&'static [&'static str], Vec<String>) {
+    match name {
+        "math" | "m" => (&["math.h"], vec!["-lm".to_string()]),
+        "pthread" => (&["pthread.h"], vec!["-lpthread".to_string()]),
+        "ssl" => (
+            &["openssl/ssl.h"],
+            vec!["-lssl".to_string(), "-lcrypto".to_string()],
+        ),
+        name => (&[], vec![format!("-l{name}")]),
+    }
 }
 
 impl CBackend {
-    pub fn new(mut program: Vec<ir::IR>) -> Self {
-        program.reverse();
-        Self { program }
+    pub fn new(program: ir::IrProgram, options: CBackendOptions) -> Self {
+        Self {
+            program,
+            options,
+            checked_operations: BTreeSet::new(),
+            prints: false,
+            panics: false,
+            parameter_types: HashMap::new(),
+            struct_fields: HashMap::new(),
+            return_types: HashMap::new(),
+            unmangled: HashSet::new(),
+            return_type: None,
+            variable_types: HashMap::new(),
+        }
     }
 
     pub fn compile(mut self) -> std::io::Result<Vec<u8>> {
+        let ir::IrProgram {
+            enums,
+            structs,
+            consts,
+            functions,
+            main,
+        } = std::mem::take(&mut self.program);
+        // Every program has the structs of ranges, which its own can have fields of
+        let ranges: Vec<_> = [false, true]
+            .into_iter()
+            .map(|inclusive| ir::IrStruct {
+                doc: None,
+                name: ast::range_type(inclusive),
+                fields: ast::range_fields(),
+            })
+            .collect();
+        self.struct_fields = ranges
+            .iter()
+            .chain(&structs)
+            .map(|s| (s.name, s.fields.clone()))
+            .collect();
+        self.parameter_types = functions
+            .iter()
+            .map(|function| {
+                let types = function.params.iter().map(|param| param.t).collect();
+                (function.name, types)
+            })
+            .collect();
+        self.return_types = functions
+            .iter()
+            .map(|function| (function.name, function.return_type))
+            .collect();
+        self.unmangled = functions
+            .iter()
+            .filter(|function| function.attrs.contains(&ast::Attribute::NoMangle))
+            .map(|function| function.name)
+            .collect();
+        let constant_types: HashMap<_, _> = consts.iter().map(|c| (c.name, c.t)).collect();
+        let profile = self.options.profile.then(|| self.profile(&functions));
+        let tests: Vec<_> = functions
+            .iter()
+            .filter(|function| function.attrs.contains(&ast::Attribute::Test))
+            .map(|function| function.name)
+            .collect();
+        // The bodies have to be generated first to know which overflow checking helpers they need
+        let mut prototypes = vec![];
+        let mut definitions = vec![];
+        for function in functions {
+            let signature = self.signature(&function);
+            prototypes.write_all(format!("{signature};\n").as_bytes())?;
+            definitions.write_all(comment(function.doc.as_deref()).as_bytes())?;
+            definitions.write_all(format!("{signature} {{\n").as_bytes())?;
+            self.return_type = function.return_type;
+            self.variable_types = constant_types.clone();
+            self.variable_types
+                .extend(function.params.iter().map(|param| (param.name, param.t)));
+            self.statements(function.body, &mut definitions)?;
+            definitions.write_all(b"}\n")?;
+        }
+        let mut body = vec![];
+        if self.options.test {
+            body = self.test_runner(&tests).into_bytes();
+        } else {
+            self.return_type = None;
+            self.variable_types = constant_types;
+            self.statements(main, &mut body)?;
+        }
+
         let mut buffer = vec![];
         {
             let mut file = std::io::BufWriter::new(&mut buffer);
 
             file.write_all(b"#include <stdlib.h>\n")?;
             file.write_all(b"#include <stdint.h>\n")?;
-            file.write_all(b"#define u64 uint64_t\n")?;
+            file.write_all(b"#include <stdbool.h>\n")?;
+            if self.prints || self.panics || profile.is_some() || self.options.test {
+                file.write_all(b"#include <stdio.h>\n")?;
+                file.write_all(b"#include <inttypes.h>\n")?;
+            }
+            if profile.is_some() {
+                file.write_all(b"#include <time.h>\n")?;
+            }
+            if self.options.test {
+                file.write_all(b"#include <setjmp.h>\n")?;
+            }
+            for header in &self.options.extra_headers {
+                file.write_all(format!("#include <{header}>\n").as_bytes())?;
+            }
+            for bits in [8, 16, 32, 64] {
+                file.write_all(format!("#define u{bits} uint{bits}_t\n").as_bytes())?;
+                file.write_all(format!("#define i{bits} int{bits}_t\n").as_bytes())?;
+            }
+            file.write_all(b"#define f32 float\n")?;
+            file.write_all(b"#define f64 double\n")?;
+            for s in &ranges {
+                file.write_all(struct_definition(s).as_bytes())?;
+            }
+            // Before the structs, whose fields can be enums
+            for e in &enums {
+                file.write_all(enum_definition(e).as_bytes())?;
+            }
+            for s in &structs {
+                file.write_all(struct_definition(s).as_bytes())?;
+            }
+            for constant in &consts {
+                file.write_all(format!("{}\n", constant_definition(constant)).as_bytes())?;
+            }
+            for (t, operation) in &self.checked_operations {
+                file.write_all(
+                    format!(
+                        "static {t} __checked_{operation}_{t}({t} a, {t} b) {{\n\
+                         {t} result;\n\
+                         if (__builtin_{operation}_overflow(a, b, &result)) abort();\n\
+                         return result;\n\
+                         }}\n"
+                    )
+                    .as_bytes(),
+                )?;
+            }
+            // A failed test jumps back to the runner, anything else ends the program
+            if self.options.test {
+                file.write_all(
+                    b"static jmp_buf __test_failed;\n\
+                      static _Noreturn void __panic(void) {\n\
+                      longjmp(__test_failed, 1);\n\
+                      }\n",
+                )?;
+            } else if self.panics {
+                file.write_all(b"static _Noreturn void __panic(void) {\nexit(101);\n}\n")?;
+            }
+            file.write_all(&prototypes)?;
+            if let Some(profile) = &profile {
+                file.write_all(profile.as_bytes())?;
+            }
+            file.write_all(&definitions)?;
             file.write_all(b"int main() {\n")?;
-            while let Some(ir) = self.eat() {
-                match ir {
-                    ir::IR::DefineVariable { name, t, value } => {
-                        file.write_all(
-                            format!("{} {} = {};\n", t, name, value).as_str().as_bytes(),
-                        )?;
-                    }
-                    ir::IR::Exit { value } => {
-                        file.write_all(format!("exit({});\n", value).as_str().as_bytes())?;
-                    }
-                }
+            if profile.is_some() {
+                file.write_all(b"atexit(__print_profile);\n")?;
             }
+            file.write_all(&body)?;
             file.write_all(b"}\n")?;
             file.flush()?;
         }
         Ok(buffer)
     }
 
-    fn eat(&mut self) -> Option<ir::IR> {
-        self.program.pop()
+    fn statements(&mut self, body: Vec<ir::IR>, out: &mut Vec<u8>) -> std::io::Result<()> {
+        for ir in body {
+            match ir {
+                ir::IR::DefineVariable { name, t, value } => {
+                    let value = self.expression(&value, t.as_str());
+                    self.variable_types.insert(name, t);
+                    let declaration = match ast::array_type(t) {
+                        Some((element, length)) => {
+                            format!("{} {}[{length}]", type_name(element), variable_name(name))
+                        }
+                        None => format!("{} {}", type_name(t), variable_name(name)),
+                    };
+                    out.write_all(format!("{declaration} = {value};\n").as_bytes())?;
+                }
+                ir::IR::Exit { value } => {
+                    let value = self.operand(&value);
+                    out.write_all(format!("exit({});\n", value).as_bytes())?;
+                }
+                ir::IR::Print { value, newline } => {
+                    let t = self.operand_type(&value);
+                    let value = self.expression(&value, t.as_str());
+                    let newline = if newline { "\\n" } else { "" };
+                    let (format, cast) = print_format(t.as_str());
+                    out.write_all(
+                        format!("printf(\"{format}{newline}\", ({cast})({value}));\n").as_bytes(),
+                    )?;
+                    self.prints = true;
+                }
+                ir::IR::AssertEq {
+                    left,
+                    right,
+                    t,
+                    message,
+                } => {
+                    let left = self.expression(&left, t.as_str());
+                    let right = self.expression(&right, t.as_str());
+                    let (format, values) = match t.as_str() {
+                        ast::BOOL => (
+                            "%s",
+                            ["__left", "__right"].map(|v| format!("{v} ? \"true\" : \"false\"")),
+                        ),
+                        t => {
+                            let (format, cast) = print_format(t);
+                            (
+                                format,
+                                ["__left", "__right"].map(|v| format!("({cast})({v})")),
+                            )
+                        }
+                    };
+                    let t = type_name(t);
+                    out.write_all(
+                        format!(
+                            "{{\n{t} __left = {left};\n{t} __right = {right};\n\
+                             if (__left != __right) {{\n\
+                             fflush(stdout);\n\
+                             fprintf(stderr, \"assertion `%s` failed\\n  left: {format}\\n right: {format}\\n\", {}, {}, {});\n\
+                             __panic();\n\
+                             }}\n\
+                             }}\n",
+                            string_literal(&message),
+                            values[0],
+                            values[1],
+                        )
+                        .as_bytes(),
+                    )?;
+                    self.panics = true;
+                }
+                ir::IR::Assign { name, t, value } => {
+                    let value = self.expression(&value, t.as_str());
+                    let name = variable_name(name);
+                    out.write_all(format!("{name} = {value};\n").as_bytes())?;
+                }
+                ir::IR::Call { name, args } => {
+                    let args = self.arguments(name, &args);
+                    let name = self.callee(name);
+                    out.write_all(format!("{name}({args});\n").as_bytes())?;
+                }
+                ir::IR::Return { value: None } => out.write_all(b"return;\n")?,
+                ir::IR::Break => out.write_all(b"break;\n")?,
+                ir::IR::Continue => out.write_all(b"continue;\n")?,
+                ir::IR::Block { body } => {
+                    out.write_all(b"{\n")?;
+                    self.statements(body, out)?;
+                    out.write_all(b"}\n")?;
+                }
+                ir::IR::Return { value: Some(value) } => {
+                    let t = self
+                        .return_type
+                        .expect("the parser only allows values in functions that return one");
+                    let value = self.expression(&value, t.as_str());
+                    out.write_all(format!("return {value};\n").as_bytes())?;
+                }
+                ir::IR::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => {
+                    let condition = self.operand(&condition);
+                    out.write_all(format!("if ({condition}) {{\n").as_bytes())?;
+                    self.statements(then, out)?;
+                    if !otherwise.is_empty() {
+                        out.write_all(b"} else {\n")?;
+                        self.statements(otherwise, out)?;
+                    }
+                    out.write_all(b"}\n")?;
+                }
+                ir::IR::Loop { condition, body } => {
+                    let condition = self.operand(&condition);
+                    out.write_all(format!("while ({condition}) {{\n").as_bytes())?;
+                    self.statements(body, out)?;
+                    out.write_all(b"}\n")?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders an expression whose result is stored as a `t`. With overflow checking every
+    /// arithmetic operation becomes a call to a helper that aborts when `t` overflows
+    fn expression(&mut self, expr: &ast::AstExpression, t: &str) -> String {
+        // Arithmetic stored in anything but a number, like a `bool`, is computed in its own type
+        let own_type;
+        let t = match expr {
+            ast::AstExpression::BinaryOperation { .. }
+            | ast::AstExpression::UnaryOperation { .. }
+                if !is_number(t) =>
+            {
+                own_type = self.operand_type(expr);
+                own_type.as_str()
+            }
+            _ => t,
+        };
+        match expr {
+            ast::AstExpression::Number {
+                raw,
+                flags,
+                suffix,
+                span: _,
+            } => match suffix {
+                // The type macros make every suffix a valid C type
+                Some(suffix) => format!("(({suffix}){})", literal(raw, flags)),
+                // Otherwise C computes `1 / 3` of a float as integers
+                None if matches!(t, "f32" | "f64") && integer_literal(raw, flags).is_some() => {
+                    format!("(({t}){})", literal(raw, flags))
+                }
+                None => literal(raw, flags),
+            },
+            ast::AstExpression::Identifier { name, span: _ } => variable_name(*name),
+            // Every value is an integer for now, so booleans are too
+            ast::AstExpression::Bool { value, span: _ } => u8::from(*value).to_string(),
+            ast::AstExpression::Call {
+                name,
+                args,
+                span: _,
+            } => {
+                format!("{}({})", self.callee(*name), self.arguments(*name, args))
+            }
+            ast::AstExpression::MethodCall { .. } => unreachable!("desugared into calls"),
+            ast::AstExpression::Cast { value, t, span: _ } => {
+                let value_code = self.operand(value);
+                let t = type_name(*t);
+                match **value {
+                    ast::AstExpression::BinaryOperation { .. }
+                    | ast::AstExpression::UnaryOperation { .. }
+                        if !enclosed(&value_code) =>
+                    {
+                        format!("(({t})({value_code}))")
+                    }
+                    _ => format!("(({t}){value_code})"),
+                }
+            }
+            ast::AstExpression::StructLiteral {
+                name,
+                fields,
+                span: _,
+            } => {
+                let types = self.struct_fields.get(name).cloned().unwrap_or_default();
+                let fields = fields
+                    .iter()
+                    .zip(types)
+                    .map(|((field, value), definition)| {
+                        format!(
+                            ".{} = {}",
+                            field_name(*field),
+                            self.expression(value, definition.t.as_str())
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("(({}){{{fields}}})", type_name(*name))
+            }
+            // Only structs have fields, so the value is never arithmetic that needs parentheses
+            ast::AstExpression::FieldAccess {
+                value,
+                field,
+                span: _,
+            } => format!("{}.{}", self.expression(value, "u64"), field_name(*field)),
+            ast::AstExpression::Variant {
+                name,
+                variant,
+                span: _,
+            } => variant_name(*name, *variant),
+            // Only the value of a `let`, which gives the element type
+            ast::AstExpression::ArrayLiteral { values, span: _ } => {
+                let element = ast::array_type(Symbol::intern(t))
+                    .map_or("u64", |(element, _)| element.as_str());
+                let values = values
+                    .iter()
+                    .map(|value| self.expression(value, element))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{{{values}}}")
+            }
+            ast::AstExpression::Index {
+                value,
+                index,
+                span: _,
+            } => format!("{}[{}]", self.expression(value, "u64"), self.operand(index)),
+            ast::AstExpression::Range {
+                start,
+                end,
+                inclusive,
+                span: _,
+            } => format!(
+                "(({}){{.{} = {}, .{} = {}}})",
+                type_name(ast::range_type(*inclusive)),
+                field_name(Symbol::intern("start")),
+                self.expression(start, "u64"),
+                field_name(Symbol::intern("end")),
+                self.expression(end, "u64")
+            ),
+            ast::AstExpression::UnaryOperation {
+                operator: ast::UnaryOp::Negate,
+                operand,
+                span: _,
+            } => {
+                let checked = self.options.overflow_check && !matches!(t, "f32" | "f64");
+                let operand_code = self.expression(operand, t);
+                if checked {
+                    // Negating overflows exactly when `0 - x` does
+                    self.checked_operations.insert((t.to_string(), "sub"));
+                    format!("__checked_sub_{t}(0, {operand_code})")
+                } else {
+                    let code = match **operand {
+                        ast::AstExpression::BinaryOperation { .. }
+                        | ast::AstExpression::UnaryOperation { .. }
+                            if !enclosed(&operand_code) =>
+                        {
+                            format!("-({operand_code})")
+                        }
+                        _ => format!("-{operand_code}"),
+                    };
+                    wrap(t, code)
+                }
+            }
+            ast::AstExpression::UnaryOperation {
+                operator: operator @ (ast::UnaryOp::Not | ast::UnaryOp::BitNot),
+                operand,
+                span: _,
+            } => {
+                // What `!` negates is a condition, not something of the type of the result
+                let operand_code = match operator {
+                    ast::UnaryOp::Not => self.operand(operand),
+                    _ => self.expression(operand, t),
+                };
+                let code = match **operand {
+                    ast::AstExpression::BinaryOperation { .. }
+                    | ast::AstExpression::UnaryOperation { .. }
+                        if !enclosed(&operand_code) =>
+                    {
+                        format!("{operator}({operand_code})")
+                    }
+                    _ => format!("{operator}{operand_code}"),
+                };
+                match operator {
+                    ast::UnaryOp::Not => code,
+                    _ => wrap(t, code),
+                }
+            }
+            ast::AstExpression::BinaryOperation {
+                left,
+                operator,
+                right,
+                span: _,
+            } => {
+                // Floating point arithmetic can't overflow, it goes to infinity instead
+                let checked = self.options.overflow_check && !matches!(t, "f32" | "f64");
+                // Division, remainder and shifts have no overflow builtins and are left unchecked
+                let operation = match operator {
+                    BinaryOp::Plus if checked => Some("add"),
+                    BinaryOp::Minus if checked => Some("sub"),
+                    BinaryOp::Star if checked => Some("mul"),
+                    _ => None,
+                };
+                // The operands of comparisons and logical operators have types of their own. Both
+                // sides of a comparison are computed in the same one
+                let operand_type = match operator {
+                    BinaryOp::And | BinaryOp::Or => None,
+                    operator if operator.is_comparison() => {
+                        Some(self.compared_type(left, right).to_string())
+                    }
+                    _ => Some(t.to_string()),
+                };
+                let mut operand = |e: &ast::AstExpression| {
+                    let code = match &operand_type {
+                        Some(t) => self.expression(e, t),
+                        None => self.operand(e),
+                    };
+                    match e {
+                        ast::AstExpression::BinaryOperation { .. }
+                        | ast::AstExpression::UnaryOperation { .. }
+                            if operation.is_none() && !enclosed(&code) =>
+                        {
+                            format!("({code})")
+                        }
+                        _ => code,
+                    }
+                };
+                let (left, right) = (operand(left), operand(right));
+                match operation {
+                    Some(operation) => {
+                        self.checked_operations.insert((t.to_string(), operation));
+                        format!("__checked_{operation}_{t}({left}, {right})")
+                    }
+                    None if leaves_range(operator, t) => {
+                        wrap(t, format!("{left}{operator}{right}"))
+                    }
+                    None => format!("{left}{operator}{right}"),
+                }
+            }
+        }
+    }
+
+    /// The C declarator of a function, a missing return type is `void` and `!` is `_Noreturn void`.
+    /// `#[inline]` functions are `static inline`, since gcc only accepts `always_inline` on functions
+    /// it can inline, and `#[deprecated]` ones are deprecated for the C code calling them too.
+    /// A shared library exports every other function
+    fn signature(&self, function: &ir::IrFunction) -> String {
+        let mut attrs: String = function
+            .attrs
+            .iter()
+            .map(|attr| match attr {
+                ast::Attribute::Inline => {
+                    "static inline __attribute__((always_inline)) ".to_string()
+                }
+                ast::Attribute::NoMangle | ast::Attribute::Test => String::new(),
+                ast::Attribute::Deprecated { message: None } => {
+                    "__attribute__((deprecated)) ".to_string()
+                }
+                ast::Attribute::Deprecated {
+                    message: Some(message),
+                } => format!("__attribute__((deprecated({}))) ", string_literal(message)),
+            })
+            .collect();
+        if self.options.pic && !function.attrs.contains(&ast::Attribute::Inline) {
+            attrs.insert_str(0, "__attribute__((visibility(\"default\"))) ");
+        }
+        let name = self.function_name(function.name);
+        format!("{attrs}{}", declarator(function, &name))
+    }
+
+    /// The C name of the function `name`, the one from the source if it is `#[no_mangle]`
+    fn function_name(&self, name: Symbol) -> String {
+        if self.unmangled.contains(&name) {
+            name.to_string()
+        } else {
+            function_name(name)
+        }
+    }
+
+    /// The body of `main` with `--test`, which runs every test and prints whether it passed.
+    /// A failing test jumps back to its `setjmp`
+    fn test_runner(&self, tests: &[Symbol]) -> String {
+        let mut runner = format!(
+            "volatile int passed = 0;\n\
+             volatile int failed = 0;\n\
+             printf(\"running {} tests\\n\");\n",
+            tests.len()
+        );
+        for test in tests {
+            let name = self.callee(*test);
+            let ok = string_literal(&format!("test {test} ... ok\n"));
+            let failed = string_literal(&format!("test {test} ... FAILED\n"));
+            runner.push_str(&format!(
+                "if (setjmp(__test_failed) == 0) {{\n\
+                 {name}();\n\
+                 printf({ok});\n\
+                 passed++;\n\
+                 }} else {{\n\
+                 printf({failed});\n\
+                 failed++;\n\
+                 }}\n"
+            ));
+        }
+        runner.push_str(
+            "printf(\"\\ntest result: %s. %d passed; %d failed\\n\", \
+             failed == 0 ? \"ok\" : \"FAILED\", passed, failed);\n\
+             return failed != 0;\n",
+        );
+        runner
+    }
+
+    /// `__profile_data` with the calls and time of each function, the functions timing the calls
+    /// and `__print_profile`, which prints them to stderr
+    fn profile(&self, functions: &[ir::IrFunction]) -> String {
+        let mut profile = "typedef struct __profile_entry {\n\
+                           const char *function_name;\n\
+                           u64 call_count;\n\
+                           u64 total_ns;\n\
+                           } __profile_entry;\n"
+            .to_string();
+        // C has no arrays without elements
+        if !functions.is_empty() {
+            profile.push_str("static __profile_entry __profile_data[] = {\n");
+            for function in functions {
+                let name = string_literal(function.name.as_str());
+                profile.push_str(&format!("{{{name}, 0, 0}},\n"));
+            }
+            profile.push_str("};\n");
+        }
+        for (index, function) in functions.iter().enumerate() {
+            profile.push_str(&self.profiled(index, function));
+        }
+        profile.push_str("static void __print_profile(void) {\n");
+        if !functions.is_empty() {
+            profile.push_str(&format!(
+                "for (int i = 0; i < {}; i++) {{\n\
+                 fprintf(stderr, \"%s: %\" PRIu64 \" calls, %\" PRIu64 \" ns\\n\", \
+                 __profile_data[i].function_name, __profile_data[i].call_count, \
+                 __profile_data[i].total_ns);\n\
+                 }}\n",
+                functions.len()
+            ));
+        }
+        profile.push_str("}\n");
+        profile
+    }
+
+    /// The C function a call to `name` calls, which times the call when profiling
+    fn callee(&self, name: Symbol) -> String {
+        let name = self.function_name(name);
+        if self.options.profile {
+            format!("__profiled_{name}")
+        } else {
+            name
+        }
+    }
+
+    /// The function timing the calls to `function` in entry `index` of `__profile_data`. A call
+    /// to a `!` function is only counted, it never ends
+    fn profiled(&self, index: usize, function: &ir::IrFunction) -> String {
+        let name = self.function_name(function.name);
+        let wrapper = declarator(function, &format!("__profiled_{name}"));
+        let args = function
+            .params
+            .iter()
+            .map(|param| variable_name(param.name))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let entry = format!("__profile_data[{index}]");
+        let (call, result) = match function.return_type {
+            Some(t) if t.as_str() == ast::NEVER => {
+                return format!(
+                    "static {wrapper} {{\n{entry}.call_count += 1;\n{name}({args});\n}}\n"
+                );
+            }
+            Some(t) => (
+                format!("{} __result = {name}({args});\n", type_name(t)),
+                "return __result;\n",
+            ),
+            None => (format!("{name}({args});\n"), ""),
+        };
+        format!(
+            "static {wrapper} {{\n\
+             struct timespec __start, __end;\n\
+             clock_gettime(CLOCK_MONOTONIC, &__start);\n\
+             {call}\
+             clock_gettime(CLOCK_MONOTONIC, &__end);\n\
+             {entry}.call_count += 1;\n\
+             {entry}.total_ns += (u64)(__end.tv_sec - __start.tv_sec) * 1000000000 + \
+             (__end.tv_nsec - __start.tv_nsec);\n\
+             {result}}}\n"
+        )
+    }
+
+    /// Renders an expression that isn't stored anywhere, like a condition, in its own type
+    fn operand(&mut self, expr: &ast::AstExpression) -> String {
+        let t = self.operand_type(expr);
+        self.expression(expr, t.as_str())
+    }
+
+    /// The type arithmetic in `expr` is computed in if nothing else decides it. Numbers of no
+    /// particular type are `u64`s, or `f64`s if a literal in them is a float, like in a `let`
+    fn operand_type(&self, expr: &ast::AstExpression) -> Symbol {
+        self.value_type(expr)
+            .filter(|t| is_number(t.as_str()))
+            .unwrap_or_else(|| {
+                if ast::has_float_literal(expr) {
+                    Symbol::intern("f64")
+                } else {
+                    Symbol::intern("u64")
+                }
+            })
+    }
+
+    /// The type both sides of a comparison are computed in, that of whichever side has one
+    fn compared_type(&self, left: &ast::AstExpression, right: &ast::AstExpression) -> Symbol {
+        match self.value_type(left).filter(|t| is_number(t.as_str())) {
+            Some(t) => t,
+            None => self.operand_type(right),
+        }
+    }
+
+    /// The type of `expr` as far as its variables, calls and casts tell, like the parser
+    /// figured it out
+    fn value_type(&self, expr: &ast::AstExpression) -> Option<Symbol> {
+        if expr.is_boolean() {
+            return Some(Symbol::intern("bool"));
+        }
+        match expr {
+            ast::AstExpression::Number { suffix, .. } => suffix.as_deref().map(Symbol::intern),
+            ast::AstExpression::Identifier { name, span: _ } => {
+                self.variable_types.get(name).copied()
+            }
+            ast::AstExpression::Call {
+                name,
+                args: _,
+                span: _,
+            } => self.return_types.get(name).copied().flatten(),
+            ast::AstExpression::MethodCall { .. } => unreachable!("desugared into calls"),
+            ast::AstExpression::Cast {
+                value: _,
+                t,
+                span: _,
+            } => Some(*t),
+            ast::AstExpression::UnaryOperation {
+                operator: _,
+                operand,
+                span: _,
+            } => self.value_type(operand),
+            ast::AstExpression::BinaryOperation {
+                left,
+                operator,
+                right: _,
+                span: _,
+            } if ast::is_shift(operator) => self.value_type(left),
+            ast::AstExpression::BinaryOperation {
+                left,
+                operator: _,
+                right,
+                span: _,
+            } => self.value_type(left).or_else(|| self.value_type(right)),
+            ast::AstExpression::StructLiteral {
+                name,
+                fields: _,
+                span: _,
+            }
+            | ast::AstExpression::Variant {
+                name,
+                variant: _,
+                span: _,
+            } => Some(*name),
+            ast::AstExpression::FieldAccess {
+                value,
+                field,
+                span: _,
+            } => self
+                .struct_fields
+                .get(&self.value_type(value)?)?
+                .iter()
+                .find(|other| other.name == *field)
+                .map(|field| field.t),
+            ast::AstExpression::Index {
+                value,
+                index: _,
+                span: _,
+            } => ast::array_type(self.value_type(value)?).map(|(element, _)| element),
+            ast::AstExpression::Range { inclusive, .. } => Some(ast::range_type(*inclusive)),
+            ast::AstExpression::Bool { .. } | ast::AstExpression::ArrayLiteral { .. } => None,
+        }
+    }
+
+    /// The arguments of a call, the parser already checked there is one for every parameter
+    fn arguments(&mut self, name: Symbol, args: &[ast::AstExpression]) -> String {
+        let types = self.parameter_types.get(&name).cloned().unwrap_or_default();
+        args.iter()
+            .zip(types)
+            .map(|(arg, t)| self.expression(arg, t.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Whether `operator` on two values of type `t` can give a result that `t` has no room for.
+/// Comparisons and logical operators give a `bool` instead
+fn leaves_range(operator: &BinaryOp, t: &str) -> bool {
+    match operator {
+        BinaryOp::Plus | BinaryOp::Minus | BinaryOp::Star | BinaryOp::ShiftLeft => true,
+        // Only `MIN / -1`
+        BinaryOp::Slash => t.starts_with('i'),
+        _ => false,
+    }
+}
+
+/// C computes with types narrower than `int` in `int`, so their arithmetic is cast back to wrap
+/// around like the type itself does
+fn wrap(t: &str, code: String) -> String {
+    if matches!(t, "u8" | "u16" | "i8" | "i16") {
+        format!("(({t})({code}))")
+    } else {
+        code
+    }
+}
+
+/// Whether all of `code` is inside one pair of parentheses, so it needs no more of them
+fn enclosed(code: &str) -> bool {
+    let mut depth = 0usize;
+    for (i, c) in code.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+        if depth == 0 {
+            return i == code.len() - 1 && i > 0;
+        }
+    }
+    false
+}
+
+fn is_number(t: &str) -> bool {
+    matches!(
+        t,
+        "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32" | "i64" | "f32" | "f64"
+    )
+}
+
+/// The `printf` format of a value of type `t` and the type it's cast to for it
+fn print_format(t: &str) -> (&'static str, &'static str) {
+    match t {
+        "i8" | "i16" | "i32" | "i64" => ("%\" PRId64 \"", "int64_t"),
+        "f32" | "f64" => ("%g", "double"),
+        _ => ("%\" PRIu64 \"", "uint64_t"),
+    }
+}
+
+/// The C name of a function. Like every other name from the source it is prefixed, so it can't
+/// collide with `main`, C keywords, the C library or a different kind of name. The method
+/// `T::method` is named like a variant
+fn function_name(name: Symbol) -> String {
+    match name.as_str().split_once("::") {
+        Some((t, method)) => format!("__M_{t}_{method}"),
+        None => format!("__fn_{name}"),
+    }
+}
+
+/// `name(params)` returning the return type of `function`
+fn declarator(function: &ir::IrFunction, name: &str) -> String {
+    let params = if function.params.is_empty() {
+        "void".to_string()
+    } else {
+        function
+            .params
+            .iter()
+            .map(|param| format!("{} {}", type_name(param.t), variable_name(param.name)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let return_type = match function.return_type {
+        Some(t) if t.as_str() == ast::NEVER => "_Noreturn void".to_string(),
+        Some(t) => type_name(t),
+        None => "void".to_string(),
+    };
+    format!("{return_type} {name}({params})")
+}
+
+/// The C name of a variable, parameter or constant
+fn variable_name(name: Symbol) -> String {
+    format!("__v_{name}")
+}
+
+/// The C name of a struct field
+fn field_name(name: Symbol) -> String {
+    format!("__f_{name}")
+}
+
+/// The C name of a type. Numbers are named by the type macros and `bool` by stdbool, structs
+/// and enums are prefixed
+fn type_name(t: Symbol) -> String {
+    if is_number(t.as_str()) || t.as_str() == ast::BOOL {
+        t.to_string()
+    } else {
+        format!("__t_{t}")
+    }
+}
+
+/// `typedef struct Name { ... } Name;`, so the struct is named like any other type
+fn struct_definition(s: &ir::IrStruct) -> String {
+    let fields: String = s
+        .fields
+        .iter()
+        .map(|field| format!("{} {};\n", type_name(field.t), field_name(field.name)))
+        .collect();
+    format!(
+        "{}typedef struct {1} {{\n{fields}}} {1};\n",
+        comment(s.doc.as_deref()),
+        type_name(s.name)
+    )
+}
+
+/// `typedef enum Name { ... } Name;`. C puts the variants of every enum in one namespace, so
+/// each is prefixed with the name of its enum
+fn enum_definition(e: &ir::IrEnum) -> String {
+    let variants: String = e
+        .variants
+        .iter()
+        .map(|variant| format!("{},\n", variant_name(e.name, *variant)))
+        .collect();
+    format!(
+        "typedef enum {0} {{\n{variants}}} {0};\n",
+        type_name(e.name)
+    )
+}
+
+fn variant_name(name: Symbol, variant: Symbol) -> String {
+    format!("__V_{name}_{variant}")
+}
+
+/// A doc comment as a `/* ... */` comment on lines of its own, nothing without one
+fn comment(doc: Option<&str>) -> String {
+    let Some(doc) = doc else {
+        return String::new();
+    };
+    // The comment would end early otherwise
+    let doc = doc.replace("*/", "* /");
+    let lines = doc
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| match (i, line) {
+            (0, line) => line.to_string(),
+            (_, "") => " *".to_string(),
+            (_, line) => format!(" * {line}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("/* {lines} */\n")
+}
+
+/// `value` as a C string literal
+fn string_literal(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `static const t name = value;`. Negative values are stored as their 64 bit two's
+/// complement, which only fits an unsigned literal that C then converts back
+fn constant_definition(constant: &ir::IrConst) -> String {
+    let ir::IrConst { name, t, value } = constant;
+    let const_eval::Value::Integer(value) = value;
+    let (name, t) = (variable_name(*name), type_name(*t));
+    if i64::try_from(*value).is_ok() {
+        format!("static const {t} {name} = {value};")
+    } else {
+        format!("static const {t} {name} = ({t}){value}ULL;")
+    }
+}
+
+/// A number literal in a form C understands, which has no binary or octal prefixes
+fn literal(raw: &str, flags: &[NumberTypeFlag]) -> String {
+    match flags {
+        // C writes octal with a bare leading zero, so decimal literals must not have one
+        [NumberTypeFlag::Octal] => format!("0{}", &raw[2..]),
+        [] if raw.len() > 1 && raw.starts_with('0') => {
+            let trimmed = raw.trim_start_matches('0');
+            if trimmed.is_empty() { "0" } else { trimmed }.to_string()
+        }
+        [NumberTypeFlag::Binary] => {
+            integer_literal(raw, flags).map_or_else(|| raw.to_string(), |value| value.to_string())
+        }
+        _ => raw.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CBackend, CBackendOptions};
+    use crate::{ast, desugar, ir, tokenizer};
+
+    fn compile(src: &str, options: CBackendOptions) -> String {
+        let tokens = tokenizer::Tokenizer::new(src, "tests::cbackend".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let ir = ir::IrGenerator::new(desugar::desugar(ast)).generate();
+        String::from_utf8(CBackend::new(ir, options).compile().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn link_library_headers() {
+        let out = compile(
+            "exit(0);",
+            CBackendOptions {
+                extra_headers: vec!["math.h".to_string()],
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("#include <math.h>\n"));
+    }
+
+    /// The first C compiler found on the system, tests that need one are skipped without
+    fn c_compiler() -> Option<&'static str> {
+        ["clang", "gcc", "cc"].into_iter().find(|cc| {
+            std::process::Command::new(cc)
+                .arg("--version")
+                .output()
+                .is_ok_and(|out| out.status.success())
+        })
+    }
+
+    /// Builds and runs generated C code, `None` if there is no C compiler to build it with
+    fn run(name: &str, c: &str) -> Option<std::process::Output> {
+        let Some(cc) = c_compiler() else {
+            eprintln!("no C compiler found, skipping the run of the generated program");
+            return None;
+        };
+        let dir = std::env::temp_dir().join(format!("ghetto-llvm-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.c"), c).unwrap();
+        let status = std::process::Command::new(cc)
+            .arg(dir.join("main.c"))
+            .arg("-o")
+            .arg(dir.join("main"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let output = std::process::Command::new(dir.join("main"))
+            .output()
+            .unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        Some(output)
+    }
+
+    #[test]
+    fn overflow_check_aborts() {
+        let src = "let a: u8 = 200; let b: u8 = a + 100; exit(b);";
+        let out = compile(
+            src,
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(out.matches("static u8 __checked_add_u8(").count(), 1);
+        assert!(out.contains("u8 __v_b = __checked_add_u8(__v_a, 100);"));
+
+        if let Some(output) = run("overflow", &out) {
+            assert!(!output.status.success());
+        }
+    }
+
+    #[test]
+    fn pic_exports_functions() {
+        let src =
+            "#[no_mangle] fn f() { } #[inline] fn g() { } #[deprecated] fn h() { } f(); g(); h();";
+        let out = compile(
+            src,
+            CBackendOptions {
+                pic: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("__attribute__((visibility(\"default\"))) void f(void) {\n"));
+        assert!(
+            out.contains("\nstatic inline __attribute__((always_inline)) void __fn_g(void) {\n")
+        );
+        assert!(out.contains(
+            "__attribute__((visibility(\"default\"))) __attribute__((deprecated)) void __fn_h(void);\n"
+        ));
+        assert!(!compile(src, CBackendOptions::default()).contains("visibility"));
+    }
+
+    #[test]
+    fn profile() {
+        let src = "fn f(a: u64) -> u64 { return a + 1; } fn g() { println f(1); } fn h() -> ! { exit(f(2)); } \
+                   g(); g(); h();";
+        let out = compile(
+            src,
+            CBackendOptions {
+                profile: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("#include <time.h>\n"));
+        assert!(out.contains("static void __print_profile(void) {\n"));
+        assert!(out.contains("int main() {\natexit(__print_profile);\n"));
+        assert!(out.contains("static __profile_entry __profile_data[] = {\n{\"f\", 0, 0},\n"));
+        assert!(out.contains("static u64 __profiled___fn_f(u64 __v_a) {\n"));
+        assert!(out.contains("printf(\"%\" PRIu64 \"\\n\", (uint64_t)(__profiled___fn_f(1)));\n"));
+        assert!(out.contains("__profiled___fn_g();\n"));
+        assert!(!compile(src, CBackendOptions::default()).contains("__profile"));
+
+        if let Some(output) = run("profile", &out) {
+            assert_eq!(output.status.code(), Some(3));
+            assert_eq!(output.stdout, b"2\n2\n");
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains("f: 3 calls, "), "{stderr}");
+            assert!(stderr.contains("g: 2 calls, "), "{stderr}");
+            assert!(stderr.contains("h: 1 calls, 0 ns\n"), "{stderr}");
+        }
+        let out = compile(
+            "exit(0);",
+            CBackendOptions {
+                profile: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("static void __print_profile(void) {\n}\n"));
+        assert!(run("profile_nothing", &out).is_none_or(|output| output.status.success()));
+    }
+
+    #[test]
+    fn test_runner() {
+        let src = include_str!("../examples/tests.ghl");
+        let out = compile(
+            src,
+            CBackendOptions {
+                test: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("#include <setjmp.h>\n"));
+        assert!(out.contains(
+            "if (setjmp(__test_failed) == 0) {\n__fn_arithmetic();\nprintf(\"test arithmetic ... ok\\n\");\n"
+        ));
+        // The top level statements don't run
+        assert!(!out.contains("__fn_factorial(10)"));
+        if let Some(output) = run("test_runner", &out) {
+            assert!(output.status.success());
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            assert!(
+                stdout.starts_with("running 6 tests\ntest arithmetic ... ok\n"),
+                "{stdout}"
+            );
+            assert!(
+                stdout.ends_with("\ntest result: ok. 6 passed; 0 failed\n"),
+                "{stdout}"
+            );
+        }
+
+        let src = format!(
+            "{src}\n#[test] fn broken() {{ println 1; assert_eq!(difference(1, 4) as u8, 2); println 2; }}"
+        );
+        let out = compile(
+            &src,
+            CBackendOptions {
+                test: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("u8 __left = ((u8)__fn_difference(1, 4));\nu8 __right = 2;\n"));
+        if let Some(output) = run("test_runner_failing", &out) {
+            assert_eq!(output.status.code(), Some(1));
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            assert!(
+                stdout.contains("test bools ... ok\n1\ntest broken ... FAILED\n"),
+                "{stdout}"
+            );
+            assert!(
+                stdout.ends_with("test result: FAILED. 6 passed; 1 failed\n"),
+                "{stdout}"
+            );
+            assert_eq!(
+                String::from_utf8(output.stderr).unwrap(),
+                "assertion `difference(1, 4) as u8 == 2` failed\n  left: 3\n right: 2\n"
+            );
+        }
+    }
+
+    #[test]
+    fn assert_eq_outside_of_tests() {
+        let out = compile(
+            "let a = true; assert_eq!(a, 1 < 2); println 1; assert_eq!(a, false); println 2;",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("static _Noreturn void __panic(void) {\nexit(101);\n}\n"));
+        if let Some(output) = run("assert_eq_outside_of_tests", &out) {
+            assert_eq!(output.status.code(), Some(101));
+            assert_eq!(output.stdout, b"1\n");
+            assert_eq!(
+                String::from_utf8(output.stderr).unwrap(),
+                "assertion `a == false` failed\n  left: true\n right: false\n"
+            );
+        }
+    }
+
+    #[test]
+    fn operands_have_their_own_type() {
+        let out = compile(
+            "let a: i64 = -1; let b: bool = a + 1 > 0; if a + 1 > 0 { exit(1); } \
+             while a * 2 > 0 {} let c: u8 = (a + 3) as u8; exit(a + 3);",
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("bool __v_b = (__checked_add_i64(__v_a, 1))>0;"));
+        assert!(out.contains("if ((__checked_add_i64(__v_a, 1))>0) {"));
+        assert!(out.contains("while ((__checked_mul_i64(__v_a, 2))>0) {"));
+        assert!(out.contains("u8 __v_c = ((u8)(__checked_add_i64(__v_a, 3)));"));
+        assert!(out.contains("exit(__checked_add_i64(__v_a, 3));"));
+        assert!(!out.contains("_u64(") && !out.contains("_bool("));
+
+        if let Some(output) = run("operand_types", &out) {
+            assert_eq!(output.status.code(), Some(2));
+        }
+    }
+
+    #[test]
+    fn print_integers() {
+        let out = compile(
+            "let a: u64 = 34; print a; println a * 2 + 1; exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("#include <stdio.h>\n"));
+        assert!(out.contains("printf(\"%\" PRIu64 \"\", (uint64_t)(__v_a));"));
+        assert!(out.contains("printf(\"%\" PRIu64 \"\\n\", (uint64_t)((__v_a*2)+1));"));
+        assert!(!compile("exit(0);", CBackendOptions::default()).contains("stdio.h"));
+
+        if let Some(output) = run("print", &out) {
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), "3469\n");
+        }
+    }
+
+    #[test]
+    fn print_signed_and_floats() {
+        let out = compile(
+            "let a: i8 = -3; println a; let f: f64 = 1.5; println f * 3.0; print 0.25; \
+             println -a * 2; exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("printf(\"%\" PRId64 \"\\n\", (int64_t)(__v_a));"));
+        assert!(out.contains("printf(\"%g\\n\", (double)(__v_f*3.0));"));
+        assert!(out.contains("printf(\"%g\", (double)(0.25));"));
+
+        if let Some(output) = run("print_signed_and_floats", &out) {
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                "-3\n4.5\n0.256\n"
+            );
+        }
+    }
+
+    #[test]
+    fn float_variables() {
+        let out = compile(
+            "let a: f64 = 1.5 * 2.25; exit(0);",
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("#define f64 double\n"));
+        assert!(out.contains("f64 __v_a = 1.5*2.25;"));
+
+        if let Some(output) = run("float", &out) {
+            assert!(output.status.success());
+        }
+    }
+
+    #[test]
+    fn integer_literals_as_floats() {
+        let out = compile(
+            "let y: f32 = 1 / 4; let mut z: f64 = 3; z = z / 2 + 0x1 / 8; println y; println z; \
+             exit((y * 8.0) as u8 + (z * 8.0) as u8);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("f32 __v_y = ((f32)1)/((f32)4);"));
+
+        if let Some(output) = run("integer_literals_as_floats", &out) {
+            assert_eq!(output.status.code(), Some(15));
+        }
+    }
+
+    #[test]
+    fn hexadecimal_literal() {
+        let out = compile("let a: u64 = 0xFF; exit(a);", CBackendOptions::default());
+        assert!(out.contains("u64 __v_a = 0xFF;"));
+
+        if let Some(output) = run("hexadecimal", &out) {
+            assert_eq!(output.status.code(), Some(255));
+        }
+    }
+
+    #[test]
+    fn suffixed_literals() {
+        let out = compile(
+            "let a: u64 = 4294967295u32 + 1u32; let b: f64 = 1.5f32; exit((a == 0) as u8);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __v_a = ((u32)4294967295)+((u32)1);"));
+        assert!(out.contains("f64 __v_b = ((f32)1.5);"));
+
+        // The addition wraps around in 32 bits
+        if let Some(output) = run("suffixed_literals", &out) {
+            assert_eq!(output.status.code(), Some(1));
+        }
+    }
+
+    #[test]
+    fn binary_literal() {
+        let out = compile(
+            "let a: u64 = 0b1010 * 2; exit(a);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __v_a = 10*2;"));
+
+        if let Some(output) = run("binary", &out) {
+            assert_eq!(output.status.code(), Some(20));
+        }
+    }
+
+    #[test]
+    fn octal_literal() {
+        let out = compile(
+            "let a: u64 = 0o17; let b: u64 = 017; exit(a + b);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __v_a = 017;"));
+        assert!(out.contains("u64 __v_b = 17;"));
+
+        if let Some(output) = run("octal", &out) {
+            assert_eq!(output.status.code(), Some(32));
+        }
+    }
+
+    #[test]
+    fn negation() {
+        let out = compile(
+            "let a: i64 = -5; let b: i64 = 2 - -a; exit(-(b - 4));",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("i64 __v_a = -5;"));
+        assert!(out.contains("i64 __v_b = 2-(-__v_a);"));
+        assert!(out.contains("exit(-(__v_b-4));"));
+
+        if let Some(output) = run("negation", &out) {
+            assert_eq!(output.status.code(), Some(7));
+        }
+
+        let checked = compile(
+            "let a: u64 = 1; exit(-a);",
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert!(checked.contains("exit(__checked_sub_u64(0, __v_a));"));
+        if let Some(output) = run("checked_negation", &checked) {
+            assert!(!output.status.success());
+        }
+    }
+
+    #[test]
+    fn division() {
+        let out = compile(
+            "let a: u64 = 17; exit(a / 5 * 10 + a % 5);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("exit(((__v_a/5)*10)+(__v_a%5));"));
+
+        if let Some(output) = run("division", &out) {
+            assert_eq!(output.status.code(), Some(32));
+        }
+    }
+
+    #[test]
+    fn comparisons() {
+        let out = compile(
+            "let a: i64 = -1; exit((a < 0) as i64 + (a >= 0) as i64 * 2 + (a != 1) as i64 * 4);",
+            CBackendOptions::default(),
+        );
+        assert!(
+            out.contains("exit((((i64)(__v_a<0))+(((i64)(__v_a>=0))*2))+(((i64)(__v_a!=1))*4));")
+        );
+
+        if let Some(output) = run("comparisons", &out) {
+            assert_eq!(output.status.code(), Some(5));
+        }
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let out = compile(
+            "let a: u8 = 0b1010; let b: u8 = ~a ^ a << 4 | a >> 1 & 1; exit(b);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u8 __v_b = (((u8)(~__v_a))^((u8)(__v_a<<4)))|((__v_a>>1)&1);"));
+
+        if let Some(output) = run("bitwise_operators", &out) {
+            assert_eq!(output.status.code(), Some(0x55));
+        }
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        // The division by zero and the overflowing addition are never evaluated
+        let out = compile(
+            "let a: u8 = 0; let b: u8 = 255; let c: u8 = (a != 0 && 1 / a != 0) as u8 + (a == 0 || b + 1 > 0) as u8 * 2 + (b == 0) as u8 * 4; exit(c);",
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("u8 __v_c = __checked_add_u8(__checked_add_u8(((u8)((__v_a!=0)&&((1/__v_a)!=0))), __checked_mul_u8(((u8)((__v_a==0)||((__checked_add_u8(__v_b, 1))>0))), 2)), __checked_mul_u8(((u8)(__v_b==0)), 4));"));
+
+        if let Some(output) = run("logical_operators", &out) {
+            assert_eq!(output.status.code(), Some(2));
+        }
+    }
+
+    #[test]
+    fn bool_literals() {
+        let out = compile(
+            "let t: bool = true; let f: bool = 1 > 2; exit(t as u8 + t as u8 + f as u8);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("bool __v_t = 1;\nbool __v_f = 1>2;\n"));
+        assert!(out.contains("exit(((u8)(((u8)(((u8)__v_t)+((u8)__v_t)))+((u8)__v_f))));"));
+
+        if let Some(output) = run("bool_literals", &out) {
+            assert_eq!(output.status.code(), Some(2));
+        }
+    }
+
+    #[test]
+    fn functions() {
+        let out = compile(
+            "fn quit(code: u8, times: u64) { let total: u64 = code as u64 * times; exit(total); }\nfn hello() { println 42; }\nexit(3);",
+            CBackendOptions::default(),
+        );
+        assert!(
+            out.contains("void __fn_quit(u8 __v_code, u64 __v_times);\nvoid __fn_hello(void);\n")
+        );
+        assert!(out.contains(
+            "void __fn_quit(u8 __v_code, u64 __v_times) {\nu64 __v_total = ((u64)__v_code)*__v_times;\nexit(__v_total);\n}\n"
+        ));
+        assert!(out.contains("int main() {\nexit(3);\n"));
+
+        if let Some(output) = run("functions", &out) {
+            assert_eq!(output.status.code(), Some(3));
+        }
+    }
+
+    #[test]
+    fn inline_functions() {
+        let out = compile(
+            "#[inline] fn add(a: u64, b: u64) -> u64 { return a + b; } exit(add(1, 2));",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains(
+            "static inline __attribute__((always_inline)) u64 __fn_add(u64 __v_a, u64 __v_b);\n"
+        ));
+        assert!(out.contains(
+            "static inline __attribute__((always_inline)) u64 __fn_add(u64 __v_a, u64 __v_b) {\n"
+        ));
+
+        if let Some(output) = run("inline_functions", &out) {
+            assert_eq!(output.status.code(), Some(3));
+        }
+    }
+
+    #[test]
+    fn never_returning_functions() {
+        let out = compile(
+            "fn fail(code: u8) -> ! { exit(code); } fail(4);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("_Noreturn void __fn_fail(u8 __v_code);\n"));
+        assert!(out.contains("_Noreturn void __fn_fail(u8 __v_code) {\n"));
+
+        if let Some(output) = run("never_returning_functions", &out) {
+            assert_eq!(output.status.code(), Some(4));
+        }
+    }
+
+    #[test]
+    fn names_of_c() {
+        let out = compile(
+            "struct int { double: u64 } enum EOF { NULL, stdin } const errno: u8 = 2; \
+             fn free(int: int) -> u64 { return int.double; } let printf: u64 = 1; println printf; \
+             let NULL = int { double: 3 }; let main = EOF::stdin; \
+             exit(free(NULL) + printf + errno as u64 + (main == EOF::stdin) as u64 - 2);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("typedef struct __t_int {\nu64 __f_double;\n} __t_int;\n"));
+        assert!(out.contains("u64 __fn_free(__t_int __v_int) {\n"));
+        assert!(out.contains("printf(\"%\" PRIu64 \"\\n\", (uint64_t)(__v_printf));"));
+
+        if let Some(output) = run("names_of_c", &out) {
+            assert_eq!(output.status.code(), Some(5));
+            assert_eq!(output.stdout, b"1\n");
+        }
+    }
+
+    #[test]
+    fn narrow_arithmetic() {
+        let out = compile(
+            "let x: u8 = 255; println x + 1; let m: i8 = -127 - 1; println -m; println m / -1; \
+             let y: u8 = x + 1; if x + 1 == 0 { exit(y + 3); } exit(1);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u8 __v_y = ((u8)(__v_x+1));\n"));
+
+        if let Some(output) = run("narrow_arithmetic", &out) {
+            assert_eq!(output.status.code(), Some(3));
+            assert_eq!(output.stdout, b"0\n-128\n-128\n");
+        }
+    }
+
+    #[test]
+    fn no_mangle_functions() {
+        let out = compile(
+            "#[no_mangle] fn my_function(a: u8) -> u8 { return a + 1; } fn other() { } \
+             other(); exit(my_function(2));",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u8 my_function(u8 __v_a);\n"));
+        assert!(out.contains("void __fn_other(void);\n"));
+        assert!(out.contains("exit(my_function(2));\n"));
+
+        if let Some(output) = run("no_mangle_functions", &out) {
+            assert_eq!(output.status.code(), Some(3));
+        }
+    }
+
+    #[test]
+    fn deprecated_functions() {
+        let out = compile(
+            "#[deprecated = \"use \\\"g\\\"\"] fn f() -> u8 { return 2; } #[deprecated] fn g() { } \
+             g(); exit(f());",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("__attribute__((deprecated(\"use \\\"g\\\"\"))) u8 __fn_f(void);\n"));
+        assert!(out.contains("__attribute__((deprecated)) void __fn_g(void) {\n"));
+
+        if let Some(output) = run("deprecated_functions", &out) {
+            assert_eq!(output.status.code(), Some(2));
+        }
+    }
+
+    #[test]
+    fn doc_comments() {
+        let out = compile(
+            "/// This adds two numbers\nfn add(a: u8, b: u8) -> u8 { return a + b; }\n\
+             /// A point\n///\n/// Not a */ comment end\nstruct Point { x: u8 }\n\
+             /// Dropped\nlet p = Point { x: 1 }; exit(add(p.x, 2));",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("/* This adds two numbers */\nu8 __fn_add(u8 __v_a, u8 __v_b) {\n"));
+        assert!(out.contains("u8 __fn_add(u8 __v_a, u8 __v_b);\n"));
+        assert!(out
+            .contains("/* A point\n *\n * Not a * / comment end */\ntypedef struct __t_Point {\n"));
+        assert!(!out.contains("Dropped"));
+
+        if let Some(output) = run("doc_comments", &out) {
+            assert_eq!(output.status.code(), Some(3));
+        }
+    }
+
+    #[test]
+    fn function_names_of_c() {
+        let out = compile(
+            "fn main() -> u8 { return 3; } fn printf(a: u8) { println a; } printf(main()); exit(main());",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u8 __fn_main(void) {\n"));
+        assert!(out.contains("__fn_printf(__fn_main());\n"));
+        assert_eq!(out.matches("int main()").count(), 1);
+
+        if let Some(output) = run("function_names_of_c", &out) {
+            assert_eq!(output.status.code(), Some(3));
+            assert_eq!(output.stdout, b"3\n");
+        }
+    }
+
+    #[test]
+    fn calls() {
+        let out = compile(
+            "fn quit(code: u8) { exit(code + 1); }\nfn twice(x: u8) -> u8 { exit(x * 2); }\nlet a: u8 = 2; quit(a * 2);",
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains("__fn_quit(__checked_mul_u8(__v_a, 2));\n"));
+
+        if let Some(output) = run("calls", &out) {
+            assert_eq!(output.status.code(), Some(5));
+        }
+    }
+
+    #[test]
+    fn if_else() {
+        let out = compile(
+            "let a: u64 = 7; if a < 5 { exit(1); } else if a % 2 == 1 { println a; exit(2); } else { exit(3); }",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("if (__v_a<5) {\nexit(1);\n} else {\nif ((__v_a%2)==1) {\n"));
+
+        if let Some(output) = run("if_else", &out) {
+            assert_eq!(output.status.code(), Some(2));
+            assert_eq!(output.stdout, b"7\n");
+        }
+    }
+
+    #[test]
+    fn while_loop() {
+        let out = compile(
+            "let a: u64 = 3; while a > 1 { println a; exit(a); } exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("while (__v_a>1) {\nprintf("));
+
+        if let Some(output) = run("while_loop", &out) {
+            assert_eq!(output.status.code(), Some(3));
+            assert_eq!(output.stdout, b"3\n");
+        }
+    }
+
+    #[test]
+    fn for_loop() {
+        let out = compile(
+            "let n: u8 = 3; for i: u8 in 0..n + 1 { if i == 1 { continue; } println i; } exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains(
+            "u8 __v___next_i = 0;\nu8 __v___end_i = ((u8)(__v_n+1));\nwhile (__v___next_i<__v___end_i) {\nu8 __v_i = __v___next_i;\n__v___next_i = ((u8)(__v___next_i+1));\n"
+        ));
+
+        if let Some(output) = run("for_loop", &out) {
+            assert_eq!(output.status.code(), Some(0));
+            assert_eq!(output.stdout, b"0\n2\n3\n");
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let out = compile(
+            "let mut sum: u64 = 0; for i: u8 in 250..=255 { sum += i as u64; } \
+             let r = 1..=4; for j in r { sum += j; } for k in 0..r.end { sum += k; } \
+             let empty: Range = 3..3; for k in empty { sum += 100; } println sum; exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out
+            .contains("typedef struct __t_Range {\nu64 __f_start;\nu64 __f_end;\n} __t_Range;\n"));
+        assert!(out.contains(
+            "__t_RangeInclusive __v_r = ((__t_RangeInclusive){.__f_start = 1, .__f_end = 4});"
+        ));
+        assert!(out.contains("u64 __v___next_j = __v___range_j.__f_start;"));
+
+        if let Some(output) = run("ranges", &out) {
+            assert_eq!(output.status.code(), Some(0));
+            assert_eq!(output.stdout, b"1531\n");
+        }
+    }
+
+    #[test]
+    fn return_values() {
+        let out = compile(
+            "fn add(a: u8, b: u8) -> u8 { return a + b; }\nfn nothing() { return; }\nnothing(); exit(add(2, 3));",
+            CBackendOptions {
+                overflow_check: true,
+                ..Default::default()
+            },
+        );
+        assert!(out.contains(
+            "u8 __fn_add(u8 __v_a, u8 __v_b) {\nreturn __checked_add_u8(__v_a, __v_b);\n}\n"
+        ));
+        assert!(out.contains("void __fn_nothing(void) {\nreturn;\n}\n"));
+
+        if let Some(output) = run("return_values", &out) {
+            assert_eq!(output.status.code(), Some(5));
+        }
+    }
+
+    #[test]
+    fn blocks() {
+        let out = compile(
+            "let a: u64 = 1; { let a: u64 = 2; println a; } exit(a);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __v_a = 1;\n{\nu64 __v_a__1 = 2;\n"));
+
+        if let Some(output) = run("blocks", &out) {
+            assert_eq!(output.status.code(), Some(1));
+            assert_eq!(output.stdout, b"2\n");
+        }
+    }
+
+    #[test]
+    fn shadowing() {
+        let out = compile(
+            "fn twice(a: u64) -> u64 { let a: u64 = a * 2; return a; } \
+             let a: u64 = 1; let a: u64 = a + 1; { let a: u64 = a + 1; println a; } \
+             exit(twice(a) + a);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __v_a__1 = __v_a*2;\nreturn __v_a__1;\n"));
+        assert!(out
+            .contains("u64 __v_a = 1;\nu64 __v_a__1 = __v_a+1;\n{\nu64 __v_a__2 = __v_a__1+1;\n"));
+
+        if let Some(output) = run("shadowing", &out) {
+            assert_eq!(output.status.code(), Some(6));
+            assert_eq!(output.stdout, b"3\n");
+        }
+    }
+
+    #[test]
+    fn break_and_continue() {
+        let out = compile(
+            "for i in 0..10 { if i == 2 { continue; } if i == 4 { break; } println i; } exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("if (__v_i==2) {\ncontinue;\n}\n"));
+
+        if let Some(output) = run("break_and_continue", &out) {
+            assert_eq!(output.stdout, b"0\n1\n3\n");
+        }
+    }
+
+    #[test]
+    fn assignments() {
+        let out = compile(
+            "let mut a: u64 = 1; let mut i: u8 = 0; while i < 5 { a = a * 2; i = i + 1; } exit(a);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("__v_a = __v_a*2;\n__v_i = ((u8)(__v_i+1));\n"));
+
+        if let Some(output) = run("assignments", &out) {
+            assert_eq!(output.status.code(), Some(32));
+        }
+    }
+
+    #[test]
+    fn constants() {
+        let out = compile(
+            "const BASE: u8 = 40; const OFFSET: i8 = -1; fn f() -> u8 { return BASE; } exit(f() as i8 + OFFSET + 3);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains(
+            "static const u8 __v_BASE = 40;\nstatic const i8 __v_OFFSET = (i8)18446744073709551615ULL;\n"
+        ));
+
+        if let Some(output) = run("constants", &out) {
+            assert_eq!(output.status.code(), Some(42));
+        }
+    }
+
+    #[test]
+    fn inferred_types() {
+        let out = compile(
+            "let a = 40; let b = 2u8; let big = a > 1; exit(a + b as u64 + big as u64 - 1);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __v_a = 40;\nu8 __v_b = ((u8)2);\nbool __v_big = __v_a>1;\n"));
+
+        if let Some(output) = run("inferred_types", &out) {
+            assert_eq!(output.status.code(), Some(42));
+        }
+    }
+
+    #[test]
+    fn structs() {
+        let out = compile(
+            "struct Point { x: u8, y: u64 } struct Line { from: Point, to: Point } \
+             fn origin() -> Point { return Point { x: 0, y: 0 }; } \
+             let l = Line { to: Point { x: 3, y: 40 }, from: origin() }; exit((l.to.x - l.from.x) as u64 + l.to.y);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains(
+            "typedef struct __t_Point {\nu8 __f_x;\nu64 __f_y;\n} __t_Point;\ntypedef struct __t_Line {\n__t_Point __f_from;\n__t_Point __f_to;\n} __t_Line;\n"
+        ));
+        assert!(out.contains(
+            "__t_Line __v_l = ((__t_Line){.__f_from = __fn_origin(), .__f_to = ((__t_Point){.__f_x = 3, .__f_y = 40})});\n"
+        ));
+        assert!(out.contains(
+            "exit(((u64)((u8)(__v_l.__f_to.__f_x-__v_l.__f_from.__f_x)))+__v_l.__f_to.__f_y);"
+        ));
+
+        if let Some(output) = run("structs", &out) {
+            assert_eq!(output.status.code(), Some(43));
+        }
+    }
+
+    #[test]
+    fn enums() {
+        let out = compile(
+            "enum Color { Red, Green, Blue } struct Pixel { color: Color } \
+             fn next(c: Color) -> Color { if c == Color::Red { return Color::Green; } return Color::Blue; } \
+             let p = Pixel { color: next(Color::Red) }; exit(p.color as u8 * 10 + (p.color != Color::Blue) as u8);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains(
+            "typedef enum __t_Color {\n__V_Color_Red,\n__V_Color_Green,\n__V_Color_Blue,\n} __t_Color;\ntypedef struct __t_Pixel {"
+        ));
+        assert!(out.contains("if (__v_c==__V_Color_Red) {"));
+
+        if let Some(output) = run("enums", &out) {
+            assert_eq!(output.status.code(), Some(11));
+        }
+    }
+
+    #[test]
+    fn methods() {
+        let out = compile(
+            "struct Point { x: u64, y: u64 } \
+             impl Point { fn distance(self, to: Point) -> u64 { return to.x - self.x + to.y - self.y; } \
+             fn scale(self: Point, by: u64) -> Point { return Point { x: self.x * by, y: self.y * by }; } \
+             fn show(self) { println self.x + self.y; } } \
+             let p = Point { x: 3, y: 4 }; p.scale(2).show(); exit(p.scale(2).distance(p.scale(5)));",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u64 __M_Point_distance(__t_Point __v_self, __t_Point __v_to) {\n"));
+        assert!(out.contains("__M_Point_show(__M_Point_scale(__v_p, 2));\n"));
+
+        if let Some(output) = run("methods", &out) {
+            assert_eq!(output.status.code(), Some(21));
+            assert_eq!(output.stdout, b"14\n");
+        }
+    }
+
+    #[test]
+    fn match_statements() {
+        let out = compile(
+            "enum Op { Add, Mul } \
+             fn apply(op: Op, a: u64, b: u64) -> u64 { match op { Op::Add => { return a + b; } Op::Mul => { return a * b; } } return a; } \
+             let mut total: u64 = 1; let mut i: u64 = 0; \
+             while true { i += 1; match i { 1 => { total = apply(Op::Add, total, 2); } \
+             2 => { total = apply(Op::Mul, total, 5); } 3 => { continue; } _ => { break; } } } \
+             exit(total + i);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("__t_Op __v___match = __v_op;\nif (__v___match==__V_Op_Add) {"));
+        assert!(out.contains("u64 __v___match = __v_i;\nif (__v___match==1) {"));
+
+        // `break` and `continue` in an arm leave or restart the loop
+        if let Some(output) = run("match_statements", &out) {
+            assert_eq!(output.status.code(), Some(19));
+        }
+    }
+
+    #[test]
+    fn match_patterns() {
+        let out = compile(
+            "fn sign(n: i8) -> u8 { match n { -128..0 => { return 0; } 0 => { return 1; } 1..=127 => { return 2; } } return 3; } \
+             fn bit(b: bool) -> u8 { match b { true => { return 1; } false => { return 0; } } return 2; } \
+             println sign(-128); println sign(0); println sign(127); println bit(3 > 2); println bit(false); exit(0);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("if ((__v___match>=((i8)(-128)))&&(__v___match<0)) {"));
+        assert!(out.contains("bool __v___match = __v_b;\nif (__v___match==1) {"));
+
+        if let Some(output) = run("match_patterns", &out) {
+            assert_eq!(output.stdout, b"0\n1\n2\n1\n0\n");
+        }
+    }
+
+    #[test]
+    fn arrays() {
+        let out = compile(
+            "let primes: [u8; 4] = [2, 3, 5, 7]; let flags = [true, false]; let mut sum: u64 = 0; \
+             for i in 0..4 { sum += primes[i]; } \
+             exit(sum * flags[0] as u64 + (primes[3 - 1] * flags[1] as u8) as u64);",
+            CBackendOptions::default(),
+        );
+        assert!(out.contains("u8 __v_primes[4] = {2, 3, 5, 7};\nbool __v_flags[2] = {1, 0};\n"));
+        assert!(out.contains(
+            "exit((__v_sum*((u64)__v_flags[0]))+((u64)((u8)(__v_primes[3-1]*((u8)__v_flags[1])))));"
+        ));
+
+        if let Some(output) = run("arrays", &out) {
+            assert_eq!(output.status.code(), Some(17));
+        }
     }
 }
@@ -1,14 +1,33 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+
+use crate::cbackend;
+
+/// Which sanitizers the final executable is instrumented with
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum SanitizeMode {
+    Address,
+    Undefined,
+    AddressAndUndefined,
+}
+
+/// How the intermediate dumps are written
+#[derive(Debug, PartialEq, Eq, Clone, Copy, ValueEnum)]
+pub enum DumpFormat {
+    /// Rust's pretty-printed `Debug` output
+    Debug,
+    /// JSON for consumption by external tools
+    Json,
+}
 
 /// Compiler / interpreter for the ghetto-llvm language
 #[derive(Debug, PartialEq, Eq, Parser)]
 pub struct Config {
     /// The name of the source code file
-    #[arg(short)]
-    pub input_file_name: String,
+    #[arg(short, required_unless_present = "lsp")]
+    pub input_file_name: Option<String>,
 
     /// Output executable name
-    #[arg(short, long = "out", default_value_t = String::from("main"))]
+    #[arg(short = 'o', long = "out", default_value_t = default_output_name())]
     pub output_exe_name: String,
 
     /// Dump ast to file
@@ -19,6 +38,18 @@ pub struct Config {
     #[arg(long = "ast_out", default_value_t = String::from("out.ghl_ast"))]
     pub ast_out_name: String,
 
+    /// Format of the AST dump
+    #[arg(long = "ast_format", value_enum, default_value_t = DumpFormat::Debug)]
+    pub ast_format: DumpFormat,
+
+    /// Dump a Graphviz graph of the AST to file
+    #[arg(long = "dump_ast_dot")]
+    pub dump_ast_dot: bool,
+
+    /// File name to which the AST graph should be dumped
+    #[arg(long = "ast_dot_out", default_value_t = String::from("out.dot"))]
+    pub ast_dot_out_name: String,
+
     /// Dump tokens to file
     #[arg(long)]
     pub dump_tokens: bool,
@@ -27,6 +58,10 @@ pub struct Config {
     #[arg(long = "tokens_out", default_value_t = String::from("out.ghl_tokens"))]
     pub tokens_out_name: String,
 
+    /// Format of the token dump
+    #[arg(long = "dump_format", value_enum, default_value_t = DumpFormat::Debug)]
+    pub dump_format: DumpFormat,
+
     /// Dump c code to file
     #[arg(long)]
     pub dump_c: bool,
@@ -34,4 +69,282 @@ pub struct Config {
     /// File name to which the C code should be dumped
     #[arg(long = "c_out", default_value_t = String::from("out.c"))]
     pub c_out_name: String,
+
+    /// Always run every compiler phase instead of reusing cached output
+    #[arg(long)]
+    pub no_cache: bool,
+
+    /// Run as a language server over stdin/stdout instead of compiling
+    #[arg(long)]
+    pub lsp: bool,
+
+    /// Print the input file with canonical formatting to stdout instead of compiling it
+    #[arg(long)]
+    pub format_src: bool,
+
+    /// Link against a library, including its headers when it is a known one (math, pthread,
+    /// ssl). Can be given multiple times
+    #[arg(short, long = "link-library")]
+    pub link_libraries: Vec<String>,
+
+    /// Abort the compiled program when integer arithmetic overflows
+    #[arg(long)]
+    pub overflow_check: bool,
+
+    /// Time every function call of the compiled program and print the number of calls and the
+    /// time spent in each function to stderr when it exits
+    #[arg(long)]
+    pub profile: bool,
+
+    /// Build a test runner that runs the `#[test]` functions instead of the program
+    #[arg(long)]
+    pub test: bool,
+
+    /// Instrument the executable with AddressSanitizer and/or UndefinedBehaviorSanitizer
+    #[arg(long, value_enum)]
+    pub sanitize: Option<SanitizeMode>,
+
+    /// Build a shared library of position-independent code instead of an executable
+    #[arg(long)]
+    pub pic: bool,
+
+    /// Write a Makefile rule listing the source files the output depends on
+    #[arg(long = "dependency-file")]
+    pub dependency_file: Option<String>,
+}
+
+/// `a.out`, or `a.exe` on platforms where executables need an extension
+fn default_output_name() -> String {
+    match std::env::consts::EXE_EXTENSION {
+        "" => "a.out".to_string(),
+        extension => format!("a.{extension}"),
+    }
+}
+
+impl Config {
+    pub fn backend_options(&self) -> cbackend::CBackendOptions {
+        let mut extra_headers: Vec<String> = vec![];
+        for library in &self.link_libraries {
+            for header in cbackend::link_library(library).0 {
+                if !extra_headers.iter().any(|h| h == header) {
+                    extra_headers.push(header.to_string());
+                }
+            }
+        }
+        cbackend::CBackendOptions {
+            extra_headers,
+            overflow_check: self.overflow_check,
+            profile: self.profile,
+            test: self.test,
+            pic: self.pic,
+        }
+    }
+
+    /// Flags passed to the C compiler when linking the final executable
+    pub fn link_flags(&self) -> Vec<String> {
+        self.link_libraries
+            .iter()
+            .flat_map(|library| cbackend::link_library(library).1)
+            .collect()
+    }
+
+    /// The file the C compiler writes, which gets the platform's shared library extension
+    /// with `--pic` and the executable extension otherwise, if the platform has one
+    pub fn output_file_name(&self) -> String {
+        let extension = match std::env::consts::OS {
+            _ if !self.pic => std::env::consts::EXE_EXTENSION,
+            "macos" => "dylib",
+            "windows" => "dll",
+            _ => "so",
+        };
+        let path = std::path::Path::new(&self.output_exe_name);
+        if extension.is_empty() || path.extension().is_some_and(|e| e == extension) {
+            return self.output_exe_name.clone();
+        }
+        format!("{}.{extension}", self.output_exe_name)
+    }
+
+    /// The Makefile rule written with `--dependency-file`. Every program is a single file for
+    /// now, so the input is the only prerequisite
+    pub fn dependency_rule(&self) -> String {
+        // Make splits prerequisites on spaces
+        let escape = |path: &str| path.replace(' ', "\\ ");
+        let input = self.input_file_name.as_deref().unwrap_or_default();
+        format!("{}: {}\n", escape(&self.output_file_name()), escape(input))
+    }
+
+    /// Every flag passed to the C compiler besides the input and output file names
+    pub fn c_compiler_flags(&self) -> Vec<String> {
+        let mut flags = vec![];
+        match self.sanitize {
+            Some(SanitizeMode::Address) => {
+                flags.extend(["-fsanitize=address", "-fno-omit-frame-pointer"]);
+            }
+            Some(SanitizeMode::Undefined) => flags.push("-fsanitize=undefined"),
+            Some(SanitizeMode::AddressAndUndefined) => flags.extend([
+                "-fsanitize=address",
+                "-fsanitize=undefined",
+                "-fno-omit-frame-pointer",
+            ]),
+            None => {}
+        }
+        if self.pic {
+            flags.extend(["-fPIC", "-shared"]);
+        }
+        let mut flags: Vec<String> = flags.into_iter().map(String::from).collect();
+        flags.extend(self.link_flags());
+        flags
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, DumpFormat};
+    use clap::Parser;
+
+    #[test]
+    fn link_library_math() {
+        let config = Config::try_parse_from([
+            "ghetto-llvm",
+            "-i",
+            "main.ghl",
+            "--link-library",
+            "math",
+            "-l",
+            "z",
+        ])
+        .unwrap();
+        assert_eq!(
+            config.backend_options().extra_headers,
+            vec!["math.h".to_string()]
+        );
+        assert_eq!(
+            config.link_flags(),
+            vec!["-lm".to_string(), "-lz".to_string()]
+        );
+    }
+
+    #[test]
+    fn sanitize_address() {
+        let config =
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "--sanitize", "address"])
+                .unwrap();
+        let flags = config.c_compiler_flags();
+        assert!(flags.iter().any(|f| f == "-fsanitize=address"));
+        assert!(flags.iter().any(|f| f == "-fno-omit-frame-pointer"));
+        assert!(!flags.iter().any(|f| f == "-fsanitize=undefined"));
+    }
+
+    #[test]
+    fn pic_builds_shared_library() {
+        let config =
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "-o", "main", "--pic"])
+                .unwrap();
+        assert!(config
+            .c_compiler_flags()
+            .windows(2)
+            .any(|flags| flags == ["-fPIC", "-shared"]));
+        assert!(config.backend_options().pic);
+        assert_ne!(config.output_file_name(), "main");
+        assert!(config.output_file_name().starts_with("main."));
+
+        let pic = |out: &str| {
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "-o", out, "--pic"])
+                .unwrap()
+                .output_file_name()
+        };
+        let extension = &config.output_file_name()["main.".len()..];
+        assert_eq!(pic("libfoo.v2"), format!("libfoo.v2.{extension}"));
+        assert_eq!(
+            pic(&format!("libfoo.{extension}")),
+            format!("libfoo.{extension}")
+        );
+    }
+
+    #[test]
+    fn profile() {
+        let config = Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl"]).unwrap();
+        assert!(!config.backend_options().profile);
+        let config =
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "--profile"]).unwrap();
+        assert!(config.backend_options().profile);
+    }
+
+    #[test]
+    fn test_runner() {
+        let config = Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl"]).unwrap();
+        assert!(!config.backend_options().test);
+        let config = Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "--test"]).unwrap();
+        assert!(config.backend_options().test);
+    }
+
+    #[test]
+    fn dependency_rule() {
+        let config = Config::try_parse_from([
+            "ghetto-llvm",
+            "-i",
+            "my program.ghl",
+            "-o",
+            "prog",
+            "--dependency-file",
+            "prog.d",
+        ])
+        .unwrap();
+        assert_eq!(config.dependency_file.as_deref(), Some("prog.d"));
+        assert_eq!(config.dependency_rule(), "prog: my\\ program.ghl\n");
+    }
+
+    #[test]
+    fn output_name() {
+        let config = Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl"]).unwrap();
+        assert_eq!(config.output_exe_name, super::default_output_name());
+        #[cfg(not(windows))]
+        assert_eq!(config.output_exe_name, "a.out");
+
+        let config =
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "-o", "my_program"]).unwrap();
+        assert_eq!(config.output_exe_name, "my_program");
+        assert_eq!(
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "--out", "my_program"])
+                .unwrap(),
+            config
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_exe_extension() {
+        let config =
+            Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl", "-o", "my_program"]).unwrap();
+        assert_eq!(config.output_file_name(), "my_program.exe");
+        let config = Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl"]).unwrap();
+        assert_eq!(config.output_file_name(), "a.exe");
+    }
+
+    #[test]
+    fn dump_format() {
+        let config = Config::try_parse_from(["ghetto-llvm", "-i", "main.ghl"]).unwrap();
+        assert_eq!(config.dump_format, DumpFormat::Debug);
+        let config = Config::try_parse_from([
+            "ghetto-llvm",
+            "-i",
+            "main.ghl",
+            "--dump-tokens",
+            "--dump_format",
+            "json",
+        ])
+        .unwrap();
+        assert_eq!(config.dump_format, DumpFormat::Json);
+        assert_eq!(config.ast_format, DumpFormat::Debug);
+        let config = Config::try_parse_from([
+            "ghetto-llvm",
+            "-i",
+            "main.ghl",
+            "--dump-ast",
+            "--ast_format",
+            "json",
+        ])
+        .unwrap();
+        assert_eq!(config.ast_format, DumpFormat::Json);
+    }
 }
@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::{
+    ast::{AstExpression, NodeSpan, UnaryOp},
+    symbol::Symbol,
+    tokenizer::{BinaryOp, NumberTypeFlag},
+};
+
+/// The result of evaluating an expression at compile time
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Value {
+    Integer(u64),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ConstEvalError {
+    #[error("`{name}` is not a constant")]
+    NotConstant { name: Symbol },
+    #[error("`{name}` can't be called in a constant expression")]
+    Call { name: Symbol },
+    #[error("casting to `{t}` can't be done in a constant expression")]
+    UnsupportedCast { t: Symbol },
+    #[error("`{raw}` is not a valid integer literal")]
+    InvalidLiteral { raw: String },
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("the value doesn't fit into `{t}`")]
+    Overflow { t: Symbol },
+    #[error("shifting by {amount} bits is out of range")]
+    ShiftOverflow { amount: u64 },
+    #[error("operator {operator:?} can't be used in a constant expression")]
+    UnsupportedOperator { operator: BinaryOp },
+    #[error("structs can't be used in a constant expression")]
+    Struct,
+    #[error("`{name}::{variant}` can't be used in a constant expression")]
+    Variant { name: Symbol, variant: Symbol },
+    #[error("arrays can't be used in a constant expression")]
+    Array,
+}
+
+/// Like in C, comparisons and logical operators evaluate to `1` or `0`
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl Value {
+    /// The literal for the value, placed at `span`
+    pub fn into_expression(self, span: NodeSpan) -> AstExpression {
+        match self {
+            Value::Integer(value) => AstExpression::Number {
+                raw: value.to_string(),
+                flags: vec![],
+                suffix: None,
+                span,
+            },
+        }
+    }
+}
+
+/// The value of an integer literal as the tokenizer produced it, `None` for floats and
+/// literals that don't fit into 64 bits
+pub fn integer_literal(raw: &str, flags: &[NumberTypeFlag]) -> Option<u64> {
+    match flags {
+        [] => raw.parse().ok(),
+        [NumberTypeFlag::Hexadecimal] => u64::from_str_radix(&raw[2..], 16).ok(),
+        [NumberTypeFlag::Binary] => u64::from_str_radix(&raw[2..], 2).ok(),
+        [NumberTypeFlag::Octal] => u64::from_str_radix(&raw[2..], 8).ok(),
+        _ => None,
+    }
+}
+
+/// Evaluates `expr` at compile time as a `t`. Identifiers are only allowed if they name one of
+/// `consts`, which holds the type and value of each.
+///
+/// Integer arithmetic happens in 64 bits, signed ones if `t` is signed, and overflowing them is an
+/// error instead of wrapping around. Compared values are computed in the type of the values
+/// themselves. Only casts truncate, like they do in C
+pub fn const_eval(
+    expr: &AstExpression,
+    t: Symbol,
+    consts: &HashMap<Symbol, (Symbol, Value)>,
+) -> Result<Value, ConstEvalError> {
+    let signed = is_signed(t);
+    let overflow = || ConstEvalError::Overflow { t };
+    match expr {
+        AstExpression::Number {
+            raw,
+            flags,
+            suffix: _,
+            span: _,
+        } => match integer_literal(raw, flags) {
+            Some(value) if signed && i64::try_from(value).is_err() => Err(overflow()),
+            Some(value) => Ok(Value::Integer(value)),
+            None => Err(ConstEvalError::InvalidLiteral { raw: raw.clone() }),
+        },
+        AstExpression::Bool { value, span: _ } => Ok(Value::from(*value)),
+        AstExpression::Identifier { name, span: _ } => consts
+            .get(name)
+            .map(|(_, value)| *value)
+            .ok_or(ConstEvalError::NotConstant { name: *name }),
+        AstExpression::Call {
+            name,
+            args: _,
+            span: _,
+        } => Err(ConstEvalError::Call { name: *name }),
+        AstExpression::MethodCall {
+            value: _,
+            t: _,
+            method,
+            args: _,
+            span: _,
+        } => Err(ConstEvalError::Call { name: *method }),
+        AstExpression::Cast {
+            value,
+            t: target,
+            span: _,
+        } => {
+            let Value::Integer(value) = const_eval(value, operand_type(value, consts), consts)?;
+            cast(value, *target)
+        }
+        AstExpression::StructLiteral { .. }
+        | AstExpression::FieldAccess { .. }
+        | AstExpression::Range { .. } => Err(ConstEvalError::Struct),
+        AstExpression::Variant {
+            name,
+            variant,
+            span: _,
+        } => Err(ConstEvalError::Variant {
+            name: *name,
+            variant: *variant,
+        }),
+        AstExpression::ArrayLiteral { .. } | AstExpression::Index { .. } => {
+            Err(ConstEvalError::Array)
+        }
+        AstExpression::UnaryOperation {
+            operator: UnaryOp::Negate,
+            operand,
+            span: _,
+        } => {
+            let Value::Integer(value) = const_eval(operand, t, consts)?;
+            checked(
+                value,
+                0,
+                signed,
+                |value, _| value.checked_neg(),
+                |value, _| value.checked_neg(),
+            )
+            .map(Value::Integer)
+            .ok_or_else(overflow)
+        }
+        AstExpression::UnaryOperation {
+            operator: UnaryOp::Not,
+            operand,
+            span: _,
+        } => {
+            let Value::Integer(value) = const_eval(operand, operand_type(operand, consts), consts)?;
+            Ok(Value::from(value == 0))
+        }
+        AstExpression::UnaryOperation {
+            operator: UnaryOp::BitNot,
+            operand,
+            span: _,
+        } => {
+            let Value::Integer(value) = const_eval(operand, t, consts)?;
+            Ok(Value::Integer(!value))
+        }
+        AstExpression::BinaryOperation {
+            left,
+            operator,
+            right,
+            span: _,
+        } => {
+            // The operands of comparisons and logical operators don't have the type of the result
+            let operands = if is_comparison(operator) {
+                known_type(left, consts).unwrap_or_else(|| operand_type(right, consts))
+            } else {
+                t
+            };
+            let Value::Integer(l) = const_eval(left, operands, consts)?;
+            // Like in C the right side is only evaluated if it decides the result
+            match operator {
+                BinaryOp::And if l == 0 => return Ok(Value::from(false)),
+                BinaryOp::Or if l != 0 => return Ok(Value::from(true)),
+                _ => {}
+            }
+            let Value::Integer(r) = const_eval(right, operands, consts)?;
+            let signed = if is_comparison(operator) {
+                is_signed(operands)
+            } else {
+                signed
+            };
+            match operator {
+                BinaryOp::Plus => checked(l, r, signed, i64::checked_add, u64::checked_add)
+                    .map(Value::Integer)
+                    .ok_or_else(overflow),
+                BinaryOp::Minus => checked(l, r, signed, i64::checked_sub, u64::checked_sub)
+                    .map(Value::Integer)
+                    .ok_or_else(overflow),
+                BinaryOp::Star => checked(l, r, signed, i64::checked_mul, u64::checked_mul)
+                    .map(Value::Integer)
+                    .ok_or_else(overflow),
+                BinaryOp::Slash | BinaryOp::Percent if r == 0 => {
+                    Err(ConstEvalError::DivisionByZero)
+                }
+                // Only `MIN / -1` overflows
+                BinaryOp::Slash => checked(l, r, signed, i64::checked_div, u64::checked_div)
+                    .map(Value::Integer)
+                    .ok_or_else(overflow),
+                BinaryOp::Percent => checked(l, r, signed, i64::checked_rem, u64::checked_rem)
+                    .map(Value::Integer)
+                    .ok_or_else(overflow),
+                BinaryOp::DoubleEqual => Ok(Value::from(l == r)),
+                BinaryOp::NotEqual => Ok(Value::from(l != r)),
+                BinaryOp::Less if signed => Ok(Value::from((l as i64) < r as i64)),
+                BinaryOp::Greater if signed => Ok(Value::from(l as i64 > r as i64)),
+                BinaryOp::LessEqual if signed => Ok(Value::from(l as i64 <= r as i64)),
+                BinaryOp::GreaterEqual if signed => Ok(Value::from(l as i64 >= r as i64)),
+                BinaryOp::Less => Ok(Value::from(l < r)),
+                BinaryOp::Greater => Ok(Value::from(l > r)),
+                BinaryOp::LessEqual => Ok(Value::from(l <= r)),
+                BinaryOp::GreaterEqual => Ok(Value::from(l >= r)),
+                BinaryOp::And | BinaryOp::Or => Ok(Value::from(r != 0)),
+                BinaryOp::BitAnd => Ok(Value::Integer(l & r)),
+                BinaryOp::BitOr => Ok(Value::Integer(l | r)),
+                BinaryOp::BitXor => Ok(Value::Integer(l ^ r)),
+                // Shifting by the width of the type or more is undefined behavior in C
+                BinaryOp::ShiftLeft => {
+                    let amount = u32::try_from(r)
+                        .ok()
+                        .filter(|amount| *amount < u64::BITS)
+                        .ok_or(ConstEvalError::ShiftOverflow { amount: r })?;
+                    // It overflows if shifting back doesn't give the same value
+                    checked(
+                        l,
+                        r,
+                        signed,
+                        |l, _| Some(l << amount).filter(|value| value >> amount == l),
+                        |l, _| Some(l << amount).filter(|value| value >> amount == l),
+                    )
+                    .map(Value::Integer)
+                    .ok_or_else(overflow)
+                }
+                BinaryOp::ShiftRight if signed => u32::try_from(r)
+                    .ok()
+                    .and_then(|r| (l as i64).checked_shr(r))
+                    .map(|value| Value::Integer(value as u64))
+                    .ok_or(ConstEvalError::ShiftOverflow { amount: r }),
+                BinaryOp::ShiftRight => u32::try_from(r)
+                    .ok()
+                    .and_then(|r| l.checked_shr(r))
+                    .map(Value::Integer)
+                    .ok_or(ConstEvalError::ShiftOverflow { amount: r }),
+                BinaryOp::SingleEqual => Err(ConstEvalError::UnsupportedOperator {
+                    operator: operator.clone(),
+                }),
+            }
+        }
+    }
+}
+
+/// Applies `if_signed` to the operands if they are signed and `if_unsigned` otherwise, `None`
+/// if the result doesn't fit into 64 bits
+fn checked(
+    l: u64,
+    r: u64,
+    signed: bool,
+    if_signed: impl Fn(i64, i64) -> Option<i64>,
+    if_unsigned: impl Fn(u64, u64) -> Option<u64>,
+) -> Option<u64> {
+    if signed {
+        if_signed(l as i64, r as i64).map(|value| value as u64)
+    } else {
+        if_unsigned(l, r)
+    }
+}
+
+/// Whether `t` is a signed integer type
+pub fn is_signed(t: Symbol) -> bool {
+    matches!(t.as_str(), "i8" | "i16" | "i32" | "i64")
+}
+
+/// Whether the operands of `operator` have a type of their own instead of that of its result
+fn is_comparison(operator: &BinaryOp) -> bool {
+    matches!(
+        operator,
+        BinaryOp::DoubleEqual
+            | BinaryOp::NotEqual
+            | BinaryOp::Less
+            | BinaryOp::Greater
+            | BinaryOp::LessEqual
+            | BinaryOp::GreaterEqual
+            | BinaryOp::And
+            | BinaryOp::Or
+    )
+}
+
+/// The type `expr` is computed in when it isn't the one of whatever uses it, like an operand of a
+/// comparison. Without one of its own that's a signed one, like C's `int` for literals
+fn operand_type(expr: &AstExpression, consts: &HashMap<Symbol, (Symbol, Value)>) -> Symbol {
+    known_type(expr, consts).unwrap_or_else(|| Symbol::intern("i64"))
+}
+
+/// The integer type of `expr` if its suffixed literals, casts and constants give it one
+fn known_type(expr: &AstExpression, consts: &HashMap<Symbol, (Symbol, Value)>) -> Option<Symbol> {
+    let t = match expr {
+        AstExpression::Number { suffix, .. } => suffix.as_deref().map(Symbol::intern),
+        AstExpression::Identifier { name, span: _ } => consts.get(name).map(|(t, _)| *t),
+        AstExpression::Cast {
+            value: _,
+            t,
+            span: _,
+        } => Some(*t),
+        AstExpression::UnaryOperation {
+            operator: UnaryOp::Negate | UnaryOp::BitNot,
+            operand,
+            span: _,
+        } => known_type(operand, consts),
+        AstExpression::BinaryOperation {
+            left,
+            operator,
+            right,
+            span: _,
+        } if !is_comparison(operator) => {
+            known_type(left, consts).or_else(|| known_type(right, consts))
+        }
+        _ => None,
+    };
+    t.filter(|t| t.as_str() != "bool")
+}
+
+/// Converts `value` like a C cast to `t` does. Integer types keep the low bits they have room
+/// for, signed ones extended to 64 bits again
+pub fn cast(value: u64, t: Symbol) -> Result<Value, ConstEvalError> {
+    match t.as_str() {
+        "bool" => Ok(Value::from(value != 0)),
+        "u8" => Ok(Value::Integer(u64::from(value as u8))),
+        "u16" => Ok(Value::Integer(u64::from(value as u16))),
+        "u32" => Ok(Value::Integer(u64::from(value as u32))),
+        "i8" => Ok(Value::Integer(value as i8 as u64)),
+        "i16" => Ok(Value::Integer(value as i16 as u64)),
+        "i32" => Ok(Value::Integer(value as i32 as u64)),
+        "u64" | "i64" => Ok(Value::Integer(value)),
+        _ => Err(ConstEvalError::UnsupportedCast { t }),
+    }
+}
+
+/// Stores `value`, computed by [`const_eval`] as a `t`, as a `t`. Unlike a cast an error if it
+/// doesn't fit
+pub fn convert(value: u64, t: Symbol) -> Result<Value, ConstEvalError> {
+    let fits = match t.as_str() {
+        "bool" => value <= 1,
+        "u8" => u8::try_from(value).is_ok(),
+        "u16" => u16::try_from(value).is_ok(),
+        "u32" => u32::try_from(value).is_ok(),
+        "i8" => i8::try_from(value as i64).is_ok(),
+        "i16" => i16::try_from(value as i64).is_ok(),
+        "i32" => i32::try_from(value as i64).is_ok(),
+        "u64" | "i64" => true,
+        _ => return Err(ConstEvalError::UnsupportedCast { t }),
+    };
+    if fits {
+        Ok(Value::Integer(value))
+    } else {
+        Err(ConstEvalError::Overflow { t })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{const_eval, convert, ConstEvalError, Value};
+    use crate::{ast, symbol::Symbol, tokenizer};
+
+    fn u64() -> Symbol {
+        Symbol::intern("u64")
+    }
+
+    fn expression(src: &str) -> ast::AstExpression {
+        let src = format!("let SIZE: u64 = 4; let x: u64 = 0; let value = {src};");
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::const".to_string())
+            .tokenize()
+            .unwrap();
+        match ast::AstParser::new(tokens).parse().unwrap().pop().unwrap() {
+            ast::AstStatement::Let { value, .. } => value,
+            other => panic!("expected let, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn number() {
+        assert_eq!(
+            const_eval(&expression("69"), u64(), &HashMap::new()),
+            Ok(Value::Integer(69))
+        );
+    }
+
+    #[test]
+    fn binary_operation() {
+        assert_eq!(
+            const_eval(
+                &expression("(1 + 2) * 3 - 10"),
+                Symbol::intern("i64"),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(u64::MAX))
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let eval =
+            |src: &str, t: &str| const_eval(&expression(src), Symbol::intern(t), &HashMap::new());
+        let overflow = |t| {
+            Err(ConstEvalError::Overflow {
+                t: Symbol::intern(t),
+            })
+        };
+        assert_eq!(eval("18446744073709551615 + 1", "u64"), overflow("u64"));
+        assert_eq!(eval("1 - 2", "u64"), overflow("u64"));
+        assert_eq!(eval("-1", "u8"), overflow("u8"));
+        assert_eq!(eval("4294967296 * 4294967296", "u64"), overflow("u64"));
+        assert_eq!(eval("3 << 63", "u64"), overflow("u64"));
+        assert_eq!(eval("9223372036854775807 + 1", "i64"), overflow("i64"));
+        assert_eq!(eval("18446744073709551615", "i64"), overflow("i64"));
+        assert_eq!(
+            eval("(-9223372036854775807 - 1) / -1", "i64"),
+            overflow("i64")
+        );
+        assert_eq!(eval("1 << 63", "u64"), Ok(Value::Integer(1 << 63)));
+
+        assert_eq!(convert(256, Symbol::intern("u8")), overflow("u8"));
+        assert_eq!(
+            convert(-129i64 as u64, Symbol::intern("i8")),
+            overflow("i8")
+        );
+        assert_eq!(
+            convert(-128i64 as u64, Symbol::intern("i8")),
+            Ok(Value::Integer(-128i64 as u64))
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        let consts = HashMap::from([(Symbol::intern("SIZE"), (u64(), Value::Integer(4)))]);
+        assert_eq!(
+            const_eval(&expression("SIZE * 2"), u64(), &consts),
+            Ok(Value::Integer(8))
+        );
+        assert_eq!(
+            const_eval(&expression("SIZE * x"), u64(), &consts),
+            Err(ConstEvalError::NotConstant {
+                name: Symbol::intern("x")
+            })
+        );
+    }
+
+    #[test]
+    fn prefixed_literals() {
+        assert_eq!(
+            const_eval(&expression("0xFF + 1"), u64(), &HashMap::new()),
+            Ok(Value::Integer(256))
+        );
+        assert_eq!(
+            const_eval(&expression("0b101 * 2"), u64(), &HashMap::new()),
+            Ok(Value::Integer(10))
+        );
+        assert_eq!(
+            const_eval(&expression("0o17"), u64(), &HashMap::new()),
+            Ok(Value::Integer(15))
+        );
+        assert_eq!(
+            const_eval(
+                &expression("-5 + 10"),
+                Symbol::intern("i64"),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(5))
+        );
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(
+            const_eval(&expression("7 / 2"), u64(), &HashMap::new()),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            const_eval(&expression("7 % 4"), u64(), &HashMap::new()),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            const_eval(
+                &expression("SIZE / (x * 2)"),
+                u64(),
+                &HashMap::from([
+                    (Symbol::intern("SIZE"), (u64(), Value::Integer(1))),
+                    (Symbol::intern("x"), (u64(), Value::Integer(0))),
+                ])
+            ),
+            Err(ConstEvalError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn signed_operations() {
+        let i64 = Symbol::intern("i64");
+        let eval = |src: &str, t| const_eval(&expression(src), t, &HashMap::new());
+        assert_eq!(eval("-7 / 2", i64), Ok(Value::Integer(-3i64 as u64)));
+        assert_eq!(eval("-7 % 2", i64), Ok(Value::Integer(-1i64 as u64)));
+        assert_eq!(eval("-8 >> 1", i64), Ok(Value::Integer(-4i64 as u64)));
+        assert_eq!(
+            eval("0xFFFFFFFFFFFFFFF8 >> 1", u64()),
+            Ok(Value::Integer(u64::MAX / 2 - 3))
+        );
+        // Compared in their own type, whatever the result is stored in
+        assert_eq!(
+            eval("-1 < 0", Symbol::intern("bool")),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            eval("(-1 < 0) as u64 + (-1 as u8 < 0) as u64", u64()),
+            Ok(Value::Integer(1))
+        );
+        let consts = HashMap::from([(Symbol::intern("SIZE"), (u64(), Value::Integer(u64::MAX)))]);
+        assert_eq!(
+            const_eval(&expression("SIZE > 0"), Symbol::intern("bool"), &consts),
+            Ok(Value::Integer(1))
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(
+            const_eval(
+                &expression("(1 < 2) as u64 + (2 <= 2) as u64 + (3 == 4) as u64 + (3 != 4) as u64"),
+                u64(),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(3))
+        );
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        assert_eq!(
+            const_eval(
+                &expression("(0b1100 & 0b1010 | 1 ^ 3) << 4 >> 1"),
+                u64(),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(0b1010 << 3))
+        );
+        assert_eq!(
+            const_eval(&expression("~0"), u64(), &HashMap::new()),
+            Ok(Value::Integer(u64::MAX))
+        );
+        assert_eq!(
+            const_eval(&expression("1 << 64"), u64(), &HashMap::new()),
+            Err(ConstEvalError::ShiftOverflow { amount: 64 })
+        );
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(
+            const_eval(
+                &expression(
+                    "!(0 != 0) as u64 + !(7 != 0) as u64 + (2 != 0 && 3 != 0) as u64 \
+                     + (0 != 0 || 0 != 0) as u64"
+                ),
+                u64(),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(2))
+        );
+        let consts = HashMap::from([(Symbol::intern("x"), (u64(), Value::Integer(0)))]);
+        assert_eq!(
+            const_eval(&expression("x != 0 && 1 / x != 0"), u64(), &consts),
+            Ok(Value::Integer(0))
+        );
+        assert_eq!(
+            const_eval(&expression("x == 0 || 1 / x != 0"), u64(), &consts),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            const_eval(&expression("x == 0 && 1 / x != 0"), u64(), &consts),
+            Err(ConstEvalError::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn casts() {
+        assert_eq!(
+            const_eval(
+                &expression("300 as u8 as u64 + (2 < 3) as u64"),
+                u64(),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(45))
+        );
+        assert_eq!(
+            const_eval(&expression("255 as i8 as u64"), u64(), &HashMap::new()),
+            Ok(Value::Integer(u64::MAX))
+        );
+        assert_eq!(
+            const_eval(&expression("7 as bool"), u64(), &HashMap::new()),
+            Ok(Value::Integer(1))
+        );
+        assert_eq!(
+            const_eval(&expression("1 as f64"), u64(), &HashMap::new()),
+            Err(ConstEvalError::UnsupportedCast {
+                t: Symbol::intern("f64")
+            })
+        );
+    }
+
+    #[test]
+    fn bool_literals() {
+        assert_eq!(
+            const_eval(
+                &expression("true as u64 + true as u64 + false as u64"),
+                u64(),
+                &HashMap::new()
+            ),
+            Ok(Value::Integer(2))
+        );
+        assert_eq!(
+            const_eval(&expression("!true || false"), u64(), &HashMap::new()),
+            Ok(Value::Integer(0))
+        );
+    }
+}
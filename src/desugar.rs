@@ -0,0 +1,508 @@
+use crate::{
+    ast::{self, AstExpression, AstProgram, AstStatement, AstVisitorMut, MatchArm, NodeSpan},
+    ir_passes,
+    symbol::Symbol,
+    tokenizer::BinaryOp,
+};
+
+/// Rewrites the statements that are shorthands for others, so generating the IR only has to know
+/// about the core ones. Runs after parsing, everything it produces has already been checked.
+/// What a statement becomes has the span of the statement. The methods of an `impl` become
+/// functions named [`ast::method_name`] and calling one passes the value it's called on first
+pub fn desugar(program: AstProgram) -> AstProgram {
+    let mut program = statements(program);
+    MethodCalls.visit_program_mut(&mut program);
+    program
+}
+
+fn statements(stmts: Vec<AstStatement>) -> Vec<AstStatement> {
+    stmts
+        .into_iter()
+        .flat_map(|stmt| match stmt {
+            AstStatement::Impl {
+                name: t,
+                methods,
+                span: _,
+            } => methods
+                .into_iter()
+                .map(|method| match method {
+                    AstStatement::Function {
+                        doc,
+                        attrs,
+                        name,
+                        params,
+                        return_type,
+                        body,
+                        span,
+                    } => statement(AstStatement::Function {
+                        doc,
+                        attrs,
+                        name: ast::method_name(t, name),
+                        params,
+                        return_type,
+                        body,
+                        span,
+                    }),
+                    _ => unreachable!("the parser only allows functions in an `impl`"),
+                })
+                .collect(),
+            stmt => vec![statement(stmt)],
+        })
+        .collect()
+}
+
+fn statement(stmt: AstStatement) -> AstStatement {
+    match stmt {
+        // `a += b;` is `a = a + b;`
+        AstStatement::CompoundAssign {
+            name,
+            t,
+            operator,
+            value,
+            span,
+        } => AstStatement::Assign {
+            name,
+            t,
+            value: AstExpression::BinaryOperation {
+                left: Box::new(AstExpression::Identifier { name, span }),
+                operator,
+                right: Box::new(value),
+                span,
+            },
+            span,
+        },
+        AstStatement::For {
+            name,
+            t,
+            range,
+            inclusive,
+            body,
+            span,
+        } => for_loop(name, t, range, inclusive, statements(body), span),
+        AstStatement::Match {
+            value,
+            t,
+            arms,
+            span,
+        } => match_statement(value, t, arms, span),
+        AstStatement::MethodCall {
+            value,
+            t,
+            method,
+            args,
+            span,
+        } => AstStatement::Call {
+            name: ast::method_name(t, method),
+            args: std::iter::once(value).chain(args).collect(),
+            span,
+        },
+        AstStatement::Function {
+            doc,
+            attrs,
+            name,
+            params,
+            return_type,
+            body,
+            span,
+        } => AstStatement::Function {
+            doc,
+            attrs,
+            name,
+            params,
+            return_type,
+            body: statements(body),
+            span,
+        },
+        AstStatement::If {
+            condition,
+            then,
+            otherwise,
+            span,
+        } => AstStatement::If {
+            condition,
+            then: statements(then),
+            otherwise: statements(otherwise),
+            span,
+        },
+        AstStatement::While {
+            condition,
+            body,
+            span,
+        } => AstStatement::While {
+            condition,
+            body: statements(body),
+            span,
+        },
+        AstStatement::Block { body, span } => AstStatement::Block {
+            body: statements(body),
+            span,
+        },
+        stmt @ (AstStatement::Let { .. }
+        | AstStatement::Const { .. }
+        | AstStatement::Struct { .. }
+        | AstStatement::Enum { .. }
+        | AstStatement::Exit { .. }
+        | AstStatement::Print { .. }
+        | AstStatement::AssertEq { .. }
+        | AstStatement::Call { .. }
+        | AstStatement::Assign { .. }
+        | AstStatement::Return { .. }
+        | AstStatement::Break { .. }
+        | AstStatement::Continue { .. }) => stmt,
+        AstStatement::Impl { .. } => unreachable!("the parser only allows `impl` at the top level"),
+    }
+}
+
+/// Turns every `value.method(args)` into a call of the function of the method
+struct MethodCalls;
+
+impl AstVisitorMut for MethodCalls {
+    fn visit_expression_mut(&mut self, expr: &mut AstExpression) {
+        let span = NodeSpan(expr.span());
+        let owned = std::mem::replace(expr, AstExpression::Bool { value: false, span });
+        *expr = ir_passes::rewrite(owned, &|expr| match expr {
+            AstExpression::MethodCall {
+                value,
+                t,
+                method,
+                args,
+                span,
+            } => AstExpression::Call {
+                name: ast::method_name(t, method),
+                args: std::iter::once(*value).chain(args).collect(),
+                span,
+            },
+            expr => expr,
+        });
+    }
+}
+
+/// `match value { p => { a } q => { b } _ => { c } }` becomes
+///
+/// ```text
+/// {
+///     let __match: t = value;
+///     if __match == p { a } else if __match == q { b } else { c }
+/// }
+/// ```
+///
+/// so `value` is computed once, and unlike in a C `switch`, `break` and `continue` in an arm
+/// still belong to the loop around the match. A nested match shadows `__match` in its own block.
+/// A range pattern `p..=q` is `__match >= p && __match <= q`
+fn match_statement(
+    value: AstExpression,
+    t: Symbol,
+    arms: Vec<MatchArm>,
+    span: NodeSpan,
+) -> AstStatement {
+    let name = Symbol::intern("__match");
+    let mut chain = vec![];
+    for arm in arms.into_iter().rev() {
+        let body = statements(arm.body);
+        chain = match arm.pattern {
+            Some(pattern) => vec![AstStatement::If {
+                condition: pattern_condition(name, pattern, span),
+                then: body,
+                otherwise: chain,
+                span,
+            }],
+            // Nothing after a `_` is ever reached
+            None => body,
+        };
+    }
+    let mut body = vec![AstStatement::Let {
+        value,
+        name,
+        t,
+        mutable: false,
+        span,
+    }];
+    body.extend(chain);
+    AstStatement::Block { body, span }
+}
+
+/// Whether the value of the variable `name` is one `pattern` matches
+fn pattern_condition(name: Symbol, pattern: AstExpression, span: NodeSpan) -> AstExpression {
+    let compare = |operator, value| AstExpression::BinaryOperation {
+        left: Box::new(AstExpression::Identifier { name, span }),
+        operator,
+        right: Box::new(value),
+        span,
+    };
+    match pattern {
+        AstExpression::Range {
+            start,
+            end,
+            inclusive,
+            span: _,
+        } => AstExpression::BinaryOperation {
+            left: Box::new(compare(BinaryOp::GreaterEqual, *start)),
+            operator: BinaryOp::And,
+            right: Box::new(compare(
+                if inclusive {
+                    BinaryOp::LessEqual
+                } else {
+                    BinaryOp::Less
+                },
+                *end,
+            )),
+            span,
+        },
+        pattern => compare(BinaryOp::DoubleEqual, pattern),
+    }
+}
+
+/// `for i: t in start..end { body }` becomes
+///
+/// ```text
+/// {
+///     let mut __next_i: t = start;
+///     let __end_i: t = end;
+///     while __next_i < __end_i {
+///         let i: t = __next_i;
+///         __next_i = __next_i + 1;
+///         body
+///     }
+/// }
+/// ```
+///
+/// Counting up before the body runs keeps `continue` from skipping it, and the counter the body
+/// sees is a copy, so assigning to it doesn't change how often the loop runs.
+/// `for i: t in start..=end { body }` decides whether there is another round before counting
+/// up, so ending at the largest `t` doesn't overflow it:
+///
+/// ```text
+/// {
+///     let mut __next_i: t = start;
+///     let __end_i: t = end;
+///     let mut __more_i: bool = __next_i <= __end_i;
+///     while __more_i {
+///         let i: t = __next_i;
+///         __more_i = __next_i < __end_i;
+///         if __more_i { __next_i = __next_i + 1; }
+///         body
+///     }
+/// }
+/// ```
+///
+/// A range that isn't written out in the loop is stored in `let __range_i: Range = range;`
+/// first, and the loop counts from `__range_i.start` to `__range_i.end`
+fn for_loop(
+    name: Symbol,
+    t: Symbol,
+    range: AstExpression,
+    inclusive: bool,
+    body: Vec<AstStatement>,
+    span: NodeSpan,
+) -> AstStatement {
+    let next = Symbol::intern(&format!("__next_{name}"));
+    let end_name = Symbol::intern(&format!("__end_{name}"));
+    let more = Symbol::intern(&format!("__more_{name}"));
+    let identifier = |name| Box::new(AstExpression::Identifier { name, span });
+    let compare = |operator| AstExpression::BinaryOperation {
+        left: identifier(next),
+        operator,
+        right: identifier(end_name),
+        span,
+    };
+
+    let mut block = vec![];
+    let (start, end) = match range {
+        AstExpression::Range {
+            start,
+            end,
+            inclusive: _,
+            span: _,
+        } => (*start, *end),
+        range => {
+            let range_name = Symbol::intern(&format!("__range_{name}"));
+            block.push(AstStatement::Let {
+                value: range,
+                name: range_name,
+                t: ast::range_type(inclusive),
+                mutable: false,
+                span,
+            });
+            let bound = |field: &str| AstExpression::FieldAccess {
+                value: identifier(range_name),
+                field: Symbol::intern(field),
+                span,
+            };
+            (bound("start"), bound("end"))
+        }
+    };
+    block.extend([
+        AstStatement::Let {
+            value: start,
+            name: next,
+            t,
+            mutable: true,
+            span,
+        },
+        AstStatement::Let {
+            value: end,
+            name: end_name,
+            t,
+            mutable: false,
+            span,
+        },
+    ]);
+
+    let count_up = AstStatement::Assign {
+        name: next,
+        t,
+        value: AstExpression::BinaryOperation {
+            left: identifier(next),
+            operator: BinaryOp::Plus,
+            right: Box::new(AstExpression::Number {
+                raw: "1".to_string(),
+                flags: vec![],
+                suffix: None,
+                span,
+            }),
+            span,
+        },
+        span,
+    };
+    let mut while_body = vec![AstStatement::Let {
+        value: AstExpression::Identifier { name: next, span },
+        name,
+        t,
+        mutable: false,
+        span,
+    }];
+    let condition = if inclusive {
+        let bool_type = Symbol::intern(ast::BOOL);
+        block.push(AstStatement::Let {
+            value: compare(BinaryOp::LessEqual),
+            name: more,
+            t: bool_type,
+            mutable: true,
+            span,
+        });
+        while_body.extend([
+            AstStatement::Assign {
+                name: more,
+                t: bool_type,
+                value: compare(BinaryOp::Less),
+                span,
+            },
+            AstStatement::If {
+                condition: AstExpression::Identifier { name: more, span },
+                then: vec![count_up],
+                otherwise: vec![],
+                span,
+            },
+        ]);
+        AstExpression::Identifier { name: more, span }
+    } else {
+        while_body.push(count_up);
+        compare(BinaryOp::Less)
+    };
+    while_body.extend(body);
+    block.push(AstStatement::While {
+        condition,
+        body: while_body,
+        span,
+    });
+    AstStatement::Block { body: block, span }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::desugar;
+    use crate::{ast, tokenizer};
+
+    fn parse(src: &str) -> ast::AstProgram {
+        let tokens = tokenizer::Tokenizer::new(src, "tests::desugar".to_string())
+            .tokenize()
+            .unwrap();
+        ast::AstParser::new(tokens).parse().unwrap()
+    }
+
+    #[test]
+    fn compound_assignments() {
+        assert_eq!(
+            desugar(parse(
+                "let mut a: u64 = 1; if a > 0 { a += 2; a /= a - 1; }"
+            )),
+            parse("let mut a: u64 = 1; if a > 0 { a = a + (2); a = a / (a - 1); }")
+        );
+    }
+
+    #[test]
+    fn for_loops() {
+        assert_eq!(
+            desugar(parse(
+                "fn f(n: u8) { for i: u8 in 1..n * 2 { for j in 0..i { println j; } } }"
+            )),
+            parse(
+                "fn f(n: u8) { { let mut __next_i: u8 = 1; let __end_i: u8 = n * 2; while __next_i < __end_i { \
+                 let i: u8 = __next_i; __next_i = __next_i + 1; \
+                 { let mut __next_j: u64 = 0; let __end_j: u64 = i; while __next_j < __end_j { \
+                 let j: u64 = __next_j; __next_j = __next_j + 1; println j; } } } } }"
+            )
+        );
+    }
+
+    #[test]
+    fn inclusive_for_loops() {
+        assert_eq!(
+            desugar(parse(
+                "let r = 1..=3; for i: u8 in 0..=255 { } for j in r { continue; }"
+            )),
+            parse(
+                "let r = 1..=3; \
+                 { let mut __next_i: u8 = 0; let __end_i: u8 = 255; let mut __more_i: bool = __next_i <= __end_i; \
+                 while __more_i { let i: u8 = __next_i; __more_i = __next_i < __end_i; \
+                 if __more_i { __next_i = __next_i + 1; } } } \
+                 { let __range_j: RangeInclusive = r; let mut __next_j: u64 = __range_j.start; \
+                 let __end_j: u64 = __range_j.end; let mut __more_j: bool = __next_j <= __end_j; \
+                 while __more_j { let j: u64 = __next_j; __more_j = __next_j < __end_j; \
+                 if __more_j { __next_j = __next_j + 1; } continue; } }"
+            )
+        );
+    }
+
+    #[test]
+    fn match_statements() {
+        assert_eq!(
+            desugar(parse(
+                "let mut n: u8 = 1; match n { 1 => { print 1; } 2 => {} _ => { match n { _ => { n += 1; } } } }"
+            )),
+            parse(
+                "let mut n: u8 = 1; { let __match: u8 = n; if __match == 1 { print 1; } \
+                 else if __match == 2 { } else { { let __match: u8 = n; n = n + (1); } } }"
+            )
+        );
+        assert_eq!(
+            desugar(parse(
+                "let n: i8 = 1; match n { -128..0 => { print 1; } 0..=9 => {} _ => {} }"
+            )),
+            parse(
+                "let n: i8 = 1; { let __match: i8 = n; if __match >= -128 && __match < 0 { print 1; } \
+                 else if __match >= 0 && __match <= 9 { } else { } }"
+            )
+        );
+    }
+
+    #[test]
+    fn methods() {
+        let program = desugar(parse(
+            "struct P { x: u64 } impl P { fn get(self) -> u64 { return self.x; } fn show(self, n: u64) { println n; } } \
+             let p = P { x: 1 }; p.show(p.get() + 1);",
+        ));
+        let ast::AstStatement::Function { name, .. } = &program[2] else {
+            panic!("expected function");
+        };
+        assert_eq!(name.as_str(), "P::show");
+        let ast::AstStatement::Call { name, args, .. } = &program[4] else {
+            panic!("expected call");
+        };
+        assert_eq!(name.as_str(), "P::show");
+        assert_eq!(
+            args.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["p", "P::get(p)+1"]
+        );
+    }
+}
@@ -0,0 +1,426 @@
+use error_stack::{AttachmentKind, FrameKind, Report};
+
+use crate::tokenizer::Span;
+
+/// A position in a source file, both line and column start at 1
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// The source line a diagnostic points into, with the part it is about underlined
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Snippet {
+    /// The whole line, without its line break
+    pub line: String,
+    /// Where the underline starts, in chars from the start of the line
+    pub start: usize,
+    /// How many chars are underlined, at least one
+    pub length: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Error {
+    pub location: Option<Location>,
+    pub snippet: Option<Snippet>,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Warning {
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+/// Everything the compiler phases had to complain about during a single run.
+///
+/// Phases take a `&mut Diagnostics` and keep going after recording an error where they can, so
+/// `main` gets to show every problem at once before deciding whether to continue.
+#[derive(Debug, Default)]
+pub struct Diagnostics {
+    pub errors: Vec<Error>,
+    pub warnings: Vec<Warning>,
+}
+
+impl Location {
+    /// Translates a char offset into `source` into a line and column
+    pub fn from_offset(file: &str, source: &str, offset: usize) -> Self {
+        let mut line = 1;
+        let mut column = 1;
+        for c in source.chars().take(offset) {
+            if c == '\n' {
+                line += 1;
+                column = 1;
+            } else {
+                column += 1;
+            }
+        }
+        Self {
+            file: file.to_string(),
+            line,
+            column,
+        }
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "./{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl Snippet {
+    /// The line of `source` that `span` starts on. A span going on past the end of the line is
+    /// only underlined up to it
+    pub fn from_span(source: &str, span: Span) -> Self {
+        let line_start = source
+            .chars()
+            .take(span.start)
+            .enumerate()
+            .filter(|(_, c)| *c == '\n')
+            .last()
+            .map_or(0, |(newline, _)| newline + 1);
+        let line: String = source
+            .chars()
+            .skip(line_start)
+            .take_while(|c| *c != '\n')
+            .collect();
+        let start = span.start - line_start;
+        let end = span.end.min(line_start + line.chars().count());
+        Self {
+            line,
+            start,
+            length: end.saturating_sub(span.start).max(1),
+        }
+    }
+}
+
+impl Diagnostics {
+    pub fn error(&mut self, location: Option<Location>, message: impl Into<String>) {
+        self.errors.push(Error {
+            location,
+            snippet: None,
+            message: message.into(),
+            notes: vec![],
+        });
+    }
+
+    /// Records an error about `span` of `source`, the contents of the file `file`
+    pub fn error_at(&mut self, file: &str, source: &str, span: Span, message: impl Into<String>) {
+        self.errors.push(Error {
+            location: Some(Location::from_offset(file, source, span.start)),
+            snippet: Some(Snippet::from_span(source, span)),
+            message: message.into(),
+            notes: vec![],
+        });
+    }
+
+    pub fn warning(&mut self, location: Option<Location>, message: impl Into<String>) {
+        self.warnings.push(Warning {
+            location,
+            message: message.into(),
+        });
+    }
+
+    /// Records a warning about `span` of `source`, the contents of the file `file`
+    pub fn warning_at(&mut self, file: &str, source: &str, span: Span, message: impl Into<String>) {
+        self.warning(
+            Some(Location::from_offset(file, source, span.start)),
+            message,
+        );
+    }
+
+    /// Records a failed phase, keeping the printable attachments of the report as notes
+    pub fn report<C: error_stack::Context>(
+        &mut self,
+        location: Option<Location>,
+        report: &Report<C>,
+    ) {
+        let notes = report
+            .frames()
+            .filter_map(|frame| match frame.kind() {
+                FrameKind::Attachment(AttachmentKind::Printable(printable)) => {
+                    Some(printable.to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        self.errors.push(Error {
+            location,
+            snippet: None,
+            message: report.current_context().to_string(),
+            notes,
+        });
+    }
+
+    /// Like [`Diagnostics::report`] for a report grouping several errors, the way
+    /// [`Report::extend_one`] does, recording each of them on its own. `span` says where in
+    /// `source`, the contents of the file `file`, one of them happened
+    pub fn report_each<C: error_stack::Context>(
+        &mut self,
+        report: &Report<C>,
+        file: &str,
+        source: &str,
+        span: impl Fn(&C) -> Option<Span>,
+    ) {
+        for top in report.current_frames() {
+            let mut context = None;
+            let mut notes = vec![];
+            let mut frames = vec![top];
+            while let Some(frame) = frames.pop() {
+                match frame.kind() {
+                    FrameKind::Context(_) => context = context.or(frame.downcast_ref::<C>()),
+                    FrameKind::Attachment(AttachmentKind::Printable(printable)) => {
+                        notes.push(printable.to_string())
+                    }
+                    FrameKind::Attachment(_) => {}
+                }
+                frames.extend(frame.sources().iter().rev());
+            }
+            let Some(context) = context else {
+                continue;
+            };
+            let span = span(context);
+            self.errors.push(Error {
+                location: span.map(|span| Location::from_offset(file, source, span.start)),
+                snippet: span.map(|span| Snippet::from_span(source, span)),
+                message: context.to_string(),
+                notes,
+            });
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Renders every diagnostic like rustc does, ordered by file, line and column. Diagnostics
+    /// without a location come last.
+    ///
+    /// ```text
+    /// error: use of undeclared variable `b`
+    ///  --> ./main.ghl:2:6
+    ///   |
+    /// 2 | exit(b);
+    ///   |      ^
+    ///   |
+    ///   = note: variables have to be declared with `let` before they are used
+    /// ```
+    pub fn render(&self) -> String {
+        let mut entries: Vec<(&Option<Location>, String)> = self
+            .errors
+            .iter()
+            .map(|e| {
+                let text = render_entry(
+                    "error",
+                    &e.message,
+                    e.location.as_ref(),
+                    e.snippet.as_ref(),
+                    &e.notes,
+                );
+                (&e.location, text)
+            })
+            .chain(self.warnings.iter().map(|w| {
+                let text = render_entry("warning", &w.message, w.location.as_ref(), None, &[]);
+                (&w.location, text)
+            }))
+            .collect();
+        entries.sort_by_key(|(location, _)| (location.is_none(), (*location).clone()));
+
+        entries
+            .into_iter()
+            .map(|(_, text)| text)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// One diagnostic, the source line is left out without a snippet
+fn render_entry(
+    kind: &str,
+    message: &str,
+    location: Option<&Location>,
+    snippet: Option<&Snippet>,
+    notes: &[String],
+) -> String {
+    // Wide enough for the line number in the margin
+    let gutter = location.map_or(0, |location| location.line.to_string().len());
+    let margin = " ".repeat(gutter);
+
+    let mut out = format!("{kind}: {message}\n");
+    if let Some(location) = location {
+        out.push_str(&format!("{margin}--> {location}\n"));
+    }
+    if let (Some(location), Some(snippet)) = (location, snippet) {
+        // Tabs stay tabs, so the underline lines up however wide they are shown
+        let indent: String = snippet
+            .line
+            .chars()
+            .take(snippet.start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        out.push_str(&format!("{margin} |\n"));
+        out.push_str(&format!("{} | {}\n", location.line, snippet.line));
+        out.push_str(&format!(
+            "{margin} | {indent}{}\n",
+            "^".repeat(snippet.length)
+        ));
+        if !notes.is_empty() {
+            out.push_str(&format!("{margin} |\n"));
+        }
+    }
+    for note in notes {
+        out.push_str(&format!("{margin} = note: {note}\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostics, Location, Snippet};
+    use crate::{ast, tokenizer};
+
+    /// The first line of every diagnostic and where it points
+    fn headers(rendered: &str) -> Vec<&str> {
+        rendered
+            .lines()
+            .filter(|line| line.starts_with("error") || line.trim_start().starts_with("-->"))
+            .collect()
+    }
+
+    #[test]
+    fn location_from_offset() {
+        let src = "let a: u64 = 1;\nexit(a);";
+        assert_eq!(
+            Location::from_offset("tests::location", src, 17),
+            Location {
+                file: "tests::location".to_string(),
+                line: 2,
+                column: 2
+            }
+        );
+    }
+
+    #[test]
+    fn snippet_from_span() {
+        let src = "let a: u64 = 1;\n\texit(ab\n);";
+        let span = |start, end| tokenizer::Span { start, end };
+        assert_eq!(
+            Snippet::from_span(src, span(22, 24)),
+            Snippet {
+                line: "\texit(ab".to_string(),
+                start: 6,
+                length: 2
+            }
+        );
+        // Only underlined up to the end of the line, and never less than a char
+        assert_eq!(Snippet::from_span(src, span(22, 27)).length, 2);
+        assert_eq!(Snippet::from_span(src, span(0, 0)).length, 1);
+    }
+
+    #[test]
+    fn errors_from_multiple_phases() {
+        let src = "let a: u64 = 1 @ 2;\nexit(a) ) $".to_string();
+        let mut diagnostics = Diagnostics::default();
+        let tokens = tokenizer::Tokenizer::new(&src, "tests::multiple_phases".to_string())
+            .tokenize_recovering(&mut diagnostics);
+        assert_eq!(diagnostics.errors.len(), 2);
+
+        if let Err(report) = ast::AstParser::new(tokens).parse() {
+            diagnostics.report(None, &report);
+        }
+        assert_eq!(diagnostics.errors.len(), 3);
+
+        let rendered = diagnostics.render();
+        assert_eq!(
+            headers(&rendered),
+            vec![
+                "error: unexpected character found: '@'",
+                " --> ./tests::multiple_phases:1:16",
+                "error: unexpected character found: '$'",
+                " --> ./tests::multiple_phases:2:11",
+                "error: invalid let statement",
+            ]
+        );
+        assert!(rendered.contains("= note: expected `;`, found invalid input at 15..16"));
+    }
+
+    #[test]
+    fn unterminated_comment_is_reported_once() {
+        let src = "exit(1);\n/* never /* closed */";
+        let mut diagnostics = Diagnostics::default();
+        let tokens = tokenizer::Tokenizer::new(src, "tests::unterminated".to_string())
+            .tokenize_recovering(&mut diagnostics);
+        if let Err(report) = ast::AstParser::new(tokens).parse() {
+            diagnostics.report(None, &report);
+        }
+        assert_eq!(
+            headers(&diagnostics.render()),
+            vec![
+                "error: unterminated block comment",
+                " --> ./tests::unterminated:2:1",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_parse_error() {
+        let src = "let a: u64 = ;\nexit(b);\nlet c: bool = 1;";
+        let tokens = tokenizer::Tokenizer::new(src, "tests::every_parse_error".to_string())
+            .tokenize()
+            .unwrap();
+        let report = ast::AstParser::new(tokens).parse().unwrap_err();
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.report_each(
+            &report,
+            "tests::every_parse_error",
+            src,
+            ast::AstParseError::span,
+        );
+
+        let rendered = diagnostics.render();
+        assert_eq!(
+            headers(&rendered),
+            vec![
+                "error: invalid expression found during ast parsing",
+                " --> ./tests::every_parse_error:1:14",
+                "error: use of undeclared variable `b`",
+                " --> ./tests::every_parse_error:2:6",
+                "error: expected a value of type `bool`, found one of type `{integer}`",
+                " --> ./tests::every_parse_error:3:15",
+            ]
+        );
+        // Each error only keeps its own notes
+        assert_eq!(diagnostics.errors[1].notes.len(), 1, "{rendered}");
+    }
+
+    #[test]
+    fn snippets() {
+        let src = "let a: u64 = 1;\n\n\n\n\n\n\n\n\nlet long_name: u64 = 1 @ 2;";
+        let mut diagnostics = Diagnostics::default();
+        tokenizer::Tokenizer::new(src, "main.ghl".to_string())
+            .tokenize_recovering(&mut diagnostics);
+        diagnostics.errors[0]
+            .notes
+            .push("not an operator".to_string());
+        diagnostics.error(None, "something else");
+
+        assert_eq!(
+            diagnostics.render(),
+            concat!(
+                "error: unexpected character found: '@'\n",
+                "  --> ./main.ghl:10:24\n",
+                "   |\n",
+                "10 | let long_name: u64 = 1 @ 2;\n",
+                "   |                        ^\n",
+                "   |\n",
+                "   = note: not an operator\n",
+                "\n",
+                "error: something else\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,504 @@
+use crate::tokenizer::{escape_for, BinaryOp, Token, Tokenizer};
+
+/// Rebuilds source code from a token stream with canonical spacing: one statement per line,
+/// one space around binary operators and after `:`, and no space inside parentheses and
+/// brackets, around `.`, `..` and `::` or before `;` and `,`. Braces open an indented block,
+/// with every statement, field, variant and arm in it on a line of its own. Comments stay
+/// after the code they follow on a line, or on a line of their own.
+///
+/// Tokens that would be read as other tokens without a space in between, like `: :`, keep one,
+/// so the output has the tokens of the input. The output only depends on the tokens, so
+/// formatting already formatted code is a no-op.
+pub fn format_tokens(tokens: &[Token]) -> String {
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .take_while(|token| !matches!(token, Token::Eof { span: _ }))
+        .collect();
+    let mut out = String::new();
+    // `None` at the start of a line
+    let mut previous: Option<&Token> = None;
+    // Unary operators stick to their operand, `a - -b`, `!a` and `~a`
+    let mut previous_unary = false;
+    // The parentheses, brackets and braces the token is in, innermost last
+    let mut open: Vec<&Token> = vec![];
+    // The tokens written since the last space, which the next one could glue onto
+    let mut run: Vec<&Token> = vec![];
+
+    for (i, &token) in tokens.iter().enumerate() {
+        let next = tokens.get(i + 1).copied();
+        if let Token::Comment {
+            text,
+            trailing,
+            span: _,
+        } = token
+        {
+            // Stays after the code before it on its line, or on a line of its own
+            let after_line = previous.is_none() && !out.is_empty();
+            if *trailing {
+                if after_line {
+                    out.pop();
+                }
+                out.push(' ');
+            } else {
+                if previous.is_some() {
+                    out.push('\n');
+                }
+                out.push_str(&INDENT.repeat(depth(&open)));
+            }
+            out.push_str(text);
+            // Only a block comment can have more code after it on the same line
+            if text.starts_with("//") || !trailing || after_line {
+                out.push('\n');
+                previous = None;
+            } else {
+                previous = Some(token);
+            }
+            continue;
+        }
+        if matches!(token, Token::CloseBrace { span: _ }) {
+            open.pop();
+            // `{}` stays on the line it was opened on
+            if previous.is_some_and(|previous| !matches!(previous, Token::OpenBrace { span: _ })) {
+                out.push('\n');
+                previous = None;
+            }
+        }
+        if matches!(token, Token::DocComment { .. }) && previous.is_some() {
+            out.push('\n');
+            previous = None;
+        }
+        let space = match previous {
+            None => {
+                out.push_str(&INDENT.repeat(depth(&open)));
+                true
+            }
+            Some(previous) => !previous_unary && needs_space(previous, token) || glues(&run, token),
+        };
+        if space {
+            if previous.is_some() {
+                out.push(' ');
+            }
+            run.clear();
+        }
+        run.push(token);
+        // The `!` of `-> !` is a type
+        previous_unary = matches!(token, Token::Bang { span: _ } | Token::Tilde { span: _ })
+            && !matches!(previous, Some(Token::Arrow { span: _ }))
+            || is_minus(token) && previous.is_none_or(|p| !ends_operand(p));
+        out.push_str(&lexeme(token));
+
+        let ends_line = match token {
+            // An attribute like `#[inline]` is on a line of its own, its `[` is remembered as
+            // the `#` so that the `]` knows
+            Token::OpenBracket { span: _ } if matches!(previous, Some(Token::Hash { span: _ })) => {
+                open.extend(previous);
+                false
+            }
+            Token::OpenParen { span: _ } | Token::OpenBracket { span: _ } => {
+                open.push(token);
+                false
+            }
+            Token::CloseParen { span: _ } => {
+                open.pop();
+                false
+            }
+            Token::CloseBracket { span: _ } => matches!(open.pop(), Some(Token::Hash { span: _ })),
+            Token::OpenBrace { span: _ } => {
+                open.push(token);
+                !matches!(next, Some(Token::CloseBrace { span: _ }))
+            }
+            // Unless what follows still belongs to the same statement, like `} else {` or `},`
+            Token::CloseBrace { span: _ } => !matches!(
+                next,
+                Some(
+                    Token::Else { span: _ }
+                        | Token::Comma { span: _ }
+                        | Token::Semicolon { span: _ }
+                        | Token::CloseParen { span: _ }
+                        | Token::CloseBracket { span: _ }
+                        | Token::Dot { span: _ }
+                        | Token::As { span: _ }
+                        | Token::BinaryOperator { .. }
+                )
+            ),
+            // Not the `;` of `[u8; 3]`, or the `,` between arguments
+            Token::Semicolon { span: _ } => open.last().is_none_or(|open| is_brace(open)),
+            Token::Comma { span: _ } => open.last().is_some_and(|open| is_brace(open)),
+            Token::DocComment { .. } => true,
+            _ => false,
+        };
+        if ends_line {
+            out.push('\n');
+            previous = None;
+        } else {
+            previous = Some(token);
+        }
+    }
+
+    if previous.is_some() {
+        out.push('\n');
+    }
+    out
+}
+
+const INDENT: &str = "    ";
+
+/// How deep the braces around a line indent it
+fn depth(open: &[&Token]) -> usize {
+    open.iter().filter(|open| is_brace(open)).count()
+}
+
+fn is_brace(token: &Token) -> bool {
+    matches!(token, Token::OpenBrace { span: _ })
+}
+
+fn is_minus(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::BinaryOperator {
+            op: BinaryOp::Minus,
+            span: _
+        }
+    )
+}
+
+/// Whether a `-` after this token subtracts from it rather than negating what follows
+fn ends_operand(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Number { .. }
+            | Token::Identifier { .. }
+            | Token::CloseParen { span: _ }
+            | Token::CloseBracket { span: _ }
+    )
+}
+
+/// Whether `current` written right after the tokens of `run` would be read as other tokens,
+/// like `: :` as `::`, `! ==` as `!= =` or `1.5 .25` as `1.5.25`
+fn glues(run: &[&Token], current: &Token) -> bool {
+    let lexemes: Vec<String> = run
+        .iter()
+        .chain([&current])
+        .map(|token| lexeme(token))
+        .collect();
+    Tokenizer::new(&lexemes.concat(), String::new())
+        .with_comments()
+        .tokenize()
+        .map_or(true, |tokens| {
+            tokens
+                .iter()
+                .filter(|token| !matches!(token, Token::Eof { span: _ }))
+                .map(lexeme)
+                .ne(lexemes)
+        })
+}
+
+fn needs_space(previous: &Token, current: &Token) -> bool {
+    match (previous, current) {
+        (
+            Token::OpenParen { span: _ }
+            | Token::OpenBracket { span: _ }
+            | Token::Dot { span: _ }
+            | Token::DotDot { span: _ }
+            | Token::DotDotEqual { span: _ }
+            | Token::DoubleColon { span: _ }
+            | Token::Hash { span: _ },
+            _,
+        ) => false,
+        (
+            _,
+            Token::CloseParen { span: _ }
+            | Token::CloseBracket { span: _ }
+            | Token::Semicolon { span: _ }
+            | Token::Colon { span: _ }
+            | Token::Comma { span: _ }
+            | Token::Dot { span: _ }
+            | Token::DotDot { span: _ }
+            | Token::DotDotEqual { span: _ }
+            | Token::DoubleColon { span: _ },
+        ) => false,
+        (Token::OpenBrace { span: _ }, Token::CloseBrace { span: _ }) => false,
+        // `assert_eq!(...)`
+        (Token::Identifier { .. }, Token::Bang { span: _ }) => false,
+        // `a[i]`, but `= [1, 2]`
+        (
+            Token::Identifier { .. }
+            | Token::CloseParen { span: _ }
+            | Token::CloseBracket { span: _ },
+            Token::OpenBracket { span: _ },
+        ) => false,
+        (Token::BinaryOperator { op: _, span: _ }, Token::OpenParen { span: _ }) => true,
+        // `exit(...)`
+        (_, Token::OpenParen { span: _ }) => false,
+        _ => true,
+    }
+}
+
+fn lexeme(token: &Token) -> String {
+    match token {
+        Token::Number {
+            raw,
+            flags: _,
+            suffix,
+            span: _,
+        } => format!("{raw}{}", suffix.as_deref().unwrap_or_default()),
+        Token::BinaryOperator { op, span: _ } => op.to_string(),
+        Token::CompoundAssign { op, span: _ } => format!("{op}="),
+        Token::OpenParen { span: _ } => "(".to_string(),
+        Token::CloseParen { span: _ } => ")".to_string(),
+        Token::OpenBrace { span: _ } => "{".to_string(),
+        Token::CloseBrace { span: _ } => "}".to_string(),
+        Token::OpenBracket { span: _ } => "[".to_string(),
+        Token::CloseBracket { span: _ } => "]".to_string(),
+        Token::Comma { span: _ } => ",".to_string(),
+        Token::Dot { span: _ } => ".".to_string(),
+        Token::Bang { span: _ } => "!".to_string(),
+        Token::Tilde { span: _ } => "~".to_string(),
+        Token::Hash { span: _ } => "#".to_string(),
+        Token::Colon { span: _ } => ":".to_string(),
+        Token::Arrow { span: _ } => "->".to_string(),
+        Token::FatArrow { span: _ } => "=>".to_string(),
+        Token::DotDot { span: _ } => "..".to_string(),
+        Token::DotDotEqual { span: _ } => "..=".to_string(),
+        Token::DoubleColon { span: _ } => "::".to_string(),
+        Token::Let { span: _ }
+        | Token::Mut { span: _ }
+        | Token::Exit { span: _ }
+        | Token::Print { span: _ }
+        | Token::Println { span: _ }
+        | Token::If { span: _ }
+        | Token::Else { span: _ }
+        | Token::While { span: _ }
+        | Token::For { span: _ }
+        | Token::In { span: _ }
+        | Token::Break { span: _ }
+        | Token::Continue { span: _ }
+        | Token::As { span: _ }
+        | Token::Fn { span: _ }
+        | Token::Const { span: _ }
+        | Token::Struct { span: _ }
+        | Token::Enum { span: _ }
+        | Token::Match { span: _ }
+        | Token::Impl { span: _ }
+        | Token::Return { span: _ } => token
+            .keyword()
+            .expect("keyword tokens are in the table")
+            .to_string(),
+        Token::Semicolon { span: _ } => ";".to_string(),
+        Token::Bool { value, span: _ } => value.to_string(),
+        Token::Identifier { name, span: _ } => name.to_string(),
+        Token::String { value, span: _ } => quoted(value.chars(), '"'),
+        Token::RawString {
+            value,
+            hashes,
+            span: _,
+        } => {
+            let hashes = "#".repeat(*hashes);
+            format!("r{hashes}\"{value}\"{hashes}")
+        }
+        Token::Char { value, span: _ } => quoted(std::iter::once(*value), '\''),
+        Token::DocComment { content, span: _ } => format!("///{content}"),
+        Token::Comment {
+            text,
+            trailing: _,
+            span: _,
+        } => text.to_string(),
+        Token::Invalid { span: _ } | Token::Eof { span: _ } => String::new(),
+    }
+}
+
+fn quoted(chars: impl Iterator<Item = char>, quote: char) -> String {
+    let mut lexeme = String::from(quote);
+    for c in chars {
+        if let Some(escaped) = escape_for(c, quote) {
+            lexeme.push('\\');
+            lexeme.push(escaped);
+        } else {
+            lexeme.push(c);
+        }
+    }
+    lexeme.push(quote);
+    lexeme
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_tokens;
+    use crate::tokenizer::Tokenizer;
+
+    fn format(src: &str) -> String {
+        let tokens = Tokenizer::new(src, "tests::formatter".to_string())
+            .with_comments()
+            .tokenize()
+            .unwrap();
+        format_tokens(&tokens)
+    }
+
+    #[test]
+    fn canonical_spacing() {
+        assert_eq!(
+            format("let   a :u64=( 1+2 )*  3 ;exit (a) ;"),
+            "let a: u64 = (1 + 2) * 3;\nexit(a);\n"
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        let once = format("let a:u64=1*(2-a);\n\n\nexit(  a*(1)  );");
+        assert_eq!(once, "let a: u64 = 1 * (2 - a);\nexit(a * (1));\n");
+        assert_eq!(format(&once), once);
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(
+            format("let a:i64=- 5;exit(a- -(a*-2));print -a;"),
+            "let a: i64 = -5;\nexit(a - -(a * -2));\nprint -a;\n"
+        );
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_eq!(
+            format("exit(! a&&b||!(a<b));"),
+            "exit(!a && b || !(a < b));\n"
+        );
+        assert_eq!(format("exit(~ a<<1|a>>2);"), "exit(~a << 1 | a >> 2);\n");
+    }
+
+    #[test]
+    fn compound_assignments() {
+        assert_eq!(format("a+=1;b  /=a;"), "a += 1;\nb /= a;\n");
+        assert_eq!(
+            format("let  mut a:u64=1;a=a*2;"),
+            "let mut a: u64 = 1;\na = a * 2;\n"
+        );
+    }
+
+    #[test]
+    fn raw_strings_stay_raw() {
+        assert_eq!(format(r##"print r#"a\"b"#;"##), "print r#\"a\\\"b\"#;\n");
+    }
+
+    #[test]
+    fn number_suffixes() {
+        assert_eq!(
+            format("exit(1_000u16+0xFF_u8);"),
+            "exit(1000u16 + 0xFFu8);\n"
+        );
+    }
+
+    #[test]
+    fn doc_comments() {
+        assert_eq!(
+            format("/// The answer\nlet a: u64 = 42; /// Exits\nexit(a);"),
+            "/// The answer\nlet a: u64 = 42;\n/// Exits\nexit(a);\n"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let once = format(
+            "// Header\nlet a:u64=1; // one\n/* two */ let b:u64=a/* inline */+1;\n\
+             fn f() { // opens\n    /* multi\n   line */\n    exit(0);\n}",
+        );
+        assert_eq!(
+            once,
+            "// Header\nlet a: u64 = 1; // one\n/* two */\nlet b: u64 = a /* inline */ + 1;\n\
+             fn f() { // opens\n    /* multi\n   line */\n    exit(0);\n}\n"
+        );
+        assert_eq!(format(&once), once);
+    }
+
+    #[test]
+    fn punctuation() {
+        assert_eq!(
+            format("let a:u64=[ 1 ,2 ] ;exit(a [0] . len+a);{ }"),
+            "let a: u64 = [1, 2];\nexit(a[0].len + a);\n{}\n"
+        );
+    }
+
+    #[test]
+    fn attributes() {
+        let once = format("#[ inline ]fn f(a:[u8;2]){exit(a [0]);}");
+        assert_eq!(once, "#[inline]\nfn f(a: [u8; 2]) {\n    exit(a[0]);\n}\n");
+        assert_eq!(format(&once), once);
+    }
+
+    #[test]
+    fn assertions() {
+        assert_eq!(
+            format("#[test]fn t(){assert_eq ! ( a,!b );}"),
+            "#[test]\nfn t() {\n    assert_eq!(a, !b);\n}\n"
+        );
+    }
+
+    /// The formatted source has the tokens of the original, whatever spaces it leaves out
+    #[test]
+    fn keeps_tokens() {
+        let tokens = |src: &str| {
+            Tokenizer::new(src, "tests::formatter".to_string())
+                .with_comments()
+                .tokenize()
+                .unwrap()
+                .iter()
+                .map(|token| (token.kind(), super::lexeme(token)))
+                .collect::<Vec<_>>()
+        };
+        for src in [
+            "let a: [u8; 2] = [1, 2]; let b = a : :c;",
+            "exit(! == !=);",
+            "print 1.5 .25; print 1. .5; print 1 .. 2 ..= 3 . . 4;",
+            "let a = - -1 - - 2; let b = a/ /c; print a/ *b*/ 2;",
+            "if a < -b && ! !c || !d { exit(-a as u8 - -1i8 as u8); }",
+            "#[ inline ] fn f() -> ! { while true { } }",
+            "let s = \"a\\\"b\" r#\"c\"# '\\n'; exit(x[0]. 1 .a);",
+            "/* a */ let a = 1 /* b */ ; // c\n/// d\nfn e() {}",
+        ] {
+            let formatted = format(src);
+            assert_eq!(tokens(&formatted), tokens(src), "{src} -> {formatted}");
+            assert_eq!(format(&formatted), formatted);
+        }
+    }
+
+    #[test]
+    fn never_type() {
+        assert_eq!(
+            format("fn f()->!{exit(!a as u8);}"),
+            "fn f() -> ! {\n    exit(!a as u8);\n}\n"
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(
+            format("for i in 0 .. -n{println i;}"),
+            "for i in 0..-n {\n    println i;\n}\n"
+        );
+        assert_eq!(format("let r=a ..= b;"), "let r = a..=b;\n");
+    }
+
+    #[test]
+    fn blocks() {
+        let once = format(
+            "fn f(a:u8)->u8{if a{/// One\nreturn 1;}else{let b:[u8;2]=[a,a];return b[0];}} \
+             enum E{A,B}struct P{x:u8,y:u8}match e{E::A=>{exit(f(P{x:1,y:2}.x));},_=>{}}",
+        );
+        assert_eq!(
+            once,
+            "fn f(a: u8) -> u8 {\n    if a {\n        /// One\n        return 1;\n    } else {\n        \
+             let b: [u8; 2] = [a, a];\n        return b[0];\n    }\n}\nenum E {\n    A,\n    B\n}\n\
+             struct P {\n    x: u8,\n    y: u8\n}\nmatch e {\n    E::A => {\n        \
+             exit(f(P {\n            x: 1,\n            y: 2\n        }.x));\n    },\n    _ => {}\n}\n"
+        );
+        assert_eq!(format(&once), once);
+    }
+
+    #[test]
+    fn casts() {
+        assert_eq!(
+            format("exit((a<b)as u8+-a  as u8);"),
+            "exit((a < b) as u8 + -a as u8);\n"
+        );
+    }
+}
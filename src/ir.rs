@@ -1,17 +1,145 @@
-use crate::ast::{self};
+use std::collections::HashMap;
+
+use crate::{
+    ast::{self},
+    const_eval, ir_passes,
+    symbol::Symbol,
+};
 
 pub struct IrGenerator {
     program: ast::AstProgram,
 }
 
-#[derive(Debug)]
+/// The variables of one function body. In C a declaration's initializer already sees the
+/// variable it declares, and a scope can't declare a name twice, so every declaration after the
+/// first of a name gets a name of its own
+struct Names {
+    /// Innermost last, what each source name in it refers to
+    scopes: Vec<HashMap<Symbol, Symbol>>,
+    /// How many declarations took each name, including the constants the body can see
+    taken: HashMap<Symbol, usize>,
+}
+
+/// A whole program. The top level statements become `main`, enums, structs, functions and
+/// constants are emitted next to it
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct IrProgram {
+    pub enums: Vec<IrEnum>,
+    pub structs: Vec<IrStruct>,
+    pub consts: Vec<IrConst>,
+    pub functions: Vec<IrFunction>,
+    pub main: Vec<IR>,
+}
+
+/// A struct type, in the order of its definition among the other structs
+#[derive(Debug, PartialEq, Clone)]
+pub struct IrStruct {
+    pub doc: Option<String>,
+    pub name: Symbol,
+    pub fields: Vec<ast::Field>,
+}
+
+/// An enum type, its variants in the order they were declared in
+#[derive(Debug, PartialEq, Clone)]
+pub struct IrEnum {
+    pub name: Symbol,
+    pub variants: Vec<Symbol>,
+}
+
+/// A `const` of type `t`, visible to `main` and every function
+#[derive(Debug, PartialEq, Clone)]
+pub struct IrConst {
+    pub name: Symbol,
+    pub t: Symbol,
+    pub value: const_eval::Value,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct IrFunction {
+    pub doc: Option<String>,
+    pub attrs: Vec<ast::Attribute>,
+    pub name: Symbol,
+    pub params: Vec<ast::Parameter>,
+    pub return_type: Option<Symbol>,
+    pub body: Vec<IR>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum IR {
     DefineVariable {
-        name: String,
-        t: String,
-        value: ast::AstExpression
+        name: Symbol,
+        t: Symbol,
+        value: ast::AstExpression,
     },
-    Exit { value: ast::AstExpression },
+    Exit {
+        value: ast::AstExpression,
+    },
+    Print {
+        value: ast::AstExpression,
+        newline: bool,
+    },
+    /// Fails the program, or the test running it, if `left` and `right` of type `t` differ.
+    /// `message` is the comparison as it was written
+    AssertEq {
+        left: ast::AstExpression,
+        right: ast::AstExpression,
+        t: Symbol,
+        message: String,
+    },
+    /// Calls a function for its side effects, dropping the result
+    Call {
+        name: Symbol,
+        args: Vec<ast::AstExpression>,
+    },
+    /// Runs `then` if `condition` is non-zero and `otherwise` if it is zero
+    Branch {
+        condition: ast::AstExpression,
+        then: Vec<IR>,
+        otherwise: Vec<IR>,
+    },
+    /// Runs `body` for as long as `condition` is non-zero, checking it before every iteration
+    Loop {
+        condition: ast::AstExpression,
+        body: Vec<IR>,
+    },
+    /// Stores a new value in a variable defined earlier, of type `t`
+    Assign {
+        name: Symbol,
+        t: Symbol,
+        value: ast::AstExpression,
+    },
+    /// Leaves the function, with a value if it returns one
+    Return {
+        value: Option<ast::AstExpression>,
+    },
+    /// A nested scope, the variables defined in `body` are gone after it
+    Block {
+        body: Vec<IR>,
+    },
+    /// Leaves the innermost loop
+    Break,
+    /// Skips to the next iteration of the innermost loop
+    Continue,
+}
+
+impl IrProgram {
+    /// Rewrites `main` and every function body with `f`
+    pub fn map_bodies(self, f: impl Fn(Vec<IR>) -> Vec<IR>) -> Self {
+        Self {
+            enums: self.enums,
+            structs: self.structs,
+            consts: self.consts,
+            functions: self
+                .functions
+                .into_iter()
+                .map(|function| IrFunction {
+                    body: f(function.body),
+                    ..function
+                })
+                .collect(),
+            main: f(self.main),
+        }
+    }
 }
 
 impl IrGenerator {
@@ -20,31 +148,230 @@ impl IrGenerator {
         Self { program }
     }
 
-    pub fn generate(mut self) -> Vec<IR> {
-        let mut ir = vec![];
+    pub fn generate(mut self) -> IrProgram {
+        let mut program = IrProgram::default();
+        let consts: Vec<Symbol> = self
+            .program
+            .iter()
+            .filter_map(|stmt| match stmt {
+                ast::AstStatement::Const { name, .. } => Some(*name),
+                _ => None,
+            })
+            .collect();
+        let mut main = Names::new(&consts, &[]);
 
         while let Some(stmt) = self.eat() {
             match stmt {
-                ast::AstStatement::Exit{value} => {
-                    ir.push(IR::Exit{value});
+                ast::AstStatement::Function {
+                    doc,
+                    attrs,
+                    name,
+                    params,
+                    return_type,
+                    body,
+                    span: _,
+                } => {
+                    let mut names = Names::new(&consts, &params);
+                    program.functions.push(IrFunction {
+                        doc,
+                        attrs,
+                        name,
+                        params,
+                        return_type,
+                        body: names.block(body),
+                    });
                 }
-                ast::AstStatement::Let {
+                ast::AstStatement::Const {
+                    name,
+                    t,
                     value,
+                    span: _,
+                } => {
+                    program.consts.push(IrConst { name, t, value });
+                }
+                ast::AstStatement::Struct {
+                    doc,
                     name,
-                    t
+                    fields,
+                    span: _,
                 } => {
-                    ir.push(IR::DefineVariable{value, t, name});
+                    program.structs.push(IrStruct { doc, name, fields });
                 }
-            }                        
+                ast::AstStatement::Enum {
+                    name,
+                    variants,
+                    span: _,
+                } => {
+                    program.enums.push(IrEnum { name, variants });
+                }
+                stmt => program.main.push(main.statement(stmt)),
+            }
         }
 
-        ir
+        program
     }
 
-    fn peek(&self) -> Option<&ast::AstStatement> {
-        self.program.last()
-    }
     fn eat(&mut self) -> Option<ast::AstStatement> {
         self.program.pop()
     }
 }
+
+impl Names {
+    fn new(consts: &[Symbol], params: &[ast::Parameter]) -> Self {
+        let mut names = Self {
+            scopes: vec![HashMap::new()],
+            taken: consts.iter().map(|name| (*name, 1)).collect(),
+        };
+        for param in params {
+            names.declare(param.name);
+        }
+        names
+    }
+
+    /// Lowers the statements of a nested scope
+    fn block(&mut self, body: Vec<ast::AstStatement>) -> Vec<IR> {
+        self.scopes.push(HashMap::new());
+        let body = body.into_iter().map(|stmt| self.statement(stmt)).collect();
+        self.scopes.pop();
+        body
+    }
+
+    fn statement(&mut self, stmt: ast::AstStatement) -> IR {
+        match stmt {
+            ast::AstStatement::Exit { value, span: _ } => IR::Exit {
+                value: self.expression(value),
+            },
+            ast::AstStatement::Let {
+                value,
+                name,
+                t,
+                mutable: _,
+                span: _,
+            } => {
+                // The initializer still sees the variable this one shadows
+                let value = self.expression(value);
+                IR::DefineVariable {
+                    value,
+                    t,
+                    name: self.declare(name),
+                }
+            }
+            ast::AstStatement::Print {
+                value,
+                newline,
+                span: _,
+            } => IR::Print {
+                value: self.expression(value),
+                newline,
+            },
+            ast::AstStatement::AssertEq {
+                left,
+                right,
+                t,
+                span: _,
+            } => IR::AssertEq {
+                message: format!("{left} == {right}"),
+                left: self.expression(left),
+                right: self.expression(right),
+                t,
+            },
+            ast::AstStatement::Call {
+                name,
+                args,
+                span: _,
+            } => IR::Call {
+                name,
+                args: args.into_iter().map(|arg| self.expression(arg)).collect(),
+            },
+            ast::AstStatement::If {
+                condition,
+                then,
+                otherwise,
+                span: _,
+            } => IR::Branch {
+                condition: self.expression(condition),
+                then: self.block(then),
+                otherwise: self.block(otherwise),
+            },
+            ast::AstStatement::While {
+                condition,
+                body,
+                span: _,
+            } => IR::Loop {
+                condition: self.expression(condition),
+                body: self.block(body),
+            },
+            ast::AstStatement::Assign {
+                name,
+                t,
+                value,
+                span: _,
+            } => IR::Assign {
+                name: self.resolve(name),
+                t,
+                value: self.expression(value),
+            },
+            ast::AstStatement::Return { value, span: _ } => IR::Return {
+                value: value.map(|value| self.expression(value)),
+            },
+            ast::AstStatement::Block { body, span: _ } => IR::Block {
+                body: self.block(body),
+            },
+            ast::AstStatement::Break { span: _ } => IR::Break,
+            ast::AstStatement::Continue { span: _ } => IR::Continue,
+            ast::AstStatement::Function { .. }
+            | ast::AstStatement::Const { .. }
+            | ast::AstStatement::Struct { .. }
+            | ast::AstStatement::Enum { .. } => {
+                unreachable!(
+                    "the parser only allows functions, constants, structs and enums at the top level"
+                )
+            }
+            ast::AstStatement::For { .. }
+            | ast::AstStatement::Match { .. }
+            | ast::AstStatement::CompoundAssign { .. }
+            | ast::AstStatement::Impl { .. }
+            | ast::AstStatement::MethodCall { .. } => {
+                unreachable!("desugared into the statements they are shorthands for")
+            }
+        }
+    }
+
+    /// Adds a variable to the innermost scope, returning its name in the IR. That's its own if
+    /// nothing took it yet, otherwise the first free one of `name__1`, `name__2`, ...
+    fn declare(&mut self, name: Symbol) -> Symbol {
+        let unique = match self.taken.get(&name) {
+            None => name,
+            Some(&count) => (count..)
+                .map(|n| Symbol::intern(&format!("{name}__{n}")))
+                .find(|candidate| !self.taken.contains_key(candidate))
+                .expect("there are infinitely many candidates"),
+        };
+        *self.taken.entry(name).or_insert(0) += 1;
+        self.taken.entry(unique).or_insert(1);
+        self.scopes
+            .last_mut()
+            .expect("the body always has a scope")
+            .insert(name, unique);
+        unique
+    }
+
+    /// The IR name of the variable `name` refers to here. Constants keep their own
+    fn resolve(&self, name: Symbol) -> Symbol {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(&name).copied())
+            .unwrap_or(name)
+    }
+
+    fn expression(&self, expr: ast::AstExpression) -> ast::AstExpression {
+        ir_passes::rewrite(expr, &|expr| match expr {
+            ast::AstExpression::Identifier { name, span } => ast::AstExpression::Identifier {
+                name: self.resolve(name),
+                span,
+            },
+            other => other,
+        })
+    }
+}
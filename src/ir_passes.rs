@@ -0,0 +1,1222 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{AstExpression, NodeSpan},
+    const_eval::{const_eval, integer_literal, Value},
+    ir::IR,
+    symbol::Symbol,
+    tokenizer::BinaryOp,
+};
+
+/// A single IR -> IR rewrite that can be chained with others through [`run_passes`]
+pub trait IrTransform: std::fmt::Debug {
+    fn transform(&self, ir: Vec<IR>) -> Vec<IR>;
+    fn name(&self) -> &'static str;
+}
+
+/// Runs every pass over the IR in order, feeding the output of one into the next
+pub fn run_passes(ir: Vec<IR>, passes: &[Box<dyn IrTransform>]) -> Vec<IR> {
+    passes.iter().fold(ir, |ir, pass| pass.transform(ir))
+}
+
+/// The passes `main` runs by default
+pub fn default_passes() -> Vec<Box<dyn IrTransform>> {
+    vec![
+        Box::new(CopyPropagation),
+        Box::new(ConstantFolding),
+        Box::new(StrengthReduction),
+        Box::new(CommonSubexpressionElimination),
+        Box::new(DeadCodeElimination),
+    ]
+}
+
+/// The passes `main` runs when arithmetic can abort on overflow. Folding would hide the
+/// overflow at compile time and eliminating unused variables would drop the check entirely
+pub fn overflow_checked_passes() -> Vec<Box<dyn IrTransform>> {
+    vec![
+        Box::new(CopyPropagation),
+        Box::new(StrengthReduction),
+        Box::new(CommonSubexpressionElimination),
+    ]
+}
+
+/// Evaluates binary operations whose operands are both plain decimal literals
+#[derive(Debug)]
+pub struct ConstantFolding;
+
+/// Drops everything after the first `exit`, `return`, `break` or `continue` and variables that
+/// are never read
+#[derive(Debug)]
+pub struct DeadCodeElimination;
+
+/// Replaces multiplications by `0`, `1` and `2` with cheaper equivalents
+#[derive(Debug)]
+pub struct StrengthReduction;
+
+/// Replaces reads of `let b: T = a;` copies with reads of `a`
+#[derive(Debug)]
+pub struct CopyPropagation;
+
+/// Reuses an earlier variable holding the same computation instead of recomputing it
+#[derive(Debug)]
+pub struct CommonSubexpressionElimination;
+
+impl IrTransform for ConstantFolding {
+    fn transform(&self, ir: Vec<IR>) -> Vec<IR> {
+        ir.into_iter()
+            .flat_map(|ir| match ir {
+                IR::DefineVariable { name, t, value } => {
+                    let unsigned = t.as_str().starts_with('u');
+                    let value = rewrite(value, &|expr| fold(expr, unsigned));
+                    vec![IR::DefineVariable { name, t, value }]
+                }
+                IR::Assign { name, t, value } => {
+                    let unsigned = t.as_str().starts_with('u');
+                    let value = rewrite(value, &|expr| fold(expr, unsigned));
+                    vec![IR::Assign { name, t, value }]
+                }
+                IR::Branch {
+                    condition,
+                    then,
+                    otherwise,
+                } => vec![IR::Branch {
+                    condition: rewrite(condition, &|expr| fold(expr, false)),
+                    then: self.transform(then),
+                    otherwise: self.transform(otherwise),
+                }],
+                IR::Loop { condition, body } => vec![IR::Loop {
+                    condition: rewrite(condition, &|expr| fold(expr, false)),
+                    body: self.transform(body),
+                }],
+                IR::Block { body } => vec![IR::Block {
+                    body: self.transform(body),
+                }],
+                // Without a declared type the arithmetic happens in C's signed int
+                other => map_expressions(vec![other], &|expr| {
+                    rewrite(expr, &|expr| fold(expr, false))
+                }),
+            })
+            .collect()
+    }
+
+    fn name(&self) -> &'static str {
+        "constant-folding"
+    }
+}
+
+impl IrTransform for DeadCodeElimination {
+    fn transform(&self, ir: Vec<IR>) -> Vec<IR> {
+        DeadCodeElimination::eliminate(ir, &mut HashSet::new())
+    }
+
+    fn name(&self) -> &'static str {
+        "dead-code-elimination"
+    }
+}
+
+impl DeadCodeElimination {
+    /// Eliminates the dead code of a block after which the variables in `used` are read. The
+    /// ones read inside of the block are added to `used`
+    fn eliminate(mut ir: Vec<IR>, used: &mut HashSet<Symbol>) -> Vec<IR> {
+        if let Some(exit) = ir.iter().position(|ir| {
+            matches!(
+                ir,
+                IR::Exit { value: _ } | IR::Return { value: _ } | IR::Break | IR::Continue
+            )
+        }) {
+            ir.truncate(exit + 1);
+        }
+
+        // Removing a variable can make the ones it reads unused as well, so walk backwards
+        let mut live = vec![];
+        for ir in ir.into_iter().rev() {
+            match &ir {
+                // A call can print or exit, so its result is computed even if nobody reads it
+                IR::DefineVariable { name, t: _, value } | IR::Assign { name, t: _, value }
+                    if !used.contains(name) && !has_call(value) =>
+                {
+                    continue
+                }
+                // The variable has to stay declared for the kept assignment to compile
+                IR::Assign { name, t: _, value } => {
+                    used.insert(*name);
+                    collect_identifiers(value, used);
+                }
+                IR::DefineVariable {
+                    name: _,
+                    t: _,
+                    value,
+                }
+                | IR::Exit { value }
+                | IR::Print { value, newline: _ } => collect_identifiers(value, used),
+                IR::Call { name: _, args } => {
+                    for arg in args {
+                        collect_identifiers(arg, used);
+                    }
+                }
+                IR::AssertEq {
+                    left,
+                    right,
+                    t: _,
+                    message: _,
+                } => {
+                    collect_identifiers(left, used);
+                    collect_identifiers(right, used);
+                }
+                IR::Return { value } => {
+                    if let Some(value) = value {
+                        collect_identifiers(value, used);
+                    }
+                }
+                IR::Break | IR::Continue => {}
+                IR::Branch { .. } => {
+                    let IR::Branch {
+                        condition,
+                        then,
+                        otherwise,
+                    } = ir
+                    else {
+                        unreachable!("just matched a branch")
+                    };
+                    // Only one side runs, so whatever either side reads stays live
+                    let mut used_by_then = used.clone();
+                    let then = DeadCodeElimination::eliminate(then, &mut used_by_then);
+                    let otherwise = DeadCodeElimination::eliminate(otherwise, used);
+                    used.extend(used_by_then);
+                    collect_identifiers(&condition, used);
+                    live.push(IR::Branch {
+                        condition,
+                        then,
+                        otherwise,
+                    });
+                    continue;
+                }
+                IR::Block { .. } => {
+                    let IR::Block { body } = ir else {
+                        unreachable!("just matched a block")
+                    };
+                    live.push(IR::Block {
+                        body: DeadCodeElimination::eliminate(body, used),
+                    });
+                    continue;
+                }
+                IR::Loop { .. } => {
+                    let IR::Loop { condition, body } = ir else {
+                        unreachable!("just matched a loop")
+                    };
+                    // The body can run any number of times, each followed by the condition
+                    let mut used_by_body = used.clone();
+                    collect_identifiers(&condition, &mut used_by_body);
+                    let body = DeadCodeElimination::eliminate_loop_body(body, &mut used_by_body);
+                    used.extend(used_by_body);
+                    live.push(IR::Loop { condition, body });
+                    continue;
+                }
+            }
+            live.push(ir);
+        }
+        live.reverse();
+        live
+    }
+
+    /// Like [`DeadCodeElimination::eliminate`], but for a body that runs again after itself.
+    /// What one iteration reads is live at the end of the one before it, so this repeats until
+    /// no more variables turn out to be used
+    fn eliminate_loop_body(body: Vec<IR>, used: &mut HashSet<Symbol>) -> Vec<IR> {
+        loop {
+            let known = used.len();
+            DeadCodeElimination::eliminate(body.clone(), used);
+            if used.len() == known {
+                return DeadCodeElimination::eliminate(body, used);
+            }
+        }
+    }
+}
+
+impl IrTransform for StrengthReduction {
+    fn transform(&self, ir: Vec<IR>) -> Vec<IR> {
+        map_expressions(ir, &|expr| rewrite(expr, &reduce))
+    }
+
+    fn name(&self) -> &'static str {
+        "strength-reduction"
+    }
+}
+
+impl IrTransform for CopyPropagation {
+    fn transform(&self, ir: Vec<IR>) -> Vec<IR> {
+        CopyPropagation::propagate(ir, HashMap::new(), HashMap::new())
+    }
+
+    fn name(&self) -> &'static str {
+        "copy-propagation"
+    }
+}
+
+impl CopyPropagation {
+    /// Propagates the copies of a block, starting from the variables defined around it
+    fn propagate(
+        ir: Vec<IR>,
+        mut types: HashMap<Symbol, Symbol>,
+        mut copies: HashMap<Symbol, Symbol>,
+    ) -> Vec<IR> {
+        ir.into_iter()
+            .map(|ir| {
+                // What a body assigns to is stale after it, and in a loop already in the next
+                // iteration
+                let mut assigned = HashSet::new();
+                collect_assigned(&ir, &mut assigned);
+                copies
+                    .retain(|copy, source| !assigned.contains(copy) && !assigned.contains(source));
+                match ir {
+                    IR::DefineVariable { name, t, value } => {
+                        let value = substitute(value, &copies);
+                        // A new variable shadows the old one, which copies of either can't stand
+                        // in for
+                        copies.retain(|copy, source| *copy != name && *source != name);
+                        if let AstExpression::Identifier {
+                            name: source,
+                            span: _,
+                        } = &value
+                        {
+                            // Only a copy if no conversion happens on the way
+                            if types.get(source) == Some(&t) {
+                                copies.insert(name, *source);
+                            }
+                        }
+                        types.insert(name, t);
+                        IR::DefineVariable { name, t, value }
+                    }
+                    IR::Assign { name, t, value } => {
+                        let value = substitute(value, &copies);
+                        copies.retain(|copy, source| *copy != name && *source != name);
+                        IR::Assign { name, t, value }
+                    }
+                    IR::Exit { value } => IR::Exit {
+                        value: substitute(value, &copies),
+                    },
+                    IR::Print { value, newline } => IR::Print {
+                        value: substitute(value, &copies),
+                        newline,
+                    },
+                    IR::AssertEq {
+                        left,
+                        right,
+                        t,
+                        message,
+                    } => IR::AssertEq {
+                        left: substitute(left, &copies),
+                        right: substitute(right, &copies),
+                        t,
+                        message,
+                    },
+                    IR::Call { name, args } => IR::Call {
+                        name,
+                        args: args
+                            .into_iter()
+                            .map(|arg| substitute(arg, &copies))
+                            .collect(),
+                    },
+                    IR::Return { value } => IR::Return {
+                        value: value.map(|value| substitute(value, &copies)),
+                    },
+                    ir @ (IR::Break | IR::Continue) => ir,
+                    // Whatever a branch defines goes out of scope at its end
+                    IR::Branch {
+                        condition,
+                        then,
+                        otherwise,
+                    } => IR::Branch {
+                        condition: substitute(condition, &copies),
+                        then: CopyPropagation::propagate(then, types.clone(), copies.clone()),
+                        otherwise: CopyPropagation::propagate(
+                            otherwise,
+                            types.clone(),
+                            copies.clone(),
+                        ),
+                    },
+                    IR::Loop { condition, body } => IR::Loop {
+                        condition: substitute(condition, &copies),
+                        body: CopyPropagation::propagate(body, types.clone(), copies.clone()),
+                    },
+                    IR::Block { body } => IR::Block {
+                        body: CopyPropagation::propagate(body, types.clone(), copies.clone()),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+impl IrTransform for CommonSubexpressionElimination {
+    fn transform(&self, ir: Vec<IR>) -> Vec<IR> {
+        CommonSubexpressionElimination::eliminate(ir, vec![])
+    }
+
+    fn name(&self) -> &'static str {
+        "common-subexpression-elimination"
+    }
+}
+
+impl CommonSubexpressionElimination {
+    /// Eliminates the common subexpressions of a block, reusing the `(value, name, type)`
+    /// computations from around it
+    fn eliminate(ir: Vec<IR>, mut computed: Vec<(AstExpression, Symbol, Symbol)>) -> Vec<IR> {
+        ir.into_iter()
+            .map(|ir| {
+                // Like copies, computations with what a body assigns to are stale after it
+                let mut assigned = HashSet::new();
+                collect_assigned(&ir, &mut assigned);
+                computed.retain(|(expr, earlier, _)| {
+                    !assigned.contains(earlier) && !assigned.iter().any(|name| reads(expr, *name))
+                });
+                match ir {
+                    IR::DefineVariable { name, t, value } => {
+                        // A new variable shadows the old one, so computations with it are stale
+                        computed
+                            .retain(|(expr, earlier, _)| *earlier != name && !reads(expr, name));
+                        // Calling twice can have a different result or effect than calling once
+                        if !matches!(value, AstExpression::BinaryOperation { .. })
+                            || has_call(&value)
+                        {
+                            return IR::DefineVariable { name, t, value };
+                        }
+                        let earlier = computed
+                            .iter()
+                            .find(|(expr, _, other_t)| *expr == value && *other_t == t)
+                            .map(|(_, earlier, _)| *earlier);
+                        match earlier {
+                            Some(earlier) => IR::DefineVariable {
+                                name,
+                                t,
+                                value: AstExpression::Identifier {
+                                    name: earlier,
+                                    span: NodeSpan(value.span()),
+                                },
+                            },
+                            None => {
+                                computed.push((value.clone(), name, t));
+                                IR::DefineVariable { name, t, value }
+                            }
+                        }
+                    }
+                    IR::Assign { name, t, value } => {
+                        computed
+                            .retain(|(expr, earlier, _)| *earlier != name && !reads(expr, name));
+                        IR::Assign { name, t, value }
+                    }
+                    ir @ (IR::Exit { value: _ }
+                    | IR::Print {
+                        value: _,
+                        newline: _,
+                    }
+                    | IR::AssertEq { .. }
+                    | IR::Call { name: _, args: _ }
+                    | IR::Return { value: _ }
+                    | IR::Break
+                    | IR::Continue) => ir,
+                    IR::Branch {
+                        condition,
+                        then,
+                        otherwise,
+                    } => IR::Branch {
+                        condition,
+                        then: CommonSubexpressionElimination::eliminate(then, computed.clone()),
+                        otherwise: CommonSubexpressionElimination::eliminate(
+                            otherwise,
+                            computed.clone(),
+                        ),
+                    },
+                    IR::Loop { condition, body } => IR::Loop {
+                        condition,
+                        body: CommonSubexpressionElimination::eliminate(body, computed.clone()),
+                    },
+                    IR::Block { body } => IR::Block {
+                        body: CommonSubexpressionElimination::eliminate(body, computed.clone()),
+                    },
+                }
+            })
+            .collect()
+    }
+}
+
+fn map_expressions(ir: Vec<IR>, f: &impl Fn(AstExpression) -> AstExpression) -> Vec<IR> {
+    ir.into_iter()
+        .map(|ir| match ir {
+            IR::DefineVariable { name, t, value } => IR::DefineVariable {
+                name,
+                t,
+                value: f(value),
+            },
+            IR::Assign { name, t, value } => IR::Assign {
+                name,
+                t,
+                value: f(value),
+            },
+            IR::Exit { value } => IR::Exit { value: f(value) },
+            IR::Print { value, newline } => IR::Print {
+                value: f(value),
+                newline,
+            },
+            IR::AssertEq {
+                left,
+                right,
+                t,
+                message,
+            } => IR::AssertEq {
+                left: f(left),
+                right: f(right),
+                t,
+                message,
+            },
+            IR::Call { name, args } => IR::Call {
+                name,
+                args: args.into_iter().map(f).collect(),
+            },
+            IR::Return { value } => IR::Return {
+                value: value.map(f),
+            },
+            ir @ (IR::Break | IR::Continue) => ir,
+            IR::Branch {
+                condition,
+                then,
+                otherwise,
+            } => IR::Branch {
+                condition: f(condition),
+                then: map_expressions(then, f),
+                otherwise: map_expressions(otherwise, f),
+            },
+            IR::Loop { condition, body } => IR::Loop {
+                condition: f(condition),
+                body: map_expressions(body, f),
+            },
+            IR::Block { body } => IR::Block {
+                body: map_expressions(body, f),
+            },
+        })
+        .collect()
+}
+
+/// Rewrites every node of `expr` with `f`, the children before their parent
+pub(crate) fn rewrite(
+    expr: AstExpression,
+    f: &impl Fn(AstExpression) -> AstExpression,
+) -> AstExpression {
+    let expr = match expr {
+        AstExpression::BinaryOperation {
+            left,
+            operator,
+            right,
+            span,
+        } => AstExpression::BinaryOperation {
+            left: Box::new(rewrite(*left, f)),
+            operator,
+            right: Box::new(rewrite(*right, f)),
+            span,
+        },
+        AstExpression::UnaryOperation {
+            operator,
+            operand,
+            span,
+        } => AstExpression::UnaryOperation {
+            operator,
+            operand: Box::new(rewrite(*operand, f)),
+            span,
+        },
+        AstExpression::Call { name, args, span } => AstExpression::Call {
+            name,
+            args: args.into_iter().map(|arg| rewrite(arg, f)).collect(),
+            span,
+        },
+        AstExpression::MethodCall {
+            value,
+            t,
+            method,
+            args,
+            span,
+        } => AstExpression::MethodCall {
+            value: Box::new(rewrite(*value, f)),
+            t,
+            method,
+            args: args.into_iter().map(|arg| rewrite(arg, f)).collect(),
+            span,
+        },
+        AstExpression::Cast { value, t, span } => AstExpression::Cast {
+            value: Box::new(rewrite(*value, f)),
+            t,
+            span,
+        },
+        AstExpression::StructLiteral { name, fields, span } => AstExpression::StructLiteral {
+            name,
+            fields: fields
+                .into_iter()
+                .map(|(field, value)| (field, rewrite(value, f)))
+                .collect(),
+            span,
+        },
+        AstExpression::FieldAccess { value, field, span } => AstExpression::FieldAccess {
+            value: Box::new(rewrite(*value, f)),
+            field,
+            span,
+        },
+        AstExpression::ArrayLiteral { values, span } => AstExpression::ArrayLiteral {
+            values: values.into_iter().map(|value| rewrite(value, f)).collect(),
+            span,
+        },
+        AstExpression::Index { value, index, span } => AstExpression::Index {
+            value: Box::new(rewrite(*value, f)),
+            index: Box::new(rewrite(*index, f)),
+            span,
+        },
+        AstExpression::Range {
+            start,
+            end,
+            inclusive,
+            span,
+        } => AstExpression::Range {
+            start: Box::new(rewrite(*start, f)),
+            end: Box::new(rewrite(*end, f)),
+            inclusive,
+            span,
+        },
+        leaf => leaf,
+    };
+    f(expr)
+}
+
+fn substitute(expr: AstExpression, copies: &HashMap<Symbol, Symbol>) -> AstExpression {
+    rewrite(expr, &|expr| match expr {
+        AstExpression::Identifier { name, span } => AstExpression::Identifier {
+            name: copies.get(&name).copied().unwrap_or(name),
+            span,
+        },
+        other => other,
+    })
+}
+
+fn collect_identifiers(expr: &AstExpression, into: &mut HashSet<Symbol>) {
+    match expr {
+        AstExpression::Identifier { name, span: _ } => {
+            into.insert(*name);
+        }
+        AstExpression::BinaryOperation {
+            left,
+            operator: _,
+            right,
+            span: _,
+        } => {
+            collect_identifiers(left, into);
+            collect_identifiers(right, into);
+        }
+        AstExpression::UnaryOperation {
+            operator: _,
+            operand,
+            span: _,
+        } => collect_identifiers(operand, into),
+        AstExpression::Call {
+            name: _,
+            args,
+            span: _,
+        } => {
+            for arg in args {
+                collect_identifiers(arg, into);
+            }
+        }
+        AstExpression::MethodCall {
+            value,
+            t: _,
+            method: _,
+            args,
+            span: _,
+        } => {
+            collect_identifiers(value, into);
+            for arg in args {
+                collect_identifiers(arg, into);
+            }
+        }
+        AstExpression::Cast {
+            value,
+            t: _,
+            span: _,
+        }
+        | AstExpression::FieldAccess {
+            value,
+            field: _,
+            span: _,
+        } => collect_identifiers(value, into),
+        AstExpression::StructLiteral {
+            name: _,
+            fields,
+            span: _,
+        } => {
+            for (_, value) in fields {
+                collect_identifiers(value, into);
+            }
+        }
+        AstExpression::ArrayLiteral { values, span: _ } => {
+            for value in values {
+                collect_identifiers(value, into);
+            }
+        }
+        AstExpression::Index {
+            value,
+            index,
+            span: _,
+        } => {
+            collect_identifiers(value, into);
+            collect_identifiers(index, into);
+        }
+        AstExpression::Range {
+            start,
+            end,
+            inclusive: _,
+            span: _,
+        } => {
+            collect_identifiers(start, into);
+            collect_identifiers(end, into);
+        }
+        AstExpression::Number { .. }
+        | AstExpression::Bool { .. }
+        | AstExpression::Variant { .. } => {}
+    }
+}
+
+/// The statements directly in the bodies of `ir`
+fn nested(ir: &IR) -> Vec<&IR> {
+    match ir {
+        IR::Branch {
+            condition: _,
+            then,
+            otherwise,
+        } => then.iter().chain(otherwise).collect(),
+        IR::Loop { body, .. } | IR::Block { body } => body.iter().collect(),
+        _ => vec![],
+    }
+}
+
+/// Adds every variable assigned to in the bodies nested in `ir` to `into`
+fn collect_assigned(ir: &IR, into: &mut HashSet<Symbol>) {
+    for ir in nested(ir) {
+        if let IR::Assign { name, .. } = ir {
+            into.insert(*name);
+        }
+        collect_assigned(ir, into);
+    }
+}
+
+fn reads(expr: &AstExpression, name: Symbol) -> bool {
+    let mut identifiers = HashSet::new();
+    collect_identifiers(expr, &mut identifiers);
+    identifiers.contains(&name)
+}
+
+/// Whether evaluating the expression calls a function, which can have side effects
+fn has_call(expr: &AstExpression) -> bool {
+    match expr {
+        AstExpression::Call { .. } | AstExpression::MethodCall { .. } => true,
+        AstExpression::BinaryOperation {
+            left,
+            operator: _,
+            right,
+            span: _,
+        } => has_call(left) || has_call(right),
+        AstExpression::UnaryOperation {
+            operator: _,
+            operand,
+            span: _,
+        } => has_call(operand),
+        AstExpression::Cast {
+            value,
+            t: _,
+            span: _,
+        }
+        | AstExpression::FieldAccess {
+            value,
+            field: _,
+            span: _,
+        } => has_call(value),
+        AstExpression::StructLiteral {
+            name: _,
+            fields,
+            span: _,
+        } => fields.iter().any(|(_, value)| has_call(value)),
+        AstExpression::ArrayLiteral { values, span: _ } => values.iter().any(has_call),
+        AstExpression::Index {
+            value,
+            index,
+            span: _,
+        } => has_call(value) || has_call(index),
+        AstExpression::Range {
+            start,
+            end,
+            inclusive: _,
+            span: _,
+        } => has_call(start) || has_call(end),
+        AstExpression::Number { .. }
+        | AstExpression::Identifier { .. }
+        | AstExpression::Bool { .. }
+        | AstExpression::Variant { .. } => false,
+    }
+}
+
+/// Whether the expression contains a division, remainder, right shift or ordering comparison,
+/// which give different results for signed and unsigned operands
+fn depends_on_signedness(expr: &AstExpression) -> bool {
+    match expr {
+        AstExpression::BinaryOperation {
+            left,
+            operator,
+            right,
+            span: _,
+        } => {
+            matches!(
+                operator,
+                BinaryOp::Slash
+                    | BinaryOp::Percent
+                    | BinaryOp::ShiftRight
+                    | BinaryOp::Less
+                    | BinaryOp::Greater
+                    | BinaryOp::LessEqual
+                    | BinaryOp::GreaterEqual
+            ) || depends_on_signedness(left)
+                || depends_on_signedness(right)
+        }
+        AstExpression::UnaryOperation {
+            operator: _,
+            operand,
+            span: _,
+        } => depends_on_signedness(operand),
+        AstExpression::Call {
+            name: _,
+            args,
+            span: _,
+        } => args.iter().any(depends_on_signedness),
+        AstExpression::MethodCall {
+            value,
+            t: _,
+            method: _,
+            args,
+            span: _,
+        } => depends_on_signedness(value) || args.iter().any(depends_on_signedness),
+        // Operations on the result of the cast happen in its type
+        AstExpression::Cast { value, t, span: _ } => {
+            t.as_str().starts_with('i') || depends_on_signedness(value)
+        }
+        AstExpression::StructLiteral {
+            name: _,
+            fields,
+            span: _,
+        } => fields.iter().any(|(_, value)| depends_on_signedness(value)),
+        AstExpression::FieldAccess {
+            value,
+            field: _,
+            span: _,
+        } => depends_on_signedness(value),
+        AstExpression::ArrayLiteral { values, span: _ } => values.iter().any(depends_on_signedness),
+        AstExpression::Index {
+            value,
+            index,
+            span: _,
+        } => depends_on_signedness(value) || depends_on_signedness(index),
+        AstExpression::Range {
+            start,
+            end,
+            inclusive: _,
+            span: _,
+        } => depends_on_signedness(start) || depends_on_signedness(end),
+        AstExpression::Number { .. }
+        | AstExpression::Identifier { .. }
+        | AstExpression::Bool { .. }
+        | AstExpression::Variant { .. } => false,
+    }
+}
+
+/// Whether the expression contains a literal like `255u8`. Its type decides how the arithmetic
+/// on it wraps, which [`const_eval`] knows nothing about
+fn has_suffixed_literal(expr: &AstExpression) -> bool {
+    match expr {
+        AstExpression::Number { suffix, .. } => suffix.is_some(),
+        AstExpression::BinaryOperation {
+            left,
+            operator: _,
+            right,
+            span: _,
+        } => has_suffixed_literal(left) || has_suffixed_literal(right),
+        AstExpression::UnaryOperation {
+            operator: _,
+            operand,
+            span: _,
+        } => has_suffixed_literal(operand),
+        AstExpression::Call {
+            name: _,
+            args,
+            span: _,
+        } => args.iter().any(has_suffixed_literal),
+        AstExpression::MethodCall {
+            value,
+            t: _,
+            method: _,
+            args,
+            span: _,
+        } => has_suffixed_literal(value) || args.iter().any(has_suffixed_literal),
+        AstExpression::Cast {
+            value,
+            t: _,
+            span: _,
+        }
+        | AstExpression::FieldAccess {
+            value,
+            field: _,
+            span: _,
+        } => has_suffixed_literal(value),
+        AstExpression::StructLiteral {
+            name: _,
+            fields,
+            span: _,
+        } => fields.iter().any(|(_, value)| has_suffixed_literal(value)),
+        AstExpression::ArrayLiteral { values, span: _ } => values.iter().any(has_suffixed_literal),
+        AstExpression::Index {
+            value,
+            index,
+            span: _,
+        } => has_suffixed_literal(value) || has_suffixed_literal(index),
+        AstExpression::Range {
+            start,
+            end,
+            inclusive: _,
+            span: _,
+        } => has_suffixed_literal(start) || has_suffixed_literal(end),
+        AstExpression::Identifier { .. }
+        | AstExpression::Bool { .. }
+        | AstExpression::Variant { .. } => false,
+    }
+}
+
+fn literal_value(expr: &AstExpression) -> Option<u64> {
+    match expr {
+        AstExpression::Number {
+            raw,
+            flags,
+            suffix: None,
+            span: _,
+        } => integer_literal(raw, flags),
+        _ => None,
+    }
+}
+
+/// Folds a binary operation into a literal. Operations that depend on signedness are only folded
+/// for `unsigned` results since they are computed as `u64`s
+fn fold(expr: AstExpression, unsigned: bool) -> AstExpression {
+    if !matches!(expr, AstExpression::BinaryOperation { .. }) {
+        return expr;
+    }
+    if !unsigned && depends_on_signedness(&expr) || has_suffixed_literal(&expr) {
+        return expr;
+    }
+    match const_eval(&expr, Symbol::intern("u64"), &HashMap::new()) {
+        Ok(value) => value.into_expression(NodeSpan(expr.span())),
+        Err(_) => expr,
+    }
+}
+
+fn reduce(expr: AstExpression) -> AstExpression {
+    let AstExpression::BinaryOperation {
+        left,
+        operator: BinaryOp::Star,
+        right,
+        span,
+    } = expr
+    else {
+        return expr;
+    };
+    let (other, constant) = match (literal_value(&left), literal_value(&right)) {
+        (_, Some(r)) => (*left, r),
+        (Some(l), None) => (*right, l),
+        (None, None) => {
+            return AstExpression::BinaryOperation {
+                left,
+                operator: BinaryOp::Star,
+                right,
+                span,
+            }
+        }
+    };
+    match constant {
+        0 if !has_call(&other) => Value::Integer(0).into_expression(span),
+        1 => other,
+        // Duplicating is only cheaper when the operand itself is cheap
+        2 if matches!(other, AstExpression::Identifier { name: _, span: _ }) => {
+            AstExpression::BinaryOperation {
+                left: Box::new(other.clone()),
+                operator: BinaryOp::Plus,
+                right: Box::new(other),
+                span,
+            }
+        }
+        _ => AstExpression::BinaryOperation {
+            left: Box::new(other),
+            operator: BinaryOp::Star,
+            right: Box::new(Value::Integer(constant).into_expression(span)),
+            span,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        run_passes, CommonSubexpressionElimination, ConstantFolding, CopyPropagation,
+        DeadCodeElimination, IrTransform, StrengthReduction,
+    };
+    use crate::{ast, desugar, ir, symbol::Symbol, tokenizer};
+
+    /// Spans don't take part in comparing nodes, so expected trees can put them anywhere
+    const SPAN: ast::NodeSpan = ast::NodeSpan(tokenizer::Span { start: 0, end: 0 });
+
+    fn ir(src: &str) -> Vec<ir::IR> {
+        let tokens = tokenizer::Tokenizer::new(src, "tests::ir_passes".to_string())
+            .tokenize()
+            .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        ir::IrGenerator::new(desugar::desugar(ast)).generate().main
+    }
+
+    #[test]
+    fn constant_folding() {
+        assert_eq!(
+            ConstantFolding.transform(ir("exit((1 + 2) * 3 - 4);")),
+            ir("exit(5);")
+        );
+    }
+
+    #[test]
+    fn dead_code_elimination() {
+        assert_eq!(
+            DeadCodeElimination.transform(ir(
+                "let a: u64 = 1; let b: u64 = 2; let c: u64 = b * 2; exit(a); exit(c);"
+            )),
+            ir("let a: u64 = 1; exit(a);")
+        );
+    }
+
+    #[test]
+    fn copy_propagation_and_cse() {
+        let input =
+            ir("let a: u64 = 1; let b: u64 = a; let c: u64 = b + a; let d: u64 = a + a; exit(d);");
+        let expected =
+            ir("let a: u64 = 1; let b: u64 = a; let c: u64 = a + a; let d: u64 = c; exit(d);");
+
+        assert_eq!(
+            run_passes(
+                input,
+                &[
+                    Box::new(CopyPropagation),
+                    Box::new(CommonSubexpressionElimination)
+                ]
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn chained_passes_differ_from_each_alone() {
+        let input = ir("let x: u64 = 1; exit(x * (1 + 1));");
+        let passes: [Box<dyn IrTransform>; 2] =
+            [Box::new(ConstantFolding), Box::new(StrengthReduction)];
+
+        let folded = passes[0].transform(input.clone());
+        let reduced = passes[1].transform(input.clone());
+        let chained = run_passes(input, &passes);
+
+        assert_eq!(folded, ir("let x: u64 = 1; exit(x * 2);"));
+        assert_eq!(reduced, ir("let x: u64 = 1; exit(x * (1 + 1));"));
+        assert_eq!(chained, ir("let x: u64 = 1; exit(x + x);"));
+        assert_ne!(chained, folded);
+        assert_ne!(chained, reduced);
+    }
+
+    #[test]
+    fn division_only_folds_unsigned() {
+        assert_eq!(
+            ConstantFolding.transform(ir(
+                "let a: u64 = 7 / 2 + 7 % 2; let b: i64 = -6 / 2; exit(-7 % 2); exit((-1 < 0) as i64);"
+            )),
+            ir("let a: u64 = 4; let b: i64 = -6 / 2; exit(-7 % 2); exit((-1 < 0) as i64);")
+        );
+    }
+
+    #[test]
+    fn suffixed_literals_are_not_folded() {
+        assert_eq!(
+            ConstantFolding.transform(ir("let a: u64 = 4294967295u32 + 1u32 + (1 + 1);")),
+            ir("let a: u64 = 4294967295u32 + 1u32 + 2;")
+        );
+    }
+
+    #[test]
+    fn calls_keep_their_side_effects() {
+        let input = ir("fn f() -> u64 { println 1; return 1; } let a: u64 = f(); let b: u64 = f() + 1; let c: u64 = f() + 1; let d: u64 = f() * 0; f(); exit(d);");
+        assert_eq!(
+            run_passes(
+                input.clone(),
+                &[
+                    Box::new(StrengthReduction),
+                    Box::new(CommonSubexpressionElimination),
+                    Box::new(DeadCodeElimination),
+                ]
+            ),
+            input
+        );
+    }
+
+    #[test]
+    fn branches() {
+        let input = ir("let a: u64 = 1; let b: u64 = a; let c: u64 = 2; let d: u64 = a + 1; if b > 0 { let e: u64 = a + 1; exit(e); } else { let a: u64 = 5; let f: u64 = a + 1; exit(b + f); } exit(0);");
+        // The inner `a` gets a name of its own, so `b` is a copy of the outer one everywhere
+        let expected = ir("let a: u64 = 1; let d: u64 = a + 1; if a > 0 { let e: u64 = d; exit(e); } else { let a__1: u64 = 5; let f: u64 = a__1 + 1; exit(a + f); } exit(0);");
+        assert_eq!(
+            run_passes(
+                input,
+                &[
+                    Box::new(CopyPropagation),
+                    Box::new(CommonSubexpressionElimination),
+                    Box::new(DeadCodeElimination),
+                ]
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let input = ir("let a: u64 = 1; let b: u64 = a; let c: u64 = 2; while b > 0 { let d: u64 = c; let e: u64 = a + 1; println a + 1; } exit(0);");
+        let expected = ir("let a: u64 = 1; while a > 0 { println a + 1; } exit(0);");
+        assert_eq!(
+            run_passes(
+                input,
+                &[
+                    Box::new(CopyPropagation),
+                    Box::new(CommonSubexpressionElimination),
+                    Box::new(DeadCodeElimination),
+                ]
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn for_loops() {
+        let input = ir("let a: u64 = 1; let b: u64 = a; let c: u64 = a + 1; for a in 0..b { let d: u64 = a + 1; let e: u64 = c; println b + d + e; }");
+        // The loop variable gets a name of its own, the outer `a` is still there in the body
+        let expected = ir("let a: u64 = 1; let b: u64 = a; let c: u64 = a + 1; { let mut __next_a: u64 = 0; let __end_a: u64 = a; while __next_a < a { let a__1: u64 = __next_a; __next_a = __next_a + 1; let d: u64 = a__1 + 1; let e: u64 = c; println a + d + c; } }");
+        assert_eq!(
+            run_passes(
+                input,
+                &[
+                    Box::new(CopyPropagation),
+                    Box::new(CommonSubexpressionElimination),
+                ]
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn nothing_runs_after_return() {
+        let tokens = tokenizer::Tokenizer::new(
+            "fn f(a: u64) -> u64 { let b: u64 = 2; let c: u64 = a; return b; println c; }",
+            "tests::ir_passes".to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        let ast = ast::AstParser::new(tokens).parse().unwrap();
+        let mut program = ir::IrGenerator::new(desugar::desugar(ast)).generate();
+        let body = program.functions.pop().unwrap().body;
+        assert_eq!(
+            DeadCodeElimination.transform(body),
+            [
+                ir::IR::DefineVariable {
+                    name: Symbol::intern("b"),
+                    t: Symbol::intern("u64"),
+                    value: ast::AstExpression::Number {
+                        raw: "2".to_string(),
+                        flags: vec![],
+                        suffix: None,
+                        span: SPAN,
+                    },
+                },
+                ir::IR::Return {
+                    value: Some(ast::AstExpression::Identifier {
+                        name: Symbol::intern("b"),
+                        span: SPAN,
+                    })
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn blocks() {
+        let input = ir("let a: u64 = 1; let b: u64 = a; { let a: u64 = 2; let c: u64 = 3; println b; } println a;");
+        // The inner `a` gets a name of its own, so it can't hide the one `b` is a copy of
+        let expected = ir("let a: u64 = 1; { println a; } println a;");
+        assert_eq!(
+            run_passes(
+                input,
+                &[Box::new(CopyPropagation), Box::new(DeadCodeElimination)]
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn nothing_runs_after_break() {
+        assert_eq!(
+            DeadCodeElimination.transform(ir(
+                "let a: u64 = 1; while true { let b: u64 = 2; println a; break; println b; } exit(0);"
+            )),
+            ir("let a: u64 = 1; while true { println a; break; } exit(0);")
+        );
+    }
+
+    #[test]
+    fn assignments() {
+        let passes: [Box<dyn IrTransform>; 3] = [
+            Box::new(CopyPropagation),
+            Box::new(CommonSubexpressionElimination),
+            Box::new(DeadCodeElimination),
+        ];
+        let src = "let mut a: u64 = 1; let b: u64 = a; let c: u64 = a * 2; a = 5; let d: u64 = a * 2; exit(b + c + d);";
+        assert_eq!(run_passes(ir(src), &passes), ir(src));
+
+        // A later iteration sees what an earlier one assigned
+        let src = "let mut i: u64 = 0; let n: u64 = i; let m: u64 = i * 2; while i < 10 { println n; let k: u64 = i * 2; println k; i = i + 1; } exit(m);";
+        assert_eq!(run_passes(ir(src), &passes), ir(src));
+        let src = "let mut x: u64 = 0; for i in 0..3 { println x; x = x + i; }";
+        assert_eq!(run_passes(ir(src), &passes), ir(src));
+
+        assert_eq!(
+            run_passes(
+                ir("let mut y: u64 = 0; let mut z: u64 = 1; y = z; z = 2; exit(y);"),
+                &passes
+            ),
+            ir("let mut y: u64 = 0; let mut z: u64 = 1; y = z; exit(y);")
+        );
+
+        // The result is unused, but the call in it keeps the declaration alive
+        let src = "fn f() -> u64 { println 1; return 2; } let mut x: u64 = 0; x = f(); exit(0);";
+        assert_eq!(run_passes(ir(src), &passes), ir(src));
+    }
+
+    #[test]
+    fn arrays() {
+        let input = ir(
+            "let n: u64 = 2; let i: u64 = n; let a: [u64; 3] = [1 + 1, i, 3]; \
+                        let unused: [u8; 1] = [0]; exit(a[i - 1]);",
+        );
+        assert_eq!(
+            run_passes(input, &super::default_passes()),
+            ir("let n: u64 = 2; let a: [u64; 3] = [2, n, 3]; exit(a[n - 1]);")
+        );
+    }
+}
@@ -0,0 +1,332 @@
+use thiserror::Error;
+
+/// A minimal JSON value, enough for the language server and the machine-readable dumps
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys keep their insertion order so the output is stable
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum JsonError {
+    #[error("unexpected end of json input")]
+    UnexpectedEnd,
+    #[error("unexpected character {found:?} at offset {offset} in json input")]
+    UnexpectedChar { found: char, offset: usize },
+    #[error("invalid escape sequence at offset {offset} in json input")]
+    InvalidEscape { offset: usize },
+    #[error("invalid number at offset {offset} in json input")]
+    InvalidNumber { offset: usize },
+}
+
+impl Json {
+    pub fn parse(input: &str) -> Result<Json, JsonError> {
+        let mut parser = JsonParser {
+            input: input.chars().collect(),
+            offset: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(value),
+            Some(c) => Err(JsonError::UnexpectedChar {
+                found: c,
+                offset: parser.offset,
+            }),
+        }
+    }
+
+    pub fn object<K: Into<String>>(entries: impl IntoIterator<Item = (K, Json)>) -> Json {
+        Json::Object(entries.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    pub fn string(s: impl Into<String>) -> Json {
+        Json::String(s.into())
+    }
+
+    /// Looks up a key of an object, `None` for missing keys and non-objects
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl std::fmt::Display for Json {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(s) => write_string(f, s),
+            Json::Array(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    f.write_str("\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    f.write_str("\"")
+}
+
+struct JsonParser {
+    input: Vec<char>,
+    offset: usize,
+}
+
+impl JsonParser {
+    fn value(&mut self) -> Result<Json, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(JsonError::UnexpectedEnd),
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.offset += 1;
+                let mut values = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.offset += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => continue,
+                        ']' => return Ok(Json::Array(values)),
+                        found => return Err(self.unexpected(found)),
+                    }
+                }
+            }
+            Some('{') => {
+                self.offset += 1;
+                let mut entries = vec![];
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.offset += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ':' => {}
+                        found => return Err(self.unexpected(found)),
+                    }
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next()? {
+                        ',' => continue,
+                        '}' => return Ok(Json::Object(entries)),
+                        found => return Err(self.unexpected(found)),
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(found) => Err(JsonError::UnexpectedChar {
+                found,
+                offset: self.offset,
+            }),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+        for expected in keyword.chars() {
+            let found = self.next()?;
+            if found != expected {
+                return Err(self.unexpected(found));
+            }
+        }
+        Ok(value)
+    }
+
+    fn string(&mut self) -> Result<String, JsonError> {
+        match self.next()? {
+            '"' => {}
+            found => return Err(self.unexpected(found)),
+        }
+        let mut s = String::new();
+        loop {
+            match self.next()? {
+                '"' => return Ok(s),
+                '\\' => {
+                    let escape = self.offset - 1;
+                    s.push(match self.next()? {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let high = self.hex4(escape)?;
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                if self.next()? != '\\' || self.next()? != 'u' {
+                                    return Err(JsonError::InvalidEscape { offset: escape });
+                                }
+                                let low = self.hex4(escape)?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(JsonError::InvalidEscape { offset: escape });
+                                }
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code)
+                                .ok_or(JsonError::InvalidEscape { offset: escape })?
+                        }
+                        _ => return Err(JsonError::InvalidEscape { offset: escape }),
+                    });
+                }
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self, escape: usize) -> Result<u32, JsonError> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .next()?
+                .to_digit(16)
+                .ok_or(JsonError::InvalidEscape { offset: escape })?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Json, JsonError> {
+        let start = self.offset;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+        {
+            self.offset += 1;
+        }
+        let raw: String = self.input[start..self.offset].iter().collect();
+        raw.parse()
+            .map(Json::Number)
+            .map_err(|_| JsonError::InvalidNumber { offset: start })
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.offset += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.offset).copied()
+    }
+
+    fn next(&mut self) -> Result<char, JsonError> {
+        let c = self.peek().ok_or(JsonError::UnexpectedEnd)?;
+        self.offset += 1;
+        Ok(c)
+    }
+
+    fn unexpected(&self, found: char) -> JsonError {
+        JsonError::UnexpectedChar {
+            found,
+            offset: self.offset - 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Json, JsonError};
+
+    #[test]
+    fn round_trip() {
+        let src = r#"{"id":1,"params":{"text":"a\n\"b\"","list":[true,false,null,-2.5]}}"#;
+        let json = Json::parse(src).unwrap();
+        assert_eq!(
+            json.get("params").and_then(|p| p.get("text")),
+            Some(&Json::string("a\n\"b\""))
+        );
+        assert_eq!(json.to_string(), src);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Json::parse("[1,"), Err(JsonError::UnexpectedEnd));
+        assert_eq!(
+            Json::parse("{} x"),
+            Err(JsonError::UnexpectedChar {
+                found: 'x',
+                offset: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,15 @@
+pub mod ast;
+pub mod cache;
+pub mod cbackend;
+pub mod config;
+pub mod const_eval;
+pub mod desugar;
+pub mod diagnostics;
+pub mod formatter;
+pub mod ir;
+pub mod ir_passes;
+pub mod json;
+pub mod lsp;
+pub mod symbol;
+pub mod token_cursor;
+pub mod tokenizer;
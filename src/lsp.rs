@@ -0,0 +1,280 @@
+use std::io::{BufRead, Write};
+
+use crate::{
+    ast,
+    diagnostics::{self, Diagnostics},
+    json::Json,
+    tokenizer,
+};
+
+/// Runs a minimal language server over the given streams until the client sends `exit`.
+///
+/// Supported are `initialize`, `shutdown`, `exit`, and full-document sync through
+/// `textDocument/didOpen` and `textDocument/didChange`, each of which answers with a
+/// `textDocument/publishDiagnostics` notification. A message that isn't JSON gets a parse error.
+pub fn run(mut input: impl BufRead, mut output: impl Write) -> std::io::Result<()> {
+    while let Some(message) = read_message(&mut input)? {
+        let Ok(message) = Json::parse(&message) else {
+            write_message(&mut output, &error(None, -32700, "parse error"))?;
+            continue;
+        };
+        let id = message.get("id").cloned();
+        let params = message.get("params");
+
+        match message.get("method").and_then(Json::as_str) {
+            Some("initialize") => {
+                let capabilities = Json::object([(
+                    "capabilities",
+                    // 1 = the client always sends the full document
+                    Json::object([("textDocumentSync", Json::from(1))]),
+                )]);
+                write_message(&mut output, &response(id, capabilities))?;
+            }
+            Some("shutdown") => write_message(&mut output, &response(id, Json::Null))?,
+            Some("exit") => return Ok(()),
+            Some("textDocument/didOpen") => {
+                let document = params.and_then(|p| p.get("textDocument"));
+                if let (Some(uri), Some(text)) = (
+                    document.and_then(|d| d.get("uri")).and_then(Json::as_str),
+                    document.and_then(|d| d.get("text")).and_then(Json::as_str),
+                ) {
+                    write_message(&mut output, &publish_diagnostics(uri, text))?;
+                }
+            }
+            Some("textDocument/didChange") => {
+                let uri = params
+                    .and_then(|p| p.get("textDocument"))
+                    .and_then(|d| d.get("uri"))
+                    .and_then(Json::as_str);
+                let text = params
+                    .and_then(|p| p.get("contentChanges"))
+                    .and_then(Json::as_array)
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change.get("text"))
+                    .and_then(Json::as_str);
+                if let (Some(uri), Some(text)) = (uri, text) {
+                    write_message(&mut output, &publish_diagnostics(uri, text))?;
+                }
+            }
+            // Unknown notifications are ignored, unknown requests still need an answer
+            _ if id.is_some() => {
+                write_message(&mut output, &error(id, -32601, "method not found"))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Runs the front end on a document and collects everything it reports
+pub fn check(file_name: &str, text: &str) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+    let tokens = tokenizer::Tokenizer::new(text, file_name.to_string())
+        .tokenize_recovering(&mut diagnostics);
+    let mut parser = ast::AstParser::new(tokens);
+    if let Err(report) = parser.parse() {
+        diagnostics.report_each(&report, file_name, text, ast::AstParseError::span);
+    }
+    for warning in parser.warnings() {
+        diagnostics.warning_at(file_name, text, warning.span(), warning.to_string());
+    }
+    diagnostics
+}
+
+fn publish_diagnostics(uri: &str, text: &str) -> Json {
+    let diagnostics = check(uri, text);
+    let errors = diagnostics.errors.iter().map(|e| {
+        let length = e.snippet.as_ref().map_or(1, |snippet| snippet.length);
+        diagnostic_to_json(text, e.location.as_ref(), length, &e.message, 1)
+    });
+    let warnings = diagnostics
+        .warnings
+        .iter()
+        .map(|w| diagnostic_to_json(text, w.location.as_ref(), 1, &w.message, 2));
+
+    Json::object([
+        ("jsonrpc", Json::string("2.0")),
+        ("method", Json::string("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object([
+                ("uri", Json::string(uri)),
+                ("diagnostics", Json::Array(errors.chain(warnings).collect())),
+            ]),
+        ),
+    ])
+}
+
+/// Converts a diagnostic about `length` chars of `text` into an LSP `Diagnostic` object. LSP
+/// positions are 0-based and count UTF-16 code units, and diagnostics without a location are
+/// put at the start of the document
+pub fn diagnostic_to_json(
+    text: &str,
+    location: Option<&diagnostics::Location>,
+    length: usize,
+    message: &str,
+    severity: usize,
+) -> Json {
+    let (line, column) = location.map_or((0, 0), |l| (l.line - 1, l.column - 1));
+    let line_text = text.lines().nth(line).unwrap_or_default();
+    let position = |chars: usize| {
+        let character: usize = line_text.chars().take(chars).map(char::len_utf16).sum();
+        Json::object([
+            ("line", Json::from(line)),
+            ("character", Json::from(character)),
+        ])
+    };
+    Json::object([
+        (
+            "range",
+            Json::object([
+                ("start", position(column)),
+                ("end", position(column + length)),
+            ]),
+        ),
+        ("severity", Json::from(severity)),
+        ("source", Json::string("ghetto-llvm")),
+        ("message", Json::string(message)),
+    ])
+}
+
+/// An error response, `id` is `None` when the request couldn't be read
+fn error(id: Option<Json>, code: i32, message: &str) -> Json {
+    Json::object([
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id.unwrap_or(Json::Null)),
+        (
+            "error",
+            Json::object([
+                ("code", Json::Number(code.into())),
+                ("message", Json::string(message)),
+            ]),
+        ),
+    ])
+}
+
+fn response(id: Option<Json>, result: Json) -> Json {
+    Json::object([
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id.unwrap_or(Json::Null)),
+        ("result", result),
+    ])
+}
+
+/// Reads one `Content-Length` framed message, `None` once the input is closed
+fn read_message(input: &mut impl BufRead) -> std::io::Result<Option<String>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse::<usize>().ok();
+        }
+    }
+
+    let Some(length) = content_length else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "lsp message without a Content-Length header",
+        ));
+    };
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diagnostic_to_json, run};
+    use crate::{diagnostics::Location, json::Json};
+
+    #[test]
+    fn diagnostic_serialization() {
+        let location = Location {
+            file: "file:///main.ghl".to_string(),
+            line: 2,
+            column: 5,
+        };
+        let text = "exit(0);\nlet @ = 1;";
+        assert_eq!(
+            diagnostic_to_json(
+                text,
+                Some(&location),
+                1,
+                "unexpected character found: '@'",
+                1
+            )
+            .to_string(),
+            concat!(
+                r#"{"range":{"start":{"line":1,"character":4},"end":{"line":1,"character":5}},"#,
+                r#""severity":1,"source":"ghetto-llvm","message":"unexpected character found: '@'"}"#
+            )
+        );
+
+        // `é` is one UTF-16 code unit and `𝄞` two
+        let location = Location {
+            file: "file:///main.ghl".to_string(),
+            line: 1,
+            column: 5,
+        };
+        let range = diagnostic_to_json("// é𝄞ab", Some(&location), 2, "", 1);
+        let range = range.get("range").unwrap();
+        let character = |key| range.get(key).unwrap().get("character").cloned();
+        assert_eq!(character("start"), Some(Json::from(4)));
+        assert_eq!(character("end"), Some(Json::from(7)));
+    }
+
+    #[test]
+    fn did_open_publishes_diagnostics() {
+        let frame = |body: &str| format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        let input = [
+            frame(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#),
+            frame(
+                r#"{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{"textDocument":{"uri":"file:///a.ghl","text":"exit(1 @ 2);"}}}"#,
+            ),
+            frame(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+        ]
+        .concat();
+
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(r#""id":1,"result":{"capabilities""#));
+        assert!(output.contains(r#""method":"textDocument/publishDiagnostics""#));
+        assert!(output.contains(r#""start":{"line":0,"character":7}"#));
+    }
+
+    #[test]
+    fn malformed_json_is_a_parse_error() {
+        let frame = |body: &str| format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        let input = [
+            frame("{not json"),
+            frame(r#"{"jsonrpc":"2.0","method":"exit"}"#),
+        ]
+        .concat();
+
+        let mut output = vec![];
+        run(input.as_bytes(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(
+            output.contains(r#""id":null,"error":{"code":-32700"#),
+            "{output}"
+        );
+    }
+}
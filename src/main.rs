@@ -1,13 +1,14 @@
-mod ast;
-mod cbackend;
-mod config;
-mod ir;
-mod tokenizer;
-
-use std::{io::Write, process::Command};
+use std::{
+    io::Write,
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use clap::Parser;
 use error_stack::ResultExt;
+use ghetto_llvm::{
+    ast, cache, cbackend, config, desugar, diagnostics, formatter, ir, ir_passes, lsp, tokenizer,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -16,73 +17,214 @@ pub struct CompilerError;
 
 fn main() -> error_stack::Result<(), CompilerError> {
     let config = config::Config::parse();
-    let input = std::fs::read_to_string(&config.input_file_name)
+    if config.lsp {
+        return lsp::run(std::io::stdin().lock(), std::io::stdout())
+            .change_context(CompilerError)
+            .attach_printable("language server failed");
+    }
+
+    let input_file_name = config
+        .input_file_name
+        .clone()
+        .expect("clap requires an input file unless --lsp is given");
+    let input = std::fs::read_to_string(&input_file_name)
         .attach_printable(format!(
             "failed to open and/read input file: {}",
-            config.input_file_name
+            input_file_name
         ))
         .change_context(CompilerError)?;
 
-    let tokenizer = tokenizer::Tokenizer::new(input, config.input_file_name);
-    let tokens = tokenizer
-        .tokenize()
-        .change_context(CompilerError)
-        .attach_printable("failed to tokenize source code")?;
-
-    if config.dump_tokens {
-        std::fs::write(config.tokens_out_name, format!("{:#?}", tokens))
+    if config.format_src {
+        let tokens = tokenizer::Tokenizer::new(&input, input_file_name)
+            .with_comments()
+            .tokenize()
             .change_context(CompilerError)
-            .attach_printable("failed to dump tokens to file")?;
-    }
-    let mut ast_parser = ast::AstParser::new(tokens);
-    let ast = ast_parser
-        .parse()
-        .change_context(CompilerError)
-        .attach_printable("failed to parse the ast tree")?;
-    if config.dump_ast {
-        std::fs::write(config.ast_out_name, format!("{:#?}", ast))
-            .change_context(CompilerError)
-            .attach_printable("failed to dump ast to file")?;
+            .attach_printable("refusing to format source code that does not tokenize")?;
+        // The shebang is skipped like a comment, but has to survive formatting
+        if let Some(shebang) = tokenizer::shebang(&input) {
+            println!("{shebang}");
+        }
+        print!("{}", formatter::format_tokens(&tokens));
+        return Ok(());
     }
 
-    let ir_generator = ir::IrGenerator::new(ast);
-    let ir = ir_generator.generate();
+    // A cache hit can't produce the intermediate dumps, so don't even look
+    let cache = if config.no_cache || config.dump_tokens || config.dump_ast || config.dump_ast_dot {
+        None
+    } else {
+        cache::Cache::default_location()
+    };
+    let cache_key = cache::Cache::key(&input, &config.backend_options());
+
+    let out = match cache.as_ref().and_then(|cache| cache.load(&cache_key)) {
+        Some(out) => out,
+        None => {
+            let out = generate_c(&config, &input_file_name, &input)?;
+            if let Some(cache) = &cache {
+                if let Err(e) = cache.store(&cache_key, &out) {
+                    eprintln!("warning: failed to store compilation cache: {e}");
+                }
+            }
+            out
+        }
+    };
 
-    let cb = cbackend::CBackend::new(ir);
-    let out = cb.compile().unwrap();
     if config.dump_c {
         let mut file = std::fs::File::options()
             .write(true)
             .create(true)
             .truncate(true)
-            .open(config.c_out_name)
+            .open(&config.c_out_name)
             .unwrap();
         file.write_all(&out)
             .change_context(CompilerError)
             .attach_printable("failed to dump out the c code")?;
     }
 
-    compile_c(&out, &config.output_exe_name);
+    compile_c(&out, &config.output_file_name(), &config.c_compiler_flags());
+
+    if let Some(dependency_file) = &config.dependency_file {
+        std::fs::write(dependency_file, config.dependency_rule())
+            .change_context(CompilerError)
+            .attach_printable("failed to write the dependency file")?;
+    }
 
     Ok(())
 }
 
-fn compile_c(source: &[u8], out_name: &str) {
-    let mut file = std::fs::File::options()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open("main.c")
-        .unwrap();
-    file.write_all(source)
+/// Runs every phase from tokenizing up to the C backend
+fn generate_c(
+    config: &config::Config,
+    input_file_name: &str,
+    input: &str,
+) -> error_stack::Result<Vec<u8>, CompilerError> {
+    let mut diagnostics = diagnostics::Diagnostics::default();
+    let tokenizer = tokenizer::Tokenizer::new(input, input_file_name.to_string());
+    let tokens = tokenizer.tokenize_recovering(&mut diagnostics);
+
+    if config.dump_tokens {
+        let dump = match config.dump_format {
+            config::DumpFormat::Debug => format!("{:#?}", tokens),
+            config::DumpFormat::Json => tokenizer::tokens_to_json(&tokens, input).to_string(),
+        };
+        std::fs::write(&config.tokens_out_name, dump)
+            .change_context(CompilerError)
+            .attach_printable("failed to dump tokens to file")?;
+    }
+    let mut ast_parser = ast::AstParser::new(tokens);
+    let ast = match ast_parser.parse() {
+        Ok(ast) => ast,
+        Err(report) => {
+            diagnostics.report_each(&report, input_file_name, input, ast::AstParseError::span);
+            vec![]
+        }
+    };
+    for warning in ast_parser.warnings() {
+        diagnostics.warning_at(input_file_name, input, warning.span(), warning.to_string());
+    }
+
+    eprint!("{}", diagnostics.render());
+    if diagnostics.has_errors() {
+        return Err(error_stack::Report::new(CompilerError)).attach_printable(format!(
+            "aborting due to {} previous error(s)",
+            diagnostics.errors.len()
+        ));
+    }
+
+    if config.dump_ast {
+        let dump = match config.ast_format {
+            config::DumpFormat::Debug => format!("{:#?}", ast),
+            config::DumpFormat::Json => ast::program_to_json(&ast).to_string(),
+        };
+        std::fs::write(&config.ast_out_name, dump)
+            .change_context(CompilerError)
+            .attach_printable("failed to dump ast to file")?;
+    }
+    if config.dump_ast_dot {
+        std::fs::write(&config.ast_dot_out_name, ast::dot::program(&ast))
+            .change_context(CompilerError)
+            .attach_printable("failed to dump ast graph to file")?;
+    }
+
+    let ir_generator = ir::IrGenerator::new(desugar::desugar(ast));
+    let passes = if config.overflow_check {
+        ir_passes::overflow_checked_passes()
+    } else {
+        ir_passes::default_passes()
+    };
+    let ir = ir_generator
+        .generate()
+        .map_bodies(|body| ir_passes::run_passes(body, &passes));
+
+    let cb = cbackend::CBackend::new(ir, config.backend_options());
+    Ok(cb.compile().unwrap())
+}
+
+/// The C compiler `compile_c` invokes
+const C_COMPILER: &str = if cfg!(windows) { "clang.exe" } else { "clang" };
+
+fn compile_c(source: &[u8], out_name: &str, flags: &[String]) {
+    let file = TempSource::create(source)
         .change_context(CompilerError)
         .attach_printable("failed to dump out the c code")
         .unwrap();
-    Command::new("clang")
-        .arg("main.c")
+    Command::new(C_COMPILER)
+        .arg(&file.path)
         .arg("-o")
         .arg(out_name)
+        .args(flags)
         .output()
         .unwrap();
-    Command::new("rm").arg("main.c").output().unwrap();
+}
+
+/// A uniquely named C file in the system temp directory, removed again on drop
+struct TempSource {
+    path: std::path::PathBuf,
+}
+
+impl TempSource {
+    fn create(source: &[u8]) -> std::io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "ghetto-llvm-{}-{}.c",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = std::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        file.write_all(source)?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for TempSource {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TempSource;
+
+    #[test]
+    fn concurrent_temp_sources_do_not_clobber() {
+        let handles: Vec<_> = (0..2)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let source = format!("int main() {{ return {i}; }}\n");
+                    let file = TempSource::create(source.as_bytes()).unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    assert_eq!(std::fs::read_to_string(&file.path).unwrap(), source);
+                    file.path.clone()
+                })
+            })
+            .collect();
+        let paths: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_ne!(paths[0], paths[1]);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
 }
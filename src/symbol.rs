@@ -0,0 +1,82 @@
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+/// An interned name. Interning the same text twice gives the same symbol, so comparing and
+/// hashing symbols never looks at the text itself
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+/// Every name interned so far. Names are never freed, which is fine as long as the number of
+/// distinct names stays bounded by the source code being compiled
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(Default::default)
+}
+
+impl Symbol {
+    pub fn intern(name: &str) -> Self {
+        let mut interner = interner().lock().expect("the interner never panics");
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(
+            interner
+                .names
+                .len()
+                .try_into()
+                .expect("fewer than 2^32 distinct names"),
+        );
+        let name: &'static str = Box::leak(name.into());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().lock().expect("the interner never panics").names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl std::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Shows the name rather than the number, so AST and IR dumps stay readable
+impl std::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symbol;
+
+    #[test]
+    fn interning_deduplicates() {
+        let a = Symbol::intern("interning_deduplicates");
+        assert_eq!(a, Symbol::intern(&String::from("interning_deduplicates")));
+        assert_ne!(a, Symbol::intern("interning_deduplicates_too"));
+        assert_eq!(a.as_str(), "interning_deduplicates");
+        assert_eq!(
+            format!("{a} {a:?}"),
+            "interning_deduplicates \"interning_deduplicates\""
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use error_stack::ResultExt;
+use thiserror::Error;
+
+use crate::{
+    symbol::Symbol,
+    tokenizer::{Span, Token, TokenKind},
+};
+
+/// Reading a token through [`TokenCursor::expect`] failed
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("expected {expected}, found {found}")]
+pub struct UnexpectedToken {
+    pub expected: TokenKind,
+    pub found: TokenKind,
+    pub span: Span,
+}
+
+/// A token stream the parser reads front to back, with lookahead of any distance
+#[derive(Debug)]
+pub struct TokenCursor<'src> {
+    /// Reversed, so the next token is the last one
+    tokens: Vec<Token<'src>>,
+    /// The token eaten last
+    previous: Option<Token<'src>>,
+}
+
+impl<'src> TokenCursor<'src> {
+    pub fn new(mut tokens: Vec<Token<'src>>) -> Self {
+        tokens.reverse();
+        Self {
+            tokens,
+            previous: None,
+        }
+    }
+
+    pub fn peek(&self) -> Option<&Token<'src>> {
+        self.tokens.last()
+    }
+
+    /// Looks `k` tokens past the next one without consuming anything, `peek_n(0)` is the same as
+    /// `peek()`
+    pub fn peek_n(&self, k: usize) -> Option<&Token<'src>> {
+        self.tokens
+            .len()
+            .checked_sub(k + 1)
+            .map(|index| &self.tokens[index])
+    }
+
+    /// The span of the next token, or an empty span at offset 0 if there is none
+    pub fn peek_span(&self) -> Span {
+        self.peek().map_or(Span { start: 0, end: 0 }, Token::span)
+    }
+
+    /// How many tokens are left, the end of file included
+    pub fn remaining(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// The token eaten last, `None` before the first one
+    pub fn previous(&self) -> Option<&Token<'src>> {
+        self.previous.as_ref()
+    }
+
+    pub fn eat(&mut self) -> Option<Token<'src>> {
+        let token = self.tokens.pop();
+        if token.is_some() {
+            self.previous.clone_from(&token);
+        }
+        token
+    }
+
+    /// Consumes the next token if it is a `kind`. Anything else is left in place
+    pub fn expect(&mut self, kind: TokenKind) -> error_stack::Result<Token<'src>, UnexpectedToken> {
+        let found = self.peek().map_or(TokenKind::Eof, Token::kind);
+        if found == kind {
+            return Ok(self.eat().expect("just peeked"));
+        }
+        let span = self.peek_span();
+        let error = UnexpectedToken {
+            expected: kind,
+            found,
+            span,
+        };
+        let message = format!("{error} at {span}");
+        Err(error_stack::Report::new(error)).attach_printable(message)
+    }
+
+    /// Like [`TokenCursor::expect`] for an identifier, giving back just its name
+    pub fn expect_identifier(&mut self) -> error_stack::Result<Symbol, UnexpectedToken> {
+        match self.expect(TokenKind::Identifier)? {
+            Token::Identifier { name, span: _ } => Ok(name),
+            _ => unreachable!("expect only returns identifiers"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TokenCursor, UnexpectedToken};
+    use crate::tokenizer::{BinaryOp, Span, Token, TokenKind, Tokenizer};
+
+    fn cursor(src: &str) -> TokenCursor<'_> {
+        TokenCursor::new(
+            Tokenizer::new(src, "tests::cursor".to_string())
+                .tokenize()
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn lookahead() {
+        let cursor = cursor("a = 1");
+        assert_eq!(cursor.peek(), cursor.peek_n(0));
+        assert_eq!(
+            cursor.peek_n(1).map(Token::kind),
+            Some(TokenKind::BinaryOperator(BinaryOp::SingleEqual))
+        );
+        assert_eq!(cursor.peek_n(3).map(Token::kind), Some(TokenKind::Eof));
+        assert_eq!(cursor.peek_n(4), None);
+    }
+
+    #[test]
+    fn expect() {
+        let mut cursor = cursor("a: = 1");
+        assert_eq!(cursor.expect_identifier().unwrap().as_str(), "a");
+        assert!(cursor.expect(TokenKind::Colon).is_ok());
+
+        let err = cursor.expect_identifier().unwrap_err();
+        assert_eq!(
+            *err.current_context(),
+            UnexpectedToken {
+                expected: TokenKind::Identifier,
+                found: TokenKind::BinaryOperator(BinaryOp::SingleEqual),
+                span: Span { start: 3, end: 4 },
+            }
+        );
+        assert_eq!(
+            err.current_context().to_string(),
+            "expected an identifier, found `=`"
+        );
+        // The unexpected token is still there
+        assert!(cursor
+            .expect(TokenKind::BinaryOperator(BinaryOp::SingleEqual))
+            .is_ok());
+    }
+}
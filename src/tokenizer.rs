@@ -1,12 +1,25 @@
-use error_stack::ResultExt;
+use std::borrow::Cow;
+
 use thiserror::Error;
 
+use crate::{
+    diagnostics::{Diagnostics, Location},
+    json::Json,
+    symbol::Symbol,
+};
+
 #[derive(Debug)]
-pub struct Tokenizer {
-    source: Vec<char>,
+pub struct Tokenizer<'src> {
+    source: &'src str,
     source_code_file_name: String,
-    not_changed: String,
+    /// Byte index of the next character in `source`
+    position: usize,
+    /// Char index of the next character, which is what spans count in
     offset: usize,
+    /// Set once the [`Iterator`] has produced either [`Token::Eof`] or an error
+    finished: bool,
+    /// Whether plain comments become [`Token::Comment`]s instead of being skipped
+    comments: bool,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -14,183 +27,1329 @@ pub enum BinaryOp {
     Plus,
     Minus,
     Star,
+    Slash,
+    Percent,
     SingleEqual,
+    DoubleEqual,
+    NotEqual,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    And,
+    Or,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+}
+
+impl BinaryOp {
+    /// `==`, `!=`, `<`, `>`, `<=` and `>=`
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            BinaryOp::DoubleEqual
+                | BinaryOp::NotEqual
+                | BinaryOp::Less
+                | BinaryOp::Greater
+                | BinaryOp::LessEqual
+                | BinaryOp::GreaterEqual
+        )
+    }
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BinaryOp::Plus => "+",
+            BinaryOp::Minus => "-",
+            BinaryOp::Star => "*",
+            BinaryOp::Slash => "/",
+            BinaryOp::Percent => "%",
+            BinaryOp::SingleEqual => "=",
+            BinaryOp::DoubleEqual => "==",
+            BinaryOp::NotEqual => "!=",
+            BinaryOp::Less => "<",
+            BinaryOp::Greater => ">",
+            BinaryOp::LessEqual => "<=",
+            BinaryOp::GreaterEqual => ">=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::ShiftLeft => "<<",
+            BinaryOp::ShiftRight => ">>",
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
-#[expect(dead_code)]
 pub enum NumberTypeFlag {
     Signed,
     Floating,
     Hexadecimal,
     Binary,
+    Octal,
 }
 
+/// A token borrowing its text from the source code it was lexed from. Only literals that had
+/// separators or escape sequences removed need their own copy, and names are interned
 #[derive(Debug, PartialEq, Eq, Clone)]
-pub enum Token {
+pub enum Token<'src> {
     Number {
-        raw: String,
+        raw: Cow<'src, str>,
         flags: Vec<NumberTypeFlag>,
-        offset: usize,
+        /// The type written right after the literal, like the `u8` of `255u8`
+        suffix: Option<&'src str>,
+        span: Span,
     },
     BinaryOperator {
         op: BinaryOp,
-        offset: usize,
+        span: Span,
+    },
+    OpenParen {
+        span: Span,
+    },
+    CloseParen {
+        span: Span,
+    },
+    OpenBrace {
+        span: Span,
+    },
+    CloseBrace {
+        span: Span,
+    },
+    OpenBracket {
+        span: Span,
+    },
+    CloseBracket {
+        span: Span,
+    },
+    Comma {
+        span: Span,
+    },
+    /// A `.` that isn't the decimal point of a number literal
+    Dot {
+        span: Span,
+    },
+    /// `+=`, `-=`, `*=` or `/=`, `op` is the operator applied before assigning
+    CompoundAssign {
+        op: BinaryOp,
+        span: Span,
+    },
+    /// The logical not `!`, `!=` is a [`Token::BinaryOperator`]
+    Bang {
+        span: Span,
+    },
+    /// The bitwise not `~`
+    Tilde {
+        span: Span,
+    },
+    /// The `#` starting an attribute like `#[inline]`
+    Hash {
+        span: Span,
+    },
+    Colon {
+        span: Span,
+    },
+    /// `->`
+    Arrow {
+        span: Span,
+    },
+    /// `=>`
+    FatArrow {
+        span: Span,
+    },
+    /// `..`
+    DotDot {
+        span: Span,
+    },
+    /// `..=`
+    DotDotEqual {
+        span: Span,
+    },
+    /// `::`
+    DoubleColon {
+        span: Span,
+    },
+    Let {
+        span: Span,
+    },
+    Mut {
+        span: Span,
+    },
+    Exit {
+        span: Span,
+    },
+    Print {
+        span: Span,
     },
+    Println {
+        span: Span,
+    },
+    If {
+        span: Span,
+    },
+    Else {
+        span: Span,
+    },
+    While {
+        span: Span,
+    },
+    For {
+        span: Span,
+    },
+    In {
+        span: Span,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+    As {
+        span: Span,
+    },
+    Fn {
+        span: Span,
+    },
+    Const {
+        span: Span,
+    },
+    Struct {
+        span: Span,
+    },
+    Enum {
+        span: Span,
+    },
+    Match {
+        span: Span,
+    },
+    Impl {
+        span: Span,
+    },
+    Return {
+        span: Span,
+    },
+    Semicolon {
+        span: Span,
+    },
+    /// `true` or `false`
+    Bool {
+        value: bool,
+        span: Span,
+    },
+    Identifier {
+        name: Symbol,
+        span: Span,
+    },
+    /// A double-quoted string literal, `value` has its escape sequences already resolved
+    String {
+        value: Cow<'src, str>,
+        span: Span,
+    },
+    /// `r"..."` or `r#"..."#` with any number of `#`s, `value` is taken as written
+    RawString {
+        value: &'src str,
+        hashes: usize,
+        span: Span,
+    },
+    /// A single-quoted character literal like `'a'` or `'\n'`
+    Char {
+        value: char,
+        span: Span,
+    },
+    /// A `///` comment, `content` is the rest of the line after the slashes
+    DocComment {
+        content: &'src str,
+        span: Span,
+    },
+    /// A plain `//` or `/* */` comment, slashes included. Only produced for
+    /// [`Tokenizer::with_comments`], `trailing` if there is code before it on its line
+    Comment {
+        text: &'src str,
+        trailing: bool,
+        span: Span,
+    },
+    /// Stands in for input the tokenizer could not make sense of, see
+    /// [`Tokenizer::tokenize_recovering`]
+    Invalid {
+        span: Span,
+    },
+    /// Always the last token of a successfully tokenized input
+    Eof {
+        span: Span,
+    },
+}
+
+/// The types a number literal can be suffixed with
+pub const NUMBER_SUFFIXES: &[&str] = &[
+    "u8", "u16", "u32", "u64", "i8", "i16", "i32", "i64", "f32", "f64",
+];
+
+/// A half-open range of char offsets into the source code
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl Token<'_> {
+    /// The keyword this token was lexed from, `None` if it is not a keyword
+    pub fn keyword(&self) -> Option<&'static str> {
+        // Both literals share a variant, so the discriminant can't tell them apart
+        if let Token::Bool { value, span: _ } = self {
+            return Some(if *value { "true" } else { "false" });
+        }
+        KEYWORDS
+            .iter()
+            .find(|(_, token)| {
+                std::mem::discriminant(&token(Span { start: 0, end: 0 }))
+                    == std::mem::discriminant(self)
+            })
+            .map(|(keyword, _)| *keyword)
+    }
+
+    /// The part of the source code this token was read from
+    pub fn span(&self) -> Span {
+        match self {
+            Token::Number { span, .. }
+            | Token::BinaryOperator { span, .. }
+            | Token::OpenParen { span }
+            | Token::CloseParen { span }
+            | Token::OpenBrace { span }
+            | Token::CloseBrace { span }
+            | Token::OpenBracket { span }
+            | Token::CloseBracket { span }
+            | Token::Comma { span }
+            | Token::Dot { span }
+            | Token::CompoundAssign { span, .. }
+            | Token::Bang { span }
+            | Token::Tilde { span }
+            | Token::Hash { span }
+            | Token::Colon { span }
+            | Token::Arrow { span }
+            | Token::FatArrow { span }
+            | Token::DotDot { span }
+            | Token::DotDotEqual { span }
+            | Token::DoubleColon { span }
+            | Token::Let { span }
+            | Token::Mut { span }
+            | Token::Exit { span }
+            | Token::Print { span }
+            | Token::Println { span }
+            | Token::If { span }
+            | Token::Else { span }
+            | Token::While { span }
+            | Token::For { span }
+            | Token::In { span }
+            | Token::Break { span }
+            | Token::Continue { span }
+            | Token::As { span }
+            | Token::Fn { span }
+            | Token::Const { span }
+            | Token::Struct { span }
+            | Token::Enum { span }
+            | Token::Match { span }
+            | Token::Impl { span }
+            | Token::Return { span }
+            | Token::Semicolon { span }
+            | Token::Bool { span, .. }
+            | Token::Identifier { span, .. }
+            | Token::String { span, .. }
+            | Token::RawString { span, .. }
+            | Token::Char { span, .. }
+            | Token::DocComment { span, .. }
+            | Token::Comment { span, .. }
+            | Token::Invalid { span }
+            | Token::Eof { span } => *span,
+        }
+    }
+
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            Token::Number { .. } => TokenKind::Number,
+            Token::Identifier { .. } => TokenKind::Identifier,
+            Token::String { .. } | Token::RawString { .. } => TokenKind::String,
+            Token::Char { .. } => TokenKind::Char,
+            Token::Bool { .. } => TokenKind::Bool,
+            Token::DocComment { .. } => TokenKind::DocComment,
+            Token::Comment { .. } => TokenKind::Comment,
+            Token::BinaryOperator { op, span: _ } => TokenKind::BinaryOperator(op.clone()),
+            Token::CompoundAssign { op, span: _ } => TokenKind::CompoundAssign(op.clone()),
+            Token::OpenParen { span: _ } => TokenKind::OpenParen,
+            Token::CloseParen { span: _ } => TokenKind::CloseParen,
+            Token::OpenBrace { span: _ } => TokenKind::OpenBrace,
+            Token::CloseBrace { span: _ } => TokenKind::CloseBrace,
+            Token::OpenBracket { span: _ } => TokenKind::OpenBracket,
+            Token::CloseBracket { span: _ } => TokenKind::CloseBracket,
+            Token::Comma { span: _ } => TokenKind::Comma,
+            Token::Dot { span: _ } => TokenKind::Dot,
+            Token::Bang { span: _ } => TokenKind::Bang,
+            Token::Tilde { span: _ } => TokenKind::Tilde,
+            Token::Hash { span: _ } => TokenKind::Hash,
+            Token::Colon { span: _ } => TokenKind::Colon,
+            Token::Arrow { span: _ } => TokenKind::Arrow,
+            Token::FatArrow { span: _ } => TokenKind::FatArrow,
+            Token::DotDot { span: _ } => TokenKind::DotDot,
+            Token::DotDotEqual { span: _ } => TokenKind::DotDotEqual,
+            Token::DoubleColon { span: _ } => TokenKind::DoubleColon,
+            Token::Semicolon { span: _ } => TokenKind::Semicolon,
+            Token::Invalid { span: _ } => TokenKind::Invalid,
+            Token::Eof { span: _ } => TokenKind::Eof,
+            keyword => {
+                TokenKind::Keyword(keyword.keyword().expect("every other token is a keyword"))
+            }
+        }
+    }
+}
+
+/// A token without its contents or position, used to say which token the parser expected
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TokenKind {
+    Number,
+    Identifier,
+    /// Both plain and raw string literals
+    String,
+    Char,
+    Bool,
+    DocComment,
+    Comment,
+    Keyword(&'static str),
+    BinaryOperator(BinaryOp),
+    CompoundAssign(BinaryOp),
     OpenParen,
     CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Comma,
+    Dot,
+    Bang,
+    Tilde,
+    Hash,
     Colon,
-    Let,
-    Exit,
+    Arrow,
+    FatArrow,
+    DotDot,
+    DotDotEqual,
+    DoubleColon,
     Semicolon,
-    Identifier(String),
+    Invalid,
+    Eof,
+}
+
+impl std::fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenKind::Number => f.write_str("a number literal"),
+            TokenKind::Identifier => f.write_str("an identifier"),
+            TokenKind::String => f.write_str("a string literal"),
+            TokenKind::Char => f.write_str("a character literal"),
+            TokenKind::Bool => f.write_str("a boolean literal"),
+            TokenKind::DocComment => f.write_str("a doc comment"),
+            TokenKind::Comment => f.write_str("a comment"),
+            TokenKind::Keyword(keyword) => write!(f, "`{keyword}`"),
+            TokenKind::BinaryOperator(op) => write!(f, "`{op}`"),
+            TokenKind::CompoundAssign(op) => write!(f, "`{op}=`"),
+            TokenKind::OpenParen => f.write_str("`(`"),
+            TokenKind::CloseParen => f.write_str("`)`"),
+            TokenKind::OpenBrace => f.write_str("`{`"),
+            TokenKind::CloseBrace => f.write_str("`}`"),
+            TokenKind::OpenBracket => f.write_str("`[`"),
+            TokenKind::CloseBracket => f.write_str("`]`"),
+            TokenKind::Comma => f.write_str("`,`"),
+            TokenKind::Dot => f.write_str("`.`"),
+            TokenKind::Bang => f.write_str("`!`"),
+            TokenKind::Tilde => f.write_str("`~`"),
+            TokenKind::Hash => f.write_str("`#`"),
+            TokenKind::Colon => f.write_str("`:`"),
+            TokenKind::Arrow => f.write_str("`->`"),
+            TokenKind::FatArrow => f.write_str("`=>`"),
+            TokenKind::DotDot => f.write_str("`..`"),
+            TokenKind::DotDotEqual => f.write_str("`..=`"),
+            TokenKind::DoubleColon => f.write_str("`::`"),
+            TokenKind::Semicolon => f.write_str("`;`"),
+            TokenKind::Invalid => f.write_str("invalid input"),
+            TokenKind::Eof => f.write_str("the end of the file"),
+        }
+    }
+}
+
+impl TokenKind {
+    /// A stable snake_case name for machine-readable output
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenKind::Number => "number",
+            TokenKind::Identifier => "identifier",
+            TokenKind::String => "string",
+            TokenKind::Char => "char",
+            TokenKind::Bool => "bool",
+            TokenKind::DocComment => "doc_comment",
+            TokenKind::Comment => "comment",
+            TokenKind::Keyword(_) => "keyword",
+            TokenKind::BinaryOperator(_) => "binary_operator",
+            TokenKind::CompoundAssign(_) => "compound_assign",
+            TokenKind::OpenParen => "open_paren",
+            TokenKind::CloseParen => "close_paren",
+            TokenKind::OpenBrace => "open_brace",
+            TokenKind::CloseBrace => "close_brace",
+            TokenKind::OpenBracket => "open_bracket",
+            TokenKind::CloseBracket => "close_bracket",
+            TokenKind::Comma => "comma",
+            TokenKind::Dot => "dot",
+            TokenKind::Bang => "bang",
+            TokenKind::Tilde => "tilde",
+            TokenKind::Hash => "hash",
+            TokenKind::Colon => "colon",
+            TokenKind::Arrow => "arrow",
+            TokenKind::FatArrow => "fat_arrow",
+            TokenKind::DotDot => "dot_dot",
+            TokenKind::DotDotEqual => "dot_dot_equal",
+            TokenKind::DoubleColon => "double_colon",
+            TokenKind::Semicolon => "semicolon",
+            TokenKind::Invalid => "invalid",
+            TokenKind::Eof => "eof",
+        }
+    }
+}
+
+/// Serializes `tokens` lexed from `source` as an array of `{"kind", "lexeme", "span"}` objects,
+/// where the lexeme is the token's text exactly as written in the source
+pub fn tokens_to_json(tokens: &[Token], source: &str) -> Json {
+    // Spans count chars, so map them to byte positions to slice the source
+    let mut positions: Vec<usize> = source.char_indices().map(|(i, _)| i).collect();
+    positions.push(source.len());
+    Json::Array(
+        tokens
+            .iter()
+            .map(|token| {
+                let span = token.span();
+                Json::object([
+                    ("kind", Json::string(token.kind().name())),
+                    (
+                        "lexeme",
+                        Json::string(&source[positions[span.start]..positions[span.end]]),
+                    ),
+                    (
+                        "span",
+                        Json::object([
+                            ("start", Json::from(span.start)),
+                            ("end", Json::from(span.end)),
+                        ]),
+                    ),
+                ])
+            })
+            .collect(),
+    )
 }
 
+/// Builds a keyword token read from the given span
+type KeywordToken = fn(Span) -> Token<'static>;
+
+/// Every keyword with the token it is lexed as, anything else shaped like a name is an
+/// [`Token::Identifier`]
+const KEYWORDS: &[(&str, KeywordToken)] = &[
+    ("let", |span| Token::Let { span }),
+    ("mut", |span| Token::Mut { span }),
+    ("exit", |span| Token::Exit { span }),
+    ("print", |span| Token::Print { span }),
+    ("println", |span| Token::Println { span }),
+    ("if", |span| Token::If { span }),
+    ("else", |span| Token::Else { span }),
+    ("while", |span| Token::While { span }),
+    ("for", |span| Token::For { span }),
+    ("in", |span| Token::In { span }),
+    ("break", |span| Token::Break { span }),
+    ("continue", |span| Token::Continue { span }),
+    ("as", |span| Token::As { span }),
+    ("fn", |span| Token::Fn { span }),
+    ("const", |span| Token::Const { span }),
+    ("struct", |span| Token::Struct { span }),
+    ("enum", |span| Token::Enum { span }),
+    ("match", |span| Token::Match { span }),
+    ("impl", |span| Token::Impl { span }),
+    ("return", |span| Token::Return { span }),
+    ("true", |span| Token::Bool { value: true, span }),
+    ("false", |span| Token::Bool { value: false, span }),
+];
+
 #[derive(Debug, Error)]
 pub enum TokenizeError {
     #[error("unexpected char found")]
     UnexpectedChar,
+    #[error("malformed number literal")]
+    MalformedNumber,
+    #[error("unterminated string literal")]
+    UnterminatedString,
+    #[error("unknown escape sequence")]
+    UnknownEscape,
+    #[error("malformed character literal")]
+    MalformedChar,
+    #[error("unterminated block comment")]
+    UnterminatedComment,
+}
+
+/// The `#!` line at the very start of an executable script, without its newline
+pub fn shebang(source: &str) -> Option<&str> {
+    source
+        .starts_with("#!")
+        .then(|| source.lines().next().unwrap_or_default())
+}
+
+/// The character that follows `\` to write `c` inside a literal delimited by `quote`, if it
+/// needs escaping
+pub fn escape_for(c: char, quote: char) -> Option<char> {
+    match c {
+        '\n' => Some('n'),
+        '\t' => Some('t'),
+        '\0' => Some('0'),
+        '\\' => Some('\\'),
+        c if c == quote => Some(quote),
+        _ => None,
+    }
+}
+
+/// Checks the characters following a number literal, `floating` literals can only take a float
+/// type. Nothing following is no suffix at all
+fn number_suffix(suffix: &str, floating: bool) -> Result<Option<&str>, String> {
+    if suffix.is_empty() {
+        return Ok(None);
+    }
+    if !NUMBER_SUFFIXES.contains(&suffix) {
+        return Err(format!("invalid suffix `{suffix}`"));
+    }
+    if floating && !suffix.starts_with('f') {
+        return Err(format!("integer suffix `{suffix}`"));
+    }
+    Ok(Some(suffix))
+}
+
+/// `raw` with its `_` digit separators removed, only allocating if there are any
+fn without_separators(raw: &str) -> Cow<'_, str> {
+    if raw.contains('_') {
+        Cow::Owned(raw.replace('_', ""))
+    } else {
+        Cow::Borrowed(raw)
+    }
+}
+
+/// A lexical error together with where it happened, before it is turned into a report or a
+/// diagnostic
+struct LexError {
+    kind: TokenizeError,
+    offset: usize,
+    message: String,
 }
 
-pub type TokenizerResult = error_stack::Result<Vec<Token>, TokenizeError>;
+pub type TokenizerResult<'src> = error_stack::Result<Vec<Token<'src>>, TokenizeError>;
 
-impl Tokenizer {
-    pub fn new(source_code: String, file_name: String) -> Self {
-        let mut source: Vec<char> = source_code.chars().collect();
-        source.reverse();
+impl<'src> Tokenizer<'src> {
+    pub fn new(source_code: &'src str, file_name: String) -> Self {
         Self {
-            source,
+            source: source_code,
             source_code_file_name: file_name,
-            not_changed: source_code,
+            position: 0,
             offset: 0,
+            finished: false,
+            comments: false,
         }
     }
-    // TODO: Parsing floats, signed, hexadecimal, binary numbers
-    pub fn tokenize(mut self) -> TokenizerResult {
-        fn location_from_offset(input: &str, offset: usize) -> Option<(usize, usize)> {
-            if offset > input.len() {
-                return None;
-            }
-
-            let mut newline_count = 0;
-            let mut line_start = 0;
 
-            for (index, line) in input.lines().enumerate() {
-                let line_end = line_start + line.len();
+    /// Keeps plain comments as tokens, for tools like the formatter that reproduce the source.
+    /// The parser doesn't expect them
+    pub fn with_comments(mut self) -> Self {
+        self.comments = true;
+        self
+    }
+    // TODO: Parsing signed numbers
+    /// Collects every token up to and including [`Token::Eof`], stopping at the first error
+    pub fn tokenize(self) -> TokenizerResult<'src> {
+        self.collect()
+    }
 
-                if offset >= line_start && offset <= line_end {
-                    let column = offset - line_start;
-                    return Some((newline_count + 1, column + 1));
+    /// Like [`Tokenizer::tokenize`], but records every lexical error into `diagnostics` and
+    /// replaces the offending character with a [`Token::Invalid`] so parsing can still be
+    /// attempted on the rest of the input
+    pub fn tokenize_recovering(mut self, diagnostics: &mut Diagnostics) -> Vec<Token<'src>> {
+        let mut tokens = vec![];
+        loop {
+            match self.next_token() {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => {
+                    tokens.push(Token::Eof {
+                        span: self.span_from(self.offset),
+                    });
+                    return tokens;
+                }
+                Err(error) => {
+                    // Errors about a whole lexeme have already consumed it
+                    if self.offset == error.offset {
+                        self.consume();
+                    }
+                    let span = self.span_from(error.offset);
+                    diagnostics.error_at(
+                        &self.source_code_file_name,
+                        self.source,
+                        span,
+                        error.message,
+                    );
+                    tokens.push(Token::Invalid { span });
                 }
-
-                line_start = line_end + 1;
-                newline_count = index + 1;
             }
+        }
+    }
+
+    fn report(&self, error: LexError) -> error_stack::Report<TokenizeError> {
+        let location =
+            Location::from_offset(&self.source_code_file_name, self.source, error.offset);
+        error_stack::Report::new(error.kind)
+            .attach_printable(format!("{location}: {}", error.message))
+    }
 
-            None
+    fn next_token(&mut self) -> Result<Option<Token<'src>>, LexError> {
+        if let Some(comment) = self.skip_trivia()? {
+            return Ok(Some(comment));
         }
-        let mut tokens = vec![];
-        while !self.finished() {
-            self.trim_whitespace();
-            if self.peek().is_some_and(|c| c.is_ascii_digit()) {
-                let mut buffer = String::new();
-                let offset = self.offset;
-                while self.peek().is_some_and(|c| c.is_ascii_digit()) {
-                    buffer.push(self.consume().unwrap());
+        if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            return self.number().map(Some);
+        }
+        let token = match self.peek() {
+            Some('/') if self.peek_nth(1) == Some('/') => {
+                let start = self.offset;
+                let line = self.rest_of_line();
+                return Ok(Some(Token::DocComment {
+                    content: &line[3..],
+                    span: self.span_from(start),
+                }));
+            }
+            Some('+') if self.peek_nth(1) == Some('=') => self.compound_assign(BinaryOp::Plus),
+            Some('+') => Token::BinaryOperator {
+                op: BinaryOp::Plus,
+                span: self.char_span(),
+            },
+            Some('-') if self.peek_nth(1) == Some('=') => self.compound_assign(BinaryOp::Minus),
+            Some('-') if self.peek_nth(1) == Some('>') => {
+                self.two_char_token(|span| Token::Arrow { span })
+            }
+            Some('-') => Token::BinaryOperator {
+                op: BinaryOp::Minus,
+                span: self.char_span(),
+            },
+            Some('*') if self.peek_nth(1) == Some('=') => self.compound_assign(BinaryOp::Star),
+            Some('*') => Token::BinaryOperator {
+                op: BinaryOp::Star,
+                span: self.char_span(),
+            },
+            Some('/') if self.peek_nth(1) == Some('=') => self.compound_assign(BinaryOp::Slash),
+            Some('/') => Token::BinaryOperator {
+                op: BinaryOp::Slash,
+                span: self.char_span(),
+            },
+            Some('%') => Token::BinaryOperator {
+                op: BinaryOp::Percent,
+                span: self.char_span(),
+            },
+            Some('(') => Token::OpenParen {
+                span: self.char_span(),
+            },
+            Some(')') => Token::CloseParen {
+                span: self.char_span(),
+            },
+            Some('{') => Token::OpenBrace {
+                span: self.char_span(),
+            },
+            Some('}') => Token::CloseBrace {
+                span: self.char_span(),
+            },
+            Some('[') => Token::OpenBracket {
+                span: self.char_span(),
+            },
+            Some(']') => Token::CloseBracket {
+                span: self.char_span(),
+            },
+            Some(',') => Token::Comma {
+                span: self.char_span(),
+            },
+            Some('.') if self.peek_nth(1) == Some('.') && self.peek_nth(2) == Some('=') => {
+                let span = Span {
+                    start: self.offset,
+                    end: self.offset + 3,
+                };
+                self.consume();
+                self.consume();
+                Token::DotDotEqual { span }
+            }
+            Some('.') if self.peek_nth(1) == Some('.') => {
+                self.two_char_token(|span| Token::DotDot { span })
+            }
+            Some('.') => Token::Dot {
+                span: self.char_span(),
+            },
+            Some(':') if self.peek_nth(1) == Some(':') => {
+                self.two_char_token(|span| Token::DoubleColon { span })
+            }
+            Some(':') => Token::Colon {
+                span: self.char_span(),
+            },
+            Some(';') => Token::Semicolon {
+                span: self.char_span(),
+            },
+            Some('=') if self.peek_nth(1) == Some('=') => {
+                self.two_char_operator(BinaryOp::DoubleEqual)
+            }
+            Some('=') if self.peek_nth(1) == Some('>') => {
+                self.two_char_token(|span| Token::FatArrow { span })
+            }
+            Some('=') => Token::BinaryOperator {
+                op: BinaryOp::SingleEqual,
+                span: self.char_span(),
+            },
+            Some('!') if self.peek_nth(1) == Some('=') => {
+                self.two_char_operator(BinaryOp::NotEqual)
+            }
+            Some('!') => Token::Bang {
+                span: self.char_span(),
+            },
+            Some('&') if self.peek_nth(1) == Some('&') => self.two_char_operator(BinaryOp::And),
+            Some('&') => Token::BinaryOperator {
+                op: BinaryOp::BitAnd,
+                span: self.char_span(),
+            },
+            Some('|') if self.peek_nth(1) == Some('|') => self.two_char_operator(BinaryOp::Or),
+            Some('|') => Token::BinaryOperator {
+                op: BinaryOp::BitOr,
+                span: self.char_span(),
+            },
+            Some('^') => Token::BinaryOperator {
+                op: BinaryOp::BitXor,
+                span: self.char_span(),
+            },
+            Some('~') => Token::Tilde {
+                span: self.char_span(),
+            },
+            // Only attributes use `#`
+            Some('#') if self.peek_nth(1) == Some('[') => Token::Hash {
+                span: self.char_span(),
+            },
+            Some('<') if self.peek_nth(1) == Some('<') => {
+                self.two_char_operator(BinaryOp::ShiftLeft)
+            }
+            Some('<') if self.peek_nth(1) == Some('=') => {
+                self.two_char_operator(BinaryOp::LessEqual)
+            }
+            Some('<') => Token::BinaryOperator {
+                op: BinaryOp::Less,
+                span: self.char_span(),
+            },
+            Some('>') if self.peek_nth(1) == Some('>') => {
+                self.two_char_operator(BinaryOp::ShiftRight)
+            }
+            Some('>') if self.peek_nth(1) == Some('=') => {
+                self.two_char_operator(BinaryOp::GreaterEqual)
+            }
+            Some('>') => Token::BinaryOperator {
+                op: BinaryOp::Greater,
+                span: self.char_span(),
+            },
+            Some('r') if matches!(self.peek_nth(1), Some('"' | '#')) => {
+                return self.raw_string().map(Some)
+            }
+            c if c.is_some_and(|c| c.is_ascii_alphabetic() || c == '_') => {
+                let (start, position) = (self.offset, self.position);
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+                {
+                    self.consume();
                 }
-                tokens.push(Token::Number {
-                    raw: buffer,
-                    flags: vec![],
-                    offset,
-                });
-                continue;
+
+                let name = self.lexeme(position);
+                let span = self.span_from(start);
+                return Ok(Some(
+                    match KEYWORDS.iter().find(|(keyword, _)| *keyword == name) {
+                        Some((_, token)) => token(span),
+                        None => Token::Identifier {
+                            name: Symbol::intern(name),
+                            span,
+                        },
+                    },
+                ));
             }
+            Some('"') => return self.string().map(Some),
+            Some('\'') => return self.char_literal().map(Some),
+            None => return Ok(None),
+            Some(c) => {
+                return Err(LexError {
+                    kind: TokenizeError::UnexpectedChar,
+                    offset: self.offset,
+                    message: format!("unexpected character found: {c:?}"),
+                })
+            }
+        };
+        self.consume();
+        Ok(Some(token))
+    }
+
+    fn string(&mut self) -> Result<Token<'src>, LexError> {
+        let offset = self.offset;
+        self.consume(); // `"`
+        let start = self.position;
+        // Only allocated once an escape sequence makes the value differ from the source
+        let mut owned: Option<String> = None;
+        let mut unknown_escape = None;
+        loop {
             match self.peek() {
-                Some('+') => {
-                    tokens.push(Token::BinaryOperator {
-                        op: BinaryOp::Plus,
-                        offset: self.offset,
-                    });
-                    self.consume();
+                // Strings can't span lines, which keeps a missing quote from eating the file
+                None | Some('\n') => {
+                    return Err(LexError {
+                        kind: TokenizeError::UnterminatedString,
+                        offset,
+                        message: "unterminated string literal, missing a closing `\"`".to_string(),
+                    })
                 }
-                Some('-') => {
-                    tokens.push(Token::BinaryOperator {
-                        op: BinaryOp::Minus,
-                        offset: self.offset,
-                    });
+                Some('"') => {
+                    let value = match owned {
+                        Some(value) => Cow::Owned(value),
+                        None => Cow::Borrowed(self.lexeme(start)),
+                    };
                     self.consume();
+                    match unknown_escape {
+                        None => {
+                            return Ok(Token::String {
+                                value,
+                                span: self.span_from(offset),
+                            })
+                        }
+                        Some(error) => return Err(error),
+                    }
                 }
-                Some('*') => {
-                    tokens.push(Token::BinaryOperator {
-                        op: BinaryOp::Star,
-                        offset: self.offset,
-                    });
-                    self.consume();
+                Some('\\') => {
+                    let before = self.lexeme(start);
+                    match self.escape("string") {
+                        Ok(c) => owned.get_or_insert_with(|| before.to_string()).push(c),
+                        // Keep going so the whole literal is skipped
+                        Err(error) => {
+                            unknown_escape.get_or_insert(error);
+                        }
+                    }
                 }
-                Some('(') => {
-                    tokens.push(Token::OpenParen);
-                    self.consume();
+                Some(_) => {
+                    let c = self.consume().unwrap();
+                    if let Some(value) = &mut owned {
+                        value.push(c);
+                    }
+                }
+            }
+        }
+    }
+
+    fn raw_string(&mut self) -> Result<Token<'src>, LexError> {
+        let offset = self.offset;
+        self.consume(); // `r`
+        let mut hashes = 0;
+        while self.consume_if('#') {
+            hashes += 1;
+        }
+        if !self.consume_if('"') {
+            return Err(LexError {
+                kind: TokenizeError::UnexpectedChar,
+                offset,
+                message: format!("expected `\"` after `r{}`", "#".repeat(hashes)),
+            });
+        }
+
+        let start = self.position;
+        loop {
+            match self.peek() {
+                None | Some('\n') => {
+                    return Err(LexError {
+                        kind: TokenizeError::UnterminatedString,
+                        offset,
+                        message: format!(
+                            "unterminated raw string literal, missing a closing `\"{}`",
+                            "#".repeat(hashes)
+                        ),
+                    })
+                }
+                Some('"') if (1..=hashes).all(|n| self.peek_nth(n) == Some('#')) => {
+                    let value = self.lexeme(start);
+                    for _ in 0..=hashes {
+                        self.consume();
+                    }
+                    return Ok(Token::RawString {
+                        value,
+                        hashes,
+                        span: self.span_from(offset),
+                    });
                 }
-                Some(')') => {
-                    tokens.push(Token::CloseParen);
+                Some(_) => {
                     self.consume();
                 }
-                Some(':') => {
-                    tokens.push(Token::Colon);
+            }
+        }
+    }
+
+    fn char_literal(&mut self) -> Result<Token<'src>, LexError> {
+        let offset = self.offset;
+        let malformed = |message: &str| LexError {
+            kind: TokenizeError::MalformedChar,
+            offset,
+            message: message.to_string(),
+        };
+        self.consume(); // `'`
+        let value = match self.peek() {
+            Some('\\') => self.escape("character")?,
+            Some('\'') => {
+                self.consume();
+                return Err(malformed("empty character literal"));
+            }
+            None | Some('\n') => {
+                return Err(malformed(
+                    "unterminated character literal, missing a closing `'`",
+                ))
+            }
+            Some(_) => self.consume().unwrap(),
+        };
+
+        if self.peek() != Some('\'') {
+            while self.peek().is_some_and(|c| c != '\'' && c != '\n') {
+                self.consume();
+            }
+            return Err(if self.consume_if('\'') {
+                malformed("character literal may only contain one character")
+            } else {
+                malformed("unterminated character literal, missing a closing `'`")
+            });
+        }
+        self.consume();
+        Ok(Token::Char {
+            value,
+            span: self.span_from(offset),
+        })
+    }
+
+    /// Consumes an escape sequence starting at the `\\`, `literal` names what it is in for
+    /// error messages
+    fn escape(&mut self, literal: &str) -> Result<char, LexError> {
+        let offset = self.offset;
+        self.consume(); // `\`
+        match self.consume() {
+            Some('n') => Ok('\n'),
+            Some('t') => Ok('\t'),
+            Some('0') => Ok('\0'),
+            Some(c @ ('\\' | '"' | '\'')) => Ok(c),
+            other => Err(LexError {
+                kind: TokenizeError::UnknownEscape,
+                offset,
+                message: format!(
+                    "unknown escape sequence `\\{}` in {literal} literal",
+                    other.map(String::from).unwrap_or_default()
+                ),
+            }),
+        }
+    }
+
+    fn consume_if(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.consume();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Consumes the first character of a two character operator, `next_token` consumes the
+    /// second one
+    fn two_char_operator(&mut self, op: BinaryOp) -> Token<'src> {
+        let span = self.two_char_span();
+        self.consume();
+        Token::BinaryOperator { op, span }
+    }
+
+    /// Like [`Tokenizer::two_char_operator`] for punctuation that isn't an operator
+    fn two_char_token(&mut self, token: fn(Span) -> Token<'static>) -> Token<'src> {
+        let span = self.two_char_span();
+        self.consume();
+        token(span)
+    }
+
+    /// Like [`Tokenizer::two_char_operator`] for `op` followed by `=`
+    fn compound_assign(&mut self, op: BinaryOp) -> Token<'src> {
+        let span = self.two_char_span();
+        self.consume();
+        Token::CompoundAssign { op, span }
+    }
+
+    /// The span of the next character, for tokens that `next_token` consumes after building
+    fn char_span(&self) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset + 1,
+        }
+    }
+
+    fn two_char_span(&self) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset + 2,
+        }
+    }
+
+    /// The span from `start` up to everything consumed so far
+    fn span_from(&self, start: usize) -> Span {
+        Span {
+            start,
+            end: self.offset,
+        }
+    }
+
+    fn number(&mut self) -> Result<Token<'src>, LexError> {
+        let (offset, position) = (self.offset, self.position);
+        if self.peek() == Some('0') {
+            match self.peek_nth(1) {
+                Some('x') => return self.prefixed_number(16, NumberTypeFlag::Hexadecimal),
+                Some('b') => return self.prefixed_number(2, NumberTypeFlag::Binary),
+                Some('o') => return self.prefixed_number(8, NumberTypeFlag::Octal),
+                _ => {}
+            }
+        }
+
+        self.digits();
+        let mut flags = vec![];
+
+        let fraction_follows =
+            |t: &Self| t.peek() == Some('.') && t.peek_nth(1).is_some_and(|c| c.is_ascii_digit());
+        if fraction_follows(self) {
+            self.consume();
+            self.digits();
+            flags.push(NumberTypeFlag::Floating);
+
+            if fraction_follows(self) {
+                // Consume the rest so something like `1.2.3` is reported as a whole
+                while fraction_follows(self) {
                     self.consume();
+                    self.digits();
                 }
-                Some(';') => {
-                    tokens.push(Token::Semicolon);
+                return Err(LexError {
+                    kind: TokenizeError::MalformedNumber,
+                    offset,
+                    message: format!(
+                        "malformed number literal `{}`",
+                        without_separators(self.lexeme(position))
+                    ),
+                });
+            }
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek_nth(1), Some('+' | '-')));
+            if !self.peek_nth(sign + 1).is_some_and(|c| c.is_ascii_digit()) {
+                for _ in 0..=sign {
                     self.consume();
                 }
-                Some('=') => {
-                    tokens.push(Token::BinaryOperator {
-                        op: BinaryOp::SingleEqual,
-                        offset: self.offset,
-                    });
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+                {
                     self.consume();
                 }
-                c if c.is_some_and(|c| c.is_ascii_alphabetic() || *c == '_') => {
-                    let mut buf = String::new();
-                    while self
-                        .peek()
-                        .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
-                    {
-                        buf.push(self.consume().unwrap());
-                    }
+                return Err(LexError {
+                    kind: TokenizeError::MalformedNumber,
+                    offset,
+                    message: format!(
+                        "missing digits in the exponent of number literal `{}`",
+                        without_separators(self.lexeme(position))
+                    ),
+                });
+            }
+            for _ in 0..=sign {
+                self.consume();
+            }
+            self.digits();
+            if flags.is_empty() {
+                flags.push(NumberTypeFlag::Floating);
+            }
+        }
+        let raw = without_separators(self.lexeme(position));
 
-                    tokens.push(match buf.as_str() {
-                        "let" => Token::Let,
-                        "exit" => Token::Exit,
-                        _ => Token::Identifier(buf),
-                    });
-                }
-                None => {
-                    return Ok(tokens);
-                }
-                c => {
-                    let location = location_from_offset(&self.not_changed, self.offset)
-                        .expect("Something really bad happened");
-                    return Err(TokenizeError::UnexpectedChar).attach_printable(format!(
-                        "./{}:{}:{}: unexpected character found: {c:?}",
-                        self.source_code_file_name, location.0, location.1,
-                    ));
+        let suffix_position = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.consume();
+        }
+        let floating = flags.contains(&NumberTypeFlag::Floating);
+        let suffix =
+            number_suffix(self.lexeme(suffix_position), floating).map_err(|message| LexError {
+                kind: TokenizeError::MalformedNumber,
+                offset,
+                message: format!("{message} for number literal `{raw}`"),
+            })?;
+
+        Ok(Token::Number {
+            raw,
+            flags,
+            suffix,
+            span: self.span_from(offset),
+        })
+    }
+
+    /// Lexes a literal like `0xff`, whose raw form keeps the prefix
+    fn prefixed_number(
+        &mut self,
+        radix: u32,
+        flag: NumberTypeFlag,
+    ) -> Result<Token<'src>, LexError> {
+        let (offset, position) = (self.offset, self.position);
+        self.consume();
+        self.consume();
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.consume();
+        }
+
+        // `f` is a hex digit, so only integer suffixes can follow a prefixed literal
+        let lexeme = self.lexeme(position);
+        let (literal, suffix) = match lexeme[2..].find(['u', 'i']) {
+            Some(at) => lexeme.split_at(at + 2),
+            None => (lexeme, ""),
+        };
+        let raw = without_separators(literal);
+        let digits = &raw[2..];
+        let message = if digits.is_empty() {
+            format!("missing digits after `{raw}`")
+        } else if let Some(invalid) = digits.chars().find(|c| !c.is_digit(radix)) {
+            format!("invalid digit {invalid:?} in number literal `{raw}`")
+        } else {
+            match number_suffix(suffix, false) {
+                Ok(suffix) => {
+                    return Ok(Token::Number {
+                        raw,
+                        flags: vec![flag],
+                        suffix,
+                        span: self.span_from(offset),
+                    })
                 }
+                Err(message) => format!("{message} for number literal `{raw}`"),
             }
+        };
+        Err(LexError {
+            kind: TokenizeError::MalformedNumber,
+            offset,
+            message,
+        })
+    }
+
+    /// Consumes a run of decimal digits and `_` separators like in `1_000_000`
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '_') {
+            self.consume();
         }
-        Ok(tokens)
     }
 
-    fn finished(&self) -> bool {
-        self.source.is_empty()
+    /// The source code from byte `position` up to everything consumed so far
+    fn lexeme(&self, position: usize) -> &'src str {
+        &self.source[position..self.position]
     }
 
-    fn peek(&self) -> Option<&char> {
-        self.source.last()
+    fn peek(&self) -> Option<char> {
+        self.peek_nth(0)
+    }
+    /// Looks `n` characters past the next one without consuming anything, `peek_nth(0)` is
+    /// the same as `peek()`
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.source[self.position..].chars().nth(n)
     }
     fn consume(&mut self) -> Option<char> {
+        let c = self.peek()?;
         self.offset += 1;
-        self.source.pop()
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    /// Skips whitespace and comments up to the next token. With [`Tokenizer::with_comments`]
+    /// it stops at the first comment instead, returning it
+    fn skip_trivia(&mut self) -> Result<Option<Token<'src>>, LexError> {
+        if self.position == 0 && shebang(self.source).is_some() {
+            self.rest_of_line();
+        }
+        loop {
+            let code_before = self.source[..self.position].trim_end_matches([' ', '\t', '\r']);
+            self.trim_whitespace();
+            let trailing = !code_before.is_empty()
+                && !code_before.ends_with('\n')
+                && !self.source[code_before.len()..self.position].contains('\n');
+            let (position, start) = (self.position, self.offset);
+            match (self.peek(), self.peek_nth(1), self.peek_nth(2)) {
+                (Some('/'), Some('*'), _) => self.block_comment()?,
+                // Doc comments are tokens, but `////...` is a plain comment again
+                (Some('/'), Some('/'), Some('/')) if self.peek_nth(3) != Some('/') => {
+                    return Ok(None)
+                }
+                (Some('/'), Some('/'), _) => {
+                    self.rest_of_line();
+                }
+                _ => return Ok(None),
+            }
+            if self.comments {
+                return Ok(Some(Token::Comment {
+                    text: &self.source[position..self.position],
+                    trailing,
+                    span: self.span_from(start),
+                }));
+            }
+        }
+    }
+
+    /// Skips a `/* ... */` comment, which can contain other block comments
+    fn block_comment(&mut self) -> Result<(), LexError> {
+        let offset = self.offset;
+        let mut depth = 0;
+        loop {
+            match (self.peek(), self.peek_nth(1)) {
+                (Some('/'), Some('*')) => depth += 1,
+                (Some('*'), Some('/')) => depth -= 1,
+                (None, _) => {
+                    return Err(LexError {
+                        kind: TokenizeError::UnterminatedComment,
+                        offset,
+                        message: "unterminated block comment".to_string(),
+                    })
+                }
+                _ => {
+                    self.consume();
+                    continue;
+                }
+            }
+            self.consume();
+            self.consume();
+            if depth == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Consumes everything up to, but not including, the next newline
+    fn rest_of_line(&mut self) -> &'src str {
+        let position = self.position;
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.consume();
+        }
+        self.lexeme(position)
     }
 
     fn trim_whitespace(&mut self) {
@@ -200,34 +1359,93 @@ impl Tokenizer {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::Tokenizer;
-    use crate::tokenizer::{BinaryOp, Token};
+/// Lexes tokens lazily. The last item is either [`Token::Eof`] or the first error
+impl<'src> Iterator for Tokenizer<'src> {
+    type Item = error_stack::Result<Token<'src>, TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let item = match self.next_token() {
+            Ok(Some(token)) => return Some(Ok(token)),
+            Ok(None) => Ok(Token::Eof {
+                span: self.span_from(self.offset),
+            }),
+            Err(error) => Err(self.report(error)),
+        };
+        self.finished = true;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::Tokenizer;
+    use crate::{
+        diagnostics::Diagnostics,
+        json::Json,
+        tokenizer::{BinaryOp, NumberTypeFlag, Span, Token, TokenKind},
+    };
 
     #[test]
     fn empty() {
         let src = "".to_string();
-        let tokenizer = Tokenizer::new(src, "tests::empty".to_string());
-        assert_eq!(tokenizer.tokenize().unwrap(), vec![])
+        let tokenizer = Tokenizer::new(&src, "tests::empty".to_string());
+        assert_eq!(
+            tokenizer.tokenize().unwrap(),
+            vec![Token::Eof {
+                span: Span { start: 0, end: 0 }
+            }]
+        )
+    }
+
+    #[test]
+    fn kept_comments() {
+        let src = "// a\nx /* b */\n/* c */".to_string();
+        let tokens = Tokenizer::new(&src, "tests::comments".to_string())
+            .with_comments()
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens
+                .iter()
+                .filter_map(|token| match token {
+                    Token::Comment { text, trailing, .. } => Some((*text, *trailing)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            vec![("// a", false), ("/* b */", true), ("/* c */", false)]
+        );
+        let without = Tokenizer::new(&src, "tests::comments".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(without.len(), 2);
     }
 
     #[test]
     fn numbers() {
         let src = "123 69".to_string();
-        let tokenizer = Tokenizer::new(src, "tests::numbers".to_string());
+        let tokenizer = Tokenizer::new(&src, "tests::numbers".to_string());
         assert_eq!(
             tokenizer.tokenize().unwrap(),
             vec![
                 Token::Number {
-                    raw: "123".to_lowercase(),
+                    raw: "123".into(),
                     flags: vec![],
-                    offset: 0
+                    suffix: None,
+                    span: Span { start: 0, end: 3 }
                 },
                 Token::Number {
-                    raw: "69".to_lowercase(),
+                    raw: "69".into(),
                     flags: vec![],
-                    offset: 4
+                    suffix: None,
+                    span: Span { start: 4, end: 6 }
+                },
+                Token::Eof {
+                    span: Span { start: 6, end: 6 }
                 },
             ]
         )
@@ -235,21 +1453,24 @@ mod tests {
     #[test]
     fn operators() {
         let src = "- + -".to_string();
-        let tokenizer = Tokenizer::new(src, "tests::operators".to_string());
+        let tokenizer = Tokenizer::new(&src, "tests::operators".to_string());
         assert_eq!(
             tokenizer.tokenize().unwrap(),
             vec![
                 Token::BinaryOperator {
                     op: BinaryOp::Minus,
-                    offset: 0
+                    span: Span { start: 0, end: 1 }
                 },
                 Token::BinaryOperator {
                     op: BinaryOp::Plus,
-                    offset: 2
+                    span: Span { start: 2, end: 3 }
                 },
                 Token::BinaryOperator {
                     op: BinaryOp::Minus,
-                    offset: 4
+                    span: Span { start: 4, end: 5 }
+                },
+                Token::Eof {
+                    span: Span { start: 5, end: 5 }
                 },
             ]
         )
@@ -258,28 +1479,980 @@ mod tests {
     #[test]
     fn let_statement() {
         let src = "let a: u64 = 1;".to_string();
-        let tokens = Tokenizer::new(src, "tests::let".to_string())
+        let tokens = Tokenizer::new(&src, "tests::let".to_string())
             .tokenize()
             .unwrap();
 
         assert_eq!(
             tokens,
             vec![
-                Token::Let,
-                Token::Identifier("a".to_string()),
-                Token::Colon,
-                Token::Identifier("u64".to_string()),
+                Token::Let {
+                    span: Span { start: 0, end: 3 }
+                },
+                Token::Identifier {
+                    name: "a".into(),
+                    span: Span { start: 4, end: 5 }
+                },
+                Token::Colon {
+                    span: Span { start: 5, end: 6 }
+                },
+                Token::Identifier {
+                    name: "u64".into(),
+                    span: Span { start: 7, end: 10 }
+                },
                 Token::BinaryOperator {
                     op: BinaryOp::SingleEqual,
-                    offset: 11
+                    span: Span { start: 11, end: 12 }
                 },
                 Token::Number {
-                    raw: "1".to_string(),
+                    raw: "1".into(),
                     flags: vec![],
-                    offset: 13
+                    suffix: None,
+                    span: Span { start: 13, end: 14 }
+                },
+                Token::Semicolon {
+                    span: Span { start: 14, end: 15 }
+                },
+                Token::Eof {
+                    span: Span { start: 15, end: 15 }
                 },
-                Token::Semicolon
             ]
         )
     }
+
+    #[test]
+    fn binary_op_display() {
+        let ops = [
+            BinaryOp::Plus,
+            BinaryOp::Minus,
+            BinaryOp::Star,
+            BinaryOp::Slash,
+            BinaryOp::Percent,
+            BinaryOp::SingleEqual,
+            BinaryOp::DoubleEqual,
+            BinaryOp::NotEqual,
+            BinaryOp::Less,
+            BinaryOp::Greater,
+            BinaryOp::LessEqual,
+            BinaryOp::GreaterEqual,
+            BinaryOp::And,
+            BinaryOp::Or,
+            BinaryOp::BitAnd,
+            BinaryOp::BitOr,
+            BinaryOp::BitXor,
+            BinaryOp::ShiftLeft,
+            BinaryOp::ShiftRight,
+        ];
+        assert_eq!(
+            ops.map(|op| op.to_string()),
+            [
+                "+", "-", "*", "/", "%", "=", "==", "!=", "<", ">", "<=", ">=", "&&", "||", "&",
+                "|", "^", "<<", ">>"
+            ]
+        );
+    }
+
+    #[test]
+    fn streaming() {
+        let mut tokenizer = Tokenizer::new("exit(1); $ x", "tests::stream".to_string());
+        assert_eq!(
+            tokenizer.next().unwrap().unwrap(),
+            Token::Exit {
+                span: Span { start: 0, end: 4 }
+            }
+        );
+        assert_eq!(tokenizer.by_ref().take_while(Result::is_ok).count(), 4);
+        assert!(tokenizer.next().is_none());
+
+        let tokens: Vec<_> = Tokenizer::new("a", "tests::stream".to_string())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            tokens,
+            [
+                Token::Identifier {
+                    name: "a".into(),
+                    span: Span { start: 0, end: 1 }
+                },
+                Token::Eof {
+                    span: Span { start: 1, end: 1 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords() {
+        let tokens = Tokenizer::new(
+            "if else while fn return iffy _let",
+            "tests::keywords".to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        assert_eq!(
+            tokens[..7],
+            [
+                Token::If {
+                    span: Span { start: 0, end: 2 }
+                },
+                Token::Else {
+                    span: Span { start: 3, end: 7 }
+                },
+                Token::While {
+                    span: Span { start: 8, end: 13 }
+                },
+                Token::Fn {
+                    span: Span { start: 14, end: 16 }
+                },
+                Token::Return {
+                    span: Span { start: 17, end: 23 }
+                },
+                Token::Identifier {
+                    name: "iffy".into(),
+                    span: Span { start: 24, end: 28 }
+                },
+                Token::Identifier {
+                    name: "_let".into(),
+                    span: Span { start: 29, end: 33 }
+                },
+            ]
+        );
+        assert_eq!(tokens[4].keyword(), Some("return"));
+        assert_eq!(tokens[4].span(), Span { start: 17, end: 23 });
+        assert_eq!(tokens[5].keyword(), None);
+    }
+
+    #[test]
+    fn compound_assignments() {
+        let tokens = Tokenizer::new("a += 1 -= *=/= + =", "tests::compound".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[1..7],
+            [
+                Token::CompoundAssign {
+                    op: BinaryOp::Plus,
+                    span: Span { start: 2, end: 4 }
+                },
+                Token::Number {
+                    raw: "1".into(),
+                    flags: vec![],
+                    suffix: None,
+                    span: Span { start: 5, end: 6 }
+                },
+                Token::CompoundAssign {
+                    op: BinaryOp::Minus,
+                    span: Span { start: 7, end: 9 }
+                },
+                Token::CompoundAssign {
+                    op: BinaryOp::Star,
+                    span: Span { start: 10, end: 12 }
+                },
+                Token::CompoundAssign {
+                    op: BinaryOp::Slash,
+                    span: Span { start: 12, end: 14 }
+                },
+                Token::BinaryOperator {
+                    op: BinaryOp::Plus,
+                    span: Span { start: 15, end: 16 }
+                },
+            ]
+        );
+        assert_eq!(tokens[5].span(), Span { start: 12, end: 14 });
+    }
+
+    #[test]
+    fn bitwise_operators() {
+        let tokens = Tokenizer::new("~a&b|c^d<<1>>2>=e", "tests::bitwise".to_string())
+            .tokenize()
+            .unwrap();
+        let kinds: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::BinaryOperator { op, span: _ } => Some(op.to_string()),
+                Token::Tilde { span: _ } => Some("~".to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            ["~", "&", "|", "^", "<<", ">>", ">="].map(String::from)
+        );
+    }
+
+    #[test]
+    fn logical_operators() {
+        let tokens = Tokenizer::new("!a&&b || !=", "tests::logical".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[..6],
+            [
+                Token::Bang {
+                    span: Span { start: 0, end: 1 }
+                },
+                Token::Identifier {
+                    name: "a".into(),
+                    span: Span { start: 1, end: 2 }
+                },
+                Token::BinaryOperator {
+                    op: BinaryOp::And,
+                    span: Span { start: 2, end: 4 }
+                },
+                Token::Identifier {
+                    name: "b".into(),
+                    span: Span { start: 4, end: 5 }
+                },
+                Token::BinaryOperator {
+                    op: BinaryOp::Or,
+                    span: Span { start: 6, end: 8 }
+                },
+                Token::BinaryOperator {
+                    op: BinaryOp::NotEqual,
+                    span: Span { start: 9, end: 11 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn two_char_operators() {
+        let tokens = Tokenizer::new("a == =b=", "tests::two_char".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[1..4],
+            [
+                Token::BinaryOperator {
+                    op: BinaryOp::DoubleEqual,
+                    span: Span { start: 2, end: 4 }
+                },
+                Token::BinaryOperator {
+                    op: BinaryOp::SingleEqual,
+                    span: Span { start: 5, end: 6 }
+                },
+                Token::Identifier {
+                    name: "b".into(),
+                    span: Span { start: 6, end: 7 }
+                },
+            ]
+        );
+        assert_eq!(tokens[1].span(), Span { start: 2, end: 4 });
+        assert_eq!(
+            tokens[4],
+            Token::BinaryOperator {
+                op: BinaryOp::SingleEqual,
+                span: Span { start: 7, end: 8 }
+            }
+        );
+    }
+
+    #[test]
+    fn floats() {
+        let tokens = Tokenizer::new("3.14 1.0", "tests::floats".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Number {
+                    raw: "3.14".into(),
+                    flags: vec![NumberTypeFlag::Floating],
+                    suffix: None,
+                    span: Span { start: 0, end: 4 }
+                },
+                Token::Number {
+                    raw: "1.0".into(),
+                    flags: vec![NumberTypeFlag::Floating],
+                    suffix: None,
+                    span: Span { start: 5, end: 8 }
+                },
+                Token::Eof {
+                    span: Span { start: 8, end: 8 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_float() {
+        let src = "exit(1.2.3);".to_string();
+        assert!(matches!(
+            Tokenizer::new(&src, "tests::malformed_float".to_string())
+                .tokenize()
+                .unwrap_err()
+                .current_context(),
+            super::TokenizeError::MalformedNumber
+        ));
+
+        let mut diagnostics = Diagnostics::default();
+        let tokens = Tokenizer::new(&src, "tests::malformed_float".to_string())
+            .tokenize_recovering(&mut diagnostics);
+        assert_eq!(diagnostics.errors.len(), 1);
+        assert_eq!(
+            diagnostics.errors[0].message,
+            "malformed number literal `1.2.3`"
+        );
+        assert_eq!(
+            tokens[2],
+            Token::Invalid {
+                span: Span { start: 5, end: 10 }
+            }
+        );
+        assert_eq!(
+            tokens[3],
+            Token::CloseParen {
+                span: Span { start: 10, end: 11 }
+            }
+        );
+    }
+
+    #[test]
+    fn hexadecimal() {
+        let tokens = Tokenizer::new("0xFF 0x1a", "tests::hex".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[..2],
+            [
+                Token::Number {
+                    raw: "0xFF".into(),
+                    flags: vec![NumberTypeFlag::Hexadecimal],
+                    suffix: None,
+                    span: Span { start: 0, end: 4 }
+                },
+                Token::Number {
+                    raw: "0x1a".into(),
+                    flags: vec![NumberTypeFlag::Hexadecimal],
+                    suffix: None,
+                    span: Span { start: 5, end: 9 }
+                },
+            ]
+        );
+
+        for (src, message) in [
+            ("0xfg", "invalid digit 'g' in number literal `0xfg`"),
+            ("0x;", "missing digits after `0x`"),
+        ] {
+            let mut diagnostics = Diagnostics::default();
+            Tokenizer::new(src, "tests::hex".to_string()).tokenize_recovering(&mut diagnostics);
+            assert_eq!(diagnostics.errors[0].message, message);
+        }
+    }
+
+    #[test]
+    fn binary() {
+        let tokens = Tokenizer::new("0b1010", "tests::binary".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::Number {
+                raw: "0b1010".into(),
+                flags: vec![NumberTypeFlag::Binary],
+                suffix: None,
+                span: Span { start: 0, end: 6 }
+            }
+        );
+
+        let mut diagnostics = Diagnostics::default();
+        Tokenizer::new("0b102", "tests::binary".to_string()).tokenize_recovering(&mut diagnostics);
+        assert_eq!(
+            diagnostics.errors[0].message,
+            "invalid digit '2' in number literal `0b102`"
+        );
+    }
+
+    #[test]
+    fn octal() {
+        let tokens = Tokenizer::new("0o755", "tests::octal".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::Number {
+                raw: "0o755".into(),
+                flags: vec![NumberTypeFlag::Octal],
+                suffix: None,
+                span: Span { start: 0, end: 5 }
+            }
+        );
+
+        let mut diagnostics = Diagnostics::default();
+        Tokenizer::new("0o78", "tests::octal".to_string()).tokenize_recovering(&mut diagnostics);
+        assert_eq!(
+            diagnostics.errors[0].message,
+            "invalid digit '8' in number literal `0o78`"
+        );
+    }
+
+    #[test]
+    fn number_suffixes() {
+        let tokens = Tokenizer::new(
+            "255u8 1_i32 1.5f64 2f32 0xFFu16 0b1_u8",
+            "tests::suffixes".to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        let numbers: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Number { raw, suffix, .. } => Some((raw.as_ref(), suffix.as_deref())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            numbers,
+            [
+                ("255", Some("u8")),
+                ("1", Some("i32")),
+                ("1.5", Some("f64")),
+                ("2", Some("f32")),
+                ("0xFF", Some("u16")),
+                ("0b1", Some("u8")),
+            ]
+        );
+        assert_eq!(tokens[0].span(), Span { start: 0, end: 5 });
+
+        for (src, message) in [
+            ("1u7", "invalid suffix `u7` for number literal `1`"),
+            ("1.5u8", "integer suffix `u8` for number literal `1.5`"),
+            ("0xFFi9", "invalid suffix `i9` for number literal `0xFF`"),
+        ] {
+            let mut diagnostics = Diagnostics::default();
+            Tokenizer::new(src, "tests::suffixes".to_string())
+                .tokenize_recovering(&mut diagnostics);
+            assert_eq!(diagnostics.errors[0].message, message);
+        }
+    }
+
+    #[test]
+    fn digit_separators() {
+        let tokens = Tokenizer::new("1_000_000 0xFF_FF 1_0.2_5", "tests::separators".to_string())
+            .tokenize()
+            .unwrap();
+        let raws: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Number { raw, .. } => Some(raw.as_ref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(raws, ["1000000", "0xFFFF", "10.25"]);
+        // The span still covers the separators
+        assert_eq!(tokens[0].span(), Span { start: 0, end: 9 });
+    }
+
+    #[test]
+    fn strings() {
+        let src = r#""hello\n\t\"world\"\\" "" x"#.to_string();
+        let tokens = Tokenizer::new(&src, "tests::strings".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[..2],
+            [
+                Token::String {
+                    value: "hello\n\t\"world\"\\".into(),
+                    span: Span { start: 0, end: 22 }
+                },
+                Token::String {
+                    value: "".into(),
+                    span: Span { start: 23, end: 25 }
+                },
+            ]
+        );
+        assert_eq!(tokens[0].span().end, 22);
+    }
+
+    #[test]
+    fn string_errors() {
+        let err = Tokenizer::new(
+            "exit(0);\nlet a = \"abc;\nexit(1);",
+            "tests::unterminated".to_string(),
+        )
+        .tokenize()
+        .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            super::TokenizeError::UnterminatedString
+        ));
+        assert!(
+            format!("{err:?}").contains("./tests::unterminated:2:9: unterminated string literal")
+        );
+
+        let mut diagnostics = Diagnostics::default();
+        let tokens = Tokenizer::new(r#""a\qb" 1"#, "tests::escape".to_string())
+            .tokenize_recovering(&mut diagnostics);
+        assert_eq!(
+            diagnostics.errors[0].message,
+            "unknown escape sequence `\\q` in string literal"
+        );
+        assert!(matches!(tokens[1], Token::Number { .. }));
+    }
+
+    #[test]
+    fn raw_strings() {
+        let src = r###"r"C:\dir\" r#"say "hi""# r"" rust"###.to_string();
+        let tokens = Tokenizer::new(&src, "tests::raw_strings".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[..4],
+            [
+                Token::RawString {
+                    value: r"C:\dir\",
+                    hashes: 0,
+                    span: Span { start: 0, end: 10 }
+                },
+                Token::RawString {
+                    value: r#"say "hi""#,
+                    hashes: 1,
+                    span: Span { start: 11, end: 24 }
+                },
+                Token::RawString {
+                    value: "",
+                    hashes: 0,
+                    span: Span { start: 25, end: 28 }
+                },
+                Token::Identifier {
+                    name: "rust".into(),
+                    span: Span { start: 29, end: 33 }
+                },
+            ]
+        );
+
+        let err = Tokenizer::new(r##"r#"abc"x"##, "tests::raw".to_string())
+            .tokenize()
+            .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            super::TokenizeError::UnterminatedString
+        ));
+        assert!(Tokenizer::new("r#x", "tests::raw".to_string())
+            .tokenize()
+            .is_err());
+    }
+
+    #[test]
+    fn chars() {
+        let tokens = Tokenizer::new(r"'a' '\n' '\''", "tests::chars".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[..3],
+            [
+                Token::Char {
+                    value: 'a',
+                    span: Span { start: 0, end: 3 }
+                },
+                Token::Char {
+                    value: '\n',
+                    span: Span { start: 4, end: 8 }
+                },
+                Token::Char {
+                    value: '\'',
+                    span: Span { start: 9, end: 13 }
+                },
+            ]
+        );
+        assert_eq!(tokens[1].span(), Span { start: 4, end: 8 });
+
+        for (src, message) in [
+            ("''", "empty character literal"),
+            ("'ab' 1", "character literal may only contain one character"),
+            (
+                "'a",
+                "unterminated character literal, missing a closing `'`",
+            ),
+            (
+                r"'\q'",
+                "unknown escape sequence `\\q` in character literal",
+            ),
+        ] {
+            let mut diagnostics = Diagnostics::default();
+            Tokenizer::new(src, "tests::chars".to_string()).tokenize_recovering(&mut diagnostics);
+            assert_eq!(diagnostics.errors[0].message, message, "{src}");
+        }
+    }
+
+    #[test]
+    fn block_comments() {
+        let tokens = Tokenizer::new(
+            "1 /* a /* nested */ still a comment */ 2 /**/3",
+            "tests::block_comments".to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        let raws: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Number { raw, .. } => Some(raw.as_ref()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(raws, ["1", "2", "3"]);
+
+        let err = Tokenizer::new("exit(1);\n  /* /* */", "tests::block_comments".to_string())
+            .tokenize()
+            .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            super::TokenizeError::UnterminatedComment
+        ));
+        assert!(
+            format!("{err:?}").contains("./tests::block_comments:2:3: unterminated block comment")
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let src = "// plain\n/// Exits\n//// plain again\nexit(0); // trailing\n///".to_string();
+        let tokens = Tokenizer::new(&src, "tests::comments".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::DocComment {
+                content: " Exits",
+                span: Span { start: 9, end: 18 }
+            }
+        );
+        assert_eq!(tokens[0].span(), Span { start: 9, end: 18 });
+        assert_eq!(
+            tokens[1],
+            Token::Exit {
+                span: Span { start: 36, end: 40 }
+            }
+        );
+        assert_eq!(
+            tokens[tokens.len() - 2],
+            Token::DocComment {
+                content: "",
+                span: Span { start: 57, end: 60 }
+            }
+        );
+    }
+
+    #[test]
+    fn comparison_operators() {
+        let tokens = Tokenizer::new("== != < > <= >= <<=", "tests::comparisons".to_string())
+            .tokenize()
+            .unwrap();
+        let ops: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::BinaryOperator { op, span: _ } => Some(op.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                BinaryOp::DoubleEqual,
+                BinaryOp::NotEqual,
+                BinaryOp::Less,
+                BinaryOp::Greater,
+                BinaryOp::LessEqual,
+                BinaryOp::GreaterEqual,
+                BinaryOp::ShiftLeft,
+                BinaryOp::SingleEqual,
+            ]
+        );
+        assert_eq!(tokens[5].span(), Span { start: 13, end: 15 });
+    }
+
+    #[test]
+    fn lexemes_borrow_the_source() {
+        let src = "\"é\" \"héllo\" \"a\\tb\" 1_000 255u8";
+        let tokens = Tokenizer::new(src, "tests::lexemes".to_string())
+            .tokenize()
+            .unwrap();
+        match &tokens[1] {
+            Token::String { value, span } => {
+                assert!(matches!(value, Cow::Borrowed("héllo")));
+                assert_eq!(*span, Span { start: 4, end: 11 });
+            }
+            other => panic!("expected a string, got {other:?}"),
+        }
+        assert_eq!(
+            tokens[2],
+            Token::String {
+                value: Cow::Owned("a\tb".to_string()),
+                span: Span { start: 12, end: 18 }
+            }
+        );
+        assert!(matches!(
+            &tokens[3],
+            Token::Number { raw: Cow::Owned(raw), .. } if raw == "1000"
+        ));
+        assert!(matches!(
+            &tokens[4],
+            Token::Number {
+                raw: Cow::Borrowed("255"),
+                suffix: Some("u8"),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn json_dump() {
+        let src = "let é: u8 = 1_0;\n";
+        let tokens = Tokenizer::new(src, "tests::json_dump".to_string())
+            .tokenize_recovering(&mut Diagnostics::default());
+        let json = super::tokens_to_json(&tokens, src);
+        let tokens = json.as_array().unwrap();
+        let lexemes: Vec<_> = tokens
+            .iter()
+            .map(|token| token.get("lexeme").and_then(Json::as_str).unwrap())
+            .collect();
+        assert_eq!(lexemes, ["let", "é", ":", "u8", "=", "1_0", ";", ""]);
+        assert_eq!(
+            tokens[1].to_string(),
+            r#"{"kind":"invalid","lexeme":"é","span":{"start":4,"end":5}}"#
+        );
+        assert_eq!(
+            tokens[0].get("kind").and_then(Json::as_str),
+            Some("keyword")
+        );
+        assert_eq!(
+            tokens[7].get("span").unwrap().to_string(),
+            r#"{"start":17,"end":17}"#
+        );
+    }
+
+    #[test]
+    fn shebang() {
+        let src = "#!/usr/bin/env ghetto-llvm\nexit(0);";
+        let tokens = Tokenizer::new(src, "tests::shebang".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[0],
+            Token::Exit {
+                span: Span { start: 27, end: 31 }
+            }
+        );
+        assert_eq!(super::shebang(src), Some("#!/usr/bin/env ghetto-llvm"));
+
+        // Anywhere else `#` is still unexpected, unless it starts an attribute
+        assert!(Tokenizer::new(" #!x", "tests::shebang".to_string())
+            .tokenize()
+            .is_err());
+        assert!(Tokenizer::new(" #[inline]", "tests::shebang".to_string())
+            .tokenize()
+            .is_ok());
+    }
+
+    #[test]
+    fn scientific_notation() {
+        let tokens = Tokenizer::new("1e9 2.5e-3 1E+5f32", "tests::exponents".to_string())
+            .tokenize()
+            .unwrap();
+        let numbers: Vec<_> = tokens
+            .iter()
+            .filter_map(|token| match token {
+                Token::Number {
+                    raw, flags, suffix, ..
+                } => Some((raw.as_ref(), flags.as_slice(), *suffix)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            numbers,
+            [
+                ("1e9", [NumberTypeFlag::Floating].as_slice(), None),
+                ("2.5e-3", &[NumberTypeFlag::Floating], None),
+                ("1E+5", &[NumberTypeFlag::Floating], Some("f32")),
+            ]
+        );
+
+        for (src, message) in [
+            (
+                "1e",
+                "missing digits in the exponent of number literal `1e`",
+            ),
+            (
+                "2.5e-",
+                "missing digits in the exponent of number literal `2.5e-`",
+            ),
+            (
+                "3ex",
+                "missing digits in the exponent of number literal `3ex`",
+            ),
+            ("1e5u8", "integer suffix `u8` for number literal `1e5`"),
+        ] {
+            let mut diagnostics = Diagnostics::default();
+            let tokens = Tokenizer::new(src, "tests::exponents".to_string())
+                .tokenize_recovering(&mut diagnostics);
+            assert_eq!(diagnostics.errors[0].message, message);
+            assert_eq!(tokens.len(), 2, "{src} should be one invalid token");
+        }
+    }
+
+    #[test]
+    fn bool_literals() {
+        let tokens = Tokenizer::new("true false truely", "tests::bools".to_string())
+            .tokenize()
+            .unwrap();
+        assert_eq!(
+            tokens[..3],
+            [
+                Token::Bool {
+                    value: true,
+                    span: Span { start: 0, end: 4 }
+                },
+                Token::Bool {
+                    value: false,
+                    span: Span { start: 5, end: 10 }
+                },
+                Token::Identifier {
+                    name: "truely".into(),
+                    span: Span { start: 11, end: 17 }
+                },
+            ]
+        );
+        assert_eq!(tokens[1].keyword(), Some("false"));
+        assert_eq!(tokens[0].kind().to_string(), "a boolean literal");
+    }
+
+    #[test]
+    fn arrows() {
+        let tokens = Tokenizer::new("-> => - > = > -= == ->>", "tests::arrows".to_string())
+            .tokenize()
+            .unwrap();
+        let kinds: Vec<_> = tokens.iter().map(Token::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Arrow,
+                TokenKind::FatArrow,
+                TokenKind::BinaryOperator(BinaryOp::Minus),
+                TokenKind::BinaryOperator(BinaryOp::Greater),
+                TokenKind::BinaryOperator(BinaryOp::SingleEqual),
+                TokenKind::BinaryOperator(BinaryOp::Greater),
+                TokenKind::CompoundAssign(BinaryOp::Minus),
+                TokenKind::BinaryOperator(BinaryOp::DoubleEqual),
+                TokenKind::Arrow,
+                TokenKind::BinaryOperator(BinaryOp::Greater),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[1].span(), Span { start: 3, end: 5 });
+    }
+
+    #[test]
+    fn punctuation() {
+        let tokens = Tokenizer::new("{[a, 1.5]}.0 1.x", "tests::punctuation".to_string())
+            .tokenize()
+            .unwrap();
+        let kinds: Vec<_> = tokens.iter().map(Token::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::OpenBrace,
+                TokenKind::OpenBracket,
+                TokenKind::Identifier,
+                TokenKind::Comma,
+                TokenKind::Number,
+                TokenKind::CloseBracket,
+                TokenKind::CloseBrace,
+                TokenKind::Dot,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::Dot,
+                TokenKind::Identifier,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[4].span(), Span { start: 5, end: 8 });
+    }
+
+    #[test]
+    fn spans_stay_inside_the_source() {
+        // The escape runs into the end of the file
+        let mut diagnostics = Diagnostics::default();
+        let tokens = Tokenizer::new("\"\\", "tests::spans".to_string())
+            .tokenize_recovering(&mut diagnostics);
+        assert_eq!(
+            tokens,
+            [
+                Token::Invalid {
+                    span: Span { start: 0, end: 2 }
+                },
+                Token::Eof {
+                    span: Span { start: 2, end: 2 }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn ranges() {
+        let tokens = Tokenizer::new(
+            "for i in 0..10 1...2 a..b 0..=n",
+            "tests::ranges".to_string(),
+        )
+        .tokenize()
+        .unwrap();
+        let kinds: Vec<_> = tokens.iter().map(Token::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Keyword("for"),
+                TokenKind::Identifier,
+                TokenKind::Keyword("in"),
+                TokenKind::Number,
+                TokenKind::DotDot,
+                TokenKind::Number,
+                TokenKind::Number,
+                TokenKind::DotDot,
+                TokenKind::Dot,
+                TokenKind::Number,
+                TokenKind::Identifier,
+                TokenKind::DotDot,
+                TokenKind::Identifier,
+                TokenKind::Number,
+                TokenKind::DotDotEqual,
+                TokenKind::Identifier,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[4].span(), Span { start: 10, end: 12 });
+        assert_eq!(tokens[14].span(), Span { start: 27, end: 30 });
+    }
+
+    #[test]
+    fn double_colon() {
+        let tokens = Tokenizer::new("Color::Red a: b :::", "tests::double_colon".to_string())
+            .tokenize()
+            .unwrap();
+        let kinds: Vec<_> = tokens.iter().map(Token::kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                TokenKind::Identifier,
+                TokenKind::DoubleColon,
+                TokenKind::Identifier,
+                TokenKind::Identifier,
+                TokenKind::Colon,
+                TokenKind::Identifier,
+                TokenKind::DoubleColon,
+                TokenKind::Colon,
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[1].span(), Span { start: 5, end: 7 });
+    }
+
+    #[test]
+    fn loop_keywords() {
+        let tokens = Tokenizer::new("break continue breaks", "tests::loop_keywords".to_string())
+            .tokenize()
+            .unwrap();
+        let keywords: Vec<_> = tokens.iter().map(Token::keyword).collect();
+        assert_eq!(keywords, [Some("break"), Some("continue"), None, None]);
+        assert_eq!(tokens[1].span(), Span { start: 6, end: 14 });
+    }
 }